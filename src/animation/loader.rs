@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+
+use crate::animation::{Animation, AnimationManifest, SpriteAnimations};
+
+pub const ANIMATIONS_EXTENSION: &str = "animations.ron";
+
+#[derive(Debug)]
+pub enum SpriteAnimationsError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    // Every authoring mistake found, not just the first one
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for SpriteAnimationsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpriteAnimationsError::Io(error) => write!(f, "could not read the animations: {}", error),
+            SpriteAnimationsError::Parse(error) => write!(
+                f,
+                "syntax error at line {}, column {}: {}",
+                error.position.line, error.position.col, error.code
+            ),
+            SpriteAnimationsError::Invalid(problems) => {
+                write!(f, "the animations have {} problem(s):", problems.len())?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpriteAnimationsError {}
+
+impl From<std::io::Error> for SpriteAnimationsError {
+    fn from(error: std::io::Error) -> Self {
+        SpriteAnimationsError::Io(error)
+    }
+}
+
+// Reads a manifest and builds an atlas layout for each of its clips, loading the sheets next to it
+#[derive(Default)]
+pub struct SpriteAnimationsLoader;
+
+impl AssetLoader for SpriteAnimationsLoader {
+    type Asset = SpriteAnimations;
+    type Settings = ();
+    type Error = SpriteAnimationsError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<SpriteAnimations, SpriteAnimationsError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let manifest = AnimationManifest::from_bytes(&bytes)?;
+
+        let mut clips = HashMap::new();
+        for (state, clip) in manifest.clips {
+            let layout = TextureAtlasLayout::from_grid(manifest.frame_size, clip.frames, 1, None, None);
+            let sheet = load_context.path().with_file_name(&clip.sheet);
+            let animation = Animation {
+                frames: clip.frames as usize,
+                frame_secs: clip.frame_secs,
+                looping: clip.looping,
+                texture_handle: load_context.load(sheet),
+                layout: load_context.add_labeled_asset(format!("{:?}", state), layout),
+            };
+            clips.insert(state, animation);
+        }
+        Ok(SpriteAnimations { clips })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[ANIMATIONS_EXTENSION]
+    }
+}
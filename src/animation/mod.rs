@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::components::player::PlayerState;
+use crate::systems::movement::{ATTACK_SECS, HURT_SECS};
+
+pub mod loader;
+
+pub use loader::{SpriteAnimationsError, SpriteAnimationsLoader};
+
+// Every sprite set folder under assets/sprites/ describes its sheets in a file of this name
+pub const MANIFEST_FILE: &str = "set.animations.ron";
+// How far a timed clip may be off the time the simulation keeps a player in its state
+const CLIP_TOLERANCE_SECS: f32 = 0.01;
+
+// A sprite set's `.animations.ron` manifest as authored
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct AnimationManifest {
+    // Sheets are a single horizontal strip of frames this size
+    pub frame_size: UVec2,
+    pub clips: HashMap<PlayerState, ClipDefinition>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ClipDefinition {
+    // Image file next to the manifest
+    pub sheet: String,
+    pub frames: u32,
+    pub frame_secs: f32,
+    // Played once and held on the last frame otherwise
+    #[serde(default)]
+    pub looping: bool,
+}
+
+impl AnimationManifest {
+    pub fn from_bytes(bytes: &[u8]) -> Result<AnimationManifest, SpriteAnimationsError> {
+        let manifest: AnimationManifest = ron::de::from_bytes(bytes).map_err(SpriteAnimationsError::Parse)?;
+        let problems = manifest.validate();
+        if problems.is_empty() {
+            Ok(manifest)
+        } else {
+            Err(SpriteAnimationsError::Invalid(problems))
+        }
+    }
+
+    // Collects every authoring mistake, each one naming the clip it is about
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.frame_size.x == 0 || self.frame_size.y == 0 {
+            problems.push(format!(
+                "frame_size: must be positive, got {} x {}",
+                self.frame_size.x, self.frame_size.y
+            ));
+        }
+        // Every state has to be drawable
+        for state in PlayerState::ALL {
+            let Some(clip) = self.clips.get(&state) else {
+                problems.push(format!("clips: no clip for {:?}", state));
+                continue;
+            };
+            if clip.sheet.trim().is_empty() {
+                problems.push(format!("clips.{:?}.sheet: must not be empty", state));
+            }
+            if clip.frames == 0 {
+                problems.push(format!("clips.{:?}.frames: needs at least one frame", state));
+            }
+            if !clip.frame_secs.is_finite() || clip.frame_secs <= 0.0 {
+                problems.push(format!("clips.{:?}.frame_secs: must be positive, got {}", state, clip.frame_secs));
+            }
+        }
+        // A swing or a stun has to play out exactly as long as the simulation holds the player in it
+        for (state, secs) in [(PlayerState::Attacking, ATTACK_SECS), (PlayerState::Hurt, HURT_SECS)] {
+            let Some(clip) = self.clips.get(&state) else {
+                continue;
+            };
+            let clip_secs = clip.frames as f32 * clip.frame_secs;
+            if (clip_secs - secs).abs() > CLIP_TOLERANCE_SECS {
+                problems.push(format!(
+                    "clips.{:?}: plays for {}s but the state lasts {}s",
+                    state, clip_secs, secs
+                ));
+            }
+        }
+        problems
+    }
+}
+
+// One clip ready to draw
+pub struct Animation {
+    pub frames: usize,
+    pub frame_secs: f32,
+    pub looping: bool,
+    pub texture_handle: Handle<Image>,
+    pub layout: Handle<TextureAtlasLayout>,
+}
+
+impl Animation {
+    // Frame on screen after this many seconds of the clip
+    pub fn frame_at(&self, elapsed: f32) -> usize {
+        let frame = (elapsed.max(0.0) / self.frame_secs) as usize;
+        if self.looping {
+            frame % self.frames.max(1)
+        } else {
+            frame.min(self.frames.saturating_sub(1))
+        }
+    }
+}
+
+// Every clip of one sprite set, built by SpriteAnimationsLoader from the set's manifest
+#[derive(Asset, TypePath)]
+pub struct SpriteAnimations {
+    clips: HashMap<PlayerState, Animation>,
+}
+
+impl SpriteAnimations {
+    pub fn clip(&self, state: PlayerState) -> Option<&Animation> {
+        self.clips.get(&state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+
+    fn shipped_manifests() -> Vec<PathBuf> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/sprites");
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path().join(MANIFEST_FILE))
+            .filter(|path| path.exists())
+            .collect()
+    }
+
+    fn city_man() -> AnimationManifest {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/sprites/City_men_1").join(MANIFEST_FILE);
+        AnimationManifest::from_bytes(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn every_shipped_manifest_loads() {
+        let manifests = shipped_manifests();
+        assert!(!manifests.is_empty());
+        for path in manifests {
+            if let Err(err) = AnimationManifest::from_bytes(&std::fs::read(&path).unwrap()) {
+                panic!("{}: {}", path.display(), err);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_states_without_a_clip() {
+        let mut manifest = city_man();
+        manifest.clips.remove(&PlayerState::Dead);
+        assert!(manifest.validate().contains(&"clips: no clip for Dead".to_string()));
+    }
+
+    #[test]
+    fn reports_swings_shorter_than_the_attack() {
+        let mut manifest = city_man();
+        let attack = manifest.clips.get_mut(&PlayerState::Attacking).unwrap();
+        attack.frame_secs = ATTACK_SECS / attack.frames as f32 / 2.0;
+        assert!(manifest.validate().iter().any(|problem| problem.starts_with("clips.Attacking: plays for")));
+    }
+
+    #[test]
+    fn holds_the_last_frame_of_a_clip_that_does_not_loop() {
+        let animation = Animation {
+            frames: 4,
+            frame_secs: 0.1,
+            looping: false,
+            texture_handle: Handle::default(),
+            layout: Handle::default(),
+        };
+        assert_eq!(animation.frame_at(0.25), 2);
+        assert_eq!(animation.frame_at(5.0), 3);
+        let looping = Animation { looping: true, ..animation };
+        assert_eq!(looping.frame_at(0.45), 0);
+    }
+}
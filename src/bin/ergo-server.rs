@@ -0,0 +1,36 @@
+use std::net::UdpSocket;
+use std::process::ExitCode;
+
+use ergo_cogito_sum::server::{build_server_app, ServerSettings, ServerShutdown};
+
+fn main() -> ExitCode {
+    let settings = match ServerSettings::from_args(std::env::args().skip(1)) {
+        Ok(settings) => settings,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let socket = match UdpSocket::bind(settings.bind_addr) {
+        Ok(socket) => socket,
+        Err(err) => {
+            eprintln!("Could not bind {}: {}", settings.bind_addr, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!(
+        "Starting dedicated server on {} ({} rooms max, {} Hz)",
+        settings.bind_addr, settings.max_rooms, settings.tick_rate
+    );
+    let mut app = match build_server_app(socket, settings, ServerShutdown::default()) {
+        Ok(app) => app,
+        Err(err) => {
+            eprintln!("Could not start server: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    app.run();
+    ExitCode::SUCCESS
+}
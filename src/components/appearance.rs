@@ -0,0 +1,45 @@
+use bevy::asset::io::file::FileAssetReader;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::animation::MANIFEST_FILE;
+
+// Where sprite sets live in the assets folder
+const SPRITES_FOLDER: &str = "sprites";
+
+// Sprite sheets a person can be drawn with, named after their folder under assets/sprites/
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct SpriteSet(pub String);
+
+impl SpriteSet {
+    // Every folder under assets/sprites/ with a manifest in it, so new sets need no code
+    pub fn discover() -> Vec<SpriteSet> {
+        let folder = FileAssetReader::get_base_path().join("assets").join(SPRITES_FOLDER);
+        let Ok(entries) = std::fs::read_dir(&folder) else {
+            println!("No sprite sets under {}", folder.display());
+            return Vec::new();
+        };
+        let mut sets: Vec<SpriteSet> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join(MANIFEST_FILE).is_file())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .map(SpriteSet)
+            .collect();
+        sets.sort();
+        sets
+    }
+
+    pub fn name(&self) -> String {
+        self.0.replace('_', " ")
+    }
+
+    // Asset path of the set's manifest
+    pub fn manifest(&self) -> String {
+        format!("{}/{}/{}", SPRITES_FOLDER, self.0, MANIFEST_FILE)
+    }
+}
+
+// How this client draws a person, which is not always how they really look
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct Appearance(pub SpriteSet);
@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::appearance::SpriteSet;
+
+// Stats and signature ability of a survivor, handed out by the scenario. Gameplay reads these instead
+// of checking who is who.
+#[derive(Component, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CharacterSheet {
+    pub max_sanity: f32,
+    // Below this share of max_sanity the survivor's mind starts playing tricks on them
+    pub breaking_point: f32,
+    // How hard being alone and being in the dark hit the survivor, 1 is the rate of the scenario's sanity settings
+    pub isolation_fear: f32,
+    pub darkness_fear: f32,
+    pub max_health: f32,
+    // How likely the survivor is to notice that something is off, from 0 to 1. Keeps the illusions
+    // they reach for from fooling them, however far gone they are.
+    pub perception: f32,
+    // Walking speed in pixels per second
+    pub speed: f32,
+    // Folder under assets/sprites/ the survivor is drawn from
+    pub sprites: SpriteSet,
+    pub ability: Ability,
+}
+
+// The one thing a survivor does differently from the others
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Ability {
+    // Gorrister stopped caring long ago, AM's torments cost him less sanity
+    Apathy { sanity_loss_scale: f32 },
+    // What AM made of Benny hits harder than anyone else
+    Strength { attack_multiplier: f32 },
+    // The others lean on Ellen, trust towards her grows faster
+    TrustBonus { amount: f32 },
+    // Nimdok forgets, some of what he hears is simply gone
+    MemoryGaps { chance: f32 },
+    // Ted suspects everything, now and then he sees through AM's illusions
+    Paranoia { insight: f32 },
+}
+
+impl Ability {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Ability::Apathy { .. } => "Apathy",
+            Ability::Strength { .. } => "Strength",
+            Ability::TrustBonus { .. } => "Trust bonus",
+            Ability::MemoryGaps { .. } => "Memory gaps",
+            Ability::Paranoia { .. } => "Paranoia",
+        }
+    }
+}
+
+impl CharacterSheet {
+    // What a sanity loss really costs this survivor
+    pub fn sanity_loss(&self, amount: f32) -> f32 {
+        match self.ability {
+            Ability::Apathy { sanity_loss_scale } => amount * sanity_loss_scale,
+            _ => amount,
+        }
+    }
+
+    // How far gone the survivor is, 0 down to the breaking point and 1 with no sanity left
+    pub fn madness(&self, sanity: f32) -> f32 {
+        let breaking_point = self.max_sanity * self.breaking_point;
+        if breaking_point <= 0.0 {
+            return 0.0;
+        }
+        (1.0 - sanity / breaking_point).clamp(0.0, 1.0)
+    }
+
+    // Chance that touching an illusion does not give it away, madness believes and perception notices
+    pub fn deceived(&self, madness: f32) -> f32 {
+        (madness * (1.0 - self.perception)).clamp(0.0, 1.0)
+    }
+}
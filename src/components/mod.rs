@@ -1 +1,5 @@
-pub mod person;
\ No newline at end of file
+pub mod person;
+pub mod player;
+pub mod role;
+pub mod character_sheet;
+pub mod appearance;
\ No newline at end of file
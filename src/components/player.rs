@@ -0,0 +1,41 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// The player controlled on this machine
+#[derive(Component)]
+pub struct Player;
+
+// Input gathered since the last input tick
+#[derive(Component)]
+pub struct PlayerInputState {
+    pub movement_velocity: Vec2,
+    pub speed_multiplier: f32,
+    pub sprinting: bool,
+    // Latched until the next input tick so a quick tap is never missed
+    pub attack_requested: bool,
+}
+
+// Seconds the player has spent in their current PlayerState, the simulation runs on it
+#[derive(Component, Default)]
+pub struct StateElapsed(pub f32);
+
+#[derive(Component, Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum PlayerState {
+    Idle,
+    Walking,
+    Running,
+    Attacking,
+    Hurt,
+    Dead,
+}
+
+impl PlayerState {
+    pub const ALL: [PlayerState; 6] = [
+        PlayerState::Idle,
+        PlayerState::Walking,
+        PlayerState::Running,
+        PlayerState::Attacking,
+        PlayerState::Hurt,
+        PlayerState::Dead,
+    ];
+}
@@ -0,0 +1,52 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// The five humans AM kept alive
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum Character {
+    Gorrister,
+    Benny,
+    Ellen,
+    Nimdok,
+    Ted,
+}
+
+impl Character {
+    pub const ALL: [Character; 5] = [
+        Character::Gorrister,
+        Character::Benny,
+        Character::Ellen,
+        Character::Nimdok,
+        Character::Ted,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Character::Gorrister => "Gorrister",
+            Character::Benny => "Benny",
+            Character::Ellen => "Ellen",
+            Character::Nimdok => "Nimdok",
+            Character::Ted => "Ted",
+        }
+    }
+}
+
+// Which side of the match a person plays
+#[derive(Component, Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum Role {
+    Survivor(Character),
+    Am,
+}
+
+impl Role {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::Survivor(character) => character.name(),
+            Role::Am => "AM",
+        }
+    }
+}
+
+// Client id of the connected player in control of this person, None until roles are handed out
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlledBy(pub Option<u64>);
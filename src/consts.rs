@@ -4,4 +4,16 @@ pub const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
 pub const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
 pub const INPUT_FIELD_BUTTON: Color = Color::srgb(0.35, 0.75, 0.35);
 pub const INPUT_FIELD_BG: Color = Color::srgb(0.50, 0.75, 0.40);
-pub const WHITE: Color = Color::srgb(0.00, 0.00, 0.00);
\ No newline at end of file
+pub const WHITE: Color = Color::srgb(0.00, 0.00, 0.00);
+pub const ERROR_TEXT: Color = Color::srgb(0.85, 0.25, 0.25);
+pub const AM_VOICE: Color = Color::srgb(0.90, 0.10, 0.10);
+
+pub const DISABLED_BUTTON: Color = Color::srgb(0.08, 0.08, 0.08);
+pub const DISABLED_TEXT: Color = Color::srgb(0.45, 0.45, 0.45);
+
+pub const DEFAULT_SERVER_PORT: u16 = 5000;
+pub const DISCOVERY_PORT: u16 = 5001;
+// Five survivors and AM
+pub const MAX_PLAYERS_PER_ROOM: usize = 6;
+// How close a survivor has to be to reach out to something
+pub const INTERACT_RANGE: f32 = 80.0;
\ No newline at end of file
@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub mod components;
+pub mod resources;
+pub mod systems;
+pub mod plugins;
+pub mod consts;
+pub mod protocol;
+pub mod server;
+pub mod link_conditioner;
+pub mod scenario;
+pub mod animation;
+
+#[derive(Debug, Eq, PartialEq, Hash, Resource, States, Default, Clone)]
+pub enum GameState {
+    #[default]
+    MainMenu,
+    Lobby,
+    CreateRoom,
+    JoinByCode,
+    // Inside a room, waiting for the host to start the match
+    PreMatch,
+    // Survivors pick their characters against the clock
+    CharacterSelect,
+    InGame,
+    // What really happened, once the match is over
+    Debrief,
+}
+
+// Stage of a running match. The server moves every room through these on the scenario's clock.
+#[derive(SubStates, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(GameState = GameState::InGame)]
+pub enum MatchPhase {
+    #[default]
+    Briefing,
+    AmSetup,
+    Exploration,
+    Confrontation,
+    Resolution,
+    Debrief,
+}
+
+impl MatchPhase {
+    pub fn next(&self) -> Option<MatchPhase> {
+        match self {
+            MatchPhase::Briefing => Some(MatchPhase::AmSetup),
+            MatchPhase::AmSetup => Some(MatchPhase::Exploration),
+            MatchPhase::Exploration => Some(MatchPhase::Confrontation),
+            MatchPhase::Confrontation => Some(MatchPhase::Resolution),
+            MatchPhase::Resolution => Some(MatchPhase::Debrief),
+            MatchPhase::Debrief => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MatchPhase::Briefing => "Briefing",
+            MatchPhase::AmSetup => "AM prepares",
+            MatchPhase::Exploration => "Exploration",
+            MatchPhase::Confrontation => "Confrontation",
+            MatchPhase::Resolution => "Resolution",
+            MatchPhase::Debrief => "Debrief",
+        }
+    }
+
+    // Survivors stand still while they are briefed, while AM prepares and once it is over
+    pub fn survivors_move(&self) -> bool {
+        matches!(self, MatchPhase::Exploration | MatchPhase::Confrontation | MatchPhase::Resolution)
+    }
+}
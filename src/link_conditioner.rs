@@ -0,0 +1,341 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BinaryHeap, HashMap};
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+// Peers that stayed quiet this long get their upstream socket closed
+const IDLE_PEER_TIMEOUT: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+const MAX_PACKET_BYTES: usize = 2048;
+
+// Bad network conditions applied to every packet in both directions
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkConditions {
+    pub latency_ms: u32,
+    // Each packet gets up to this much extra or less delay, so packets also get reordered
+    pub jitter_ms: u32,
+    // Chance between 0 and 1 of a packet being dropped
+    pub loss: f32,
+    // Chance between 0 and 1 of a packet being sent twice
+    pub duplicate: f32,
+}
+
+impl LinkConditions {
+    // Parses "latency=120,jitter=30,loss=0.05,duplicate=0.01", any key can be left out
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut conditions = Self::default();
+        for part in spec.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key=value, got '{}'", part))?;
+            let invalid = || format!("Invalid value '{}' for {}", value, key);
+            match key.trim() {
+                "latency" => conditions.latency_ms = value.trim().parse().map_err(|_| invalid())?,
+                "jitter" => conditions.jitter_ms = value.trim().parse().map_err(|_| invalid())?,
+                "loss" => conditions.loss = parse_chance(value).ok_or_else(invalid)?,
+                "duplicate" | "dup" => conditions.duplicate = parse_chance(value).ok_or_else(invalid)?,
+                other => return Err(format!("Unknown link condition '{}'", other)),
+            }
+        }
+        Ok(conditions)
+    }
+
+    fn delay(&self, rng: &mut impl Rng) -> Duration {
+        let jitter = self.jitter_ms as i64;
+        let offset = if jitter > 0 { rng.gen_range(-jitter..=jitter) } else { 0 };
+        Duration::from_millis((self.latency_ms as i64 + offset).max(0) as u64)
+    }
+}
+
+impl std::fmt::Display for LinkConditions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "latency={},jitter={},loss={},duplicate={}",
+            self.latency_ms, self.jitter_ms, self.loss, self.duplicate
+        )
+    }
+}
+
+fn parse_chance(value: &str) -> Option<f32> {
+    let chance: f32 = value.trim().parse().ok()?;
+    (0.0..=1.0).contains(&chance).then_some(chance)
+}
+
+// UDP relay that delays, drops and duplicates everything passing through it.
+// Peers talk to the relay's address, each one gets its own upstream socket so the target still
+// tells them apart by address.
+pub struct LinkConditioner {
+    local_addr: SocketAddr,
+    conditions: Arc<Mutex<LinkConditions>>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl LinkConditioner {
+    // Relays packets arriving on the socket to the target, and the target's answers back
+    pub fn spawn(socket: UdpSocket, target: SocketAddr, conditions: LinkConditions) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        let local_addr = socket.local_addr()?;
+        let conditions = Arc::new(Mutex::new(conditions));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let relay = Relay {
+            socket,
+            target,
+            conditions: conditions.clone(),
+            shutdown: shutdown.clone(),
+            peers: HashMap::new(),
+            queue: BinaryHeap::new(),
+            next_order: 0,
+        };
+        std::thread::Builder::new()
+            .name("link-conditioner".to_string())
+            .spawn(move || relay.run())?;
+        println!("Link conditioner on {} relaying to {}", local_addr, target);
+        Ok(Self {
+            local_addr,
+            conditions,
+            shutdown,
+        })
+    }
+
+    // Binds a loopback port in front of the target
+    pub fn in_front_of(target: SocketAddr, conditions: LinkConditions) -> io::Result<Self> {
+        Self::spawn(UdpSocket::bind("127.0.0.1:0")?, target, conditions)
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn conditions(&self) -> LinkConditions {
+        self.conditions.lock().unwrap().clone()
+    }
+
+    // Takes effect for the next packet, packets already queued keep their delay
+    pub fn set_conditions(&self, conditions: LinkConditions) {
+        *self.conditions.lock().unwrap() = conditions;
+    }
+}
+
+impl Drop for LinkConditioner {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    // From a peer towards the target
+    Upstream,
+    // From the target back to a peer
+    Downstream,
+}
+
+struct Peer {
+    socket: UdpSocket,
+    last_seen: Instant,
+}
+
+struct QueuedPacket {
+    deliver_at: Instant,
+    // Keeps packets with the same deadline in arrival order
+    order: u64,
+    direction: Direction,
+    peer: SocketAddr,
+    payload: Vec<u8>,
+}
+
+impl PartialEq for QueuedPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for QueuedPacket {}
+
+impl PartialOrd for QueuedPacket {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Reversed so the binary heap pops the packet due first
+impl Ord for QueuedPacket {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        (other.deliver_at, other.order).cmp(&(self.deliver_at, self.order))
+    }
+}
+
+struct Relay {
+    socket: UdpSocket,
+    target: SocketAddr,
+    conditions: Arc<Mutex<LinkConditions>>,
+    shutdown: Arc<AtomicBool>,
+    peers: HashMap<SocketAddr, Peer>,
+    queue: BinaryHeap<QueuedPacket>,
+    next_order: u64,
+}
+
+impl Relay {
+    fn run(mut self) {
+        let mut buffer = [0u8; MAX_PACKET_BYTES];
+        while !self.shutdown.load(Ordering::Relaxed) {
+            let now = Instant::now();
+            self.receive_from_peers(&mut buffer, now);
+            self.receive_from_target(&mut buffer, now);
+            self.deliver_due(now);
+            self.peers.retain(|_, peer| now.duration_since(peer.last_seen) < IDLE_PEER_TIMEOUT);
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn receive_from_peers(&mut self, buffer: &mut [u8], now: Instant) {
+        loop {
+            let (len, from) = match self.socket.recv_from(buffer) {
+                Ok(received) => received,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return,
+                // Errors like a reset from a peer that went away can repeat forever, try again next poll
+                Err(err) => {
+                    println!("Link conditioner could not read from a peer: {}", err);
+                    return;
+                }
+            };
+            if !self.peers.contains_key(&from) {
+                match self.open_upstream() {
+                    Ok(socket) => {
+                        self.peers.insert(from, Peer { socket, last_seen: now });
+                    }
+                    Err(err) => {
+                        println!("Link conditioner could not open a socket for {}: {}", from, err);
+                        continue;
+                    }
+                }
+            }
+            if let Some(peer) = self.peers.get_mut(&from) {
+                peer.last_seen = now;
+            }
+            self.schedule(Direction::Upstream, from, &buffer[..len], now);
+        }
+    }
+
+    fn receive_from_target(&mut self, buffer: &mut [u8], now: Instant) {
+        let mut received = Vec::new();
+        for (addr, peer) in &self.peers {
+            loop {
+                match peer.socket.recv_from(buffer) {
+                    Ok((len, _)) => received.push((*addr, buffer[..len].to_vec())),
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                    Err(err) => {
+                        println!("Link conditioner could not read from {}: {}", self.target, err);
+                        break;
+                    }
+                }
+            }
+        }
+        for (addr, payload) in received {
+            self.schedule(Direction::Downstream, addr, &payload, now);
+        }
+    }
+
+    fn open_upstream(&self) -> io::Result<UdpSocket> {
+        let socket = if self.target.ip().is_loopback() {
+            UdpSocket::bind("127.0.0.1:0")?
+        } else {
+            UdpSocket::bind("0.0.0.0:0")?
+        };
+        socket.set_nonblocking(true)?;
+        Ok(socket)
+    }
+
+    fn schedule(&mut self, direction: Direction, peer: SocketAddr, payload: &[u8], now: Instant) {
+        let conditions = self.conditions.lock().unwrap().clone();
+        let mut rng = rand::thread_rng();
+        if rng.gen::<f32>() < conditions.loss {
+            return;
+        }
+        let copies = if rng.gen::<f32>() < conditions.duplicate { 2 } else { 1 };
+        for _ in 0..copies {
+            self.next_order += 1;
+            self.queue.push(QueuedPacket {
+                deliver_at: now + conditions.delay(&mut rng),
+                order: self.next_order,
+                direction,
+                peer,
+                payload: payload.to_vec(),
+            });
+        }
+    }
+
+    fn deliver_due(&mut self, now: Instant) {
+        while self.queue.peek().map_or(false, |packet| packet.deliver_at <= now) {
+            let Some(packet) = self.queue.pop() else {
+                break;
+            };
+            // Sends can fail when a buffer is full, which is just more packet loss
+            let _ = match packet.direction {
+                Direction::Upstream => match self.peers.get(&packet.peer) {
+                    Some(peer) => peer.socket.send_to(&packet.payload, self.target),
+                    None => continue,
+                },
+                Direction::Downstream => self.socket.send_to(&packet.payload, packet.peer),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_any_subset_of_conditions() {
+        assert_eq!(
+            LinkConditions::parse("latency=120, jitter=30,loss=0.05,dup=0.01"),
+            Ok(LinkConditions {
+                latency_ms: 120,
+                jitter_ms: 30,
+                loss: 0.05,
+                duplicate: 0.01,
+            })
+        );
+        assert_eq!(LinkConditions::parse("loss=1"), Ok(LinkConditions { loss: 1.0, ..Default::default() }));
+        assert_eq!(LinkConditions::parse(""), Ok(LinkConditions::default()));
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        let conditions = LinkConditions {
+            latency_ms: 80,
+            jitter_ms: 5,
+            loss: 0.25,
+            duplicate: 0.5,
+        };
+        assert_eq!(LinkConditions::parse(&conditions.to_string()), Ok(conditions));
+    }
+
+    #[test]
+    fn refuses_bad_conditions() {
+        assert!(LinkConditions::parse("loss=1.5").is_err());
+        assert!(LinkConditions::parse("latency=-5").is_err());
+        assert!(LinkConditions::parse("latency").is_err());
+        assert!(LinkConditions::parse("bandwidth=10").is_err());
+    }
+
+    #[test]
+    fn delays_stay_within_the_jitter() {
+        let conditions = LinkConditions {
+            latency_ms: 5,
+            jitter_ms: 50,
+            ..Default::default()
+        };
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            assert!(conditions.delay(&mut rng) <= Duration::from_millis(55));
+        }
+    }
+}
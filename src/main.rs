@@ -1,29 +1,53 @@
-use bevy::prelude::*;
-use plugins::game_runner::GameRunnerPlugin;
-use plugins::lobby::LobbyPlugin;
-use plugins::main_menu::MainMenuPlugin;
-use plugins::create_room::RoomCreator;
-use plugins::ingame_player::PlayerInGamePlugin;
-
-mod components;
-mod resources;
-mod systems;
-mod plugins;
-mod consts; 
+use std::process::ExitCode;
 
-#[derive(Debug, Eq, PartialEq, Hash, Resource, States, Default, Clone)]
-enum GameState {
-    #[default]
-    MainMenu,
-    Lobby,
-    CreateRoom,
-    InGame,
-}
+use bevy::prelude::*;
+use ergo_cogito_sum::GameState;
+use ergo_cogito_sum::plugins::game_runner::GameRunnerPlugin;
+use ergo_cogito_sum::plugins::lobby::LobbyPlugin;
+use ergo_cogito_sum::plugins::main_menu::MainMenuPlugin;
+use ergo_cogito_sum::plugins::create_room::RoomCreator;
+use ergo_cogito_sum::plugins::ingame_player::PlayerInGamePlugin;
+use ergo_cogito_sum::plugins::network::{NetworkPlugin, NetworkSettings};
+use ergo_cogito_sum::plugins::join_by_code::JoinByCodePlugin;
+use ergo_cogito_sum::plugins::room_hud::RoomHudPlugin;
+use ergo_cogito_sum::plugins::lan_discovery::LanDiscoveryPlugin;
+use ergo_cogito_sum::plugins::prediction::PredictionPlugin;
+use ergo_cogito_sum::plugins::interpolation::InterpolationPlugin;
+use ergo_cogito_sum::plugins::connection_status::ConnectionStatusPlugin;
+#[cfg(debug_assertions)]
+use ergo_cogito_sum::plugins::dev_console::DevConsolePlugin;
+use ergo_cogito_sum::plugins::pre_match::PreMatchPlugin;
+use ergo_cogito_sum::plugins::character_select::CharacterSelectPlugin;
+use ergo_cogito_sum::plugins::scenario_library::ScenarioLibraryPlugin;
+use ergo_cogito_sum::plugins::match_phase::MatchPhasePlugin;
+use ergo_cogito_sum::plugins::narrative::NarrativePlugin;
+use ergo_cogito_sum::plugins::hallucination::HallucinationPlugin;
+use ergo_cogito_sum::plugins::world_effects::WorldEffectsPlugin;
+use ergo_cogito_sum::plugins::am_panel::AmPanelPlugin;
+use ergo_cogito_sum::plugins::trust::TrustPlugin;
+use ergo_cogito_sum::plugins::voting::VotingPlugin;
+use ergo_cogito_sum::plugins::chat::ChatPlugin;
+use ergo_cogito_sum::plugins::debrief::DebriefPlugin;
+use ergo_cogito_sum::plugins::sanity::SanityPlugin;
  
-fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
+fn main() -> ExitCode {
+    let network_settings = match NetworkSettings::from_args(std::env::args().skip(1)) {
+        Ok(settings) => settings,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins)
         .init_state::<GameState>()
-        .add_plugins((GameRunnerPlugin,MainMenuPlugin,LobbyPlugin,RoomCreator,PlayerInGamePlugin))
-        .run();
-}
\ No newline at end of file
+        .insert_resource(network_settings)
+        .add_plugins((GameRunnerPlugin,MainMenuPlugin,LobbyPlugin,RoomCreator,PlayerInGamePlugin,NetworkPlugin,JoinByCodePlugin,RoomHudPlugin,LanDiscoveryPlugin,PredictionPlugin,InterpolationPlugin,ConnectionStatusPlugin))
+        .add_plugins((PreMatchPlugin,CharacterSelectPlugin,ScenarioLibraryPlugin,MatchPhasePlugin,NarrativePlugin,HallucinationPlugin,WorldEffectsPlugin,AmPanelPlugin,TrustPlugin,VotingPlugin,ChatPlugin,DebriefPlugin,SanityPlugin));
+    // The console shows everything AM keeps from the survivors, release builds leave it out
+    #[cfg(debug_assertions)]
+    app.add_plugins(DevConsolePlugin);
+    app.run();
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,882 @@
+use std::collections::HashMap;
+
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::GameState;
+use crate::components::appearance::SpriteSet;
+use crate::components::role::Role;
+use crate::consts;
+use crate::plugins::ingame_player::PlayerAnimations;
+use crate::plugins::interpolation::RemotePlayer;
+use crate::plugins::network::LocalClientId;
+use crate::protocol::{
+    ClientMessage, Exposure, IllusionKind, InterventionAction, NarrativeEvent, NarrativeText, NarrativeVersion,
+    SanityLevel, ServerMessage, TrustPair, MAX_NARRATIVE_CHARS,
+};
+use crate::resources::match_roles::MatchRoles;
+use crate::resources::world_effects::WorldEffects;
+use crate::scenario::{Intervention, InterventionKind, Scenario};
+use crate::systems::text_input::edit_text;
+
+pub struct AmPanelPlugin;
+
+const MAP_WIDTH_PX: f32 = 560.0;
+const DOT_SIZE_PX: f32 = 10.0;
+const DOOR_WIDTH_PX: f32 = 4.0;
+const HATE_BAR_WIDTH_PX: f32 = 260.0;
+const MAX_AM_FEED_LINES: usize = 6;
+const HATE_COLOR: Color = Color::srgb(0.7, 0.05, 0.05);
+const MAP_COLOR: Color = Color::srgb(0.12, 0.1, 0.1);
+
+#[derive(Component)]
+struct OnAmPanel;
+
+#[derive(Component)]
+struct AmMap;
+
+// Everything drawn on the map, redrawn every frame from where the survivors are now
+#[derive(Component)]
+struct AmMapMarker;
+
+#[derive(Component)]
+struct HateBar;
+
+#[derive(Component)]
+struct HateText;
+
+#[derive(Component)]
+struct AmMessageText;
+
+#[derive(Component)]
+struct AmSelectionText;
+
+#[derive(Component)]
+struct AmVersionsText;
+
+#[derive(Component)]
+struct AddVersionButton;
+
+#[derive(Component, Clone, Copy)]
+enum IllusionSettingButton {
+    Kind,
+    Sprites,
+    Keep,
+}
+
+// Which illusion the hallucination intervention casts
+#[derive(Clone, Copy, Default, PartialEq)]
+enum IllusionChoice {
+    #[default]
+    FakeItem,
+    FakePlayer,
+    SpriteOverride,
+    HiddenPlayer,
+}
+
+impl IllusionChoice {
+    const ALL: [IllusionChoice; 4] = [
+        IllusionChoice::FakeItem,
+        IllusionChoice::FakePlayer,
+        IllusionChoice::SpriteOverride,
+        IllusionChoice::HiddenPlayer,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            IllusionChoice::FakeItem => "Fake item",
+            IllusionChoice::FakePlayer => "Fake survivor",
+            IllusionChoice::SpriteOverride => "Wrong face",
+            IllusionChoice::HiddenPlayer => "Hidden survivor",
+        }
+    }
+}
+
+#[derive(Component)]
+struct AmStatusText;
+
+#[derive(Component)]
+struct InterventionButton(String);
+
+#[derive(Component)]
+struct SurvivorButton(u64);
+
+#[derive(Component)]
+struct SurvivorFeedText(u64);
+
+// What AM knows and has picked. Lives outside the panel since the scenario arrives with the roles.
+#[derive(Resource, Default)]
+pub struct AmConsole {
+    pub scenario: Option<Scenario>,
+    pub hate: f32,
+    pub max_hate: f32,
+    pub cooldowns: HashMap<String, f32>,
+    pub score: f32,
+    // How much each survivor trusts each other one, only AM sees all of it
+    pub trust: Vec<TrustPair>,
+    pub sanity: Vec<SanityLevel>,
+    // Everything each survivor was told, newest last
+    pub feeds: HashMap<u64, Vec<String>>,
+    // Survivors the next version or illusion is aimed at, in the order they were picked
+    targets: Vec<u64>,
+    // Versions of the next narrative put aside so far, each told only to its own survivors
+    versions: Vec<NarrativeVersion>,
+    position: Option<Vec2>,
+    message: String,
+    // Told to the target when they see through the illusion, Tab switches typing between the two
+    exposure: String,
+    typing_exposure: bool,
+    illusion: IllusionChoice,
+    // What a wrong face looks like, one of the sets this client found
+    sprites: Option<SpriteSet>,
+    // Whether the illusion keeps fooling the target once exposed
+    keep_illusion: bool,
+    status: String,
+}
+
+impl AmConsole {
+    // First intervention in the scenario that can carry out the action
+    pub fn intervention_for(&self, action: &InterventionAction) -> Option<&Intervention> {
+        self.scenario
+            .as_ref()?
+            .interventions
+            .iter()
+            .find(|intervention| action.fits(&intervention.kind))
+    }
+
+    // What AM typed, for anything that puts words in someone's mouth
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn is_ready(&self, intervention: &Intervention) -> bool {
+        !self.cooldowns.contains_key(&intervention.id) && self.hate >= intervention.cost
+    }
+
+    // What AM typed as a version for the survivors picked right now
+    fn current_version(&self) -> Result<NarrativeVersion, String> {
+        if self.targets.is_empty() {
+            return Err("Pick who hears it first".to_string());
+        }
+        let message = self.message.trim();
+        if message.is_empty() {
+            return Err("Type something first".to_string());
+        }
+        let told = |client_id: &u64| self.versions.iter().any(|version| version.recipients.contains(client_id));
+        if self.targets.iter().any(told) {
+            return Err("Each survivor can only hear one version".to_string());
+        }
+        Ok(NarrativeVersion {
+            recipients: self.targets.clone(),
+            text: NarrativeText::parse(message),
+        })
+    }
+}
+
+impl Plugin for AmPanelPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<AmConsole>()
+            .add_systems(Update, receive_am_messages)
+            .add_systems(OnEnter(GameState::InGame), setup_am_panel.run_if(local_is_am))
+            .add_systems(
+                Update,
+                (
+                    count_down_cooldowns,
+                    type_am_message,
+                    pick_map_position,
+                    handle_survivor_buttons,
+                    handle_add_version_button,
+                    handle_illusion_setting_buttons,
+                    handle_intervention_buttons,
+                    update_am_map,
+                    update_am_panel,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame).and_then(local_is_am)),
+            )
+            .add_systems(OnExit(GameState::InGame), cleanup_am_panel);
+    }
+}
+
+// Run condition for everything only AM sees
+pub fn local_is_am(roles: Option<Res<MatchRoles>>, local_client: Option<Res<LocalClientId>>) -> bool {
+    match (roles, local_client) {
+        (Some(roles), Some(local_client)) => roles.role_of(local_client.0) == Some(Role::Am),
+        _ => false,
+    }
+}
+
+// System to keep what the server tells AM alone
+fn receive_am_messages(mut server_messages: EventReader<ServerMessage>, mut console: ResMut<AmConsole>) {
+    for message in server_messages.read() {
+        match message {
+            ServerMessage::AmScenario(scenario) => console.scenario = Some(scenario.clone()),
+            ServerMessage::AmStatus { hate, max_hate, cooldowns, score, trust, sanity } => {
+                console.hate = *hate;
+                console.max_hate = *max_hate;
+                console.score = *score;
+                console.trust = trust.clone();
+                console.sanity = sanity.clone();
+                console.cooldowns = cooldowns
+                    .iter()
+                    .map(|cooldown| (cooldown.id.clone(), cooldown.seconds_left))
+                    .collect();
+            }
+            ServerMessage::AmFeed { client_id, text, forgotten } => {
+                let line = if *forgotten { format!("(forgot) {}", text) } else { text.clone() };
+                console.feeds.entry(*client_id).or_default().push(line);
+            }
+            ServerMessage::ActionError(error) => console.status = error.to_string(),
+            _ => {}
+        }
+    }
+}
+
+// System to set up the god view, one feed column per survivor
+fn setup_am_panel(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    console: Res<AmConsole>,
+    roles: Res<MatchRoles>,
+) {
+    let font = asset_server.load("fonts/Debrosee-ALPnL.ttf");
+    let text_style = |font_size: f32, color: Color| TextStyle {
+        font: font.clone(),
+        font_size,
+        color,
+    };
+    let map_height = console
+        .scenario
+        .as_ref()
+        .map_or(MAP_WIDTH_PX / 3.0, |scenario| MAP_WIDTH_PX * scenario.map.height / scenario.map.width);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(16.0)),
+                    ..Default::default()
+                },
+                background_color: Color::srgba(0.05, 0.0, 0.0, 0.92).into(),
+                ..Default::default()
+            },
+            OnAmPanel,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        column_gap: Val::Px(24.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        })
+                        .with_children(|parent| {
+                            let map_name = console.scenario.as_ref().map_or("", |scenario| scenario.map.name.as_str());
+                            parent.spawn(TextBundle::from_section(map_name, text_style(28.0, consts::AM_VOICE)));
+                            parent.spawn((
+                                ButtonBundle {
+                                    style: Style {
+                                        width: Val::Px(MAP_WIDTH_PX),
+                                        height: Val::Px(map_height),
+                                        margin: UiRect::vertical(Val::Px(8.0)),
+                                        ..Default::default()
+                                    },
+                                    background_color: MAP_COLOR.into(),
+                                    ..Default::default()
+                                },
+                                AmMap,
+                            ));
+                            parent.spawn((TextBundle::from_section("", text_style(20.0, Color::WHITE)), AmSelectionText));
+                        });
+
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                row_gap: Val::Px(6.0),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        })
+                        .with_children(|parent| {
+                            parent.spawn((TextBundle::from_section("", text_style(24.0, Color::WHITE)), HateText));
+                            parent
+                                .spawn(NodeBundle {
+                                    style: Style {
+                                        width: Val::Px(HATE_BAR_WIDTH_PX),
+                                        height: Val::Px(14.0),
+                                        ..Default::default()
+                                    },
+                                    background_color: MAP_COLOR.into(),
+                                    ..Default::default()
+                                })
+                                .with_children(|parent| {
+                                    parent.spawn((
+                                        NodeBundle {
+                                            style: Style {
+                                                width: Val::Percent(0.0),
+                                                height: Val::Percent(100.0),
+                                                ..Default::default()
+                                            },
+                                            background_color: HATE_COLOR.into(),
+                                            ..Default::default()
+                                        },
+                                        HateBar,
+                                    ));
+                                });
+                            parent.spawn((TextBundle::from_section("", text_style(20.0, Color::WHITE)), AmMessageText));
+                            parent.spawn((TextBundle::from_section("", text_style(16.0, consts::AM_VOICE)), AmVersionsText));
+                            parent
+                                .spawn((
+                                    ButtonBundle {
+                                        style: Style {
+                                            width: Val::Px(HATE_BAR_WIDTH_PX),
+                                            height: Val::Px(32.0),
+                                            justify_content: JustifyContent::Center,
+                                            align_items: AlignItems::Center,
+                                            ..Default::default()
+                                        },
+                                        background_color: consts::NORMAL_BUTTON.into(),
+                                        ..Default::default()
+                                    },
+                                    AddVersionButton,
+                                ))
+                                .with_children(|parent| {
+                                    let label = "Keep as a version";
+                                    parent.spawn(TextBundle::from_section(label, text_style(18.0, Color::WHITE)));
+                                });
+                            for setting in [IllusionSettingButton::Kind, IllusionSettingButton::Sprites, IllusionSettingButton::Keep] {
+                                parent
+                                    .spawn((
+                                        ButtonBundle {
+                                            style: Style {
+                                                width: Val::Px(HATE_BAR_WIDTH_PX),
+                                                height: Val::Px(32.0),
+                                                justify_content: JustifyContent::Center,
+                                                align_items: AlignItems::Center,
+                                                ..Default::default()
+                                            },
+                                            background_color: consts::NORMAL_BUTTON.into(),
+                                            ..Default::default()
+                                        },
+                                        setting,
+                                    ))
+                                    .with_children(|parent| {
+                                        parent.spawn(TextBundle::from_section("", text_style(18.0, Color::WHITE)));
+                                    });
+                            }
+
+                            let interventions = console.scenario.iter().flat_map(|scenario| &scenario.interventions);
+                            for intervention in interventions {
+                                parent
+                                    .spawn((
+                                        ButtonBundle {
+                                            style: Style {
+                                                width: Val::Px(HATE_BAR_WIDTH_PX),
+                                                height: Val::Px(40.0),
+                                                justify_content: JustifyContent::Center,
+                                                align_items: AlignItems::Center,
+                                                ..Default::default()
+                                            },
+                                            background_color: consts::NORMAL_BUTTON.into(),
+                                            ..Default::default()
+                                        },
+                                        InterventionButton(intervention.id.clone()),
+                                    ))
+                                    .with_children(|parent| {
+                                        parent.spawn(TextBundle::from_section("", text_style(20.0, Color::WHITE)));
+                                    });
+                            }
+                            parent.spawn((TextBundle::from_section("", text_style(20.0, consts::ERROR_TEXT)), AmStatusText));
+                        });
+                });
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        column_gap: Val::Px(12.0),
+                        margin: UiRect::top(Val::Px(16.0)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for assignment in roles.0.iter().filter(|assignment| matches!(assignment.role, Role::Survivor(_))) {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Column,
+                                    width: Val::Px(220.0),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            })
+                            .with_children(|parent| {
+                                parent
+                                    .spawn((
+                                        ButtonBundle {
+                                            style: Style {
+                                                height: Val::Px(36.0),
+                                                justify_content: JustifyContent::Center,
+                                                align_items: AlignItems::Center,
+                                                ..Default::default()
+                                            },
+                                            background_color: consts::NORMAL_BUTTON.into(),
+                                            ..Default::default()
+                                        },
+                                        SurvivorButton(assignment.client_id),
+                                    ))
+                                    .with_children(|parent| {
+                                        let label = format!("{} ({})", assignment.role.name(), assignment.name);
+                                        parent.spawn(TextBundle::from_section(label, text_style(20.0, Color::WHITE)));
+                                    });
+                                parent.spawn((
+                                    TextBundle::from_section("", text_style(16.0, consts::AM_VOICE)),
+                                    SurvivorFeedText(assignment.client_id),
+                                ));
+                            });
+                    }
+                });
+        });
+}
+
+// System to count cooldowns down between two status updates from the server
+fn count_down_cooldowns(time: Res<Time>, mut console: ResMut<AmConsole>) {
+    let delta = time.delta_seconds();
+    for seconds_left in console.cooldowns.values_mut() {
+        *seconds_left -= delta;
+    }
+    console.cooldowns.retain(|_, seconds_left| *seconds_left > 0.0);
+}
+
+// System to type what AM will say or show, AM has no body so the keyboard is free
+fn type_am_message(mut keyboard_input: EventReader<KeyboardInput>, mut console: ResMut<AmConsole>) {
+    let console = console.as_mut();
+    for ev in keyboard_input.read() {
+        if ev.state == ButtonState::Released || ev.key_code == KeyCode::Backquote {
+            continue;
+        }
+        if ev.logical_key == Key::Tab {
+            console.typing_exposure = !console.typing_exposure;
+            continue;
+        }
+        let text = if console.typing_exposure { &mut console.exposure } else { &mut console.message };
+        edit_text(&ev.logical_key, text, MAX_NARRATIVE_CHARS);
+    }
+}
+
+// System to turn a click on the map into a spot in the world
+fn pick_map_position(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    map_query: Query<(&Interaction, &Node, &GlobalTransform), With<AmMap>>,
+    mut console: ResMut<AmConsole>,
+) {
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(cursor) = window_query.get_single().ok().and_then(|window| window.cursor_position()) else {
+        return;
+    };
+    let Ok((interaction, node, transform)) = map_query.get_single() else {
+        return;
+    };
+    if *interaction == Interaction::None {
+        return;
+    }
+    let Some(map) = console.scenario.as_ref().map(|scenario| Vec2::new(scenario.map.width, scenario.map.height)) else {
+        return;
+    };
+    let size = node.size();
+    let local = (cursor - (transform.translation().truncate() - size / 2.0)) / size;
+    console.position = Some(Vec2::new((local.x - 0.5) * map.x, (0.5 - local.y) * map.y));
+}
+
+// System to pick which survivors the next intervention is aimed at, a second press takes one back out
+fn handle_survivor_buttons(
+    interaction_query: Query<(&Interaction, &SurvivorButton), Changed<Interaction>>,
+    mut console: ResMut<AmConsole>,
+) {
+    for (interaction, SurvivorButton(client_id)) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match console.targets.iter().position(|target| target == client_id) {
+            Some(index) => {
+                console.targets.remove(index);
+            }
+            None => console.targets.push(*client_id),
+        }
+    }
+}
+
+// System to pick which illusion the next hallucination casts and how it behaves once exposed
+fn handle_illusion_setting_buttons(
+    interaction_query: Query<(&Interaction, &IllusionSettingButton), Changed<Interaction>>,
+    animations: Option<Res<PlayerAnimations>>,
+    mut console: ResMut<AmConsole>,
+) {
+    for (interaction, setting) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match setting {
+            IllusionSettingButton::Kind => {
+                let index = IllusionChoice::ALL.iter().position(|choice| *choice == console.illusion).unwrap_or_default();
+                console.illusion = IllusionChoice::ALL[(index + 1) % IllusionChoice::ALL.len()];
+            }
+            IllusionSettingButton::Sprites => {
+                let sets: Vec<&SpriteSet> = animations.iter().flat_map(|animations| animations.sets()).collect();
+                // Starts from the first set and goes round
+                let next = match sets.iter().position(|sprites| Some(*sprites) == console.sprites.as_ref()) {
+                    Some(index) => (index + 1) % sets.len(),
+                    None => 0,
+                };
+                console.sprites = sets.get(next).map(|sprites| (*sprites).clone());
+            }
+            IllusionSettingButton::Keep => console.keep_illusion = !console.keep_illusion,
+        }
+    }
+}
+
+// System to put what AM typed aside as one version, so the next one can tell the others something else
+fn handle_add_version_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<AddVersionButton>)>,
+    mut console: ResMut<AmConsole>,
+) {
+    for interaction in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match console.current_version() {
+            Ok(version) => {
+                console.versions.push(version);
+                console.targets.clear();
+                console.message.clear();
+                console.status.clear();
+            }
+            Err(error) => console.status = error,
+        }
+    }
+}
+
+// System to spend hate, the server has the last word on whether AM can afford it
+fn handle_intervention_buttons(
+    interaction_query: Query<(&Interaction, &InterventionButton), Changed<Interaction>>,
+    mut console: ResMut<AmConsole>,
+    mut client_messages: EventWriter<ClientMessage>,
+) {
+    for (interaction, InterventionButton(id)) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(intervention) = console.scenario.as_ref().and_then(|scenario| scenario.intervention(id)).cloned() else {
+            continue;
+        };
+        match build_action(&intervention, &console) {
+            Ok(action) => {
+                if matches!(action, InterventionAction::Narrate(_)) {
+                    console.message.clear();
+                    console.versions.clear();
+                }
+                console.status.clear();
+                client_messages.send(ClientMessage::Intervene { intervention: intervention.id, action });
+            }
+            Err(error) => console.status = error,
+        }
+    }
+}
+
+// Fills the intervention in from the survivors, the spot on the map and the text AM picked
+fn build_action(intervention: &Intervention, console: &AmConsole) -> Result<InterventionAction, String> {
+    let target = || console.targets.first().copied().ok_or_else(|| "Pick a survivor first".to_string());
+    let position = || console.position.ok_or_else(|| "Click the map first".to_string());
+    let message = || {
+        let message = console.message.trim();
+        if message.is_empty() {
+            Err("Type something first".to_string())
+        } else {
+            Ok(message.to_string())
+        }
+    };
+    let action = match intervention.kind {
+        InterventionKind::Narrate => {
+            let mut versions = console.versions.clone();
+            // Whatever is still typed goes out as the last version
+            if versions.is_empty() || !console.message.trim().is_empty() {
+                versions.push(console.current_version()?);
+            }
+            InterventionAction::Narrate(NarrativeEvent {
+                versions,
+                truth: None,
+                rumour: None,
+            })
+        }
+        InterventionKind::Hallucination => {
+            // The first survivor picked is fooled, the second is who the illusion is about
+            let other = || console.targets.get(1).copied().ok_or_else(|| "Pick who the illusion is about next".to_string());
+            let kind = match console.illusion {
+                IllusionChoice::FakeItem => IllusionKind::FakeItem {
+                    item: message()?,
+                    position: position()?,
+                },
+                IllusionChoice::FakePlayer => IllusionKind::FakePlayer {
+                    of: other()?,
+                    position: position()?,
+                },
+                IllusionChoice::SpriteOverride => IllusionKind::SpriteOverride {
+                    client_id: other()?,
+                    sprites: console.sprites.clone().ok_or_else(|| "Pick the face they will see".to_string())?,
+                },
+                IllusionChoice::HiddenPlayer => IllusionKind::HiddenPlayer { client_id: other()? },
+            };
+            let exposure = console.exposure.trim();
+            InterventionAction::Hallucination {
+                target: target()?,
+                kind,
+                exposure: Exposure {
+                    message: (!exposure.is_empty()).then(|| NarrativeText::parse(exposure)),
+                    dispel: !console.keep_illusion,
+                },
+            }
+        }
+        InterventionKind::Hazard { .. } => InterventionAction::Hazard { position: position()? },
+        InterventionKind::LockDoor { .. } => {
+            let position = position()?;
+            let doors = console.scenario.iter().flat_map(|scenario| &scenario.map.doors);
+            let door = doors
+                .min_by(|a, b| (a.x - position.x).abs().total_cmp(&(b.x - position.x).abs()))
+                .ok_or_else(|| "The map has no doors".to_string())?;
+            InterventionAction::LockDoor { door: door.id.clone() }
+        }
+    };
+    Ok(action)
+}
+
+// System to draw the survivors, the hazards, the dark stretches and the doors on AM's map
+fn update_am_map(
+    mut commands: Commands,
+    console: Res<AmConsole>,
+    effects: Res<WorldEffects>,
+    roles: Res<MatchRoles>,
+    map_query: Query<(Entity, &Node), With<AmMap>>,
+    remote_query: Query<(&RemotePlayer, &Transform)>,
+) {
+    let Ok((map_entity, node)) = map_query.get_single() else {
+        return;
+    };
+    let Some(scenario) = console.scenario.as_ref() else {
+        return;
+    };
+    let size = node.size();
+    // World position to the top left corner of a marker of the given size
+    let to_map = |position: Vec2, marker: Vec2| {
+        Vec2::new(
+            (position.x / scenario.map.width + 0.5) * size.x - marker.x / 2.0,
+            (0.5 - position.y / scenario.map.height) * size.y - marker.y / 2.0,
+        )
+    };
+    let marker = |at: Vec2, marker_size: Vec2, color: Color| {
+        (
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(at.x),
+                    top: Val::Px(at.y),
+                    width: Val::Px(marker_size.x),
+                    height: Val::Px(marker_size.y),
+                    ..Default::default()
+                },
+                background_color: color.into(),
+                ..Default::default()
+            },
+            AmMapMarker,
+        )
+    };
+
+    commands.entity(map_entity).despawn_descendants().with_children(|parent| {
+        for hazard in &effects.hazards {
+            let hazard_size = Vec2::splat(hazard.radius * 2.0) * size / Vec2::new(scenario.map.width, scenario.map.height);
+            parent.spawn(marker(to_map(hazard.position, hazard_size), hazard_size, HATE_COLOR.with_alpha(0.4)));
+        }
+        for stretch in &scenario.map.darkness {
+            let dark_size = Vec2::new((stretch.to_x - stretch.from_x) * size.x / scenario.map.width, size.y);
+            let middle = Vec2::new((stretch.from_x + stretch.to_x) / 2.0, 0.0);
+            parent.spawn(marker(to_map(middle, dark_size), dark_size, Color::BLACK.with_alpha(0.6)));
+        }
+        for door in &scenario.map.doors {
+            let door_size = Vec2::new(DOOR_WIDTH_PX, size.y);
+            let color = if effects.is_locked(&door.id) { HATE_COLOR } else { consts::DISABLED_BUTTON };
+            parent.spawn(marker(to_map(Vec2::new(door.x, 0.0), door_size), door_size, color));
+        }
+        for (remote, transform) in &remote_query {
+            let selected = console.targets.contains(&remote.client_id);
+            let known = roles.role_of(remote.client_id).is_some();
+            let color = if selected { consts::AM_VOICE } else if known { Color::WHITE } else { consts::DISABLED_TEXT };
+            let dot = Vec2::splat(DOT_SIZE_PX);
+            parent.spawn(marker(to_map(transform.translation.truncate(), dot), dot, color));
+        }
+        if let Some(position) = console.position {
+            let cross = Vec2::splat(DOT_SIZE_PX / 2.0);
+            parent.spawn(marker(to_map(position, cross), cross, consts::AM_VOICE));
+        }
+    });
+}
+
+// System to show AM's hate, what each intervention costs and what everyone was told
+fn update_am_panel(
+    console: Res<AmConsole>,
+    roles: Res<MatchRoles>,
+    mut bar_query: Query<&mut Style, With<HateBar>>,
+    mut button_query: Query<(&InterventionButton, &mut BackgroundColor, &Children)>,
+    mut survivor_query: Query<(&SurvivorButton, &mut BackgroundColor), Without<InterventionButton>>,
+    setting_query: Query<(&IllusionSettingButton, &Children)>,
+    mut text_query: Query<(
+        &mut Text,
+        Option<&HateText>,
+        Option<&AmMessageText>,
+        Option<&AmSelectionText>,
+        Option<&AmVersionsText>,
+        Option<&AmStatusText>,
+        Option<&SurvivorFeedText>,
+    )>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    for mut style in &mut bar_query {
+        let filled = if console.max_hate > 0.0 { console.hate / console.max_hate } else { 0.0 };
+        style.width = Val::Percent(filled.clamp(0.0, 1.0) * 100.0);
+    }
+
+    for (InterventionButton(id), mut color, children) in &mut button_query {
+        let Some(intervention) = console.scenario.as_ref().and_then(|scenario| scenario.intervention(id)) else {
+            continue;
+        };
+        let ready = console.is_ready(intervention);
+        *color = if ready { consts::NORMAL_BUTTON.into() } else { consts::DISABLED_BUTTON.into() };
+        let label = match console.cooldowns.get(id) {
+            Some(seconds_left) => format!("{} ({}s)", intervention.name, seconds_left.ceil() as u32),
+            None => format!("{} - {} hate", intervention.name, intervention.cost as u32),
+        };
+        for child in children {
+            if let Ok((mut text, ..)) = text_query.get_mut(*child) {
+                text.sections[0].value = label.clone();
+                text.sections[0].style.color = if ready { Color::WHITE } else { consts::DISABLED_TEXT };
+            }
+        }
+    }
+
+    for (SurvivorButton(client_id), mut color) in &mut survivor_query {
+        *color = if console.targets.contains(client_id) { consts::HOVERED_BUTTON.into() } else { consts::NORMAL_BUTTON.into() };
+    }
+
+    for (setting, children) in &setting_query {
+        let label = match setting {
+            IllusionSettingButton::Kind => format!("Illusion: {}", console.illusion.name()),
+            IllusionSettingButton::Sprites => match &console.sprites {
+                Some(sprites) => format!("Wrong face: {}", sprites.name()),
+                None => "Wrong face: pick one".to_string(),
+            },
+            IllusionSettingButton::Keep if console.keep_illusion => "Once exposed: keeps fooling".to_string(),
+            IllusionSettingButton::Keep => "Once exposed: vanishes".to_string(),
+        };
+        for child in children {
+            if let Ok((mut text, ..)) = text_query.get_mut(*child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+
+    let name = |client_id: &u64| roles.role_of(*client_id).map_or("someone", |role| role.name());
+    let targets = if console.targets.is_empty() {
+        "nobody".to_string()
+    } else {
+        let targets: Vec<String> = console
+            .targets
+            .iter()
+            .map(|client_id| match console.sanity.iter().find(|level| level.client_id == *client_id) {
+                Some(level) => format!("{} (sanity {:.0} / {:.0})", name(client_id), level.value, level.max),
+                None => name(client_id).to_string(),
+            })
+            .collect();
+        targets.join(", ")
+    };
+    let versions: Vec<String> = console
+        .versions
+        .iter()
+        .map(|version| {
+            let recipients: Vec<&str> = version.recipients.iter().map(name).collect();
+            let text = match &version.text {
+                NarrativeText::Line(id) => format!("@{}", id),
+                NarrativeText::Free(text) => text.clone(),
+            };
+            format!("{}: {}", recipients.join(", "), text)
+        })
+        .collect();
+    let position = console
+        .position
+        .map_or("nowhere".to_string(), |position| format!("{:.0}, {:.0}", position.x, position.y));
+
+    for (mut text, hate, message, selection, kept, status, feed) in &mut text_query {
+        let value = if hate.is_some() {
+            let trust = if console.trust.is_empty() {
+                "-".to_string()
+            } else {
+                let total: f32 = console.trust.iter().map(|pair| pair.value).sum();
+                format!("{:.0}%", total / console.trust.len() as f32 * 100.0)
+            };
+            format!(
+                "Hate {:.0} / {:.0}   Trust {}   Score {:.0}",
+                console.hate, console.max_hate, trust, console.score
+            )
+        } else if message.is_some() {
+            let cursor = |typing: bool| if typing { "_" } else { "" };
+            format!(
+                "Say: {}{}\nIf exposed: {}{}",
+                console.message,
+                cursor(!console.typing_exposure),
+                console.exposure,
+                cursor(console.typing_exposure)
+            )
+        } else if selection.is_some() {
+            format!("Target: {}   Spot: {}", targets, position)
+        } else if kept.is_some() {
+            versions.join("\n")
+        } else if status.is_some() {
+            console.status.clone()
+        } else if let Some(SurvivorFeedText(client_id)) = feed {
+            let lines = console.feeds.get(client_id).map(Vec::as_slice).unwrap_or_default();
+            lines[lines.len().saturating_sub(MAX_AM_FEED_LINES)..].join("\n")
+        } else {
+            continue;
+        };
+        text.sections[0].value = value;
+    }
+}
+
+fn cleanup_am_panel(mut commands: Commands, mut console: ResMut<AmConsole>, query: Query<Entity, With<OnAmPanel>>) {
+    *console = AmConsole::default();
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,326 @@
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::components::character_sheet::CharacterSheet;
+use crate::components::role::{Character, Role};
+use crate::consts;
+use crate::plugins::network::LocalClientId;
+use crate::protocol::{ClientMessage, DraftPick, ServerMessage};
+use crate::resources::selection_timer::SelectionTimer;
+
+pub struct CharacterSelectPlugin;
+
+#[derive(Component)]
+struct OnCharacterSelectScreen;
+
+#[derive(Component)]
+struct CountdownText;
+
+#[derive(Component)]
+struct PicksContainer;
+
+#[derive(Component)]
+struct CharacterSelectStatusText;
+
+#[derive(Component, Clone, Copy)]
+struct CharacterButton(Character);
+
+// Latest draft sent by the server
+#[derive(Resource, Default)]
+struct DraftState {
+    am_client_id: u64,
+    picks: Vec<DraftPick>,
+}
+
+impl DraftState {
+    fn picked_by(&self, character: Character) -> Option<&DraftPick> {
+        self.picks.iter().find(|pick| pick.character == Some(character))
+    }
+}
+
+impl Plugin for CharacterSelectPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DraftState>()
+            .insert_resource(SelectionTimer(Timer::from_seconds(0.0, TimerMode::Once)))
+            .add_systems(Update, receive_draft)
+            .add_systems(OnEnter(GameState::CharacterSelect), setup_character_select_ui)
+            .add_systems(
+                Update,
+                (update_countdown, update_picks, handle_character_buttons, show_character_select_errors)
+                    .chain()
+                    .run_if(in_state(GameState::CharacterSelect)),
+            )
+            .add_systems(OnExit(GameState::CharacterSelect), cleanup_character_select_ui);
+    }
+}
+
+// System to keep the latest draft and move from the room screen to the draft when it opens
+fn receive_draft(
+    mut server_messages: EventReader<ServerMessage>,
+    mut draft: ResMut<DraftState>,
+    mut timer: ResMut<SelectionTimer>,
+    state: Res<State<GameState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for message in server_messages.read() {
+        if let ServerMessage::Draft { am_client_id, seconds_left, picks } = message {
+            draft.am_client_id = *am_client_id;
+            draft.picks = picks.clone();
+            // The server owns the clock, every update resyncs ours
+            timer.0 = Timer::from_seconds(*seconds_left, TimerMode::Once);
+            if *state.get() == GameState::PreMatch {
+                game_state.set(GameState::CharacterSelect);
+            }
+        }
+    }
+}
+
+// System to set up the draft screen with one button per survivor
+fn setup_character_select_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut draft: ResMut<DraftState>,
+    roster_query: Query<(&Role, &CharacterSheet)>,
+) {
+    // Rebuild the rows from whatever arrived before the screen existed
+    draft.set_changed();
+    let font = asset_server.load("fonts/Debrosee-ALPnL.ttf");
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            OnCharacterSelectScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Choose your survivor",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            ));
+
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 30.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                CountdownText,
+            ));
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        margin: UiRect::vertical(Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for character in Character::ALL {
+                        // The server sends the sheets just before the draft opens
+                        let ability = roster_query
+                            .iter()
+                            .find(|(role, _)| **role == Role::Survivor(character))
+                            .map_or("", |(_, sheet)| sheet.ability.name());
+                        parent
+                            .spawn((
+                                ButtonBundle {
+                                    style: Style {
+                                        width: Val::Px(160.0),
+                                        height: Val::Px(70.0),
+                                        margin: UiRect::all(Val::Px(5.0)),
+                                        flex_direction: FlexDirection::Column,
+                                        justify_content: JustifyContent::Center,
+                                        align_items: AlignItems::Center,
+                                        ..Default::default()
+                                    },
+                                    background_color: consts::NORMAL_BUTTON.into(),
+                                    ..Default::default()
+                                },
+                                CharacterButton(character),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    character.name(),
+                                    TextStyle {
+                                        font: font.clone(),
+                                        font_size: 28.0,
+                                        color: Color::WHITE,
+                                    },
+                                ));
+                                parent.spawn(TextBundle::from_section(
+                                    ability,
+                                    TextStyle {
+                                        font: font.clone(),
+                                        font_size: 18.0,
+                                        color: Color::WHITE,
+                                    },
+                                ));
+                            });
+                    }
+                });
+
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        min_width: Val::Px(400.0),
+                        margin: UiRect::vertical(Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                PicksContainer,
+            ));
+
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 25.0,
+                        color: consts::ERROR_TEXT,
+                    },
+                ),
+                CharacterSelectStatusText,
+            ));
+        });
+}
+
+// System to count down until the server hands out the remaining characters
+fn update_countdown(
+    time: Res<Time>,
+    mut timer: ResMut<SelectionTimer>,
+    mut text_query: Query<&mut Text, With<CountdownText>>,
+) {
+    timer.0.tick(time.delta());
+    let seconds = timer.0.remaining_secs().ceil() as u32;
+    for mut text in &mut text_query {
+        text.sections[0].value = format!("{}s left", seconds);
+    }
+}
+
+// System to grey out taken characters and list who picked what
+fn update_picks(
+    mut commands: Commands,
+    draft: Res<DraftState>,
+    local_client: Option<Res<LocalClientId>>,
+    asset_server: Res<AssetServer>,
+    container_query: Query<Entity, With<PicksContainer>>,
+    mut button_query: Query<(&CharacterButton, &mut BackgroundColor, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    if !draft.is_changed() {
+        return;
+    }
+    let local_id = local_client.map(|id| id.0);
+
+    for (CharacterButton(character), mut color, children) in &mut button_query {
+        let picker = draft.picked_by(*character).map(|pick| pick.client_id);
+        *color = match picker {
+            Some(client_id) if Some(client_id) == local_id => consts::HOVERED_BUTTON.into(),
+            Some(_) => consts::DISABLED_BUTTON.into(),
+            None => consts::NORMAL_BUTTON.into(),
+        };
+        let text_color = match picker {
+            Some(client_id) if Some(client_id) != local_id => consts::DISABLED_TEXT,
+            _ => Color::WHITE,
+        };
+        for child in children {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                text.sections[0].style.color = text_color;
+            }
+        }
+    }
+
+    let Ok(container) = container_query.get_single() else {
+        return;
+    };
+    let font = asset_server.load("fonts/Debrosee-ALPnL.ttf");
+    let watching = local_id == Some(draft.am_client_id);
+    commands.entity(container).despawn_descendants().with_children(|parent| {
+        let style = TextStyle {
+            font: font.clone(),
+            font_size: 28.0,
+            color: Color::WHITE,
+        };
+        if watching {
+            parent.spawn(TextBundle::from_section("You are AM. Watch them choose.", style.clone()));
+        }
+        for pick in &draft.picks {
+            let label = match pick.character {
+                Some(character) => format!("{}: {}", pick.name, character.name()),
+                None => format!("{}: choosing...", pick.name),
+            };
+            parent.spawn(TextBundle::from_section(label, style.clone()));
+        }
+    });
+}
+
+// System to ask the server for a character, it decides if it is still free
+fn handle_character_buttons(
+    mut interaction_query: Query<(&Interaction, &mut BackgroundColor, &CharacterButton), Changed<Interaction>>,
+    draft: Res<DraftState>,
+    local_client: Option<Res<LocalClientId>>,
+    mut client_messages: EventWriter<ClientMessage>,
+) {
+    let local_id = local_client.map(|id| id.0);
+    for (interaction, mut color, CharacterButton(character)) in &mut interaction_query {
+        // Taken characters keep the colour update_picks gave them
+        if draft.picked_by(*character).is_some() {
+            continue;
+        }
+        match *interaction {
+            Interaction::Pressed => {
+                if local_id != Some(draft.am_client_id) {
+                    client_messages.send(ClientMessage::PickCharacter(*character));
+                }
+            }
+            Interaction::Hovered => {
+                *color = consts::HOVERED_BUTTON.into();
+            }
+            Interaction::None => {
+                *color = consts::NORMAL_BUTTON.into();
+            }
+        }
+    }
+}
+
+// System to show why the server refused a pick
+fn show_character_select_errors(
+    mut server_messages: EventReader<ServerMessage>,
+    mut status_query: Query<&mut Text, With<CharacterSelectStatusText>>,
+) {
+    for message in server_messages.read() {
+        if let ServerMessage::RoomError(error) = message {
+            for mut text in &mut status_query {
+                text.sections[0].value = error.to_string();
+            }
+        }
+    }
+}
+
+fn cleanup_character_select_ui(mut commands: Commands, query: Query<Entity, With<OnCharacterSelectScreen>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
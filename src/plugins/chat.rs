@@ -0,0 +1,387 @@
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::components::role::Role;
+use crate::consts;
+use crate::plugins::am_panel::{local_is_am, AmConsole};
+use crate::plugins::network::LocalClientId;
+use crate::protocol::{ChatChannel, ChatId, ChatRecord, ChatTamper, ClientMessage, ServerMessage, MAX_CHAT_CHARS};
+use crate::resources::match_roles::MatchRoles;
+use crate::systems::text_input::edit_text;
+
+pub struct ChatPlugin;
+
+const MAX_CHAT_LINES: usize = 8;
+const MAX_AM_CHAT_RECORDS: usize = 8;
+
+#[derive(Component)]
+struct OnChat;
+
+#[derive(Component)]
+struct ChatLogText;
+
+#[derive(Component)]
+struct OnAmChatter;
+
+#[derive(Component)]
+enum TamperButton {
+    Delay(ChatId),
+    Drop(ChatId),
+    Rewrite(ChatId),
+}
+
+// The message a survivor is typing. Enter opens and sends it, Tab picks who hears it, Escape throws it away.
+#[derive(Resource)]
+pub struct ChatDraft {
+    pub open: bool,
+    text: String,
+    channel: ChatChannel,
+}
+
+impl Default for ChatDraft {
+    fn default() -> Self {
+        Self {
+            open: false,
+            text: String::new(),
+            channel: ChatChannel::Public,
+        }
+    }
+}
+
+// Run condition for everything the keyboard does while nobody is typing
+pub fn chat_closed(draft: Res<ChatDraft>) -> bool {
+    !draft.open
+}
+
+// What this survivor said and heard, newest last
+#[derive(Resource, Default)]
+struct ChatLines(Vec<String>);
+
+// Every survivor message as AM sees it, newest last
+#[derive(Resource, Default)]
+struct AmChatter(Vec<ChatRecord>);
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ChatDraft>()
+            .init_resource::<ChatLines>()
+            .init_resource::<AmChatter>()
+            .add_systems(Update, receive_am_chatter)
+            .add_systems(OnEnter(GameState::InGame), setup_chat_log.run_if(not(local_is_am)))
+            .add_systems(
+                Update,
+                (
+                    type_chat.run_if(not(local_is_am)),
+                    receive_chat,
+                    update_chat_log,
+                    show_am_chatter.run_if(local_is_am),
+                    handle_tamper_buttons.run_if(local_is_am),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::InGame), cleanup_chat);
+    }
+}
+
+fn setup_chat_log(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                font_size: 18.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(120.0),
+            left: Val::Px(16.0),
+            max_width: Val::Px(420.0),
+            ..Default::default()
+        }),
+        ChatLogText,
+        OnChat,
+    ));
+}
+
+fn name_of(roles: Option<&MatchRoles>, client_id: u64) -> &'static str {
+    roles.and_then(|roles| roles.role_of(client_id)).map_or("Someone", |role| role.name())
+}
+
+fn channel_label(roles: Option<&MatchRoles>, channel: ChatChannel) -> String {
+    match channel {
+        ChatChannel::Public => "all".to_string(),
+        ChatChannel::Proximity => "nearby".to_string(),
+        ChatChannel::Whisper(to) => format!("to {}", name_of(roles, to)),
+    }
+}
+
+// The channel after this one: everyone, whoever is nearby, then each other survivor in turn
+fn next_channel(channel: ChatChannel, roles: Option<&MatchRoles>, local_client: Option<&LocalClientId>) -> ChatChannel {
+    let mut channels = vec![ChatChannel::Public, ChatChannel::Proximity];
+    if let Some(roles) = roles {
+        channels.extend(
+            roles
+                .0
+                .iter()
+                .filter(|assignment| matches!(assignment.role, Role::Survivor(_)))
+                .filter(|assignment| Some(assignment.client_id) != local_client.map(|local_client| local_client.0))
+                .map(|assignment| ChatChannel::Whisper(assignment.client_id)),
+        );
+    }
+    let index = channels.iter().position(|known| *known == channel).map_or(0, |index| index + 1);
+    channels[index % channels.len()]
+}
+
+fn push_line(lines: &mut ChatLines, line: String) {
+    lines.0.push(line);
+    if lines.0.len() > MAX_CHAT_LINES {
+        lines.0.remove(0);
+    }
+}
+
+// System to type and send a message, only survivors talk to each other
+fn type_chat(
+    mut keyboard_input: EventReader<KeyboardInput>,
+    mut draft: ResMut<ChatDraft>,
+    mut lines: ResMut<ChatLines>,
+    roles: Option<Res<MatchRoles>>,
+    local_client: Option<Res<LocalClientId>>,
+    mut client_messages: EventWriter<ClientMessage>,
+) {
+    for ev in keyboard_input.read() {
+        if ev.state == ButtonState::Released || ev.key_code == KeyCode::Backquote {
+            continue;
+        }
+        if !draft.open {
+            if ev.logical_key == Key::Enter {
+                draft.open = true;
+            }
+            continue;
+        }
+        match &ev.logical_key {
+            Key::Enter => {
+                draft.open = false;
+                let text = std::mem::take(&mut draft.text);
+                if text.trim().is_empty() {
+                    continue;
+                }
+                let line = format!("[{}] You: {}", channel_label(roles.as_deref(), draft.channel), text);
+                push_line(&mut lines, line);
+                client_messages.send(ClientMessage::Chat {
+                    channel: draft.channel,
+                    text,
+                });
+            }
+            Key::Escape => {
+                draft.open = false;
+                draft.text.clear();
+            }
+            Key::Tab => draft.channel = next_channel(draft.channel, roles.as_deref(), local_client.as_deref()),
+            key => {
+                edit_text(key, &mut draft.text, MAX_CHAT_CHARS);
+            }
+        }
+    }
+}
+
+// System to show what we heard, and why the server refused what we said
+fn receive_chat(
+    mut server_messages: EventReader<ServerMessage>,
+    mut lines: ResMut<ChatLines>,
+    roles: Option<Res<MatchRoles>>,
+) {
+    for message in server_messages.read() {
+        match message {
+            ServerMessage::Chat { from, channel, text } => {
+                let channel = match channel {
+                    ChatChannel::Public => "all",
+                    ChatChannel::Proximity => "nearby",
+                    ChatChannel::Whisper(_) => "whisper",
+                };
+                push_line(&mut lines, format!("[{}] {}: {}", channel, name_of(roles.as_deref(), *from), text));
+            }
+            ServerMessage::ActionError(error) if error.is_chat() => push_line(&mut lines, format!("({})", error)),
+            _ => {}
+        }
+    }
+}
+
+fn update_chat_log(
+    lines: Res<ChatLines>,
+    draft: Res<ChatDraft>,
+    roles: Option<Res<MatchRoles>>,
+    mut text_query: Query<&mut Text, With<ChatLogText>>,
+) {
+    if !lines.is_changed() && !draft.is_changed() {
+        return;
+    }
+    let mut shown = lines.0.join("\n");
+    if draft.open {
+        shown.push_str(&format!("\n[{}] > {}_", channel_label(roles.as_deref(), draft.channel), draft.text));
+    }
+    for mut text in &mut text_query {
+        text.sections[0].value = shown.clone();
+    }
+}
+
+// System to keep every survivor message AM gets to see, replacing older copies of the same one
+fn receive_am_chatter(mut server_messages: EventReader<ServerMessage>, mut chatter: ResMut<AmChatter>) {
+    for message in server_messages.read() {
+        if let ServerMessage::AmChat(record) = message {
+            match chatter.0.iter_mut().find(|known| known.id == record.id) {
+                Some(known) => *known = record.clone(),
+                None => chatter.0.push(record.clone()),
+            }
+        }
+    }
+}
+
+// System to list the latest messages for AM, the ones still held come with what AM can do to them
+fn show_am_chatter(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    chatter: Res<AmChatter>,
+    console: Res<AmConsole>,
+    roles: Option<Res<MatchRoles>>,
+    panel_query: Query<Entity, With<OnAmChatter>>,
+) {
+    if !chatter.is_changed() && !panel_query.is_empty() {
+        return;
+    }
+    for entity in &panel_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    let roles = roles.as_deref();
+    let text_style = |font_size: f32, color: Color| TextStyle {
+        font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+        font_size,
+        color,
+    };
+    let chat = console.scenario.as_ref().map(|scenario| scenario.chat.clone()).unwrap_or_default();
+    let buttons = [
+        (format!("Delay ({:.0})", chat.delay_cost), TamperButton::Delay as fn(ChatId) -> TamperButton),
+        (format!("Drop ({:.0})", chat.drop_cost), TamperButton::Drop),
+        (format!("Rewrite ({:.0})", chat.rewrite_cost), TamperButton::Rewrite),
+    ];
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(16.0),
+                    right: Val::Px(16.0),
+                    width: Val::Px(360.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..Default::default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.8).into(),
+                z_index: ZIndex::Global(10),
+                ..Default::default()
+            },
+            OnAmChatter,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "What they say to each other (rewrites use your typed message)",
+                text_style(18.0, consts::AM_VOICE),
+            ));
+            let skip = chatter.0.len().saturating_sub(MAX_AM_CHAT_RECORDS);
+            for record in chatter.0.iter().skip(skip) {
+                let heading = format!(
+                    "{} [{}]: {}",
+                    name_of(roles, record.from),
+                    channel_label(roles, record.channel),
+                    record.original
+                );
+                parent.spawn(TextBundle::from_section(heading, text_style(16.0, Color::WHITE)));
+                let status = match &record.text {
+                    None => "dropped".to_string(),
+                    Some(text) if record.delivered => {
+                        let heard: Vec<&str> =
+                            record.recipients.iter().map(|client_id| name_of(roles, *client_id)).collect();
+                        let heard = if heard.is_empty() { "nobody".to_string() } else { heard.join(", ") };
+                        if *text == record.original {
+                            format!("heard by {}", heard)
+                        } else {
+                            format!("heard by {} as: {}", heard, text)
+                        }
+                    }
+                    Some(text) if *text != record.original => format!("held, will arrive as: {}", text),
+                    Some(_) => "held".to_string(),
+                };
+                parent.spawn(TextBundle::from_section(status, text_style(14.0, consts::AM_VOICE)));
+                if record.delivered || record.text.is_none() {
+                    continue;
+                }
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            column_gap: Val::Px(6.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        for (label, button) in &buttons {
+                            parent
+                                .spawn((
+                                    ButtonBundle {
+                                        style: Style {
+                                            padding: UiRect::all(Val::Px(4.0)),
+                                            ..Default::default()
+                                        },
+                                        background_color: consts::NORMAL_BUTTON.into(),
+                                        ..Default::default()
+                                    },
+                                    button(record.id),
+                                ))
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section(label.clone(), text_style(14.0, Color::WHITE)));
+                                });
+                        }
+                    });
+            }
+        });
+}
+
+fn handle_tamper_buttons(
+    interaction_query: Query<(&Interaction, &TamperButton), Changed<Interaction>>,
+    console: Res<AmConsole>,
+    mut client_messages: EventWriter<ClientMessage>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let (id, tamper) = match button {
+            TamperButton::Delay(id) => (*id, ChatTamper::Delay),
+            TamperButton::Drop(id) => (*id, ChatTamper::Drop),
+            TamperButton::Rewrite(id) => (*id, ChatTamper::Rewrite(console.message().to_string())),
+        };
+        client_messages.send(ClientMessage::TamperChat { id, tamper });
+    }
+}
+
+fn cleanup_chat(
+    mut commands: Commands,
+    mut draft: ResMut<ChatDraft>,
+    mut lines: ResMut<ChatLines>,
+    mut chatter: ResMut<AmChatter>,
+    query: Query<Entity, Or<(With<OnChat>, With<OnAmChatter>)>>,
+) {
+    *draft = ChatDraft::default();
+    lines.0.clear();
+    chatter.0.clear();
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::consts;
+use crate::plugins::network::Reconnecting;
+use crate::protocol::{ConnectionStatus, ServerMessage};
+
+pub struct ConnectionStatusPlugin;
+
+// How long "X is back" and "X's slot was released" stay on screen
+const NOTICE_SECONDS: f32 = 4.0;
+
+#[derive(Component)]
+struct ConnectionStatusText;
+
+// Players in our room who dropped, with the time left on their held slot
+#[derive(Resource, Default)]
+struct LostPlayers(HashMap<u64, (String, Timer)>);
+
+// Short lived messages about players coming back or leaving for good
+#[derive(Resource, Default)]
+struct ConnectionNotices(Vec<(String, Timer)>);
+
+impl Plugin for ConnectionStatusPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<LostPlayers>()
+            .init_resource::<ConnectionNotices>()
+            .add_systems(OnEnter(GameState::InGame), setup_connection_status)
+            .add_systems(
+                Update,
+                (track_player_connections, update_connection_status)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::InGame), cleanup_connection_status);
+    }
+}
+
+fn setup_connection_status(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut lost_players: ResMut<LostPlayers>,
+    mut notices: ResMut<ConnectionNotices>,
+) {
+    lost_players.0.clear();
+    notices.0.clear();
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                font_size: 22.0,
+                color: consts::ERROR_TEXT,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.0),
+            right: Val::Px(10.0),
+            ..Default::default()
+        }),
+        ConnectionStatusText,
+    ));
+}
+
+// System to follow the players of our room dropping out and coming back
+fn track_player_connections(
+    time: Res<Time>,
+    mut server_messages: EventReader<ServerMessage>,
+    mut lost_players: ResMut<LostPlayers>,
+    mut notices: ResMut<ConnectionNotices>,
+) {
+    for message in server_messages.read() {
+        let ServerMessage::PlayerConnection { client_id, name, status } = message else {
+            continue;
+        };
+        match status {
+            ConnectionStatus::Lost { grace_secs } => {
+                lost_players
+                    .0
+                    .insert(*client_id, (name.clone(), Timer::from_seconds(*grace_secs as f32, TimerMode::Once)));
+            }
+            ConnectionStatus::Returned => {
+                lost_players.0.remove(client_id);
+                notices.0.push((format!("{} is back", name), Timer::from_seconds(NOTICE_SECONDS, TimerMode::Once)));
+            }
+            ConnectionStatus::SlotReleased => {
+                lost_players.0.remove(client_id);
+                notices.0.push((format!("{} left the match", name), Timer::from_seconds(NOTICE_SECONDS, TimerMode::Once)));
+            }
+        }
+    }
+
+    for (_, timer) in lost_players.0.values_mut() {
+        timer.tick(time.delta());
+    }
+    notices.0.retain_mut(|(_, timer)| !timer.tick(time.delta()).finished());
+}
+
+// System to list dropped players with their countdown, and our own reconnect attempt
+fn update_connection_status(
+    lost_players: Res<LostPlayers>,
+    notices: Res<ConnectionNotices>,
+    reconnecting: Option<Res<Reconnecting>>,
+    mut text_query: Query<&mut Text, With<ConnectionStatusText>>,
+) {
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+    let mut lines = Vec::new();
+    if let Some(reconnecting) = reconnecting {
+        lines.push(format!(
+            "Connection lost, reconnecting... {}s",
+            reconnecting.give_up.remaining_secs().ceil() as u32
+        ));
+    }
+    let mut lost: Vec<_> = lost_players.0.values().collect();
+    lost.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, timer) in lost {
+        lines.push(format!("{} lost connection, {}s", name, timer.remaining_secs().ceil() as u32));
+    }
+    lines.extend(notices.0.iter().map(|(notice, _)| notice.clone()));
+    text.sections[0].value = lines.join("\n");
+}
+
+fn cleanup_connection_status(mut commands: Commands, query: Query<Entity, With<ConnectionStatusText>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
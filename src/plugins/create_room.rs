@@ -1,10 +1,14 @@
 use bevy::prelude::*;
 use crate::GameState;
 use crate::consts;
-use bevy::window::PrimaryWindow;
+use crate::plugins::network::NetworkRequest;
+use crate::protocol::MAX_ROOM_NAME_CHARS;
+use crate::systems::text_input::edit_text;
 use bevy::input::keyboard::{Key, KeyboardInput};
 use bevy::input::ButtonState;
 
+const MAX_PASSWORD_LEN: usize = 24;
+
 pub struct RoomCreator;
 
 #[derive(Component)]
@@ -20,17 +24,33 @@ struct RoomNameInputField;
 #[derive(Component)]
 struct RoomNameText;
 
+#[derive(Component)]
+struct PasswordInputField;
+
+#[derive(Component)]
+struct PasswordText;
+
 #[derive(Component)]
 struct ConfirmButton;
 
 #[derive(Component)]
 struct RoomTypeToggleText;
 
+// Text field currently receiving key presses
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum RoomField {
+    RoomName,
+    Password,
+}
+
 // Resource to store the room creation data
 #[derive(Resource)]
 struct RoomCreationData {
     is_private: bool,
     room_name: String,
+    // Optional, only sent for private rooms
+    password: String,
+    focused: RoomField,
 }
 
 impl Default for RoomCreationData {
@@ -38,6 +58,8 @@ impl Default for RoomCreationData {
         Self {
             is_private: false,
             room_name: String::new(),
+            password: String::new(),
+            focused: RoomField::RoomName,
         }
     }
 }
@@ -47,7 +69,7 @@ impl Plugin for RoomCreator {
         app
         .init_resource::<RoomCreationData>()
         .add_systems(OnEnter(GameState::CreateRoom),setup_room_selector)
-        .add_systems(Update, (handle_button_interactions,handle_text_input).run_if(in_state(GameState::CreateRoom)))
+        .add_systems(Update, (handle_button_interactions,handle_field_focus,handle_text_input,update_input_fields).chain().run_if(in_state(GameState::CreateRoom)))
         .add_systems(OnExit(GameState::CreateRoom),cleanup_room_creator_ui);
     }
 }
@@ -55,11 +77,9 @@ impl Plugin for RoomCreator {
 // System to set up the room creator UI
 fn setup_room_selector(
     mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
     asset_server: Res<AssetServer>,
+    room_data: Res<RoomCreationData>,
 ) {
-    let _window: &Window = window_query.get_single().unwrap();
-
     commands
         .spawn((
             NodeBundle {
@@ -92,7 +112,7 @@ fn setup_room_selector(
                 ))
                 .with_children(|parent| {
                     parent.spawn((TextBundle::from_section(
-                        "Room Type: Public",
+                        format!("Room Type: {}", if room_data.is_private { "Private" } else { "Public" }),
                         TextStyle {
                             font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
                             font_size: 30.0,
@@ -116,6 +136,7 @@ fn setup_room_selector(
                         background_color: consts::INPUT_FIELD_BG.into(),
                         ..Default::default()
                     },
+                    Interaction::default(),
                     RoomNameInputField,
                 ))
                 .with_children(|parent| {
@@ -129,7 +150,7 @@ fn setup_room_selector(
                     ));
                     parent.spawn((
                         TextBundle::from_section(
-                            "",
+                            room_data.room_name.clone(),
                             TextStyle {
                                 font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
                                 font_size: 25.0,
@@ -140,6 +161,46 @@ fn setup_room_selector(
                     ));
                 });
 
+            // Password Input Field, only shown for private rooms
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            display: if room_data.is_private { Display::Flex } else { Display::None },
+                            margin: UiRect::vertical(Val::Px(5.0)),
+                            padding: UiRect::horizontal(Val::Px(10.0)),
+                            justify_content: JustifyContent::FlexStart,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        background_color: consts::INPUT_FIELD_BG.into(),
+                        ..Default::default()
+                    },
+                    Interaction::default(),
+                    PasswordInputField,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Password (optional): ",
+                        TextStyle {
+                            font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                            font_size: 25.0,
+                            color: Color::WHITE,
+                        },
+                    ));
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "*".repeat(room_data.password.chars().count()),
+                            TextStyle {
+                                font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                                font_size: 25.0,
+                                color: consts::WHITE.into(),
+                            },
+                        ),
+                        PasswordText,
+                    ));
+                });
+
             // Confirm Button
             parent
                 .spawn((
@@ -181,7 +242,7 @@ fn handle_button_interactions(
     >,
     mut room_data: ResMut<RoomCreationData>,
     mut toggle_text_query: Query<&mut Text, With<RoomTypeToggleText>>,
-    mut game_state: ResMut<NextState<GameState>>,
+    mut network_requests: EventWriter<NetworkRequest>,
 ) {
     for (interaction, mut color, is_toggle, is_confirm) in &mut interaction_query {
         match *interaction {
@@ -189,6 +250,9 @@ fn handle_button_interactions(
                 if is_toggle.is_some() {
                     // Toggle room type
                     room_data.is_private = !room_data.is_private;
+                    if !room_data.is_private {
+                        room_data.focused = RoomField::RoomName;
+                    }
                     // Update the toggle button text
                     for mut text in &mut toggle_text_query {
                         text.sections[0].value = format!(
@@ -206,8 +270,13 @@ fn handle_button_interactions(
                             if room_data.is_private { "Private" } else { "Public" },
                             room_data.room_name
                         );
-                        // Transition to the next game state or handle room creation logic
-                        game_state.set(GameState::Lobby);
+                        // Start hosting, the network plugin moves us on once connected
+                        network_requests.send(NetworkRequest::Host {
+                            room_name: room_data.room_name.clone(),
+                            is_private: room_data.is_private,
+                            password: (room_data.is_private && !room_data.password.is_empty())
+                                .then(|| room_data.password.clone()),
+                        });
                     }
                 }
             }
@@ -221,11 +290,24 @@ fn handle_button_interactions(
     }
 }
 
-// System to handle text input for the room name
+// System to move keyboard focus to the field that was clicked
+fn handle_field_focus(
+    name_field_query: Query<&Interaction, (Changed<Interaction>, With<RoomNameInputField>)>,
+    password_field_query: Query<&Interaction, (Changed<Interaction>, With<PasswordInputField>)>,
+    mut room_data: ResMut<RoomCreationData>,
+) {
+    if name_field_query.iter().any(|interaction| *interaction == Interaction::Pressed) {
+        room_data.focused = RoomField::RoomName;
+    }
+    if room_data.is_private && password_field_query.iter().any(|interaction| *interaction == Interaction::Pressed) {
+        room_data.focused = RoomField::Password;
+    }
+}
+
+// System to handle text input for the focused field
 fn handle_text_input(
     mut keyboard_input: EventReader<KeyboardInput>,
     mut room_data: ResMut<RoomCreationData>,
-    mut room_name_text_query: Query<&mut Text, With<RoomNameText>>,
 ) {
     // Handle character input
     for ev in keyboard_input.read() {
@@ -234,27 +316,57 @@ fn handle_text_input(
             continue;
         }
 
-        match &ev.logical_key {
-            // Handle Pressing Enter to finish the input
-            Key::Backspace => {
-                room_data.room_name.pop();
+        // Tab switches between the name and the password
+        if ev.logical_key == Key::Tab {
+            if room_data.is_private {
+                room_data.focused = match room_data.focused {
+                    RoomField::RoomName => RoomField::Password,
+                    RoomField::Password => RoomField::RoomName,
+                };
             }
-            Key::Character(input) => {
-            // Ignore any input that contains control (special) characters
-            if input.chars().any(|c| c.is_control()) {
-                continue;
+            continue;
+        }
+
+        match room_data.focused {
+            RoomField::RoomName => {
+                edit_text(&ev.logical_key, &mut room_data.room_name, MAX_ROOM_NAME_CHARS);
             }
-            room_data.room_name.push_str(&input);
+            RoomField::Password => {
+                edit_text(&ev.logical_key, &mut room_data.password, MAX_PASSWORD_LEN);
             }
-            _ => {}
-         }
-         // Update the displayed room name
-        for mut text in &mut room_name_text_query {
-            text.sections[0].value = room_data.room_name.clone();
         }
     }
 }
 
+// System to keep the fields on screen in sync with the room creation data
+fn update_input_fields(
+    room_data: Res<RoomCreationData>,
+    mut room_name_text_query: Query<&mut Text, (With<RoomNameText>, Without<PasswordText>)>,
+    mut password_text_query: Query<&mut Text, (With<PasswordText>, Without<RoomNameText>)>,
+    mut name_field_query: Query<&mut BackgroundColor, (With<RoomNameInputField>, Without<PasswordInputField>)>,
+    mut password_field_query: Query<(&mut Style, &mut BackgroundColor), (With<PasswordInputField>, Without<RoomNameInputField>)>,
+) {
+    if !room_data.is_changed() {
+        return;
+    }
+    for mut text in &mut room_name_text_query {
+        text.sections[0].value = room_data.room_name.clone();
+    }
+    for mut text in &mut password_text_query {
+        text.sections[0].value = "*".repeat(room_data.password.chars().count());
+    }
+    let field_color = |field: RoomField| {
+        if room_data.focused == field { consts::INPUT_FIELD_BUTTON } else { consts::INPUT_FIELD_BG }
+    };
+    for mut color in &mut name_field_query {
+        *color = field_color(RoomField::RoomName).into();
+    }
+    for (mut style, mut color) in &mut password_field_query {
+        style.display = if room_data.is_private { Display::Flex } else { Display::None };
+        *color = field_color(RoomField::Password).into();
+    }
+}
+
 // System to clean up the UI when exiting the room creator state
 fn cleanup_room_creator_ui(mut commands: Commands, query: Query<Entity, With<OnRoomCreatorScreen>>) {
     for entity in &query {
@@ -0,0 +1,343 @@
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::consts;
+use crate::plugins::network::NetworkRequest;
+use crate::protocol::{
+    ChatChannel, ChatRecord, ChatTamper, InterventionAction, MatchEvent, MatchEventKind, MatchOutcome, NarrativeRecord,
+    ServerMessage, TrustSample,
+};
+use crate::resources::match_roles::MatchRoles;
+
+pub struct DebriefPlugin;
+
+// Columns are cut to their last lines, the screen does not scroll
+const MAX_COLUMN_LINES: usize = 36;
+const CHART_HEIGHT_PX: f32 = 100.0;
+const CHART_BAR_WIDTH_PX: f32 = 6.0;
+const TRUST_COLOR: Color = Color::srgb(0.3, 0.6, 0.9);
+
+#[derive(Component)]
+struct OnDebriefScreen;
+
+#[derive(Component)]
+struct BackToMenuButton;
+
+// Everything the server reveals once the match is over, collected as it arrives
+#[derive(Resource, Default)]
+pub struct DebriefReport {
+    pub outcome: Option<(MatchOutcome, String)>,
+    pub narratives: Vec<NarrativeRecord>,
+    pub trust: Vec<TrustSample>,
+    pub am_score: f32,
+    pub chat: Vec<ChatRecord>,
+    pub events: Vec<MatchEvent>,
+    // The match log is sent last, the report is complete once it is here
+    pub complete: bool,
+}
+
+impl Plugin for DebriefPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DebriefReport>()
+            .add_systems(Update, collect_debrief)
+            .add_systems(Update, enter_debrief.run_if(in_state(GameState::InGame)))
+            .add_systems(OnEnter(GameState::PreMatch), reset_debrief)
+            .add_systems(OnEnter(GameState::Debrief), setup_debrief_screen)
+            .add_systems(Update, handle_debrief_buttons.run_if(in_state(GameState::Debrief)))
+            .add_systems(OnExit(GameState::Debrief), (cleanup_debrief_screen, reset_debrief));
+    }
+}
+
+// System to keep every reveal, they can arrive before a returning player is back in the match
+fn collect_debrief(mut server_messages: EventReader<ServerMessage>, mut report: ResMut<DebriefReport>) {
+    for message in server_messages.read() {
+        match message {
+            ServerMessage::MatchOver { outcome, text } => report.outcome = Some((*outcome, text.clone())),
+            ServerMessage::NarrativeReveal(records) => report.narratives = records.clone(),
+            ServerMessage::TrustReveal { history, am_score } => {
+                report.trust = history.clone();
+                report.am_score = *am_score;
+            }
+            ServerMessage::ChatReveal(records) => report.chat = records.clone(),
+            ServerMessage::MatchLog(events) => {
+                report.events = events.clone();
+                report.complete = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+// System to leave the match for the debrief once everything is in
+fn enter_debrief(report: Res<DebriefReport>, mut game_state: ResMut<NextState<GameState>>) {
+    if report.complete {
+        game_state.set(GameState::Debrief);
+    }
+}
+
+fn reset_debrief(mut report: ResMut<DebriefReport>) {
+    *report = DebriefReport::default();
+}
+
+fn clock(at_secs: f32) -> String {
+    let seconds = at_secs as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+// Keeps the last lines of a column, with a note that some were left out
+fn last_lines(mut lines: Vec<String>) -> String {
+    if lines.len() > MAX_COLUMN_LINES {
+        let cut = lines.len() - MAX_COLUMN_LINES;
+        lines.drain(..cut);
+        lines.insert(0, format!("({} earlier lines)", cut));
+    }
+    lines.join("\n")
+}
+
+fn outcome_name(outcome: MatchOutcome) -> &'static str {
+    match outcome {
+        MatchOutcome::SurvivorsWin => "The survivors win",
+        MatchOutcome::AmWins => "AM wins",
+    }
+}
+
+// One line of the timeline
+fn describe(event: &MatchEvent, name: &dyn Fn(u64) -> &'static str) -> String {
+    let text = match &event.kind {
+        MatchEventKind::PhaseStarted => format!("{} begins", event.phase.name()),
+        MatchEventKind::Intervention { name: intervention, cost, action } => {
+            let detail = match action {
+                InterventionAction::Narrate(narrative) => match &narrative.truth {
+                    Some(truth) => format!(", the truth was: {}", truth),
+                    None => String::new(),
+                },
+                InterventionAction::Hallucination { target, .. } => format!(" on {}", name(*target)),
+                InterventionAction::Hazard { position } => format!(" at {:.0}, {:.0}", position.x, position.y),
+                InterventionAction::LockDoor { door } => format!(" on {}", door),
+            };
+            format!("AM used {} for {:.0} hate{}", intervention, cost, detail)
+        }
+        MatchEventKind::ChatTampered { from, tamper, cost } => {
+            let verb = match tamper {
+                ChatTamper::Delay => "held back",
+                ChatTamper::Drop => "swallowed",
+                ChatTamper::Rewrite(_) => "rewrote",
+            };
+            format!("AM {} a message from {} for {:.0} hate", verb, name(*from), cost)
+        }
+        MatchEventKind::Shared { by, with } => format!("{} shared with {}", name(*by), name(*with)),
+        MatchEventKind::Accused { by, who } => format!("{} accused {}", name(*by), name(*who)),
+        MatchEventKind::RumourSettled { believer, about, was_true } => {
+            let verdict = if *was_true { "AM had told the truth" } else { "AM had lied" };
+            format!("{} confronted {}, {}", name(*believer), name(*about), verdict)
+        }
+        MatchEventKind::VoteClosed(result) => {
+            let decision = result
+                .winner
+                .as_ref()
+                .and_then(|winner| result.tally.iter().find(|count| &count.option == winner))
+                .map_or("no decision".to_string(), |count| count.label.clone());
+            format!("Vote \"{}\": {}", result.question, decision)
+        }
+        MatchEventKind::ObjectiveCompleted { description } => format!("Done: {}", description),
+        MatchEventKind::Broke { who } => format!("{} started to break", name(*who)),
+        MatchEventKind::Died { who, by: Some(by) } => format!("{} was killed by {}", name(*who), name(*by)),
+        MatchEventKind::Died { who, by: None } => format!("{} died", name(*who)),
+        MatchEventKind::MatchOver(outcome) => outcome_name(*outcome).to_string(),
+    };
+    format!("{}  {}", clock(event.at_secs), text)
+}
+
+// System to lay out what happened: what everyone was told, what AM did, the votes and the chatter, and the trust
+fn setup_debrief_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    report: Res<DebriefReport>,
+    roles: Option<Res<MatchRoles>>,
+) {
+    let font = asset_server.load("fonts/Debrosee-ALPnL.ttf");
+    let text_style = |font_size: f32, color: Color| TextStyle {
+        font: font.clone(),
+        font_size,
+        color,
+    };
+    let name = |client_id: u64| {
+        roles
+            .as_ref()
+            .and_then(|roles| roles.role_of(client_id))
+            .map_or("Someone", |role| role.name())
+    };
+
+    let headline = match &report.outcome {
+        Some((outcome, text)) => format!("{}. {}", outcome_name(*outcome), text),
+        None => "The match is over".to_string(),
+    };
+
+    let mut told = Vec::new();
+    if report.narratives.is_empty() {
+        told.push("AM said nothing at all".to_string());
+    }
+    for record in &report.narratives {
+        let truth = record.truth.as_deref().unwrap_or("AM never said");
+        told.push(format!("[{} {}] Truth: {}", record.phase.name(), clock(record.at_secs), truth));
+        for delivery in &record.deliveries {
+            let verb = if delivery.forgotten { "forgot" } else { "heard" };
+            told.push(format!("    {} {}: {}", delivery.name, verb, delivery.text));
+        }
+    }
+
+    let timeline: Vec<String> = report.events.iter().map(|event| describe(event, &name)).collect();
+
+    let mut votes_and_chatter = Vec::new();
+    for event in &report.events {
+        let MatchEventKind::VoteClosed(result) = &event.kind else {
+            continue;
+        };
+        votes_and_chatter.push(format!("{}  {} (called by {})", clock(result.at_secs), result.question, name(result.called_by)));
+        let tally: Vec<String> = result.tally.iter().map(|count| format!("{} {}", count.label, count.votes)).collect();
+        votes_and_chatter.push(format!("    {}", tally.join(", ")));
+        for ballot in &result.ballots {
+            votes_and_chatter.push(format!("    {} voted {}", name(ballot.voter), ballot.option));
+        }
+    }
+    let tampered: Vec<&ChatRecord> = report.chat.iter().filter(|record| !record.tampering.is_empty()).collect();
+    votes_and_chatter.push(format!(
+        "{} messages, AM touched {}",
+        report.chat.len(),
+        tampered.len()
+    ));
+    for record in tampered {
+        let channel = match record.channel {
+            ChatChannel::Public => "all".to_string(),
+            ChatChannel::Proximity => "nearby".to_string(),
+            ChatChannel::Whisper(to) => format!("to {}", name(to)),
+        };
+        votes_and_chatter.push(format!("{}  {} [{}]: {}", clock(record.at_secs), name(record.from), channel, record.original));
+        let arrived = match &record.text {
+            None => "never arrived".to_string(),
+            Some(text) if *text != record.original => format!("arrived as: {}", text),
+            Some(_) => "arrived late".to_string(),
+        };
+        votes_and_chatter.push(format!("    {}", arrived));
+    }
+
+    let mut trust = vec![format!("AM scored {:.0}", report.am_score)];
+    if let Some(last) = report.trust.last() {
+        for pair in &last.pairs {
+            trust.push(format!("{} trusts {}: {:.0}%", name(pair.from), name(pair.to), pair.value * 100.0));
+        }
+    }
+
+    let column_style = Style {
+        width: Val::Percent(25.0),
+        flex_direction: FlexDirection::Column,
+        row_gap: Val::Px(8.0),
+        overflow: Overflow::clip(),
+        ..Default::default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::all(Val::Px(16.0)),
+                    row_gap: Val::Px(12.0),
+                    ..Default::default()
+                },
+                background_color: Color::srgb(0.04, 0.0, 0.0).into(),
+                ..Default::default()
+            },
+            OnDebriefScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(headline, text_style(34.0, consts::AM_VOICE)));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        flex_grow: 1.0,
+                        column_gap: Val::Px(12.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    let columns = [
+                        ("What you were told", last_lines(told)),
+                        ("What AM did", last_lines(timeline)),
+                        ("Votes and chatter", last_lines(votes_and_chatter)),
+                    ];
+                    for (title, body) in columns {
+                        parent.spawn(NodeBundle { style: column_style.clone(), ..Default::default() }).with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(title, text_style(24.0, Color::WHITE)));
+                            parent.spawn(TextBundle::from_section(body, text_style(16.0, Color::WHITE)));
+                        });
+                    }
+                    parent.spawn(NodeBundle { style: column_style.clone(), ..Default::default() }).with_children(|parent| {
+                        parent.spawn(TextBundle::from_section("Trust", text_style(24.0, Color::WHITE)));
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    height: Val::Px(CHART_HEIGHT_PX),
+                                    align_items: AlignItems::FlexEnd,
+                                    column_gap: Val::Px(2.0),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            })
+                            .with_children(|parent| {
+                                for sample in &report.trust {
+                                    parent.spawn(NodeBundle {
+                                        style: Style {
+                                            width: Val::Px(CHART_BAR_WIDTH_PX),
+                                            height: Val::Px(sample.average.clamp(0.0, 1.0) * CHART_HEIGHT_PX),
+                                            ..Default::default()
+                                        },
+                                        background_color: TRUST_COLOR.into(),
+                                        ..Default::default()
+                                    });
+                                }
+                            });
+                        parent.spawn(TextBundle::from_section(last_lines(trust), text_style(16.0, Color::WHITE)));
+                    });
+                });
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::axes(Val::Px(24.0), Val::Px(8.0)),
+                            justify_content: JustifyContent::Center,
+                            ..Default::default()
+                        },
+                        background_color: consts::NORMAL_BUTTON.into(),
+                        ..Default::default()
+                    },
+                    BackToMenuButton,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("Back to menu", text_style(28.0, Color::WHITE)));
+                });
+        });
+}
+
+fn handle_debrief_buttons(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<BackToMenuButton>)>,
+    mut network_requests: EventWriter<NetworkRequest>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            network_requests.send(NetworkRequest::Disconnect);
+        }
+    }
+}
+
+fn cleanup_debrief_screen(mut commands: Commands, query: Query<Entity, With<OnDebriefScreen>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,344 @@
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+
+use crate::components::role::Role;
+use crate::link_conditioner::LinkConditions;
+use crate::components::appearance::SpriteSet;
+use crate::plugins::am_panel::AmConsole;
+use crate::plugins::hallucination::AmIllusionList;
+use crate::plugins::network::{ClientLinkConditioner, NetworkSettings};
+use crate::protocol::{
+    ClientMessage, Exposure, IllusionId, IllusionKind, InterventionAction, NarrativeEvent, NarrativeText,
+    NarrativeVersion, Rumour,
+};
+use crate::resources::match_roles::MatchRoles;
+use crate::systems::text_input::edit_text;
+
+pub struct DevConsolePlugin;
+
+const MAX_COMMAND_LEN: usize = 240;
+const MAX_LOG_LINES: usize = 8;
+const HELP: &str = "Commands: help, conditioner, conditioner off, conditioner latency=<ms>,jitter=<ms>,loss=<0-1>,duplicate=<0-1>\n          narrate <who>=<text or @line>; <who>=<text> | <truth> | <rumour about who> <true or false>   (who: survivor names separated by commas, or all)\n          illusion <target> item <x>,<y> <name> | double <who> <x>,<y> | sprite <who> <set> | hide <who>, then optionally | <exposure text or @line>\n          illusions, dispel <id>";
+
+#[derive(Component)]
+struct DevConsoleUi;
+
+#[derive(Component)]
+struct DevConsoleText;
+
+// Debug console toggled with the key left of 1, available on every screen
+#[derive(Resource, Default)]
+struct DevConsole {
+    open: bool,
+    input: String,
+    log: Vec<String>,
+}
+
+impl DevConsole {
+    fn print(&mut self, line: impl Into<String>) {
+        self.log.push(line.into());
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.remove(0);
+        }
+    }
+}
+
+impl Plugin for DevConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DevConsole>()
+            .add_systems(Update, (toggle_dev_console, sync_dev_console_ui, handle_console_input, update_dev_console).chain());
+    }
+}
+
+// System to show the console while it is open. Screens that clear every UI node on exit can
+// take it down with them, so it is spawned again whenever it is missing.
+fn sync_dev_console_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut console: ResMut<DevConsole>,
+    ui_query: Query<Entity, With<DevConsoleUi>>,
+) {
+    if !console.open {
+        for entity in &ui_query {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+    if !ui_query.is_empty() {
+        return;
+    }
+    // Makes the text redraw on the new node
+    console.set_changed();
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    width: Val::Percent(100.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..Default::default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.8).into(),
+                z_index: ZIndex::Global(100),
+                ..Default::default()
+            },
+            DevConsoleUi,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                DevConsoleText,
+            ));
+        });
+}
+
+// System to open and close the console
+fn toggle_dev_console(keyboard_input: Res<ButtonInput<KeyCode>>, mut console: ResMut<DevConsole>) {
+    if keyboard_input.just_pressed(KeyCode::Backquote) {
+        console.open = !console.open;
+    }
+}
+
+// System to type into the console and run the line on Enter
+fn handle_console_input(
+    mut keyboard_input: EventReader<KeyboardInput>,
+    mut console: ResMut<DevConsole>,
+    mut network_settings: ResMut<NetworkSettings>,
+    conditioner: Option<Res<ClientLinkConditioner>>,
+    roles: Option<Res<MatchRoles>>,
+    am_illusions: Res<AmIllusionList>,
+    am_console: Res<AmConsole>,
+    mut client_messages: EventWriter<ClientMessage>,
+) {
+    if !console.open {
+        keyboard_input.clear();
+        return;
+    }
+    for ev in keyboard_input.read() {
+        if ev.state == ButtonState::Released || ev.key_code == KeyCode::Backquote {
+            continue;
+        }
+        if ev.logical_key == Key::Enter {
+            let line = std::mem::take(&mut console.input);
+            console.print(format!("> {}", line));
+            let command = run_command(
+                line.trim(),
+                &mut console,
+                &mut network_settings,
+                conditioner.as_deref(),
+                roles.as_deref(),
+                &am_illusions,
+                &am_console,
+            );
+            if let Some(message) = command {
+                client_messages.send(message);
+            }
+            continue;
+        }
+        edit_text(&ev.logical_key, &mut console.input, MAX_COMMAND_LEN);
+    }
+}
+
+// Runs one console line, returning whatever it wants sent to the server
+fn run_command(
+    line: &str,
+    console: &mut DevConsole,
+    network_settings: &mut NetworkSettings,
+    conditioner: Option<&ClientLinkConditioner>,
+    roles: Option<&MatchRoles>,
+    am_illusions: &AmIllusionList,
+    am_console: &AmConsole,
+) -> Option<ClientMessage> {
+    let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+    match command {
+        "" => {}
+        "help" => console.print(HELP),
+        "conditioner" => {
+            let conditions = match args.trim() {
+                "" => {
+                    let current = match conditioner {
+                        Some(conditioner) => conditioner.0.conditions().to_string(),
+                        None => "off".to_string(),
+                    };
+                    console.print(format!("Link conditioner: {}", current));
+                    return None;
+                }
+                "off" => None,
+                spec => match LinkConditions::parse(spec) {
+                    Ok(conditions) => Some(conditions),
+                    Err(err) => {
+                        console.print(err);
+                        return None;
+                    }
+                },
+            };
+            network_settings.link_conditioner = conditions.clone();
+            match conditioner {
+                // The relay has to stay up for the current connection, switching off just makes it transparent
+                Some(conditioner) => {
+                    conditioner.0.set_conditions(conditions.unwrap_or_default());
+                    console.print("Link conditioner updated");
+                }
+                None => console.print("Link conditioner applies from the next connection"),
+            }
+        }
+        "narrate" | "illusion" => {
+            let action = match command {
+                "narrate" => parse_narrative(args, roles).map(InterventionAction::Narrate),
+                _ => parse_illusion(args, roles),
+            };
+            // Spent like any other intervention, on the first one in the scenario that fits
+            match action {
+                Ok(action) => match am_console.intervention_for(&action) {
+                    Some(intervention) => {
+                        return Some(ClientMessage::Intervene {
+                            intervention: intervention.id.clone(),
+                            action,
+                        })
+                    }
+                    None => console.print("The scenario gives AM no intervention for that"),
+                },
+                Err(err) => console.print(err),
+            }
+        }
+        "illusions" => {
+            if am_illusions.0.is_empty() {
+                console.print("No illusions");
+            }
+            for illusion in &am_illusions.0 {
+                let seen_through = if illusion.seen_through { ", seen through" } else { "" };
+                console.print(format!(
+                    "#{} on {}: {:?} (exposed {}x{})",
+                    illusion.id.0, illusion.target, illusion.kind, illusion.exposures, seen_through
+                ));
+            }
+        }
+        "dispel" => match args.trim().trim_start_matches('#').parse() {
+            Ok(id) => return Some(ClientMessage::DispelIllusion(IllusionId(id))),
+            Err(_) => console.print("Usage: dispel <id>"),
+        },
+        other => console.print(format!("Unknown command '{}', try help", other)),
+    }
+    None
+}
+
+// Reads "Ted item 120,0 bread | It crumbles to dust" and the like
+fn parse_illusion(args: &str, roles: Option<&MatchRoles>) -> Result<InterventionAction, String> {
+    let roles = roles.ok_or("Not in a match")?;
+    let (spec, message) = match args.split_once('|') {
+        Some((spec, message)) => (spec, Some(message.trim())),
+        None => (args, None),
+    };
+    let usage = || "Usage: illusion <target> item <x>,<y> <name> | double <who> <x>,<y> | sprite <who> <set> | hide <who>".to_string();
+    let mut words = spec.split_whitespace();
+    let target = survivor_id(roles, words.next().ok_or_else(usage)?)?;
+    let kind = match words.next().ok_or_else(usage)? {
+        "item" => {
+            let position = parse_position(words.next().ok_or_else(usage)?)?;
+            let item = words.collect::<Vec<_>>().join(" ");
+            IllusionKind::FakeItem { item, position }
+        }
+        "double" => IllusionKind::FakePlayer {
+            of: survivor_id(roles, words.next().ok_or_else(usage)?)?,
+            position: parse_position(words.next().ok_or_else(usage)?)?,
+        },
+        "sprite" => {
+            let client_id = survivor_id(roles, words.next().ok_or_else(usage)?)?;
+            let sprites = SpriteSet(words.next().ok_or_else(usage)?.to_string());
+            IllusionKind::SpriteOverride { client_id, sprites }
+        }
+        "hide" => IllusionKind::HiddenPlayer {
+            client_id: survivor_id(roles, words.next().ok_or_else(usage)?)?,
+        },
+        _ => return Err(usage()),
+    };
+    let message = message.filter(|message| !message.is_empty()).map(NarrativeText::parse);
+    Ok(InterventionAction::Hallucination {
+        target,
+        kind,
+        exposure: Exposure { message, dispel: true },
+    })
+}
+
+fn parse_position(text: &str) -> Result<Vec2, String> {
+    let (x, y) = text.split_once(',').ok_or_else(|| format!("'{}' is not a position, use x,y", text))?;
+    match (x.trim().parse(), y.trim().parse()) {
+        (Ok(x), Ok(y)) => Ok(Vec2::new(x, y)),
+        _ => Err(format!("'{}' is not a position, use x,y", text)),
+    }
+}
+
+fn survivor_id(roles: &MatchRoles, name: &str) -> Result<u64, String> {
+    roles
+        .0
+        .iter()
+        .find(|assignment| matches!(assignment.role, Role::Survivor(_)) && assignment.role.name().eq_ignore_ascii_case(name))
+        .map(|assignment| assignment.client_id)
+        .ok_or_else(|| format!("Nobody plays {}", name))
+}
+
+// Reads "Ellen=Benny took the food; Benny,Ted=Ellen took the food | Gorrister took the food | Benny false"
+fn parse_narrative(args: &str, roles: Option<&MatchRoles>) -> Result<NarrativeEvent, String> {
+    let roles = roles.ok_or("Not in a match")?;
+    let mut parts = args.split('|');
+    let versions = parts.next().unwrap_or_default();
+    let truth = parts.next().map(|truth| truth.trim().to_string()).filter(|truth| !truth.is_empty());
+    let rumour = match parts.next().map(str::trim).filter(|rumour| !rumour.is_empty()) {
+        Some(rumour) => {
+            let usage = || "A rumour needs the form <who> true or <who> false".to_string();
+            let (who, holds) = rumour.split_once(' ').ok_or_else(usage)?;
+            Some(Rumour {
+                about: survivor_id(roles, who)?,
+                is_true: holds.trim().parse().map_err(|_| usage())?,
+            })
+        }
+        None => None,
+    };
+
+    let mut event = NarrativeEvent { versions: Vec::new(), truth, rumour };
+    for version in versions.split(';').filter(|version| !version.trim().is_empty()) {
+        let (who, text) = version
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' needs the form <who>=<text>", version.trim()))?;
+        let mut recipients = Vec::new();
+        for name in who.split(',').map(str::trim) {
+            if name.eq_ignore_ascii_case("all") {
+                let survivors = roles.0.iter().filter(|assignment| matches!(assignment.role, Role::Survivor(_)));
+                recipients.extend(survivors.map(|assignment| assignment.client_id));
+                continue;
+            }
+            recipients.push(survivor_id(roles, name)?);
+        }
+        event.versions.push(NarrativeVersion {
+            recipients,
+            text: NarrativeText::parse(text.trim()),
+        });
+    }
+    if event.versions.is_empty() {
+        return Err("Usage: narrate <who>=<text>; <who>=<text> | <truth> | <about who> <true or false>".to_string());
+    }
+    Ok(event)
+}
+
+// System to redraw the log and the line being typed
+fn update_dev_console(console: Res<DevConsole>, mut text_query: Query<&mut Text, With<DevConsoleText>>) {
+    if !console.is_changed() {
+        return;
+    }
+    for mut text in &mut text_query {
+        let mut lines = console.log.clone();
+        lines.push(format!("> {}_", console.input));
+        text.sections[0].value = lines.join("\n");
+    }
+}
@@ -1,28 +1,57 @@
 use bevy::prelude::*;
 use crate::{components, GameState};
-use crate::systems::greeting_system::greeting_system;
-use crate::resources::selection_timer::SelectionTimer;
+use crate::components::role::{Character, ControlledBy, Role};
+use crate::protocol::ServerMessage;
 
 pub struct GameRunnerPlugin;
 
 impl Plugin for GameRunnerPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(SelectionTimer(Timer::from_seconds(5.0, TimerMode::Repeating)))
-            .add_systems(Startup, (add_players, welcome_monologue, start_main_menu))
-            .add_systems(Update, greeting_system);
+        app.add_systems(Startup, (add_players, welcome_monologue, start_main_menu))
+            .add_systems(Update, receive_character_sheets);
     }
 }
 
+// The cast of the match, tied to connected players once the server hands out roles
 fn add_players(mut commands: Commands) {
-    commands.spawn((components::person::Person, components::person::Name("Gorrister".to_string())));
-    commands.spawn((components::person::Person, components::person::Name("Benny".to_string())));
-    commands.spawn((components::person::Person, components::person::Name("Ellen".to_string())));
-    commands.spawn((components::person::Person, components::person::Name("Nimdok".to_string())));
-    commands.spawn((components::person::Person, components::person::Name("Ted".to_string())));
-    commands.spawn((components::person::Person, components::person::Name("AM".to_string()))); // AI player
+    for character in Character::ALL {
+        commands.spawn((
+            components::person::Person,
+            components::person::Name(character.name().to_string()),
+            Role::Survivor(character),
+            ControlledBy::default(),
+        ));
+    }
+    commands.spawn((
+        components::person::Person,
+        components::person::Name("AM".to_string()),
+        Role::Am,
+        ControlledBy::default(),
+    )); // AI player
     commands.spawn(Camera2dBundle::default());
 }
 
+// System to give the survivors of the roster the sheets of the scenario the server plays
+fn receive_character_sheets(
+    mut commands: Commands,
+    mut server_messages: EventReader<ServerMessage>,
+    roster_query: Query<(Entity, &Role)>,
+) {
+    for message in server_messages.read() {
+        let ServerMessage::CharacterSheets(sheets) = message else {
+            continue;
+        };
+        for (entity, role) in &roster_query {
+            let Role::Survivor(character) = role else {
+                continue;
+            };
+            if let Some((_, sheet)) = sheets.iter().find(|(other, _)| other == character) {
+                commands.entity(entity).insert(sheet.clone());
+            }
+        }
+    }
+}
+
 fn welcome_monologue() {
     println!("Hate Monologue");
 }
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::animation::SpriteAnimations;
+use crate::components::appearance::Appearance;
+use crate::components::character_sheet::CharacterSheet;
+use crate::components::player::{Player, PlayerState};
+use crate::components::role::ControlledBy;
+use crate::consts;
+use crate::plugins::chat::chat_closed;
+use crate::plugins::ingame_player::PlayerAnimations;
+use crate::plugins::interpolation::RemotePlayer;
+use crate::protocol::{ClientMessage, Illusion, IllusionId, IllusionKind, InteractTarget, ServerMessage};
+
+pub struct HallucinationPlugin;
+
+const FAKE_ITEM_SIZE: f32 = 24.0;
+const FAKE_ITEM_COLOR: Color = Color::srgb(0.85, 0.7, 0.2);
+
+// Something drawn on this client only, AM put it there
+#[derive(Component)]
+struct Hallucination {
+    id: IllusionId,
+}
+
+// Illusions AM cast on us, with the entity drawing the ones that have a body
+#[derive(Resource, Default)]
+struct Hallucinations(HashMap<IllusionId, (IllusionKind, Option<Entity>)>);
+
+// Every illusion in the match, only AM ever gets this
+#[derive(Resource, Clone, Debug, Default)]
+pub struct AmIllusionList(pub Vec<Illusion>);
+
+impl Plugin for HallucinationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Hallucinations>()
+            .init_resource::<AmIllusionList>()
+            .add_systems(OnEnter(GameState::InGame), reset_hallucinations)
+            .add_systems(
+                Update,
+                (receive_illusions, apply_sprite_overrides, animate_fake_players, interact.run_if(chat_closed))
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::InGame), cleanup_hallucinations);
+    }
+}
+
+fn reset_hallucinations(mut hallucinations: ResMut<Hallucinations>, mut am_list: ResMut<AmIllusionList>) {
+    hallucinations.0.clear();
+    am_list.0.clear();
+}
+
+// System to draw what AM makes us see and take it away again once it is exposed or dispelled
+fn receive_illusions(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut server_messages: EventReader<ServerMessage>,
+    mut hallucinations: ResMut<Hallucinations>,
+    mut am_list: ResMut<AmIllusionList>,
+) {
+    for message in server_messages.read() {
+        let gone = match message {
+            ServerMessage::IllusionAppeared { id, kind } => {
+                let entity = spawn_hallucination(&mut commands, &asset_server, *id, kind);
+                if let Some((_, Some(old))) = hallucinations.0.insert(*id, (kind.clone(), entity)) {
+                    commands.entity(old).despawn_recursive();
+                }
+                continue;
+            }
+            ServerMessage::IllusionExposed { id, dispelled: true } | ServerMessage::IllusionDispelled(id) => *id,
+            ServerMessage::AmIllusions(illusions) => {
+                am_list.0 = illusions.clone();
+                continue;
+            }
+            _ => continue,
+        };
+        if let Some((_, Some(entity))) = hallucinations.0.remove(&gone) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn spawn_hallucination(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    id: IllusionId,
+    kind: &IllusionKind,
+) -> Option<Entity> {
+    match kind {
+        IllusionKind::FakeItem { item, position } => {
+            let entity = commands
+                .spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: FAKE_ITEM_COLOR,
+                            custom_size: Some(Vec2::splat(FAKE_ITEM_SIZE)),
+                            ..Default::default()
+                        },
+                        transform: Transform::from_translation(position.extend(0.0)),
+                        ..Default::default()
+                    },
+                    Hallucination { id },
+                ))
+                .with_children(|parent| {
+                    parent.spawn(Text2dBundle {
+                        text: Text::from_section(
+                            item.clone(),
+                            TextStyle {
+                                font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                                font_size: 16.0,
+                                color: Color::WHITE,
+                            },
+                        ),
+                        transform: Transform::from_xyz(0.0, FAKE_ITEM_SIZE, 0.0),
+                        ..Default::default()
+                    });
+                })
+                .id();
+            Some(entity)
+        }
+        IllusionKind::FakePlayer { position, .. } => {
+            let entity = commands
+                .spawn((
+                    SpriteBundle {
+                        transform: Transform::from_translation(position.extend(0.0)),
+                        ..Default::default()
+                    },
+                    TextureAtlas::default(),
+                    PlayerState::Idle,
+                    Hallucination { id },
+                ))
+                .id();
+            Some(entity)
+        }
+        // These change how real players are drawn, there is nothing to spawn
+        IllusionKind::SpriteOverride { .. } | IllusionKind::HiddenPlayer { .. } => None,
+    }
+}
+
+// System to draw remote players with their character's sprite set, or the one AM wants us to see them in.
+// Fake players look like whoever they pass for.
+fn apply_sprite_overrides(
+    mut commands: Commands,
+    hallucinations: Res<Hallucinations>,
+    roster_query: Query<(&ControlledBy, &CharacterSheet)>,
+    remote_query: Query<(Entity, &RemotePlayer, Option<&Appearance>)>,
+    fake_query: Query<(Entity, &Hallucination, Option<&Appearance>)>,
+) {
+    let remotes = remote_query.iter().map(|(entity, remote, appearance)| (entity, Some(remote.client_id), appearance));
+    let fakes = fake_query.iter().map(|(entity, hallucination, appearance)| {
+        let of = match hallucinations.0.get(&hallucination.id) {
+            Some((IllusionKind::FakePlayer { of, .. }, _)) => Some(*of),
+            _ => None,
+        };
+        (entity, of, appearance)
+    });
+    for (entity, looks_like, appearance) in remotes.chain(fakes) {
+        // Fake items have nobody to look like
+        let Some(looks_like) = looks_like else {
+            continue;
+        };
+        let sprites = hallucinations
+            .0
+            .values()
+            .find_map(|(kind, _)| match kind {
+                IllusionKind::SpriteOverride { client_id, sprites } if *client_id == looks_like => Some(sprites),
+                _ => None,
+            })
+            .or_else(|| {
+                roster_query
+                    .iter()
+                    .find(|(controlled_by, _)| controlled_by.0 == Some(looks_like))
+                    .map(|(_, sheet)| &sheet.sprites)
+            });
+        // Not drawn until we know who they are
+        let Some(sprites) = sprites else {
+            continue;
+        };
+        if appearance.map(|appearance| &appearance.0) != Some(sprites) {
+            commands.entity(entity).insert(Appearance(sprites.clone()));
+        }
+    }
+}
+
+// System to keep fake players idling like a real one would
+fn animate_fake_players(
+    time: Res<Time>,
+    animations: Option<Res<PlayerAnimations>>,
+    sprite_animations: Res<Assets<SpriteAnimations>>,
+    mut query: Query<(&mut Handle<Image>, &mut TextureAtlas, &Appearance, &PlayerState), With<Hallucination>>,
+) {
+    let Some(animations) = animations else {
+        return;
+    };
+    for (mut texture, mut atlas, appearance, state) in &mut query {
+        let Some(animation) = animations.for_state(&appearance.0, *state, &sprite_animations) else {
+            continue;
+        };
+        if *texture != animation.texture_handle {
+            *texture = animation.texture_handle.clone();
+            atlas.layout = animation.layout.clone();
+        }
+        atlas.index = animation.frame_at(time.elapsed_seconds());
+    }
+}
+
+// System to reach out to the closest thing in range with E, the server decides what it really is
+fn interact(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    player_query: Query<&Transform, With<Player>>,
+    hallucination_query: Query<(&Hallucination, &Transform)>,
+    remote_query: Query<(&RemotePlayer, &Transform)>,
+    mut client_messages: EventWriter<ClientMessage>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyE) {
+        return;
+    }
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    let position = player.translation.truncate();
+    let hallucinations = hallucination_query
+        .iter()
+        .map(|(hallucination, transform)| (InteractTarget::Illusion(hallucination.id), transform));
+    let remote_players = remote_query
+        .iter()
+        .map(|(remote, transform)| (InteractTarget::Player(remote.client_id), transform));
+    let closest = hallucinations
+        .chain(remote_players)
+        .map(|(target, transform)| (target, transform.translation.truncate().distance(position)))
+        .filter(|(_, distance)| *distance <= consts::INTERACT_RANGE)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+    if let Some((target, _)) = closest {
+        client_messages.send(ClientMessage::Interact(target));
+    }
+}
+
+fn cleanup_hallucinations(mut commands: Commands, query: Query<Entity, With<Hallucination>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
@@ -1,35 +1,25 @@
 use bevy::prelude::*;
-use std::time::Duration;
+use std::collections::BTreeMap;
+use bevy::asset::AssetLoadFailedEvent;
 use crate::GameState;
+use crate::animation::{Animation, SpriteAnimations, SpriteAnimationsLoader};
+use crate::components::player::{Player, PlayerInputState, PlayerState, StateElapsed};
+use crate::components::appearance::SpriteSet;
+use crate::components::character_sheet::CharacterSheet;
+use crate::components::role::{ControlledBy, Role};
+use crate::plugins::chat::ChatDraft;
+use crate::plugins::interpolation::RemotePlayer;
+use crate::plugins::network::LocalClientId;
+use crate::resources::match_roles::MatchRoles;
+use crate::protocol::PlayerInputs;
 
 pub struct PlayerInGamePlugin;
 
+// Which animation the local player shows and how far into it
 #[derive(Component)]
 struct SpriteAnimState {
-    start_index: usize,
-    end_index: usize,
-    frame_size: UVec2,
-    texture_size: Vec2,
-    timer: Timer,
-}
-
-#[derive(Component)]
-struct Player;
-
-#[derive(Component, PartialEq, Eq, Debug, Clone, Copy)]
-enum PlayerState {
-    Idle,
-    Walking,
-    Running,
-    Attacking,
-    Hurt,
-    Dead,
-}
-
-#[derive(Component)]
-struct PlayerInputState {
-    movement_velocity: Vec2,
-    speed_multiplier: f32,
+    state: PlayerState,
+    elapsed: f32,
 }
 
 #[derive(Bundle)]
@@ -37,145 +27,130 @@ struct PlayerBundle {
     sprite_sheet_bundle: SpriteBundle,
     marker: Player,
     state: PlayerState,
+    state_elapsed: StateElapsed,
     input_state: PlayerInputState,
     anim_state: SpriteAnimState,
+    sheet: CharacterSheet,
 }
 
+// Animations of every sprite set found in the assets, anyone can be drawn with any of them
 #[derive(Resource)]
-struct PlayerAnimations {
-    idle: Animation,
-    walk: Animation,
-    attack: Animation,
-}
+pub(crate) struct PlayerAnimations(BTreeMap<SpriteSet, Handle<SpriteAnimations>>);
 
-struct Animation {
-    frames: usize,
-    frame_size: UVec2,
-    texture_size: Vec2,
-    texture_handle: Handle<Image>,
-}
+impl PlayerAnimations {
+    // None until the set's manifest and sheets finished loading, or when this client has no such set
+    pub(crate) fn for_state<'a>(
+        &self,
+        set: &SpriteSet,
+        state: PlayerState,
+        sets: &'a Assets<SpriteAnimations>,
+    ) -> Option<&'a Animation> {
+        sets.get(self.0.get(set)?)?.clip(state)
+    }
 
-#[derive(Event, Debug)]
-enum PlayerInputs {
-    Move(Vec2),
-    Attack,
+    // In name order
+    pub(crate) fn sets(&self) -> impl Iterator<Item = &SpriteSet> {
+        self.0.keys()
+    }
 }
 
+// Who a dead player is watching, picked with the arrow keys
+#[derive(Resource, Default)]
+struct Spectating(Option<u64>);
+
+#[derive(Component)]
+struct SpectatorText;
+
 impl Plugin for PlayerInGamePlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(OnEnter(GameState::InGame), setup_sprite_animation)
-            .add_systems(Update, (keyboard_input,player_movement_state,animate_sprite,update_player_animation).chain().run_if(in_state(GameState::InGame)))
+            .add_event::<PlayerInputs>()
+            .init_asset::<SpriteAnimations>()
+            .init_asset_loader::<SpriteAnimationsLoader>()
+            .init_resource::<Spectating>()
+            .add_systems(Startup, load_sprite_animations)
+            .add_systems(Update, report_broken_animations)
+            .add_systems(OnEnter(GameState::InGame), spawn_player)
+            .add_systems(Update, (keyboard_input,player_movement_state,update_player_animation,spectate).chain().run_if(in_state(GameState::InGame)))
             .add_systems(OnExit(GameState::InGame), cleanup_animation);
     }
 }
 
-fn setup_sprite_animation(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlasLayout>>,
-) {
-    // Load textures for each animation
-    let idle_texture_handle = asset_server.load("sprites/City_men_3/Idle.png");
-    let walk_texture_handle = asset_server.load("sprites/City_men_3/Walk.png");
-    let attack_texture_handle = asset_server.load("sprites/City_men_3/Attack.png");
-
-    let idle_frames = 6;
-    let walk_frames = 10;
-    let _run_frames = 10;
-    let attack_frames = 4;
-    let _hurt_frames = 3;
-    let _dead_frames = 5;
-
-     // Define frame sizes
-    let frame_size = UVec2::new(128, 128);
-
-    // Create TextureAtlasLayouts
-    let idle_layout = TextureAtlasLayout::from_grid(frame_size as UVec2, idle_frames, 1, None, None);
-    let idle_layout_handle = texture_atlases.add(idle_layout);
-
-    let walk_layout = TextureAtlasLayout::from_grid(frame_size, walk_frames, 1, None, None);
-    let walk_layout_handle = texture_atlases.add(walk_layout);
-
-    let attack_layout = TextureAtlasLayout::from_grid(frame_size, attack_frames, 1, None, None);
-    let attack_layout_handle = texture_atlases.add(attack_layout);
+fn load_sprite_animations(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let sets = SpriteSet::discover()
+        .into_iter()
+        .map(|set| {
+            let animations = asset_server.load(set.manifest());
+            (set, animations)
+        })
+        .collect();
+    commands.insert_resource(PlayerAnimations(sets));
+}
 
-     // Define texture sizes (assuming horizontal sprite sheets)
-     let idle_texture_size = Vec2::new(idle_frames as f32 * frame_size.x as f32, frame_size.y as f32);
-     let walk_texture_size = Vec2::new(walk_frames as f32 * frame_size.x as f32, frame_size.y as f32);
-     let attack_texture_size = Vec2::new(attack_frames as f32 * frame_size.x as f32, frame_size.y as f32);
+// System to point artists at what is wrong with a sprite set's manifest
+fn report_broken_animations(mut failures: EventReader<AssetLoadFailedEvent<SpriteAnimations>>) {
+    for failure in failures.read() {
+        println!("Animations {} failed to load: {}", failure.path, failure.error);
+    }
+}
 
-    // Store animations in a resource
-    commands.insert_resource(PlayerAnimations {
-        idle: Animation {
-            frames: idle_frames as usize,
-            frame_size,
-            texture_size: idle_texture_size,
-            texture_handle: idle_texture_handle.clone(),
-        },
-        walk: Animation {
-            frames: walk_frames as usize,
-            frame_size,
-            texture_size: walk_texture_size,
-            texture_handle: walk_texture_handle.clone(),
-        },
-        attack: Animation {
-            frames: attack_frames as usize,
-            frame_size,
-            texture_size: attack_texture_size,
-            texture_handle: attack_texture_handle.clone(),
-        },
-    });
-    
+// The animation system puts the right sheet on as soon as the set has loaded
+fn spawn_player(
+    mut commands: Commands,
+    roles: Option<Res<MatchRoles>>,
+    local_client: Option<Res<LocalClientId>>,
+    roster_query: Query<(&ControlledBy, &CharacterSheet)>,
+) {
+    // AM has no body in the world, it only watches and intervenes
+    let local_id = local_client.map(|local_client| local_client.0);
+    let local_role = roles.zip(local_id).and_then(|(roles, local_id)| roles.role_of(local_id));
+    if local_role == Some(Role::Am) {
+        return;
+    }
+    // The server moves us at the speed on our sheet, predict with the same
+    let Some(sheet) = roster_query
+        .iter()
+        .find(|(controlled_by, _)| controlled_by.0.is_some() && controlled_by.0 == local_id)
+        .map(|(_, sheet)| sheet.clone())
+    else {
+        return;
+    };
 
     // Spawn player entity using PlayerBundle
     commands.spawn((PlayerBundle {
         sprite_sheet_bundle: SpriteBundle {
-            texture: idle_texture_handle,
             transform: Transform::from_xyz(0.0, 0.0, 0.0),
             ..Default::default()
         },
         marker: Player,
         state: PlayerState::Idle,
+        state_elapsed: StateElapsed::default(),
         input_state: PlayerInputState {
             movement_velocity: Vec2::ZERO,
-            speed_multiplier: 150.0,
+            speed_multiplier: sheet.speed,
+            sprinting: false,
+            attack_requested: false,
         },
         anim_state: SpriteAnimState {
-            start_index: 0,
-            end_index: idle_frames as usize - 1,
-            frame_size,
-            texture_size: idle_texture_size,
-            timer: Timer::from_seconds(0.1, TimerMode::Repeating),
+            state: PlayerState::Idle,
+            elapsed: 0.0,
         },
+        sheet,
     },
-    TextureAtlas {
-        layout: idle_layout_handle,
-        index: 0,
-    },
+    TextureAtlas::default(),
 ));
 }
 
-
-fn animate_sprite(
-    time: Res<Time>,
-    mut query: Query<(&mut TextureAtlas, &mut SpriteAnimState)>,
-) {
-    for (mut sprite, mut anim_state) in query.iter_mut() {
-        anim_state.timer.tick(time.delta());
-        if anim_state.timer.finished() {
-            sprite.index += 1;
-            if sprite.index > anim_state.end_index {
-                sprite.index = anim_state.start_index;
-            }
-        }
-    }
-}
-
 fn keyboard_input(
     keyboard_input: Res<ButtonInput<KeyCode>>,
+    chat: Res<ChatDraft>,
     mut evw_player: EventWriter<PlayerInputs>,
 ) {
+    // Stand still while typing
+    if chat.open {
+        evw_player.send(PlayerInputs::Move(Vec2::ZERO));
+        return;
+    }
     let mut movement = Vec2::ZERO;
     if keyboard_input.pressed(KeyCode::ArrowLeft) {
         movement.x -= 1.0;
@@ -188,76 +163,150 @@ fn keyboard_input(
     } else {
         evw_player.send(PlayerInputs::Move(Vec2::ZERO));
     }
+    evw_player.send(PlayerInputs::Sprint(keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])));
 
     if keyboard_input.just_pressed(KeyCode::Space) {
         evw_player.send(PlayerInputs::Attack);
     }
 }
 
+// System to gather this frame's inputs; the prediction step turns them into movement and state
 fn player_movement_state(
     mut evr_player: EventReader<PlayerInputs>,
-    mut q_player: Query<(&mut PlayerState, &mut PlayerInputState), With<Player>>,
+    mut q_player: Query<&mut PlayerInputState, With<Player>>,
 ) {
     for ev in evr_player.read() {
         match ev {
             PlayerInputs::Move(vel) => {
-                for (mut state, mut input) in q_player.iter_mut() {
+                for mut input in q_player.iter_mut() {
                     input.movement_velocity = *vel;
-                    if *state != PlayerState::Attacking {
-                        if *vel == Vec2::ZERO {
-                            *state = PlayerState::Idle;
-                        } else {
-                            *state = PlayerState::Walking;
-                        }
-                    }
+                }
+            }
+            PlayerInputs::Sprint(sprinting) => {
+                for mut input in q_player.iter_mut() {
+                    input.sprinting = *sprinting;
                 }
             }
             PlayerInputs::Attack => {
-                for (mut state, _) in q_player.iter_mut() {
-                    *state = PlayerState::Attacking;
+                for mut input in q_player.iter_mut() {
+                    input.attack_requested = true;
                 }
             }
         }
     }
 }
 
+// System to play the animation of the state prediction put us in, from its start whenever the state changes
 fn update_player_animation(
+    time: Res<Time>,
     player_animations: Res<PlayerAnimations>,
+    sprite_animations: Res<Assets<SpriteAnimations>>,
     mut query: Query<(
         &mut Handle<Image>,
+        &mut TextureAtlas,
         &mut SpriteAnimState,
-        &mut Sprite,
         &PlayerState,
+        &CharacterSheet,
     ), With<Player>>,
 ) {
-    for (mut texture_handle, mut anim_state, mut sprite, state) in query.iter_mut() {
-        let animation = match *state {
-            PlayerState::Idle => &player_animations.idle,
-            PlayerState::Walking => &player_animations.walk,
-            PlayerState::Attacking => &player_animations.attack,
-            _ => continue,
+    for (mut texture_handle, mut atlas, mut anim_state, state, sheet) in query.iter_mut() {
+        if anim_state.state != *state {
+            anim_state.state = *state;
+            anim_state.elapsed = 0.0;
+        } else {
+            anim_state.elapsed += time.delta_seconds();
+        }
+        let Some(animation) = player_animations.for_state(&sheet.sprites, *state, &sprite_animations) else {
+            continue;
         };
-
         if *texture_handle != animation.texture_handle {
             *texture_handle = animation.texture_handle.clone();
-            anim_state.frames = animation.frames;
-            anim_state.frame_size = animation.frame_size;
-            anim_state.texture_size = animation.texture_size;
-            anim_state.current_frame = 0;
-            anim_state.timer = Timer::from_seconds(0.1, TimerMode::Repeating);
-
-            // Reset sprite rect
-            sprite.rect = Some(Rect {
-                min: Vec2::ZERO,
-                max: animation.frame_size.as_vec2(),
-            });
+            atlas.layout = animation.layout.clone();
         }
+        atlas.index = animation.frame_at(anim_state.elapsed);
     }
 }
 
+// System to hand the dead a view of the survivors still standing. The camera follows one of them.
+fn spectate(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    roles: Option<Res<MatchRoles>>,
+    mut spectating: ResMut<Spectating>,
+    player_query: Query<&PlayerState, With<Player>>,
+    remote_query: Query<(&RemotePlayer, &Transform, &PlayerState)>,
+    mut camera_query: Query<&mut Transform, (With<Camera>, Without<RemotePlayer>, Without<Player>)>,
+    mut text_query: Query<&mut Text, With<SpectatorText>>,
+) {
+    if !matches!(player_query.get_single(), Ok(PlayerState::Dead)) {
+        return;
+    }
+    let mut alive: Vec<(u64, Vec2)> = remote_query
+        .iter()
+        .filter(|(_, _, state)| **state != PlayerState::Dead)
+        .map(|(remote, transform, _)| (remote.client_id, transform.translation.truncate()))
+        .collect();
+    alive.sort_by_key(|(client_id, _)| *client_id);
+
+    let current = alive.iter().position(|(client_id, _)| Some(*client_id) == spectating.0);
+    let step = keyboard_input.just_pressed(KeyCode::ArrowRight) as usize + alive.len()
+        - keyboard_input.just_pressed(KeyCode::ArrowLeft) as usize;
+    let next = match current {
+        Some(index) if !alive.is_empty() => Some((index + step) % alive.len()),
+        _ => (!alive.is_empty()).then_some(0),
+    };
+    spectating.0 = next.map(|index| alive[index].0);
+
+    if let Some(index) = next {
+        for mut transform in &mut camera_query {
+            transform.translation = alive[index].1.extend(transform.translation.z);
+        }
+    }
+    let label = match spectating.0.and_then(|client_id| roles.as_ref()?.role_of(client_id)) {
+        Some(role) => format!("You are dead. Watching {} (Left/Right)", role.name()),
+        None => "You are dead.".to_string(),
+    };
+    if text_query.is_empty() {
+        commands.spawn((
+            TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                    font_size: 28.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_text_justify(JustifyText::Center)
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(80.0),
+                width: Val::Percent(100.0),
+                ..Default::default()
+            }),
+            SpectatorText,
+        ));
+        return;
+    }
+    for mut text in &mut text_query {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
 
-fn cleanup_animation(mut commands: Commands, query: Query<Entity, With<SpriteAnimState>>) {
+fn cleanup_animation(
+    mut commands: Commands,
+    mut spectating: ResMut<Spectating>,
+    query: Query<Entity, Or<(With<SpriteAnimState>, With<SpectatorText>)>>,
+    mut camera_query: Query<&mut Transform, With<Camera>>,
+) {
+    *spectating = Spectating::default();
     for entity in query.iter() {
         commands.entity(entity).despawn_recursive();
     }
-}
\ No newline at end of file
+    for mut transform in &mut camera_query {
+        transform.translation.x = 0.0;
+        transform.translation.y = 0.0;
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::animation::SpriteAnimations;
+use crate::components::appearance::Appearance;
+use crate::components::player::PlayerState;
+use crate::plugins::ingame_player::PlayerAnimations;
+use crate::plugins::network::LocalClientId;
+use crate::protocol::{ServerMessage, Snapshot};
+
+pub struct InterpolationPlugin;
+
+// Snapshots older than this are useless even for a very generous render delay
+const MAX_BUFFERED_SNAPSHOTS: usize = 64;
+// How quickly the server clock estimate follows samples that arrived late
+const CLOCK_SMOOTHING: f64 = 0.02;
+const RENDER_DELAY_STEP_MS: u32 = 10;
+
+// How far behind the server remote players are drawn, tunable while playing
+#[derive(Resource, Clone, Debug)]
+pub struct InterpolationSettings {
+    // Enough for two or three snapshots at the default tick rate, so one lost packet goes unnoticed
+    pub render_delay_ms: u32,
+    // Longest we keep guessing where a player is headed once snapshots stop arriving
+    pub max_extrapolation_ms: u32,
+}
+
+impl Default for InterpolationSettings {
+    fn default() -> Self {
+        Self {
+            render_delay_ms: std::env::var("ERGO_RENDER_DELAY_MS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(100),
+            max_extrapolation_ms: 250,
+        }
+    }
+}
+
+// Another player in the room, drawn from server snapshots
+#[derive(Component)]
+pub struct RemotePlayer {
+    pub client_id: u64,
+}
+
+#[derive(Clone, Copy)]
+struct BufferedState {
+    server_time: f64,
+    position: Vec2,
+    state: PlayerState,
+    state_elapsed: f32,
+}
+
+// Snapshots received for one remote player, oldest first
+#[derive(Component, Default)]
+struct SnapshotBuffer {
+    states: VecDeque<BufferedState>,
+    extrapolating: bool,
+}
+
+// Remote player entities by client id
+#[derive(Resource, Default)]
+struct RemotePlayers(HashMap<u64, Entity>);
+
+// Offset from the local clock to the server clock, estimated from snapshot arrival times
+#[derive(Resource, Default)]
+struct ServerClock {
+    offset: Option<f64>,
+    last_tick: Option<u32>,
+}
+
+#[derive(Component)]
+struct InterpolationOverlay;
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<InterpolationSettings>()
+            .init_resource::<RemotePlayers>()
+            .init_resource::<ServerClock>()
+            .add_systems(OnEnter(GameState::InGame), reset_interpolation)
+            .add_systems(
+                Update,
+                (receive_snapshots, interpolate_remote_players, animate_remote_players, toggle_overlay, update_overlay)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::InGame), cleanup_remote_players);
+    }
+}
+
+fn reset_interpolation(mut remote_players: ResMut<RemotePlayers>, mut clock: ResMut<ServerClock>) {
+    remote_players.0.clear();
+    *clock = ServerClock::default();
+}
+
+// System to buffer every snapshot and keep one entity per remote player in the room
+fn receive_snapshots(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut server_messages: EventReader<ServerMessage>,
+    local_client: Option<Res<LocalClientId>>,
+    mut clock: ResMut<ServerClock>,
+    mut remote_players: ResMut<RemotePlayers>,
+    mut buffers: Query<&mut SnapshotBuffer>,
+) {
+    let Some(local_client) = local_client else {
+        return;
+    };
+    let now = time.elapsed_seconds_f64();
+    let mut newest: Option<&Snapshot> = None;
+    // Buffers of entities spawned this frame, their components only exist once the commands run
+    let mut spawned: HashMap<Entity, SnapshotBuffer> = HashMap::new();
+
+    for message in server_messages.read() {
+        let ServerMessage::Snapshot(snapshot) = message else {
+            continue;
+        };
+        // A sample that arrived quickly pulls the estimate forward at once, late ones only nudge it back
+        let sample = snapshot.server_time - now;
+        clock.offset = Some(match clock.offset {
+            Some(offset) if sample < offset => offset + (sample - offset) * CLOCK_SMOOTHING,
+            _ => sample,
+        });
+
+        for player in snapshot.players.iter().filter(|player| player.client_id != local_client.0) {
+            let entity = *remote_players.0.entry(player.client_id).or_insert_with(|| {
+                println!("Player {} entered view", player.client_id);
+                commands
+                    .spawn((
+                        SpriteBundle {
+                            transform: Transform::from_translation(player.position.extend(0.0)),
+                            ..Default::default()
+                        },
+                        TextureAtlas::default(),
+                        RemotePlayer { client_id: player.client_id },
+                        player.state,
+                    ))
+                    .id()
+            });
+            let buffered = BufferedState {
+                server_time: snapshot.server_time,
+                position: player.position,
+                state: player.state,
+                state_elapsed: player.state_elapsed,
+            };
+            match buffers.get_mut(entity) {
+                Ok(mut buffer) => insert_ordered(&mut buffer.states, buffered),
+                Err(_) => insert_ordered(&mut spawned.entry(entity).or_default().states, buffered),
+            }
+        }
+
+        if newest.map_or(true, |current| snapshot.tick > current.tick) {
+            newest = Some(snapshot);
+        }
+    }
+
+    for (entity, buffer) in spawned {
+        commands.entity(entity).insert(buffer);
+    }
+
+    // Players missing from the newest snapshot left the room
+    let Some(newest) = newest else {
+        return;
+    };
+    if clock.last_tick.map_or(false, |tick| newest.tick <= tick) {
+        return;
+    }
+    clock.last_tick = Some(newest.tick);
+    remote_players.0.retain(|client_id, entity| {
+        let present = newest.players.iter().any(|player| player.client_id == *client_id);
+        if !present {
+            println!("Player {} left view", client_id);
+            commands.entity(*entity).despawn_recursive();
+        }
+        present
+    });
+}
+
+// Unreliable snapshots can arrive out of order or twice, the buffer stays sorted by server time
+fn insert_ordered(states: &mut VecDeque<BufferedState>, buffered: BufferedState) {
+    let index = states.partition_point(|state| state.server_time < buffered.server_time);
+    if states.get(index).map_or(false, |state| state.server_time == buffered.server_time) {
+        return;
+    }
+    states.insert(index, buffered);
+    while states.len() > MAX_BUFFERED_SNAPSHOTS {
+        states.pop_front();
+    }
+}
+
+// System to place remote players where they were one render delay ago on the server
+fn interpolate_remote_players(
+    time: Res<Time>,
+    settings: Res<InterpolationSettings>,
+    clock: Res<ServerClock>,
+    mut query: Query<(&mut Transform, &mut PlayerState, &mut SnapshotBuffer), With<RemotePlayer>>,
+) {
+    let Some(offset) = clock.offset else {
+        return;
+    };
+    let render_time = time.elapsed_seconds_f64() + offset - settings.render_delay_ms as f64 / 1000.0;
+    let max_extrapolation = settings.max_extrapolation_ms as f64 / 1000.0;
+
+    for (mut transform, mut state, mut buffer) in &mut query {
+        // Keep a single sample from before the render time to interpolate from
+        while buffer.states.len() > 2 && buffer.states[1].server_time <= render_time {
+            buffer.states.pop_front();
+        }
+        let Some(&latest) = buffer.states.back() else {
+            continue;
+        };
+
+        let position = if buffer.states.len() >= 2 && render_time <= latest.server_time {
+            let from = buffer.states[0];
+            let to = buffer.states[1];
+            let span = (to.server_time - from.server_time).max(f64::EPSILON);
+            let t = ((render_time - from.server_time) / span).clamp(0.0, 1.0) as f32;
+            buffer.extrapolating = false;
+            *state = if t < 1.0 { from.state } else { to.state };
+            from.position.lerp(to.position, t)
+        } else if buffer.states.len() >= 2 {
+            // Out of snapshots, keep going the way the player was last heading for a little while
+            let previous = buffer.states[buffer.states.len() - 2];
+            let span = (latest.server_time - previous.server_time).max(f64::EPSILON);
+            let velocity = (latest.position - previous.position) / span as f32;
+            let ahead = (render_time - latest.server_time).min(max_extrapolation) as f32;
+            buffer.extrapolating = true;
+            *state = latest.state;
+            latest.position + velocity * ahead
+        } else {
+            buffer.extrapolating = render_time > latest.server_time;
+            *state = latest.state;
+            latest.position
+        };
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
+
+// System to show the animation frame the server timeline is on at the render time
+fn animate_remote_players(
+    time: Res<Time>,
+    settings: Res<InterpolationSettings>,
+    clock: Res<ServerClock>,
+    animations: Option<Res<PlayerAnimations>>,
+    sprite_animations: Res<Assets<SpriteAnimations>>,
+    mut query: Query<
+        (&mut Handle<Image>, &mut TextureAtlas, &PlayerState, &Appearance, &SnapshotBuffer),
+        With<RemotePlayer>,
+    >,
+) {
+    let (Some(offset), Some(animations)) = (clock.offset, animations) else {
+        return;
+    };
+    let render_time = time.elapsed_seconds_f64() + offset - settings.render_delay_ms as f64 / 1000.0;
+
+    for (mut texture, mut atlas, state, appearance, buffer) in &mut query {
+        let Some(animation) = animations.for_state(&appearance.0, *state, &sprite_animations) else {
+            continue;
+        };
+        // Start from the newest sample already in this state that is not ahead of the render time
+        let Some(sample) = buffer
+            .states
+            .iter()
+            .rev()
+            .find(|sample| sample.state == *state && sample.server_time <= render_time)
+            .or_else(|| buffer.states.iter().find(|sample| sample.state == *state))
+        else {
+            continue;
+        };
+        let elapsed = sample.state_elapsed + (render_time - sample.server_time).max(0.0) as f32;
+
+        if *texture != animation.texture_handle {
+            *texture = animation.texture_handle.clone();
+            atlas.layout = animation.layout.clone();
+        }
+        atlas.index = animation.frame_at(elapsed);
+    }
+}
+
+// System to show or hide the interpolation overlay with F3, and tune the render delay while it is open
+fn toggle_overlay(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    mut settings: ResMut<InterpolationSettings>,
+    overlay_query: Query<Entity, With<InterpolationOverlay>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        if let Ok(entity) = overlay_query.get_single() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            commands.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                }),
+                InterpolationOverlay,
+            ));
+        }
+    }
+
+    if overlay_query.is_empty() {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::BracketRight) {
+        settings.render_delay_ms += RENDER_DELAY_STEP_MS;
+    }
+    if keyboard_input.just_pressed(KeyCode::BracketLeft) {
+        settings.render_delay_ms = settings.render_delay_ms.saturating_sub(RENDER_DELAY_STEP_MS);
+    }
+}
+
+// System to list how many snapshots each remote player has buffered
+fn update_overlay(
+    settings: Res<InterpolationSettings>,
+    remote_query: Query<(&RemotePlayer, &SnapshotBuffer)>,
+    mut overlay_query: Query<&mut Text, With<InterpolationOverlay>>,
+) {
+    let Ok(mut text) = overlay_query.get_single_mut() else {
+        return;
+    };
+    let mut lines = vec![format!("Render delay: {} ms  ([ / ] to adjust)", settings.render_delay_ms)];
+    let mut remotes: Vec<_> = remote_query.iter().collect();
+    remotes.sort_by_key(|(remote, _)| remote.client_id);
+    for (remote, buffer) in remotes {
+        lines.push(format!(
+            "Player {}: {} buffered{}",
+            remote.client_id,
+            buffer.states.len(),
+            if buffer.extrapolating { ", extrapolating" } else { "" }
+        ));
+    }
+    text.sections[0].value = lines.join("\n");
+}
+
+fn cleanup_remote_players(
+    mut commands: Commands,
+    mut remote_players: ResMut<RemotePlayers>,
+    query: Query<Entity, Or<(With<RemotePlayer>, With<InterpolationOverlay>)>>,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+    remote_players.0.clear();
+}
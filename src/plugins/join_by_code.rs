@@ -0,0 +1,395 @@
+use bevy::prelude::*;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+
+use crate::GameState;
+use crate::consts;
+use crate::plugins::network::{LocalClientId, NetworkRequest, NetworkSettings};
+use crate::protocol::{ClientMessage, RoomError, ServerMessage};
+use crate::systems::text_input::edit_text;
+
+const JOIN_CODE_LEN: usize = 6;
+const MAX_PASSWORD_LEN: usize = 24;
+
+pub struct JoinByCodePlugin;
+
+#[derive(Component)]
+struct OnJoinByCodeScreen;
+
+#[derive(Component)]
+struct CodeInputField;
+
+#[derive(Component)]
+struct CodeText;
+
+#[derive(Component)]
+struct PasswordInputField;
+
+#[derive(Component)]
+struct PasswordText;
+
+#[derive(Component)]
+struct JoinRoomButton;
+
+#[derive(Component)]
+struct BackButton;
+
+#[derive(Component)]
+struct JoinStatusText;
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum CodeField {
+    Code,
+    Password,
+}
+
+// Resource to store what the player typed on the join screen
+#[derive(Resource)]
+struct JoinByCodeData {
+    code: String,
+    password: String,
+    // Only asked for once the server says the room is locked
+    needs_password: bool,
+    focused: CodeField,
+}
+
+impl Default for JoinByCodeData {
+    fn default() -> Self {
+        Self {
+            code: String::new(),
+            password: String::new(),
+            needs_password: false,
+            focused: CodeField::Code,
+        }
+    }
+}
+
+impl Plugin for JoinByCodePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<JoinByCodeData>()
+            .add_systems(OnEnter(GameState::JoinByCode), setup_join_by_code_ui)
+            .add_systems(
+                Update,
+                (handle_join_buttons, handle_field_focus, handle_text_input, handle_join_errors, update_input_fields)
+                    .chain()
+                    .run_if(in_state(GameState::JoinByCode)),
+            )
+            .add_systems(OnExit(GameState::JoinByCode), cleanup_join_by_code_ui);
+    }
+}
+
+// System to set up the join by code UI
+fn setup_join_by_code_ui(mut commands: Commands, asset_server: Res<AssetServer>, mut join_data: ResMut<JoinByCodeData>) {
+    *join_data = JoinByCodeData::default();
+    let font = asset_server.load("fonts/Debrosee-ALPnL.ttf");
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    ..Default::default()
+                },
+                background_color: Color::NONE.into(),
+                ..Default::default()
+            },
+            OnJoinByCodeScreen,
+        ))
+        .with_children(|parent| {
+            // Join Code Input Field
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            margin: UiRect::vertical(Val::Px(5.0)),
+                            padding: UiRect::horizontal(Val::Px(10.0)),
+                            justify_content: JustifyContent::FlexStart,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        background_color: consts::INPUT_FIELD_BUTTON.into(),
+                        ..Default::default()
+                    },
+                    Interaction::default(),
+                    CodeInputField,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Join Code: ",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 25.0,
+                            color: Color::WHITE,
+                        },
+                    ));
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 25.0,
+                                color: consts::WHITE,
+                            },
+                        ),
+                        CodeText,
+                    ));
+                });
+
+            // Password Input Field, revealed when the room asks for one
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            display: Display::None,
+                            margin: UiRect::vertical(Val::Px(5.0)),
+                            padding: UiRect::horizontal(Val::Px(10.0)),
+                            justify_content: JustifyContent::FlexStart,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        background_color: consts::INPUT_FIELD_BG.into(),
+                        ..Default::default()
+                    },
+                    Interaction::default(),
+                    PasswordInputField,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Password: ",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 25.0,
+                            color: Color::WHITE,
+                        },
+                    ));
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 25.0,
+                                color: consts::WHITE,
+                            },
+                        ),
+                        PasswordText,
+                    ));
+                });
+
+            for (label, is_join) in [("Join Room", true), ("Back", false)] {
+                let mut button = parent.spawn(ButtonBundle {
+                    style: Style {
+                        margin: UiRect::vertical(Val::Px(5.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    background_color: consts::NORMAL_BUTTON.into(),
+                    ..Default::default()
+                });
+                if is_join {
+                    button.insert(JoinRoomButton);
+                } else {
+                    button.insert(BackButton);
+                }
+                button.with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        label,
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 30.0,
+                            color: Color::WHITE,
+                        },
+                    ));
+                });
+            }
+
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 25.0,
+                        color: consts::ERROR_TEXT,
+                    },
+                ),
+                JoinStatusText,
+            ));
+        });
+}
+
+// System to send the code to the server, connecting first if needed
+fn handle_join_buttons(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, Option<&JoinRoomButton>, Option<&BackButton>),
+        (Changed<Interaction>, With<Button>),
+    >,
+    join_data: Res<JoinByCodeData>,
+    local_client: Option<Res<LocalClientId>>,
+    network_settings: Res<NetworkSettings>,
+    mut client_messages: EventWriter<ClientMessage>,
+    mut network_requests: EventWriter<NetworkRequest>,
+    mut status_query: Query<&mut Text, With<JoinStatusText>>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, mut color, is_join, is_back) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                if is_join.is_some() {
+                    if join_data.code.chars().count() != JOIN_CODE_LEN {
+                        for mut text in &mut status_query {
+                            text.sections[0].value = format!("Join codes are {} characters long", JOIN_CODE_LEN);
+                        }
+                        continue;
+                    }
+                    let message = ClientMessage::JoinByCode {
+                        code: join_data.code.clone(),
+                        password: join_data.needs_password.then(|| join_data.password.clone()),
+                    };
+                    if local_client.is_some() {
+                        client_messages.send(message);
+                    } else {
+                        network_requests.send(NetworkRequest::Join {
+                            server_addr: network_settings.server_addr,
+                            then: Some(message),
+                        });
+                    }
+                    for mut text in &mut status_query {
+                        text.sections[0].value = String::new();
+                    }
+                } else if is_back.is_some() {
+                    if local_client.is_some() {
+                        network_requests.send(NetworkRequest::Disconnect);
+                    } else {
+                        game_state.set(GameState::MainMenu);
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *color = consts::HOVERED_BUTTON.into();
+            }
+            Interaction::None => {
+                *color = consts::NORMAL_BUTTON.into();
+            }
+        }
+    }
+}
+
+// System to move keyboard focus to the field that was clicked
+fn handle_field_focus(
+    code_field_query: Query<&Interaction, (Changed<Interaction>, With<CodeInputField>)>,
+    password_field_query: Query<&Interaction, (Changed<Interaction>, With<PasswordInputField>)>,
+    mut join_data: ResMut<JoinByCodeData>,
+) {
+    if code_field_query.iter().any(|interaction| *interaction == Interaction::Pressed) {
+        join_data.focused = CodeField::Code;
+    }
+    if join_data.needs_password && password_field_query.iter().any(|interaction| *interaction == Interaction::Pressed) {
+        join_data.focused = CodeField::Password;
+    }
+}
+
+// System to handle text input for the focused field
+fn handle_text_input(mut keyboard_input: EventReader<KeyboardInput>, mut join_data: ResMut<JoinByCodeData>) {
+    for ev in keyboard_input.read() {
+        // We don't care about key releases, only key presses
+        if ev.state == ButtonState::Released {
+            continue;
+        }
+
+        if ev.logical_key == Key::Tab {
+            if join_data.needs_password {
+                join_data.focused = match join_data.focused {
+                    CodeField::Code => CodeField::Password,
+                    CodeField::Password => CodeField::Code,
+                };
+            }
+            continue;
+        }
+
+        match join_data.focused {
+            CodeField::Code => {
+                // Codes are typed in any case but always sent upper case, without spaces
+                if let Key::Character(input) = &ev.logical_key {
+                    if !input.chars().all(|c| c.is_ascii_alphanumeric()) {
+                        continue;
+                    }
+                }
+                if ev.logical_key == Key::Space {
+                    continue;
+                }
+                if edit_text(&ev.logical_key, &mut join_data.code, JOIN_CODE_LEN) {
+                    join_data.code = join_data.code.to_uppercase();
+                }
+            }
+            CodeField::Password => {
+                edit_text(&ev.logical_key, &mut join_data.password, MAX_PASSWORD_LEN);
+            }
+        }
+    }
+}
+
+// System to show why the server refused the code
+fn handle_join_errors(
+    mut server_messages: EventReader<ServerMessage>,
+    mut join_data: ResMut<JoinByCodeData>,
+    mut status_query: Query<&mut Text, With<JoinStatusText>>,
+) {
+    for message in server_messages.read() {
+        let ServerMessage::RoomError(error) = message else {
+            continue;
+        };
+        match error {
+            RoomError::PasswordRequired => {
+                join_data.needs_password = true;
+                join_data.focused = CodeField::Password;
+            }
+            RoomError::WrongPassword => {
+                join_data.password.clear();
+                join_data.focused = CodeField::Password;
+            }
+            _ => {}
+        }
+        for mut text in &mut status_query {
+            text.sections[0].value = error.to_string();
+        }
+    }
+}
+
+// System to keep the fields on screen in sync with what was typed
+fn update_input_fields(
+    join_data: Res<JoinByCodeData>,
+    mut code_text_query: Query<&mut Text, (With<CodeText>, Without<PasswordText>, Without<JoinStatusText>)>,
+    mut password_text_query: Query<&mut Text, (With<PasswordText>, Without<CodeText>, Without<JoinStatusText>)>,
+    mut code_field_query: Query<&mut BackgroundColor, (With<CodeInputField>, Without<PasswordInputField>)>,
+    mut password_field_query: Query<(&mut Style, &mut BackgroundColor), (With<PasswordInputField>, Without<CodeInputField>)>,
+) {
+    if !join_data.is_changed() {
+        return;
+    }
+    for mut text in &mut code_text_query {
+        text.sections[0].value = join_data.code.clone();
+    }
+    for mut text in &mut password_text_query {
+        text.sections[0].value = "*".repeat(join_data.password.chars().count());
+    }
+    let field_color = |field: CodeField| {
+        if join_data.focused == field { consts::INPUT_FIELD_BUTTON } else { consts::INPUT_FIELD_BG }
+    };
+    for mut color in &mut code_field_query {
+        *color = field_color(CodeField::Code).into();
+    }
+    for (mut style, mut color) in &mut password_field_query {
+        style.display = if join_data.needs_password { Display::Flex } else { Display::None };
+        *color = field_color(CodeField::Password).into();
+    }
+}
+
+// System to clean up the UI when leaving the join by code screen
+fn cleanup_join_by_code_ui(mut commands: Commands, query: Query<Entity, With<OnJoinByCodeScreen>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::consts;
+use crate::plugins::network::NetworkSettings;
+use crate::protocol::{self, DiscoveryProbe, DiscoveryReply, RoomInfo};
+
+pub struct LanDiscoveryPlugin;
+
+const PROBE_SECONDS: f32 = 2.0;
+// A server that missed this many probes in a row is dropped from the list
+const MISSED_PROBES_BEFORE_EXPIRY: u32 = 3;
+
+// A server that answered a recent probe
+#[derive(Clone, Debug)]
+pub struct DiscoveredServer {
+    pub game_addr: SocketAddr,
+    pub rooms: Vec<RoomInfo>,
+    last_seen: Duration,
+}
+
+// Servers found on the local network, keyed by server id
+#[derive(Resource, Default)]
+pub struct DiscoveredServers(pub HashMap<u64, DiscoveredServer>);
+
+// Socket used to send probes and collect replies while the lobby is open
+#[derive(Resource)]
+struct LanDiscovery {
+    socket: UdpSocket,
+    timer: Timer,
+}
+
+impl Plugin for LanDiscoveryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DiscoveredServers>()
+            .add_systems(OnEnter(GameState::Lobby), start_discovery)
+            .add_systems(
+                Update,
+                (send_probes, collect_replies, expire_servers)
+                    .chain()
+                    .run_if(in_state(GameState::Lobby).and_then(resource_exists::<LanDiscovery>)),
+            )
+            .add_systems(OnExit(GameState::Lobby), stop_discovery);
+    }
+}
+
+fn start_discovery(mut commands: Commands, mut discovered: ResMut<DiscoveredServers>) {
+    discovered.0.clear();
+    let socket = match UdpSocket::bind("0.0.0.0:0") {
+        Ok(socket) => socket,
+        Err(err) => {
+            println!("LAN discovery unavailable: {}", err);
+            return;
+        }
+    };
+    if let Err(err) = socket.set_broadcast(true).and_then(|_| socket.set_nonblocking(true)) {
+        println!("LAN discovery unavailable: {}", err);
+        return;
+    }
+    // Fire the first probe right away rather than after a full interval
+    let mut timer = Timer::from_seconds(PROBE_SECONDS, TimerMode::Repeating);
+    timer.set_elapsed(timer.duration());
+    commands.insert_resource(LanDiscovery { socket, timer });
+}
+
+// System to probe the LAN, loopback and the configured server on an interval
+fn send_probes(time: Res<Time>, mut discovery: ResMut<LanDiscovery>, settings: Res<NetworkSettings>) {
+    if !discovery.timer.tick(time.delta()).finished() {
+        return;
+    }
+    discovery.timer.reset();
+
+    let probe = protocol::encode(&DiscoveryProbe::default());
+    let mut targets = vec![
+        SocketAddr::from(([255, 255, 255, 255], consts::DISCOVERY_PORT)),
+        SocketAddr::from(([127, 0, 0, 1], consts::DISCOVERY_PORT)),
+    ];
+    if !settings.server_addr.ip().is_loopback() {
+        targets.push(SocketAddr::new(settings.server_addr.ip(), consts::DISCOVERY_PORT));
+    }
+    for target in targets {
+        // Broadcast can be refused on some interfaces, the other targets still go out
+        let _ = discovery.socket.send_to(&probe, target);
+    }
+}
+
+// System to record every reply that arrived since the last frame
+fn collect_replies(time: Res<Time>, discovery: Res<LanDiscovery>, mut discovered: ResMut<DiscoveredServers>) {
+    let mut buffer = [0u8; 16 * 1024];
+    loop {
+        let (len, from) = match discovery.socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+            // Errors like a reset after probing an unreachable address can repeat forever, try again next frame
+            Err(err) => {
+                println!("LAN discovery could not read a reply: {}", err);
+                break;
+            }
+        };
+        let Some(reply) = protocol::decode::<DiscoveryReply>(&buffer[..len]) else {
+            continue;
+        };
+        if reply.protocol_id != protocol::PROTOCOL_ID || reply.version != protocol::PROTOCOL_VERSION {
+            continue;
+        }
+        let game_addr = SocketAddr::new(from.ip(), reply.game_port);
+        let entry = discovered.0.entry(reply.server_id).or_insert_with(|| {
+            println!("Found LAN server at {}", game_addr);
+            DiscoveredServer {
+                game_addr,
+                rooms: Vec::new(),
+                last_seen: time.elapsed(),
+            }
+        });
+        // The same server often answers on loopback and on its LAN address, loopback is the safer pick
+        if game_addr.ip().is_loopback() {
+            entry.game_addr = game_addr;
+        }
+        entry.rooms = reply.rooms;
+        entry.last_seen = time.elapsed();
+    }
+}
+
+// System to forget servers that stopped answering
+fn expire_servers(time: Res<Time>, mut discovered: ResMut<DiscoveredServers>) {
+    let max_age = Duration::from_secs_f32(PROBE_SECONDS * MISSED_PROBES_BEFORE_EXPIRY as f32);
+    let now = time.elapsed();
+    if discovered.0.values().any(|server| now - server.last_seen > max_age) {
+        discovered.0.retain(|_, server| now - server.last_seen <= max_age);
+    }
+}
+
+fn stop_discovery(mut commands: Commands) {
+    commands.remove_resource::<LanDiscovery>();
+}
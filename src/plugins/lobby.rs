@@ -1,13 +1,48 @@
+use std::net::SocketAddr;
+
 use bevy::prelude::*;
 use crate::GameState;
+use crate::consts;
+use crate::plugins::lan_discovery::DiscoveredServers;
+use crate::plugins::network::{ConnectedServerId, ConnectionError, NetworkRequest};
+use crate::protocol::{ClientMessage, RoomId, RoomInfo, RoomPhase, ServerMessage};
+use crate::resources::current_room::CurrentRoom;
 
 
 pub struct LobbyPlugin;
 
+// How often the room list is fetched again while the lobby is open
+const REFRESH_SECONDS: f32 = 2.0;
+
+#[derive(Component)]
+struct RoomListContainer;
+
+#[derive(Component)]
+struct RoomButton {
+    room: RoomId,
+    // Set for rooms found on another LAN server, which has to be connected to first
+    server: Option<SocketAddr>,
+    joinable: bool,
+}
+
+#[derive(Component)]
+struct LobbyStatusText;
+
+#[derive(Resource)]
+struct LobbyRefreshTimer(Timer);
+
+// Rooms listed by the server we are connected to
+#[derive(Resource, Default)]
+struct ServerRooms(Vec<RoomInfo>);
+
 impl Plugin for LobbyPlugin {
     fn build(&self, app: &mut App) {
         app
-        .add_systems(OnEnter(GameState::Lobby), setup_lobby_ui)
+        .insert_resource(LobbyRefreshTimer(Timer::from_seconds(REFRESH_SECONDS, TimerMode::Repeating)))
+        .init_resource::<ServerRooms>()
+        .add_systems(OnEnter(GameState::Lobby), (setup_lobby_ui, request_room_list))
+            .add_systems(Update, (refresh_room_list, receive_room_list, update_room_list, show_connection_error, handle_room_buttons).chain().run_if(in_state(GameState::Lobby)))
+            .add_systems(Update, handle_room_responses)
             .add_systems(OnExit(GameState::Lobby), cleanup_lobby);
     }
 }
@@ -34,49 +69,291 @@ fn setup_lobby_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                 bottom: Val::Auto,
                 justify_content: JustifyContent::Center, // Centers the contents in the container
                 align_items: AlignItems::Center,         // Aligns items along the cross axis (center in this case)
-                flex_direction: FlexDirection::ColumnReverse, // Lays out the children from bottom to top
+                flex_direction: FlexDirection::Column,   // Title, then the rooms, then the status line
                 ..Default::default()
             },
             ..Default::default()
         })
         .with_children(|parent| {
-            for i in 0..5 {
-                parent
-                    .spawn(ButtonBundle {
-                        style: Style {
-                            display: Display::Flex,
-                            position_type: PositionType::Relative,
-                            overflow: Overflow {
-                                x: OverflowAxis::Visible,  // Set overflow behavior for the x-axis
-                                y: OverflowAxis::Visible,  // Set overflow behavior for the y-axis
-                            },
-                            direction: Direction::Inherit,
-                            width: Val::Percent(100.0),   // 100% width for each button
-                            height: Val::Px(50.0),        // Fixed height of 50px for each button
-                            margin: UiRect::all(Val::Px(5.0)), // Margin around each button
-                            justify_content: JustifyContent::Center, // Centers text inside the button
-                            align_items: AlignItems::Center,         // Centers text vertically
-                            ..Default::default()
-                        },
+            parent.spawn(TextBundle::from_section(
+                "Rooms",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            ));
+
+            // Filled in whenever the server sends a fresh list
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        display: Display::Flex,
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        min_width: Val::Px(400.0),
                         ..Default::default()
-                    })
-                    .with_children(|parent| {
-                        parent.spawn(TextBundle::from_section(
-                            format!("Lobby {}", i + 1),
-                            TextStyle {
-                                font: font.clone(),
-                                font_size: 30.0,
-                                color: Color::WHITE,
-                            },
-                        ));
-                    });
-            }
+                    },
+                    ..Default::default()
+                },
+                RoomListContainer,
+            ));
+
+            parent.spawn((
+                TextBundle::from_section(
+                    "Fetching rooms...",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 25.0,
+                        color: consts::DISABLED_TEXT,
+                    },
+                ),
+                LobbyStatusText,
+            ));
         });
 }
 
+fn request_room_list(
+    mut client_messages: EventWriter<ClientMessage>,
+    mut timer: ResMut<LobbyRefreshTimer>,
+    mut server_rooms: ResMut<ServerRooms>,
+) {
+    timer.0.reset();
+    server_rooms.0.clear();
+    client_messages.send(ClientMessage::ListRooms);
+}
+
+// System to ask for the room list again on an interval
+fn refresh_room_list(
+    time: Res<Time>,
+    mut timer: ResMut<LobbyRefreshTimer>,
+    mut client_messages: EventWriter<ClientMessage>,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        client_messages.send(ClientMessage::ListRooms);
+    }
+}
+
+// System to keep the latest list sent by the connected server
+fn receive_room_list(mut server_messages: EventReader<ServerMessage>, mut server_rooms: ResMut<ServerRooms>) {
+    if let Some(rooms) = server_messages.read().filter_map(|message| match message {
+        ServerMessage::RoomList(rooms) => Some(rooms),
+        _ => None,
+    }).last() {
+        server_rooms.0 = rooms.clone();
+    }
+}
+
+// System to rebuild the room buttons from the connected server's list merged with the LAN
+fn update_room_list(
+    mut commands: Commands,
+    server_rooms: Res<ServerRooms>,
+    discovered: Res<DiscoveredServers>,
+    connected_server: Option<Res<ConnectedServerId>>,
+    container_query: Query<Entity, With<RoomListContainer>>,
+    mut status_query: Query<&mut Text, With<LobbyStatusText>>,
+    asset_server: Res<AssetServer>,
+) {
+    let connection_changed = connected_server.as_ref().map_or(false, |id| id.is_changed());
+    if !server_rooms.is_changed() && !discovered.is_changed() && !connection_changed {
+        return;
+    }
+    let Ok(container) = container_query.get_single() else {
+        return;
+    };
+
+    let connected_id = connected_server.map(|id| id.0);
+    let mut entries: Vec<(Option<SocketAddr>, &RoomInfo)> = server_rooms.0.iter().map(|room| (None, room)).collect();
+    // The connected server usually answers discovery too, its rooms are already listed above
+    for (server_id, server) in &discovered.0 {
+        if Some(*server_id) == connected_id {
+            continue;
+        }
+        entries.extend(server.rooms.iter().map(|room| (Some(server.game_addr), room)));
+    }
+
+    for mut text in &mut status_query {
+        text.sections[0].value = if entries.is_empty() {
+            "No rooms yet".to_string()
+        } else {
+            String::new()
+        };
+    }
+
+    let font = asset_server.load("fonts/Debrosee-ALPnL.ttf");
+    commands.entity(container).despawn_descendants().with_children(|parent| {
+        for (server, room) in entries {
+            spawn_room_button(parent, room, server, font.clone());
+        }
+    });
+}
+
+// System to explain why the connected server's rooms are missing
+fn show_connection_error(
+    connection_error: Res<ConnectionError>,
+    mut server_rooms: ResMut<ServerRooms>,
+    mut status_query: Query<&mut Text, With<LobbyStatusText>>,
+) {
+    if !connection_error.is_changed() {
+        return;
+    }
+    let Some(error) = &connection_error.0 else {
+        return;
+    };
+    server_rooms.0.clear();
+    for mut text in &mut status_query {
+        text.sections[0].value = error.clone();
+    }
+}
+
+fn spawn_room_button(parent: &mut ChildBuilder, room: &RoomInfo, server: Option<SocketAddr>, font: Handle<Font>) {
+    let joinable = room.is_joinable();
+    let (background, text_color) = if joinable {
+        (consts::NORMAL_BUTTON, Color::WHITE)
+    } else {
+        (consts::DISABLED_BUTTON, consts::DISABLED_TEXT)
+    };
+    let status = match room.phase {
+        RoomPhase::Drafting | RoomPhase::InProgress => "started".to_string(),
+        RoomPhase::Finished => "over".to_string(),
+        RoomPhase::Waiting if !joinable => "full".to_string(),
+        RoomPhase::Waiting => format!("{}/{}", room.player_count, room.max_players),
+    };
+
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    display: Display::Flex,
+                    position_type: PositionType::Relative,
+                    width: Val::Percent(100.0),   // 100% width for each button
+                    height: Val::Px(50.0),        // Fixed height of 50px for each button
+                    margin: UiRect::all(Val::Px(5.0)), // Margin around each button
+                    padding: UiRect::horizontal(Val::Px(10.0)),
+                    justify_content: JustifyContent::SpaceBetween,
+                    align_items: AlignItems::Center,         // Centers text vertically
+                    ..Default::default()
+                },
+                background_color: background.into(),
+                ..Default::default()
+            },
+            RoomButton {
+                room: room.id,
+                server,
+                joinable,
+            },
+        ))
+        .with_children(|parent| {
+            let label = match server {
+                Some(addr) => format!("{} ({}, LAN {})", room.name, room.host_name, addr.ip()),
+                None => format!("{} ({})", room.name, room.host_name),
+            };
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 30.0,
+                    color: text_color,
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                status,
+                TextStyle {
+                    font,
+                    font_size: 25.0,
+                    color: text_color,
+                },
+            ));
+        });
+}
+
+// System to join a room when its button is clicked
+fn handle_room_buttons(
+    mut interaction_query: Query<(&Interaction, &mut BackgroundColor, &RoomButton), Changed<Interaction>>,
+    mut client_messages: EventWriter<ClientMessage>,
+    mut network_requests: EventWriter<NetworkRequest>,
+) {
+    for (interaction, mut color, button) in &mut interaction_query {
+        if !button.joinable {
+            continue;
+        }
+        match *interaction {
+            Interaction::Pressed => {
+                let message = ClientMessage::JoinRoom { room: button.room };
+                match button.server {
+                    Some(server_addr) => {
+                        network_requests.send(NetworkRequest::Join {
+                            server_addr,
+                            then: Some(message),
+                        });
+                    }
+                    None => {
+                        client_messages.send(message);
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *color = consts::HOVERED_BUTTON.into();
+            }
+            Interaction::None => {
+                *color = consts::NORMAL_BUTTON.into();
+            }
+        }
+    }
+}
+
+// System to enter the match once the server placed us in a room
+fn handle_room_responses(
+    mut commands: Commands,
+    mut server_messages: EventReader<ServerMessage>,
+    state: Res<State<GameState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut status_query: Query<&mut Text, With<LobbyStatusText>>,
+    mut connection_error: ResMut<ConnectionError>,
+    mut network_requests: EventWriter<NetworkRequest>,
+) {
+    for message in server_messages.read() {
+        match message {
+            ServerMessage::RoomJoined { room, name, join_code, phase } => {
+                println!("Joined room {}", name);
+                commands.insert_resource(CurrentRoom {
+                    id: *room,
+                    name: name.clone(),
+                    join_code: join_code.clone(),
+                });
+                let next = match phase {
+                    RoomPhase::Waiting => GameState::PreMatch,
+                    RoomPhase::Drafting => GameState::CharacterSelect,
+                    RoomPhase::InProgress => GameState::InGame,
+                    RoomPhase::Finished => GameState::Debrief,
+                };
+                // A reconnect puts us back in the room we never left on screen
+                if *state.get() != next {
+                    game_state.set(next);
+                }
+            }
+            ServerMessage::RoomError(error) => match state.get() {
+                GameState::Lobby => {
+                    for mut text in &mut status_query {
+                        text.sections[0].value = error.to_string();
+                    }
+                }
+                // These screens show their own errors
+                GameState::JoinByCode | GameState::PreMatch | GameState::CharacterSelect | GameState::InGame | GameState::Debrief => {}
+                _ => {
+                    // Nowhere to show it, so drop back to the menu with the reason
+                    connection_error.0 = Some(error.to_string());
+                    network_requests.send(NetworkRequest::Disconnect);
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
 // System to cleanup the lobby UI when exiting the Lobby state
 fn cleanup_lobby(mut commands: Commands, query: Query<Entity, With<Node>>) {
     for entity in query.iter() {
         commands.entity(entity).despawn_recursive();
     }
-}
\ No newline at end of file
+}
@@ -1,8 +1,8 @@
 use bevy::prelude::*;
-use bevy::window::PrimaryWindow;
 
 use crate::GameState;
 use crate::consts;
+use crate::plugins::network::{ConnectionError, NetworkRequest, NetworkSettings};
 
 pub struct MainMenuPlugin;
 
@@ -14,15 +14,21 @@ struct HostButton;
 #[derive(Component)]
 struct JoinButton;
 
+#[derive(Component)]
+struct JoinByCodeButton;
+
 #[derive(Component)]
 struct OnMainMenuScreen;
 
+#[derive(Component)]
+struct ConnectionErrorText;
+
 impl Plugin for MainMenuPlugin {
     fn build(&self, app: &mut App) {
         app
 
         .add_systems(OnEnter(GameState::MainMenu),setup_main_menu)
-        .add_systems(Update, button_interaction_system.run_if(in_state(GameState::MainMenu)))
+        .add_systems(Update, (button_interaction_system, update_connection_error_text).run_if(in_state(GameState::MainMenu)))
         .add_systems(OnExit(GameState::MainMenu),cleanup_menu);
 
     }
@@ -31,12 +37,10 @@ impl Plugin for MainMenuPlugin {
 // System to setup the main menu UI
 fn setup_main_menu(
     mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
     asset_server: Res<AssetServer>,
+    connection_error: Res<ConnectionError>,
 ) {
-    let _window: &Window = window_query.get_single().unwrap();
     // UI setup with Host and Join buttons
-    
     commands
         .spawn((NodeBundle {
             style: Style {
@@ -99,26 +103,73 @@ fn setup_main_menu(
                         },
                     ));
                 });
+
+            parent
+                // Join By Code Button
+                .spawn(ButtonBundle {
+                    style: Style {
+                        margin: UiRect::all(Val::Px(10.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        position_type: PositionType::Relative,
+                        ..Default::default()
+                    },
+                    background_color: consts::NORMAL_BUTTON.into(),
+                    ..Default::default()
+                })
+                .insert(JoinByCodeButton)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Join by Code",
+                        TextStyle {
+                            font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                            font_size: 40.0,
+                            color: Color::WHITE,
+                        },
+                    ));
+                });
+
+            // Reason the last connection attempt failed, if any
+            parent.spawn((
+                TextBundle::from_section(
+                    connection_error.0.clone().unwrap_or_default(),
+                    TextStyle {
+                        font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                        font_size: 25.0,
+                        color: consts::ERROR_TEXT,
+                    },
+                ),
+                ConnectionErrorText,
+            ));
         });
 }
 
 // System to handle button interaction
 fn button_interaction_system(
     mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor, Option<&HostButton>, Option<&JoinButton>),
+        (&Interaction, &mut BackgroundColor, Option<&HostButton>, Option<&JoinButton>, Option<&JoinByCodeButton>),
         (Changed<Interaction>, With<Button>),
     >,
-    mut game_state: ResMut<NextState<GameState>>
+    mut game_state: ResMut<NextState<GameState>>,
+    mut network_requests: EventWriter<NetworkRequest>,
+    network_settings: Res<NetworkSettings>,
 ) {
-    for (interaction, mut color, host_button, join_button) in interaction_query.iter_mut() {
+    for (interaction, mut color, host_button, join_button, join_by_code_button) in interaction_query.iter_mut() {
         match *interaction {
             Interaction::Pressed => {
                 if host_button.is_some() {
                     println!("Host Game Button Clicked");// Switch to Lobby state
                     game_state.set(GameState::CreateRoom);
                 } else if join_button.is_some() {
-                    println!("Join Game Button Clicked");// Switch to Lobby state
-                    game_state.set(GameState::InGame);
+                    println!("Join Game Button Clicked");// Open the lobby while connecting, LAN rooms show up meanwhile
+                    game_state.set(GameState::Lobby);
+                    network_requests.send(NetworkRequest::Join {
+                        server_addr: network_settings.server_addr,
+                        then: None,
+                    });
+                } else if join_by_code_button.is_some() {
+                    println!("Join by Code Button Clicked");
+                    game_state.set(GameState::JoinByCode);
                 }
             }
             Interaction::Hovered => {
@@ -131,6 +182,19 @@ fn button_interaction_system(
     }
 }
 
+// System to keep the error line in sync with the last connection failure
+fn update_connection_error_text(
+    connection_error: Res<ConnectionError>,
+    mut text_query: Query<&mut Text, With<ConnectionErrorText>>,
+) {
+    if !connection_error.is_changed() {
+        return;
+    }
+    for mut text in &mut text_query {
+        text.sections[0].value = connection_error.0.clone().unwrap_or_default();
+    }
+}
+
 // System to cleanup menu when exiting MainMenu state
 fn cleanup_menu(mut commands: Commands, query: Query<Entity, With<OnMainMenuScreen>>) {
     for entity in query.iter() {
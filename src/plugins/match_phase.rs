@@ -0,0 +1,114 @@
+use bevy::prelude::*;
+
+use crate::{GameState, MatchPhase};
+use crate::plugins::sanity::Sanity;
+use crate::protocol::ServerMessage;
+
+pub struct MatchPhasePlugin;
+
+#[derive(Component)]
+struct PhaseText;
+
+// Phase the server last told us about, it may arrive before the match screen is up
+#[derive(Resource)]
+pub struct PhaseClock {
+    pub phase: MatchPhase,
+    pub timer: Timer,
+}
+
+impl Default for PhaseClock {
+    fn default() -> Self {
+        Self {
+            phase: MatchPhase::default(),
+            timer: Timer::from_seconds(0.0, TimerMode::Once),
+        }
+    }
+}
+
+impl Plugin for MatchPhasePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_sub_state::<MatchPhase>()
+            .init_resource::<PhaseClock>()
+            .add_systems(Update, receive_match_phase)
+            .add_systems(OnEnter(GameState::InGame), setup_phase_hud)
+            .add_systems(
+                Update,
+                (follow_match_phase, update_phase_hud)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::InGame), cleanup_phase_hud);
+    }
+}
+
+// Run condition for systems that only make sense while survivors are free to move
+pub fn survivors_can_move(phase: Option<Res<State<MatchPhase>>>) -> bool {
+    phase.map_or(false, |phase| phase.get().survivors_move())
+}
+
+// System to keep the phase and countdown the server sent
+fn receive_match_phase(mut server_messages: EventReader<ServerMessage>, mut clock: ResMut<PhaseClock>) {
+    for message in server_messages.read() {
+        if let ServerMessage::MatchPhase { phase, seconds_left } = message {
+            clock.phase = *phase;
+            clock.timer = Timer::from_seconds(*seconds_left, TimerMode::Once);
+        }
+    }
+}
+
+// System to switch the sub state to the phase the server is in
+fn follow_match_phase(
+    time: Res<Time>,
+    mut clock: ResMut<PhaseClock>,
+    phase: Res<State<MatchPhase>>,
+    mut next_phase: ResMut<NextState<MatchPhase>>,
+) {
+    clock.timer.tick(time.delta());
+    if *phase.get() != clock.phase {
+        next_phase.set(clock.phase);
+    }
+}
+
+fn setup_phase_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                font_size: 24.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_text_justify(JustifyText::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            width: Val::Percent(100.0),
+            ..Default::default()
+        }),
+        PhaseText,
+    ));
+}
+
+// System to show the phase and the time it has left, as far as we can still trust our eyes
+fn update_phase_hud(clock: Res<PhaseClock>, sanity: Res<Sanity>, mut text_query: Query<&mut Text, With<PhaseText>>) {
+    let seconds = sanity.misread(clock.timer.remaining_secs(), 60.0).max(0.0).ceil() as u32;
+    let label = format!("{}  {}:{:02}", clock.phase.name(), seconds / 60, seconds % 60);
+    for mut text in &mut text_query {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+fn cleanup_phase_hud(
+    mut commands: Commands,
+    mut clock: ResMut<PhaseClock>,
+    query: Query<Entity, With<PhaseText>>,
+) {
+    *clock = PhaseClock::default();
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
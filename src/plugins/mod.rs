@@ -2,4 +2,25 @@ pub mod game_runner;
 pub mod main_menu;
 pub mod lobby;
 pub mod create_room;
-pub mod ingame_player;
\ No newline at end of file
+pub mod ingame_player;
+pub mod network;
+pub mod join_by_code;
+pub mod room_hud;
+pub mod lan_discovery;
+pub mod prediction;
+pub mod interpolation;
+pub mod connection_status;
+pub mod dev_console;
+pub mod pre_match;
+pub mod character_select;
+pub mod scenario_library;
+pub mod match_phase;
+pub mod narrative;
+pub mod hallucination;
+pub mod world_effects;
+pub mod am_panel;
+pub mod trust;
+pub mod voting;
+pub mod chat;
+pub mod debrief;
+pub mod sanity;
\ No newline at end of file
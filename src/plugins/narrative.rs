@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::consts;
+use crate::protocol::ServerMessage;
+use crate::resources::match_roles::MatchRoles;
+
+pub struct NarrativePlugin;
+
+// How long one of AM's lines stays on screen
+const NARRATIVE_SECONDS: f32 = 8.0;
+const MAX_FEED_LINES: usize = 4;
+
+#[derive(Component)]
+struct NarrativeFeedText;
+
+// Lines AM told us, newest last
+#[derive(Resource, Default)]
+struct NarrativeFeed(Vec<(String, Timer)>);
+
+impl Plugin for NarrativePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<NarrativeFeed>()
+            .add_systems(OnEnter(GameState::InGame), setup_narrative_feed)
+            .add_systems(
+                Update,
+                (receive_narratives, update_narrative_feed)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::InGame), cleanup_narratives);
+    }
+}
+
+fn setup_narrative_feed(mut commands: Commands, asset_server: Res<AssetServer>, mut feed: ResMut<NarrativeFeed>) {
+    feed.0.clear();
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                font_size: 26.0,
+                color: consts::AM_VOICE,
+            },
+        )
+        .with_text_justify(JustifyText::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(40.0),
+            width: Val::Percent(100.0),
+            ..Default::default()
+        }),
+        NarrativeFeedText,
+    ));
+}
+
+// System to collect what AM tells us, why the server refused something, and what the others do to us
+fn receive_narratives(
+    mut server_messages: EventReader<ServerMessage>,
+    mut feed: ResMut<NarrativeFeed>,
+    roles: Option<Res<MatchRoles>>,
+) {
+    let name = |client_id: u64| {
+        roles
+            .as_ref()
+            .and_then(|roles| roles.role_of(client_id))
+            .map_or("Someone", |role| role.name())
+    };
+    for message in server_messages.read() {
+        let line = match message {
+            ServerMessage::Narrative { text } => text.clone(),
+            ServerMessage::ActionError(error) if !error.is_chat() => error.to_string(),
+            ServerMessage::Shared { by } => format!("{} shares what little they have with you", name(*by)),
+            ServerMessage::Accused { by, who } => format!("{} accuses {}", name(*by), name(*who)),
+            ServerMessage::MatchOver { text, .. } => text.clone(),
+            _ => continue,
+        };
+        feed.0.push((line, Timer::from_seconds(NARRATIVE_SECONDS, TimerMode::Once)));
+        if feed.0.len() > MAX_FEED_LINES {
+            feed.0.remove(0);
+        }
+    }
+}
+
+// System to fade out old lines
+fn update_narrative_feed(
+    time: Res<Time>,
+    mut feed: ResMut<NarrativeFeed>,
+    mut text_query: Query<&mut Text, With<NarrativeFeedText>>,
+) {
+    let before = feed.0.len();
+    feed.0.retain_mut(|(_, timer)| !timer.tick(time.delta()).finished());
+    if !feed.is_changed() && feed.0.len() == before {
+        return;
+    }
+    let lines: Vec<&str> = feed.0.iter().map(|(line, _)| line.as_str()).collect();
+    for mut text in &mut text_query {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+fn cleanup_narratives(mut commands: Commands, feed_query: Query<Entity, With<NarrativeFeedText>>) {
+    for entity in &feed_query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,538 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime};
+
+use bevy::prelude::*;
+use bevy_renet::renet::transport::{
+    ClientAuthentication, ConnectToken, NetcodeClientTransport, NetcodeTransportError, NETCODE_KEY_BYTES,
+};
+use bevy_renet::renet::RenetClient;
+use bevy_renet::transport::NetcodeClientPlugin;
+use bevy_renet::RenetClientPlugin;
+use rand::Rng;
+
+use crate::consts;
+use crate::link_conditioner::{LinkConditioner, LinkConditions};
+use crate::protocol::{self, ClientMessage, Handshake, RejectReason, ServerChannel, ServerMessage, SessionToken};
+use crate::resources::current_room::CurrentRoom;
+use crate::resources::match_roles::MatchRoles;
+use crate::server::{build_server_app, ServerSettings, ServerShutdown};
+use crate::GameState;
+
+pub struct NetworkPlugin;
+
+// Requests sent by the menus to start or stop a connection
+#[derive(Event, Debug, Clone)]
+pub enum NetworkRequest {
+    Host { room_name: String, is_private: bool, password: Option<String> },
+    // Connects, then sends the message if there is one or opens the lobby otherwise
+    Join { server_addr: SocketAddr, then: Option<ClientMessage> },
+    Disconnect,
+}
+
+const USAGE: &str = "Usage: ergo-cogito-sum [--name <player name>]
+       [--link-conditioner latency=<ms>,jitter=<ms>,loss=<0-1>,duplicate=<0-1>]";
+
+// Where the client connects when the player presses "Join Game", and who it says it is
+#[derive(Resource, Clone, Debug)]
+pub struct NetworkSettings {
+    pub server_addr: SocketAddr,
+    pub player_name: String,
+    // Debug only, degrades our own traffic to reproduce bad networks
+    pub link_conditioner: Option<LinkConditions>,
+}
+
+impl NetworkSettings {
+    // Reads the command line flags of the game client, falling back to the defaults
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut settings = Self::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value\n{}", flag, USAGE));
+            match flag.as_str() {
+                // Lets several clients on one machine tell each other apart
+                "--name" => {
+                    settings.player_name = value()?;
+                }
+                "--link-conditioner" => {
+                    let spec = value()?;
+                    settings.link_conditioner =
+                        Some(LinkConditions::parse(&spec).map_err(|err| format!("{}\n{}", err, USAGE))?);
+                }
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                _ => return Err(format!("Unknown flag {}\n{}", flag, USAGE)),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            server_addr: SocketAddr::from(([127, 0, 0, 1], consts::DEFAULT_SERVER_PORT)),
+            player_name: std::env::var("USER").unwrap_or_else(|_| "Survivor".to_string()),
+            link_conditioner: None,
+        }
+    }
+}
+
+// Id the server knows this client by, set once the handshake was accepted
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalClientId(pub u64);
+
+// Identity of the server we are connected to, used to spot it among LAN discovery replies
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConnectedServerId(pub u64);
+
+// Last connection failure, shown on the main menu
+#[derive(Resource, Default)]
+pub struct ConnectionError(pub Option<String>);
+
+// How often a dropped client knocks on the server again while its slot is held
+const RECONNECT_RETRY_SECONDS: f32 = 2.0;
+
+// Who the server knows us as, kept so a dropped connection can take back the same slot
+#[derive(Resource, Clone, Debug)]
+pub struct Session {
+    pub client_id: u64,
+    pub server_addr: SocketAddr,
+    pub token: SessionToken,
+    pub grace: Duration,
+}
+
+// Present while the client tries to get back into the match after losing the connection
+#[derive(Resource)]
+pub struct Reconnecting {
+    retry: Timer,
+    pub give_up: Timer,
+}
+
+// What to do once the client has finished connecting
+enum OnConnected {
+    Enter(GameState),
+    Send(ClientMessage),
+    // The server puts a returning player back in their room by itself
+    Resume,
+}
+
+#[derive(Resource)]
+struct PendingConnection {
+    server_addr: SocketAddr,
+    on_connected: OnConnected,
+}
+
+// Relay our traffic goes through while the link conditioner is on
+#[derive(Resource)]
+pub struct ClientLinkConditioner(pub LinkConditioner);
+
+// Server running on a background thread when this player is hosting
+#[derive(Resource)]
+struct LocalServer {
+    shutdown: ServerShutdown,
+}
+
+impl Drop for LocalServer {
+    fn drop(&mut self) {
+        self.shutdown.0.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((RenetClientPlugin, NetcodeClientPlugin))
+            .add_event::<NetworkRequest>()
+            .add_event::<ClientMessage>()
+            .add_event::<ServerMessage>()
+            .init_resource::<NetworkSettings>()
+            .init_resource::<ConnectionError>()
+            .add_systems(
+                Update,
+                (
+                    handle_network_requests,
+                    receive_server_messages.run_if(resource_exists::<RenetClient>),
+                    retry_reconnect.run_if(resource_exists::<Reconnecting>),
+                    poll_pending_connection.run_if(resource_exists::<PendingConnection>),
+                    watch_for_disconnect.run_if(not(resource_exists::<PendingConnection>)),
+                    handle_transport_errors,
+                )
+                    .chain(),
+            )
+            .add_systems(PostUpdate, send_client_messages.run_if(resource_exists::<RenetClient>));
+    }
+}
+
+// System to start hosting or joining when a menu asks for it
+fn handle_network_requests(
+    mut commands: Commands,
+    mut requests: EventReader<NetworkRequest>,
+    mut connection_error: ResMut<ConnectionError>,
+    mut game_state: ResMut<NextState<GameState>>,
+    state: Res<State<GameState>>,
+    pending: Option<Res<PendingConnection>>,
+    settings: Res<NetworkSettings>,
+) {
+    for request in requests.read() {
+        match request {
+            NetworkRequest::Host { room_name, is_private, password } => {
+                if pending.is_some() {
+                    continue;
+                }
+                // Our own server degrades everyone's traffic, ours included, so we skip the client side relay
+                let server_settings = ServerSettings {
+                    link_conditioner: settings.link_conditioner.clone(),
+                    ..Default::default()
+                };
+                let client_settings = NetworkSettings {
+                    link_conditioner: None,
+                    ..settings.clone()
+                };
+                let server_addr = SocketAddr::from(([127, 0, 0, 1], server_settings.bind_addr.port()));
+                let result = start_local_server(&mut commands, server_settings).and_then(|_| {
+                    start_client(&mut commands, &client_settings, server_addr, new_client_id(), None)
+                });
+                match result {
+                    Ok(()) => {
+                        connection_error.0 = None;
+                        commands.insert_resource(PendingConnection {
+                            server_addr,
+                            on_connected: OnConnected::Send(ClientMessage::CreateRoom {
+                                name: room_name.clone(),
+                                is_private: *is_private,
+                                password: password.clone(),
+                            }),
+                        });
+                    }
+                    Err(err) => {
+                        fail_connection(&mut commands, &mut connection_error, &mut game_state, &state, err);
+                    }
+                }
+            }
+            NetworkRequest::Join { server_addr, then } => {
+                // Joining a room on another server replaces whatever connection we had
+                teardown(&mut commands);
+                match start_client(&mut commands, &settings, *server_addr, new_client_id(), None) {
+                    Ok(()) => {
+                        connection_error.0 = None;
+                        let on_connected = match then {
+                            Some(message) => OnConnected::Send(message.clone()),
+                            None => OnConnected::Enter(GameState::Lobby),
+                        };
+                        commands.insert_resource(PendingConnection {
+                            server_addr: *server_addr,
+                            on_connected,
+                        });
+                    }
+                    Err(err) => {
+                        fail_connection(&mut commands, &mut connection_error, &mut game_state, &state, err);
+                    }
+                }
+            }
+            NetworkRequest::Disconnect => {
+                teardown(&mut commands);
+                game_state.set(GameState::MainMenu);
+            }
+        }
+    }
+}
+
+fn start_local_server(commands: &mut Commands, settings: ServerSettings) -> Result<(), String> {
+    let socket = UdpSocket::bind(settings.bind_addr)
+        .map_err(|err| format!("Could not host on {}: {}", settings.bind_addr, err))?;
+    let shutdown = ServerShutdown::default();
+    // The app is not Send, so it is built on the thread that runs it and only the outcome comes back
+    let (started_tx, started_rx) = std::sync::mpsc::channel();
+    let server_shutdown = shutdown.clone();
+    std::thread::Builder::new()
+        .name("local-server".to_string())
+        .spawn(move || match build_server_app(socket, settings, server_shutdown) {
+            Ok(mut server_app) => {
+                let _ = started_tx.send(Ok(()));
+                server_app.run();
+            }
+            Err(err) => {
+                let _ = started_tx.send(Err(err));
+            }
+        })
+        .map_err(|err| format!("Could not start server thread: {}", err))?;
+    started_rx
+        .recv()
+        .map_err(|_| "Server thread stopped before it started".to_string())?
+        .map_err(|err| format!("Could not start server: {}", err))?;
+    commands.insert_resource(LocalServer { shutdown });
+    Ok(())
+}
+
+// Random so two clients starting in the same instant do not take each other's id
+fn new_client_id() -> u64 {
+    rand::thread_rng().gen()
+}
+
+// A reconnecting client passes its old id and session, the server keys the held slot by them
+fn start_client(
+    commands: &mut Commands,
+    settings: &NetworkSettings,
+    server_addr: SocketAddr,
+    client_id: u64,
+    session: Option<SessionToken>,
+) -> Result<(), String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|err| format!("Could not open socket: {}", err))?;
+    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+    let mut handshake = Handshake::new(&settings.player_name);
+    if let Some(session) = session {
+        handshake = handshake.resuming(session);
+    }
+    let user_data = handshake.to_user_data();
+
+    let authentication = match &settings.link_conditioner {
+        Some(conditions) => {
+            let conditioner = LinkConditioner::in_front_of(server_addr, conditions.clone())
+                .map_err(|err| format!("Could not start the link conditioner: {}", err))?;
+            // Packets go to the relay first, the real address is listed too because netcode
+            // only lets in clients that dialled one of the server's own addresses.
+            // The key is the all zero one unsecure servers use.
+            let connect_token = ConnectToken::generate(
+                current_time,
+                protocol::PROTOCOL_ID,
+                300,
+                client_id,
+                15,
+                vec![conditioner.local_addr(), server_addr],
+                Some(&user_data),
+                &[0; NETCODE_KEY_BYTES],
+            )
+            .map_err(|err| format!("Could not connect to {}: {}", server_addr, err))?;
+            commands.insert_resource(ClientLinkConditioner(conditioner));
+            ClientAuthentication::Secure { connect_token }
+        }
+        None => ClientAuthentication::Unsecure {
+            protocol_id: protocol::PROTOCOL_ID,
+            client_id,
+            server_addr,
+            user_data: Some(user_data),
+        },
+    };
+    let transport = NetcodeClientTransport::new(current_time, authentication, socket)
+        .map_err(|err| format!("Could not connect to {}: {}", server_addr, err))?;
+    commands.insert_resource(RenetClient::new(protocol::connection_config()));
+    commands.insert_resource(transport);
+    println!("Connecting to {}", server_addr);
+    Ok(())
+}
+
+// System to decode everything the server sent this frame
+fn receive_server_messages(mut client: ResMut<RenetClient>, mut server_messages: EventWriter<ServerMessage>) {
+    for channel in [ServerChannel::ServerMessages, ServerChannel::Snapshots] {
+        let channel: u8 = channel.into();
+        while let Some(bytes) = client.receive_message(channel) {
+            match protocol::decode::<ServerMessage>(&bytes) {
+                Some(message) => {
+                    server_messages.send(message);
+                }
+                None => println!("Dropping malformed message from server"),
+            }
+        }
+    }
+}
+
+// System to move on once the server accepted us, or give up if it refused
+fn poll_pending_connection(
+    mut commands: Commands,
+    pending: Res<PendingConnection>,
+    client: Option<Res<RenetClient>>,
+    mut server_messages: EventReader<ServerMessage>,
+    mut client_messages: EventWriter<ClientMessage>,
+    mut connection_error: ResMut<ConnectionError>,
+    mut game_state: ResMut<NextState<GameState>>,
+    state: Res<State<GameState>>,
+    session: Option<Res<Session>>,
+) {
+    let Some(client) = client else {
+        commands.remove_resource::<PendingConnection>();
+        return;
+    };
+    for message in server_messages.read() {
+        match message {
+            ServerMessage::Welcome { client_id, server_id, session, reconnect_grace_secs } => {
+                println!("Connected as client {}", client_id);
+                commands.insert_resource(LocalClientId(*client_id));
+                commands.insert_resource(ConnectedServerId(*server_id));
+                commands.insert_resource(Session {
+                    client_id: *client_id,
+                    server_addr: pending.server_addr,
+                    token: *session,
+                    grace: Duration::from_secs(*reconnect_grace_secs as u64),
+                });
+                match &pending.on_connected {
+                    OnConnected::Enter(state) => game_state.set(state.clone()),
+                    OnConnected::Send(message) => {
+                        client_messages.send(message.clone());
+                    }
+                    OnConnected::Resume => {
+                        println!("Reconnected");
+                        commands.remove_resource::<Reconnecting>();
+                    }
+                }
+                commands.remove_resource::<PendingConnection>();
+                return;
+            }
+            ServerMessage::Rejected(reason) => {
+                fail_connection(&mut commands, &mut connection_error, &mut game_state, &state, reason.to_string());
+                return;
+            }
+            _ => {}
+        }
+    }
+    if client.is_disconnected() {
+        let reason = match client.disconnect_reason() {
+            Some(reason) => format!("Connection failed: {}", reason),
+            None => "Connection failed".to_string(),
+        };
+        lose_connection(&mut commands, &mut connection_error, &mut game_state, &state, session.as_deref(), reason);
+    }
+}
+
+// System to knock on the server again until it hands our slot back or the grace period runs out
+fn retry_reconnect(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut reconnecting: ResMut<Reconnecting>,
+    session: Option<Res<Session>>,
+    client: Option<Res<RenetClient>>,
+    settings: Res<NetworkSettings>,
+    mut connection_error: ResMut<ConnectionError>,
+    mut game_state: ResMut<NextState<GameState>>,
+    state: Res<State<GameState>>,
+) {
+    let Some(session) = session else {
+        commands.remove_resource::<Reconnecting>();
+        return;
+    };
+    if reconnecting.give_up.tick(time.delta()).finished() {
+        let reason = RejectReason::SessionExpired.to_string();
+        fail_connection(&mut commands, &mut connection_error, &mut game_state, &state, reason);
+        return;
+    }
+    // The grace period keeps running while an attempt is still waiting on the server
+    if client.is_some() {
+        return;
+    }
+    if !reconnecting.retry.tick(time.delta()).just_finished() {
+        return;
+    }
+    match start_client(&mut commands, &settings, session.server_addr, session.client_id, Some(session.token)) {
+        Ok(()) => {
+            commands.insert_resource(PendingConnection {
+                server_addr: session.server_addr,
+                on_connected: OnConnected::Resume,
+            });
+        }
+        Err(err) => println!("{}", err),
+    }
+}
+
+// System to react when an established connection drops
+fn watch_for_disconnect(
+    mut commands: Commands,
+    client: Option<Res<RenetClient>>,
+    mut connection_error: ResMut<ConnectionError>,
+    mut game_state: ResMut<NextState<GameState>>,
+    state: Res<State<GameState>>,
+    session: Option<Res<Session>>,
+) {
+    let Some(client) = client else {
+        return;
+    };
+    if client.is_disconnected() {
+        let reason = match client.disconnect_reason() {
+            Some(reason) => format!("Disconnected: {}", reason),
+            None => "Disconnected".to_string(),
+        };
+        lose_connection(&mut commands, &mut connection_error, &mut game_state, &state, session.as_deref(), reason);
+    }
+}
+
+// System to surface transport failures such as an unreachable server
+fn handle_transport_errors(
+    mut commands: Commands,
+    mut transport_errors: EventReader<NetcodeTransportError>,
+    mut connection_error: ResMut<ConnectionError>,
+    mut game_state: ResMut<NextState<GameState>>,
+    state: Res<State<GameState>>,
+    session: Option<Res<Session>>,
+) {
+    if let Some(err) = transport_errors.read().last() {
+        let reason = format!("Network error: {}", err);
+        lose_connection(&mut commands, &mut connection_error, &mut game_state, &state, session.as_deref(), reason);
+    }
+}
+
+// System to send everything queued for the server this frame
+fn send_client_messages(mut client: ResMut<RenetClient>, mut client_messages: EventReader<ClientMessage>) {
+    if !client.is_connected() {
+        client_messages.clear();
+        return;
+    }
+    for message in client_messages.read() {
+        client.send_message(message.channel(), protocol::encode(message));
+    }
+}
+
+// The lobby lists LAN rooms without a connection, so it shows the failure itself instead of closing
+fn fail_connection(
+    commands: &mut Commands,
+    connection_error: &mut ConnectionError,
+    game_state: &mut NextState<GameState>,
+    state: &State<GameState>,
+    reason: String,
+) {
+    println!("{}", reason);
+    connection_error.0 = Some(reason);
+    teardown(commands);
+    if *state.get() != GameState::Lobby {
+        game_state.set(GameState::MainMenu);
+    }
+}
+
+// Once the match started the slot is held for a while, so keep trying to get back in instead of leaving
+fn lose_connection(
+    commands: &mut Commands,
+    connection_error: &mut ConnectionError,
+    game_state: &mut NextState<GameState>,
+    state: &State<GameState>,
+    session: Option<&Session>,
+    reason: String,
+) {
+    let Some(session) = session.filter(|_| matches!(state.get(), GameState::CharacterSelect | GameState::InGame)) else {
+        fail_connection(commands, connection_error, game_state, state, reason);
+        return;
+    };
+    println!("{}, trying to reconnect", reason);
+    commands.remove_resource::<PendingConnection>();
+    commands.remove_resource::<RenetClient>();
+    commands.remove_resource::<NetcodeClientTransport>();
+    commands.remove_resource::<ClientLinkConditioner>();
+    let mut retry = Timer::from_seconds(RECONNECT_RETRY_SECONDS, TimerMode::Repeating);
+    retry.set_elapsed(retry.duration());
+    let give_up = Timer::new(session.grace, TimerMode::Once);
+    // A failed retry lands here too, it must not restart the grace period
+    commands.add(move |world: &mut World| {
+        if !world.contains_resource::<Reconnecting>() {
+            world.insert_resource(Reconnecting { retry, give_up });
+        }
+    });
+}
+
+fn teardown(commands: &mut Commands) {
+    commands.remove_resource::<PendingConnection>();
+    commands.remove_resource::<RenetClient>();
+    commands.remove_resource::<NetcodeClientTransport>();
+    commands.remove_resource::<ClientLinkConditioner>();
+    commands.remove_resource::<LocalServer>();
+    commands.remove_resource::<LocalClientId>();
+    commands.remove_resource::<ConnectedServerId>();
+    commands.remove_resource::<CurrentRoom>();
+    commands.remove_resource::<MatchRoles>();
+    commands.remove_resource::<Session>();
+    commands.remove_resource::<Reconnecting>();
+}
@@ -0,0 +1,382 @@
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::components::role::{ControlledBy, Role};
+use crate::consts;
+use crate::plugins::network::LocalClientId;
+use crate::protocol::{AmSelection, ClientMessage, LineupEntry, ServerMessage};
+use crate::resources::current_room::CurrentRoom;
+use crate::resources::match_roles::MatchRoles;
+
+pub struct PreMatchPlugin;
+
+#[derive(Component)]
+struct OnPreMatchScreen;
+
+#[derive(Component)]
+struct LineupContainer;
+
+#[derive(Component)]
+struct PreMatchStatusText;
+
+#[derive(Component)]
+struct AmSelectionText;
+
+// Player row, the host clicks one to make that player AM
+#[derive(Component)]
+struct LineupRow {
+    client_id: u64,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum PreMatchButton {
+    CycleSelection,
+    Volunteer,
+    Start,
+    Leave,
+}
+
+// Latest lineup sent by the server
+#[derive(Resource, Default)]
+struct PreMatchLineup {
+    selection: AmSelection,
+    am_pick: Option<u64>,
+    players: Vec<LineupEntry>,
+}
+
+impl Plugin for PreMatchPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<PreMatchLineup>()
+            .add_systems(OnEnter(GameState::PreMatch), setup_pre_match_ui)
+            .add_systems(
+                Update,
+                (receive_lineup, update_lineup, handle_pre_match_buttons, handle_lineup_rows, show_pre_match_errors)
+                    .chain()
+                    .run_if(in_state(GameState::PreMatch)),
+            )
+            .add_systems(Update, receive_role_assignments)
+            .add_systems(OnExit(GameState::PreMatch), cleanup_pre_match_ui);
+    }
+}
+
+// System to set up the room screen shown until the match starts
+fn setup_pre_match_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    room: Option<Res<CurrentRoom>>,
+    mut lineup: ResMut<PreMatchLineup>,
+) {
+    *lineup = PreMatchLineup::default();
+    commands.remove_resource::<MatchRoles>();
+    let font = asset_server.load("fonts/Debrosee-ALPnL.ttf");
+    let title = match room.as_deref() {
+        Some(CurrentRoom { name, join_code: Some(code), .. }) => format!("{}  |  Code: {}", name, code),
+        Some(room) => room.name.clone(),
+        None => String::new(),
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            OnPreMatchScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                title,
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            ));
+
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        min_width: Val::Px(400.0),
+                        margin: UiRect::vertical(Val::Px(10.0)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                LineupContainer,
+            ));
+
+            for (label, button) in [
+                ("AM: ", PreMatchButton::CycleSelection),
+                ("Volunteer for AM", PreMatchButton::Volunteer),
+                ("Start Match", PreMatchButton::Start),
+                ("Leave Room", PreMatchButton::Leave),
+            ] {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                margin: UiRect::vertical(Val::Px(5.0)),
+                                padding: UiRect::horizontal(Val::Px(10.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..Default::default()
+                            },
+                            background_color: consts::NORMAL_BUTTON.into(),
+                            ..Default::default()
+                        },
+                        button,
+                    ))
+                    .with_children(|parent| {
+                        let mut text = parent.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 30.0,
+                                color: Color::WHITE,
+                            },
+                        ));
+                        if button == PreMatchButton::CycleSelection {
+                            text.insert(AmSelectionText);
+                        }
+                    });
+            }
+
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 25.0,
+                        color: consts::ERROR_TEXT,
+                    },
+                ),
+                PreMatchStatusText,
+            ));
+        });
+}
+
+// System to keep the latest lineup
+fn receive_lineup(mut server_messages: EventReader<ServerMessage>, mut lineup: ResMut<PreMatchLineup>) {
+    for message in server_messages.read() {
+        if let ServerMessage::Lineup { selection, am_pick, players } = message {
+            lineup.selection = *selection;
+            lineup.am_pick = *am_pick;
+            lineup.players = players.clone();
+        }
+    }
+}
+
+// System to rebuild the player rows and show only the buttons that apply to us
+fn update_lineup(
+    mut commands: Commands,
+    lineup: Res<PreMatchLineup>,
+    local_client: Option<Res<LocalClientId>>,
+    asset_server: Res<AssetServer>,
+    container_query: Query<Entity, With<LineupContainer>>,
+    mut button_query: Query<(&PreMatchButton, &mut Style, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    if !lineup.is_changed() {
+        return;
+    }
+    let local_id = local_client.map(|id| id.0);
+    let local_entry = lineup.players.iter().find(|entry| Some(entry.client_id) == local_id);
+    let is_host = local_entry.map_or(false, |entry| entry.is_host);
+    let volunteered = local_entry.map_or(false, |entry| entry.volunteered);
+
+    for (button, mut style, children) in &mut button_query {
+        let visible = match button {
+            PreMatchButton::Volunteer => lineup.selection == AmSelection::Volunteers,
+            PreMatchButton::Start => is_host,
+            PreMatchButton::CycleSelection | PreMatchButton::Leave => true,
+        };
+        style.display = if visible { Display::Flex } else { Display::None };
+        let label = match button {
+            PreMatchButton::CycleSelection => format!("AM: {}", lineup.selection.label()),
+            PreMatchButton::Volunteer if volunteered => "Withdraw from AM".to_string(),
+            PreMatchButton::Volunteer => "Volunteer for AM".to_string(),
+            _ => continue,
+        };
+        for child in children {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+
+    let Ok(container) = container_query.get_single() else {
+        return;
+    };
+    let font = asset_server.load("fonts/Debrosee-ALPnL.ttf");
+    let picking = is_host && lineup.selection == AmSelection::HostPicks;
+    commands.entity(container).despawn_descendants().with_children(|parent| {
+        for entry in &lineup.players {
+            let mut tags = Vec::new();
+            if entry.is_host {
+                tags.push("host");
+            }
+            if lineup.selection == AmSelection::Volunteers && entry.volunteered {
+                tags.push("volunteer");
+            }
+            if lineup.selection == AmSelection::HostPicks && lineup.am_pick == Some(entry.client_id) {
+                tags.push("AM");
+            }
+            let label = if tags.is_empty() {
+                entry.name.clone()
+            } else {
+                format!("{} ({})", entry.name, tags.join(", "))
+            };
+            let mut row = parent.spawn(NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    margin: UiRect::all(Val::Px(3.0)),
+                    padding: UiRect::horizontal(Val::Px(10.0)),
+                    justify_content: JustifyContent::Center,
+                    ..Default::default()
+                },
+                background_color: consts::DISABLED_BUTTON.into(),
+                ..Default::default()
+            });
+            // Only the host choosing AM can click the rows
+            if picking {
+                row.insert((Button, Interaction::default(), LineupRow { client_id: entry.client_id }));
+            }
+            row.with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    label,
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 28.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            });
+        }
+    });
+}
+
+// System to handle the room setup buttons
+fn handle_pre_match_buttons(
+    mut commands: Commands,
+    mut interaction_query: Query<(&Interaction, &mut BackgroundColor, &PreMatchButton), Changed<Interaction>>,
+    lineup: Res<PreMatchLineup>,
+    local_client: Option<Res<LocalClientId>>,
+    mut client_messages: EventWriter<ClientMessage>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, mut color, button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => match button {
+                PreMatchButton::CycleSelection => {
+                    let next = match lineup.selection {
+                        AmSelection::HostPicks => AmSelection::Random,
+                        AmSelection::Random => AmSelection::Volunteers,
+                        AmSelection::Volunteers => AmSelection::HostPicks,
+                    };
+                    client_messages.send(ClientMessage::SetAmSelection(next));
+                }
+                PreMatchButton::Volunteer => {
+                    let volunteered = lineup
+                        .players
+                        .iter()
+                        .any(|entry| Some(entry.client_id) == local_client.as_ref().map(|id| id.0) && entry.volunteered);
+                    client_messages.send(ClientMessage::Volunteer(!volunteered));
+                }
+                PreMatchButton::Start => {
+                    client_messages.send(ClientMessage::StartMatch);
+                }
+                PreMatchButton::Leave => {
+                    client_messages.send(ClientMessage::LeaveRoom);
+                    commands.remove_resource::<CurrentRoom>();
+                    game_state.set(GameState::Lobby);
+                }
+            },
+            Interaction::Hovered => {
+                *color = consts::HOVERED_BUTTON.into();
+            }
+            Interaction::None => {
+                *color = consts::NORMAL_BUTTON.into();
+            }
+        }
+    }
+}
+
+// System to let the host pick AM by clicking a player
+fn handle_lineup_rows(
+    mut interaction_query: Query<(&Interaction, &mut BackgroundColor, &LineupRow), Changed<Interaction>>,
+    mut client_messages: EventWriter<ClientMessage>,
+) {
+    for (interaction, mut color, row) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                client_messages.send(ClientMessage::PickAm { client_id: row.client_id });
+            }
+            Interaction::Hovered => {
+                *color = consts::HOVERED_BUTTON.into();
+            }
+            Interaction::None => {
+                *color = consts::DISABLED_BUTTON.into();
+            }
+        }
+    }
+}
+
+// System to show why the server refused a setup change
+fn show_pre_match_errors(
+    mut server_messages: EventReader<ServerMessage>,
+    mut status_query: Query<&mut Text, With<PreMatchStatusText>>,
+) {
+    for message in server_messages.read() {
+        if let ServerMessage::RoomError(error) = message {
+            for mut text in &mut status_query {
+                text.sections[0].value = error.to_string();
+            }
+        }
+    }
+}
+
+// System to hand the roster to the players the server assigned and start the match
+fn receive_role_assignments(
+    mut commands: Commands,
+    mut server_messages: EventReader<ServerMessage>,
+    mut roster_query: Query<(&Role, &mut ControlledBy)>,
+    state: Res<State<GameState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    let Some(assignments) = server_messages
+        .read()
+        .filter_map(|message| match message {
+            ServerMessage::RolesAssigned(assignments) => Some(assignments),
+            _ => None,
+        })
+        .last()
+    else {
+        return;
+    };
+    for (role, mut controlled_by) in &mut roster_query {
+        controlled_by.0 = assignments
+            .iter()
+            .find(|assignment| assignment.role == *role)
+            .map(|assignment| assignment.client_id);
+    }
+    commands.insert_resource(MatchRoles(assignments.clone()));
+    if matches!(state.get(), GameState::PreMatch | GameState::CharacterSelect) {
+        game_state.set(GameState::InGame);
+    }
+}
+
+fn cleanup_pre_match_ui(mut commands: Commands, query: Query<Entity, With<OnPreMatchScreen>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
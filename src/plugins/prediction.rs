@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::components::player::{Player, PlayerInputState, PlayerState, StateElapsed};
+use crate::plugins::match_phase::survivors_can_move;
+use crate::plugins::network::LocalClientId;
+use crate::protocol::{ClientMessage, InputFrame, ServerMessage};
+use crate::resources::world_effects::WorldEffects;
+use crate::systems::movement::{simulate_input_in, INPUT_TICK_HZ};
+
+pub struct PredictionPlugin;
+
+// Frames resent with every input message, enough to ride out a few lost packets
+const REDUNDANT_INPUT_FRAMES: usize = 8;
+// Two seconds of input; anything older than that is not coming back
+const MAX_PENDING_INPUTS: usize = 120;
+
+// Input frames applied locally that the server has not acknowledged yet
+#[derive(Resource, Default)]
+struct PendingInputs {
+    last_sequence: u32,
+    frames: VecDeque<InputFrame>,
+    // Newest snapshot applied, unreliable snapshots can arrive out of order
+    last_snapshot_tick: Option<u32>,
+}
+
+impl Plugin for PredictionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Time::<Fixed>::from_hz(INPUT_TICK_HZ))
+            .init_resource::<PendingInputs>()
+            .add_systems(OnEnter(GameState::InGame), reset_pending_inputs)
+            .add_systems(
+                FixedUpdate,
+                predict_local_player.run_if(in_state(GameState::InGame).and_then(survivors_can_move)),
+            )
+            .add_systems(Update, reconcile_local_player.run_if(in_state(GameState::InGame)));
+    }
+}
+
+fn reset_pending_inputs(mut pending: ResMut<PendingInputs>) {
+    *pending = PendingInputs::default();
+}
+
+// System to sample the local input once per tick, apply it right away and send it to the server
+fn predict_local_player(
+    mut pending: ResMut<PendingInputs>,
+    mut query: Query<(&mut Transform, &mut PlayerState, &mut StateElapsed, &mut PlayerInputState), With<Player>>,
+    mut client_messages: EventWriter<ClientMessage>,
+    local_client: Option<Res<LocalClientId>>,
+    effects: Res<WorldEffects>,
+) {
+    let Ok((mut transform, mut state, mut state_elapsed, mut input_state)) = query.get_single_mut() else {
+        return;
+    };
+
+    pending.last_sequence += 1;
+    let frame = InputFrame {
+        sequence: pending.last_sequence,
+        movement: input_state.movement_velocity,
+        sprint: input_state.sprinting,
+        attack: std::mem::take(&mut input_state.attack_requested),
+    };
+
+    let mut position = transform.translation.truncate();
+    simulate_input_in(&mut position, &mut state, &mut state_elapsed.0, &frame, input_state.speed_multiplier, &effects);
+    transform.translation = position.extend(transform.translation.z);
+
+    // Offline there is nobody to acknowledge the frames, so there is nothing to keep
+    if local_client.is_none() {
+        return;
+    }
+    pending.frames.push_back(frame);
+    while pending.frames.len() > MAX_PENDING_INPUTS {
+        pending.frames.pop_front();
+    }
+    let skip = pending.frames.len().saturating_sub(REDUNDANT_INPUT_FRAMES);
+    client_messages.send(ClientMessage::Inputs(pending.frames.iter().skip(skip).copied().collect()));
+}
+
+// System to snap to the authoritative state and replay the inputs the server has not seen yet
+fn reconcile_local_player(
+    mut server_messages: EventReader<ServerMessage>,
+    local_client: Option<Res<LocalClientId>>,
+    mut pending: ResMut<PendingInputs>,
+    mut query: Query<(&mut Transform, &mut PlayerState, &mut StateElapsed, &PlayerInputState), With<Player>>,
+    effects: Res<WorldEffects>,
+) {
+    let Some(local_client) = local_client else {
+        return;
+    };
+    let Some(snapshot) = server_messages
+        .read()
+        .filter_map(|message| match message {
+            ServerMessage::Snapshot(snapshot) => Some(snapshot),
+            _ => None,
+        })
+        .max_by_key(|snapshot| snapshot.tick)
+    else {
+        return;
+    };
+    if pending.last_snapshot_tick.map_or(false, |tick| snapshot.tick <= tick) {
+        return;
+    }
+    pending.last_snapshot_tick = Some(snapshot.tick);
+
+    let Some(own) = snapshot.players.iter().find(|player| player.client_id == local_client.0) else {
+        return;
+    };
+    let Ok((mut transform, mut state, mut state_elapsed, input_state)) = query.get_single_mut() else {
+        return;
+    };
+
+    pending.frames.retain(|frame| frame.sequence > snapshot.ack_input);
+
+    let mut position = own.position;
+    let mut predicted_state = own.state;
+    let mut predicted_elapsed = own.state_elapsed;
+    for frame in &pending.frames {
+        simulate_input_in(
+            &mut position,
+            &mut predicted_state,
+            &mut predicted_elapsed,
+            frame,
+            input_state.speed_multiplier,
+            &effects,
+        );
+    }
+    transform.translation = position.extend(transform.translation.z);
+    *state = predicted_state;
+    state_elapsed.0 = predicted_elapsed;
+}
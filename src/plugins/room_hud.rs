@@ -0,0 +1,52 @@
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::resources::current_room::CurrentRoom;
+
+pub struct RoomHudPlugin;
+
+#[derive(Component)]
+struct OnRoomHud;
+
+impl Plugin for RoomHudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(GameState::InGame), setup_room_hud)
+            .add_systems(OnExit(GameState::InGame), cleanup_room_hud);
+    }
+}
+
+// System to show the room name, and the join code of private rooms, in the corner of the screen
+fn setup_room_hud(mut commands: Commands, asset_server: Res<AssetServer>, room: Option<Res<CurrentRoom>>) {
+    let Some(room) = room else {
+        return;
+    };
+    let label = match &room.join_code {
+        Some(code) => format!("{}  |  Code: {}", room.name, code),
+        None => room.name.clone(),
+    };
+
+    commands.spawn((
+        TextBundle::from_section(
+            label,
+            TextStyle {
+                font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            right: Val::Px(10.0),
+            ..Default::default()
+        }),
+        OnRoomHud,
+    ));
+}
+
+fn cleanup_room_hud(mut commands: Commands, query: Query<Entity, With<OnRoomHud>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,218 @@
+use bevy::prelude::*;
+use rand::seq::IteratorRandom;
+use rand::Rng;
+
+use crate::GameState;
+use crate::components::character_sheet::CharacterSheet;
+use crate::components::role::ControlledBy;
+use crate::consts;
+use crate::plugins::am_panel::local_is_am;
+use crate::plugins::network::LocalClientId;
+use crate::protocol::ServerMessage;
+
+pub struct SanityPlugin;
+
+// How long a misread number stays wrong the same way
+const SKEW_SECS: f32 = 1.5;
+// How often the mind may twist some text on screen, and for how long
+const GARBLE_EVERY_SECS: f32 = 0.4;
+const GARBLE_HOLD_SECS: f32 = 0.8;
+// Share of letters swapped out at full madness
+const MAX_GARBLED_SHARE: f32 = 0.4;
+const GARBLE_GLYPHS: &[char] = &['#', '%', '&', '?', '!', '*', '~', 'x', 'M', 'A'];
+
+#[derive(Component)]
+struct SanityText;
+
+// A text we read wrong for a moment, with what it really said
+#[derive(Component)]
+struct Garbled {
+    original: Vec<String>,
+    shown: Vec<String>,
+    timer: Timer,
+}
+
+// Our own sanity as the server tells it, and how far gone that leaves us
+#[derive(Resource)]
+pub struct Sanity {
+    pub value: f32,
+    pub max: f32,
+    pub madness: f32,
+    // Which way numbers are misread right now, from -1 to 1
+    skew: f32,
+    skew_timer: Timer,
+}
+
+impl Default for Sanity {
+    fn default() -> Self {
+        Self {
+            value: 0.0,
+            max: 0.0,
+            madness: 0.0,
+            skew: 0.0,
+            skew_timer: Timer::from_seconds(SKEW_SECS, TimerMode::Repeating),
+        }
+    }
+}
+
+impl Sanity {
+    // A number as we believe we read it, off by up to spread once we are gone entirely
+    pub fn misread(&self, value: f32, spread: f32) -> f32 {
+        value + self.skew * self.madness * spread
+    }
+}
+
+impl Plugin for SanityPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Sanity>()
+            .add_systems(Update, receive_sanity)
+            .add_systems(OnEnter(GameState::InGame), setup_sanity_hud.run_if(not(local_is_am)))
+            .add_systems(
+                Update,
+                (reroll_skew, update_sanity_hud, garble_text, restore_text)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::InGame), cleanup_sanity_hud);
+    }
+}
+
+// System to keep our sanity, our own sheet decides when it starts to show
+fn receive_sanity(
+    mut server_messages: EventReader<ServerMessage>,
+    mut sanity: ResMut<Sanity>,
+    local_client: Option<Res<LocalClientId>>,
+    roster_query: Query<(&ControlledBy, &CharacterSheet)>,
+) {
+    for message in server_messages.read() {
+        let ServerMessage::Sanity { value, max } = message else {
+            continue;
+        };
+        let local_id = local_client.as_ref().map(|local_client| local_client.0);
+        let sheet = roster_query
+            .iter()
+            .find(|(controlled_by, _)| controlled_by.0.is_some() && controlled_by.0 == local_id)
+            .map(|(_, sheet)| sheet);
+        sanity.value = *value;
+        sanity.max = *max;
+        sanity.madness = sheet.map_or(0.0, |sheet| sheet.madness(*value));
+    }
+}
+
+fn setup_sanity_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+                font_size: 24.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(16.0),
+            left: Val::Px(16.0),
+            ..Default::default()
+        }),
+        SanityText,
+    ));
+}
+
+fn reroll_skew(time: Res<Time>, mut sanity: ResMut<Sanity>) {
+    if sanity.skew_timer.tick(time.delta()).just_finished() {
+        sanity.skew = rand::thread_rng().gen_range(-1.0..=1.0);
+    }
+}
+
+// System to show our sanity, or what we take it to be
+fn update_sanity_hud(sanity: Res<Sanity>, mut text_query: Query<&mut Text, With<SanityText>>) {
+    let label = if sanity.max > 0.0 {
+        let shown = sanity.misread(sanity.value, sanity.max / 2.0).clamp(0.0, sanity.max);
+        format!("Sanity {:.0}", shown)
+    } else {
+        String::new()
+    };
+    let color = if sanity.madness > 0.0 { consts::AM_VOICE } else { Color::WHITE };
+    for mut text in &mut text_query {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+        text.sections[0].style.color = color;
+    }
+}
+
+// System to twist a piece of text on screen now and then, more often the further gone we are
+fn garble_text(
+    mut commands: Commands,
+    time: Res<Time>,
+    sanity: Res<Sanity>,
+    mut text_query: Query<(Entity, &mut Text), (With<Node>, Without<Garbled>)>,
+    mut garble_timer: Local<Option<Timer>>,
+) {
+    let garble_timer =
+        garble_timer.get_or_insert_with(|| Timer::from_seconds(GARBLE_EVERY_SECS, TimerMode::Repeating));
+    if !garble_timer.tick(time.delta()).just_finished() || sanity.madness <= 0.0 {
+        return;
+    }
+    let mut rng = rand::thread_rng();
+    if !rng.gen_bool(sanity.madness.clamp(0.0, 1.0) as f64) {
+        return;
+    }
+    let Some((entity, mut text)) = text_query
+        .iter_mut()
+        .filter(|(_, text)| text.sections.iter().any(|section| !section.value.trim().is_empty()))
+        .choose(&mut rng)
+    else {
+        return;
+    };
+    let share = sanity.madness * MAX_GARBLED_SHARE;
+    let original: Vec<String> = text.sections.iter().map(|section| section.value.clone()).collect();
+    for section in &mut text.sections {
+        section.value = section
+            .value
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() && rng.gen_bool(share as f64) {
+                    GARBLE_GLYPHS[rng.gen_range(0..GARBLE_GLYPHS.len())]
+                } else {
+                    c
+                }
+            })
+            .collect();
+    }
+    let shown = text.sections.iter().map(|section| section.value.clone()).collect();
+    commands.entity(entity).insert(Garbled {
+        original,
+        shown,
+        timer: Timer::from_seconds(GARBLE_HOLD_SECS, TimerMode::Once),
+    });
+}
+
+// System to let twisted text read right again. Text rewritten in the meantime is left as it is.
+fn restore_text(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut text_query: Query<(Entity, &mut Text, &mut Garbled)>,
+) {
+    for (entity, mut text, mut garbled) in &mut text_query {
+        if !garbled.timer.tick(time.delta()).finished() {
+            continue;
+        }
+        let untouched = text.sections.iter().map(|section| &section.value).eq(garbled.shown.iter());
+        if untouched {
+            for (section, original) in text.sections.iter_mut().zip(garbled.original.drain(..)) {
+                section.value = original;
+            }
+        }
+        commands.entity(entity).remove::<Garbled>();
+    }
+}
+
+fn cleanup_sanity_hud(mut commands: Commands, mut sanity: ResMut<Sanity>, query: Query<Entity, With<SanityText>>) {
+    *sanity = Sanity::default();
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
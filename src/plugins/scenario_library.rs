@@ -0,0 +1,53 @@
+use bevy::asset::{AssetLoadFailedEvent, LoadedFolder};
+use bevy::prelude::*;
+
+use crate::scenario::{Scenario, ScenarioLoader};
+
+pub struct ScenarioLibraryPlugin;
+
+// Every scenario under assets/scenarios/
+#[derive(Resource)]
+pub struct ScenarioLibrary {
+    folder: Handle<LoadedFolder>,
+}
+
+impl ScenarioLibrary {
+    // Looks a loaded scenario up by its name, None until the folder finished loading
+    pub fn find<'a>(
+        &self,
+        name: &str,
+        folders: &Assets<LoadedFolder>,
+        scenarios: &'a Assets<Scenario>,
+    ) -> Option<&'a Scenario> {
+        folders
+            .get(&self.folder)?
+            .handles
+            .iter()
+            .filter_map(|handle| handle.clone().try_typed::<Scenario>().ok())
+            .filter_map(|handle| scenarios.get(&handle))
+            .find(|scenario| scenario.name == name)
+    }
+}
+
+impl Plugin for ScenarioLibraryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_asset::<Scenario>()
+            .init_asset_loader::<ScenarioLoader>()
+            .add_systems(Startup, load_scenarios)
+            .add_systems(Update, report_broken_scenarios);
+    }
+}
+
+fn load_scenarios(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ScenarioLibrary {
+        folder: asset_server.load_folder("scenarios"),
+    });
+}
+
+// System to point scenario authors at what is wrong with their file
+fn report_broken_scenarios(mut failures: EventReader<AssetLoadFailedEvent<Scenario>>) {
+    for failure in failures.read() {
+        println!("Scenario {} failed to load: {}", failure.path, failure.error);
+    }
+}
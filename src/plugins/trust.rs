@@ -0,0 +1,50 @@
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::components::player::Player;
+use crate::consts;
+use crate::plugins::chat::chat_closed;
+use crate::plugins::interpolation::RemotePlayer;
+use crate::protocol::ClientMessage;
+
+pub struct TrustPlugin;
+
+impl Plugin for TrustPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            share_and_accuse.run_if(in_state(GameState::InGame).and_then(chat_closed)),
+        );
+    }
+}
+
+// System to share with the closest survivor in reach with F, or accuse them with R
+fn share_and_accuse(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    player_query: Query<&Transform, With<Player>>,
+    remote_query: Query<(&RemotePlayer, &Transform)>,
+    mut client_messages: EventWriter<ClientMessage>,
+) {
+    let share = keyboard_input.just_pressed(KeyCode::KeyF);
+    let accuse = keyboard_input.just_pressed(KeyCode::KeyR);
+    if !share && !accuse {
+        return;
+    }
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    let position = player.translation.truncate();
+    let closest = remote_query
+        .iter()
+        .map(|(remote, transform)| (remote.client_id, transform.translation.truncate().distance(position)))
+        .filter(|(_, distance)| *distance <= consts::INTERACT_RANGE)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+    let Some((client_id, _)) = closest else {
+        return;
+    };
+    if share {
+        client_messages.send(ClientMessage::Share { with: client_id });
+    } else {
+        client_messages.send(ClientMessage::Accuse { who: client_id });
+    }
+}
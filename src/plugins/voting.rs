@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::components::role::Role;
+use crate::consts;
+use crate::plugins::network::LocalClientId;
+use crate::protocol::{ClientMessage, ServerMessage, VoteChoice, VoteId, VoteResult, VoteType};
+use crate::resources::match_roles::MatchRoles;
+use crate::scenario::Ballot;
+
+pub struct VotingPlugin;
+
+const PANEL_WIDTH_PX: f32 = 280.0;
+
+#[derive(Component)]
+struct OnVotePanel;
+
+#[derive(Component)]
+struct VoteCountdownText;
+
+#[derive(Component)]
+enum VoteButton {
+    Call(String),
+    Cast(VoteId, String),
+}
+
+// The votes of this match, kept from the moment roles are handed out
+#[derive(Resource, Default)]
+pub struct Votes {
+    pub types: Vec<VoteType>,
+    pub open: Option<OpenVote>,
+    pub results: Vec<VoteResult>,
+}
+
+pub struct OpenVote {
+    pub id: VoteId,
+    pub question: String,
+    pub options: Vec<VoteChoice>,
+    pub ballot: Ballot,
+    pub called_by: u64,
+    pub timer: Timer,
+    // Who voted, and for what when we are allowed to know
+    pub ballots: HashMap<u64, Option<String>>,
+}
+
+impl Plugin for VotingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Votes>()
+            .add_systems(Update, receive_votes)
+            .add_systems(
+                Update,
+                (count_down_vote, show_vote_panel, handle_vote_buttons)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::InGame), cleanup_votes);
+    }
+}
+
+// System to follow the votes, the types arrive together with the roles so this runs outside the match too
+fn receive_votes(mut server_messages: EventReader<ServerMessage>, mut votes: ResMut<Votes>) {
+    for message in server_messages.read() {
+        match message {
+            ServerMessage::VoteTypes(types) => votes.types = types.clone(),
+            ServerMessage::VoteOpened { id, question, options, ballot, called_by, seconds_left } => {
+                votes.open = Some(OpenVote {
+                    id: *id,
+                    question: question.clone(),
+                    options: options.clone(),
+                    ballot: *ballot,
+                    called_by: *called_by,
+                    timer: Timer::from_seconds(seconds_left.max(0.0), TimerMode::Once),
+                    ballots: HashMap::new(),
+                });
+            }
+            ServerMessage::BallotCast { id, voter, option } => {
+                if let Some(open) = votes.open.as_mut().filter(|open| open.id == *id) {
+                    open.ballots.insert(*voter, option.clone());
+                }
+            }
+            ServerMessage::VoteClosed(result) => {
+                if votes.open.as_ref().map_or(false, |open| open.id == result.id) {
+                    votes.open = None;
+                }
+                // A resumed session gets the results again
+                votes.results.retain(|known| known.id != result.id);
+                votes.results.push(result.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+// System to count the open vote down between server messages, without rebuilding the panel every frame
+fn count_down_vote(
+    time: Res<Time>,
+    mut votes: ResMut<Votes>,
+    mut text_query: Query<&mut Text, With<VoteCountdownText>>,
+) {
+    let Some(open) = votes.bypass_change_detection().open.as_mut() else {
+        return;
+    };
+    open.timer.tick(time.delta());
+    for mut text in &mut text_query {
+        text.sections[0].value = format!("{:.0}s left", open.timer.remaining_secs().ceil());
+    }
+}
+
+// System to rebuild the vote panel whenever a vote opens, gets a ballot or closes.
+// AM sees every ballot but gets no buttons.
+fn show_vote_panel(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    votes: Res<Votes>,
+    roles: Option<Res<MatchRoles>>,
+    local_client: Option<Res<LocalClientId>>,
+    panel_query: Query<Entity, With<OnVotePanel>>,
+) {
+    if !votes.is_changed() {
+        return;
+    }
+    for entity in &panel_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    if votes.types.is_empty() {
+        return;
+    }
+    let is_am = match (&roles, &local_client) {
+        (Some(roles), Some(local_client)) => roles.role_of(local_client.0) == Some(Role::Am),
+        _ => false,
+    };
+    let name = |client_id: u64| {
+        roles
+            .as_ref()
+            .and_then(|roles| roles.role_of(client_id))
+            .map_or("Someone", |role| role.name())
+    };
+    let text_style = |font_size: f32, color: Color| TextStyle {
+        font: asset_server.load("fonts/Debrosee-ALPnL.ttf"),
+        font_size,
+        color,
+    };
+    let button_style = Style {
+        width: Val::Percent(100.0),
+        padding: UiRect::all(Val::Px(6.0)),
+        justify_content: JustifyContent::Center,
+        ..Default::default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(120.0),
+                    left: Val::Px(16.0),
+                    width: Val::Px(PANEL_WIDTH_PX),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(6.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    ..Default::default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.8).into(),
+                // Above AM's panel, AM watches the votes too
+                z_index: ZIndex::Global(10),
+                ..Default::default()
+            },
+            OnVotePanel,
+        ))
+        .with_children(|parent| {
+            if let Some(open) = &votes.open {
+                parent.spawn(TextBundle::from_section(open.question.clone(), text_style(22.0, Color::WHITE)));
+                let kind = match open.ballot {
+                    Ballot::Secret => "secret",
+                    Ballot::Public => "public",
+                };
+                parent.spawn(TextBundle::from_section(
+                    format!("Called by {}, {} ballot", name(open.called_by), kind),
+                    text_style(16.0, Color::WHITE),
+                ));
+                parent.spawn((TextBundle::from_section("", text_style(16.0, Color::WHITE)), VoteCountdownText));
+                for choice in &open.options {
+                    let votes_for = open
+                        .ballots
+                        .values()
+                        .filter(|option| option.as_deref() == Some(choice.id.as_str()))
+                        .count();
+                    let label = if open.ballot == Ballot::Public || is_am {
+                        format!("{} ({})", choice.label, votes_for)
+                    } else {
+                        choice.label.clone()
+                    };
+                    if is_am {
+                        parent.spawn(TextBundle::from_section(label, text_style(18.0, Color::WHITE)));
+                        continue;
+                    }
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: button_style.clone(),
+                                background_color: consts::NORMAL_BUTTON.into(),
+                                ..Default::default()
+                            },
+                            VoteButton::Cast(open.id, choice.id.clone()),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(label, text_style(18.0, Color::WHITE)));
+                        });
+                }
+                let mut voters: Vec<String> = open
+                    .ballots
+                    .iter()
+                    .map(|(voter, option)| match option {
+                        Some(option) => format!("{}: {}", name(*voter), option),
+                        None => name(*voter).to_string(),
+                    })
+                    .collect();
+                voters.sort();
+                if !voters.is_empty() {
+                    parent.spawn(TextBundle::from_section(
+                        format!("Voted: {}", voters.join(", ")),
+                        text_style(16.0, Color::WHITE),
+                    ));
+                }
+            } else if !is_am {
+                parent.spawn(TextBundle::from_section("Call a vote", text_style(20.0, Color::WHITE)));
+                for vote_type in &votes.types {
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: button_style.clone(),
+                                background_color: consts::NORMAL_BUTTON.into(),
+                                ..Default::default()
+                            },
+                            VoteButton::Call(vote_type.id.clone()),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(
+                                vote_type.question.clone(),
+                                text_style(18.0, Color::WHITE),
+                            ));
+                        });
+                }
+            }
+            if let Some(last) = votes.results.last() {
+                let decision = match &last.winner {
+                    Some(winner) => match last.tally.iter().find(|count| &count.option == winner) {
+                        Some(count) => format!("{} ({} votes)", count.label, count.votes),
+                        None => winner.clone(),
+                    },
+                    None => "no decision".to_string(),
+                };
+                parent.spawn(TextBundle::from_section(
+                    format!("{} {}", last.question, decision),
+                    text_style(16.0, consts::AM_VOICE),
+                ));
+            }
+        });
+}
+
+fn handle_vote_buttons(
+    interaction_query: Query<(&Interaction, &VoteButton), Changed<Interaction>>,
+    mut client_messages: EventWriter<ClientMessage>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let message = match button {
+            VoteButton::Call(vote) => ClientMessage::CallVote { vote: vote.clone() },
+            VoteButton::Cast(id, option) => ClientMessage::CastBallot {
+                id: *id,
+                option: option.clone(),
+            },
+        };
+        client_messages.send(message);
+    }
+}
+
+fn cleanup_votes(mut commands: Commands, mut votes: ResMut<Votes>, query: Query<Entity, With<OnVotePanel>>) {
+    *votes = Votes::default();
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
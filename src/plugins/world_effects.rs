@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+
+use crate::GameState;
+use crate::protocol::ServerMessage;
+use crate::resources::world_effects::WorldEffects;
+
+pub struct WorldEffectsPlugin;
+
+const HAZARD_COLOR: Color = Color::srgba(0.8, 0.15, 0.1, 0.35);
+const DOOR_COLOR: Color = Color::srgb(0.35, 0.3, 0.3);
+const DOOR_SIZE: Vec2 = Vec2::new(12.0, 160.0);
+
+// Drawing of a hazard or locked door, rebuilt whenever the server sends new effects
+#[derive(Component)]
+struct WorldEffectSprite;
+
+impl Plugin for WorldEffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<WorldEffects>()
+            .add_systems(Update, receive_world_effects)
+            .add_systems(Update, draw_world_effects.run_if(in_state(GameState::InGame)))
+            .add_systems(OnExit(GameState::InGame), cleanup_world_effects);
+    }
+}
+
+// System to keep the hazards and locked doors the server sent, prediction walks through the same ones
+fn receive_world_effects(mut server_messages: EventReader<ServerMessage>, mut effects: ResMut<WorldEffects>) {
+    for message in server_messages.read() {
+        if let ServerMessage::WorldEffects(latest) = message {
+            *effects = latest.clone();
+        }
+    }
+}
+
+// System to draw every hazard as a red zone and every locked door as a wall
+fn draw_world_effects(
+    mut commands: Commands,
+    effects: Res<WorldEffects>,
+    sprite_query: Query<Entity, With<WorldEffectSprite>>,
+) {
+    if !effects.is_changed() {
+        return;
+    }
+    for entity in &sprite_query {
+        commands.entity(entity).despawn_recursive();
+    }
+    for hazard in &effects.hazards {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: HAZARD_COLOR,
+                    custom_size: Some(Vec2::splat(hazard.radius * 2.0)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(hazard.position.extend(-1.0)),
+                ..Default::default()
+            },
+            WorldEffectSprite,
+        ));
+    }
+    for door in &effects.locked_doors {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: DOOR_COLOR,
+                    custom_size: Some(DOOR_SIZE),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(door.x, 0.0, 1.0),
+                ..Default::default()
+            },
+            WorldEffectSprite,
+        ));
+    }
+}
+
+fn cleanup_world_effects(
+    mut commands: Commands,
+    mut effects: ResMut<WorldEffects>,
+    query: Query<Entity, With<WorldEffectSprite>>,
+) {
+    *effects = WorldEffects::default();
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
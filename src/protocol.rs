@@ -0,0 +1,906 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_renet::renet::transport::NETCODE_USER_DATA_BYTES;
+use bevy_renet::renet::{ChannelConfig, ClientId, ConnectionConfig, RenetServer, SendType};
+use serde::{Deserialize, Serialize};
+
+use crate::MatchPhase;
+use crate::components::appearance::SpriteSet;
+use crate::components::character_sheet::CharacterSheet;
+use crate::components::player::PlayerState;
+use crate::components::role::{Character, Role};
+use crate::resources::world_effects::WorldEffects;
+use crate::scenario::{Ballot, InterventionKind, Scenario};
+
+// Netcode refuses connections from a different game altogether
+pub const PROTOCOL_ID: u64 = 7;
+// Bumped whenever a message below changes shape, so old builds are turned away cleanly
+pub const PROTOCOL_VERSION: u16 = 25;
+
+const MAX_PLAYER_NAME_BYTES: usize = 32;
+// Room names go out in every discovery reply, which has to fit in one datagram
+pub const MAX_ROOM_NAME_CHARS: usize = 24;
+pub const MAX_NARRATIVE_CHARS: usize = 280;
+pub const MAX_CHAT_CHARS: usize = 200;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RoomId(pub u32);
+
+// Secret handed to a client on connect, proves a reconnecting client owns the slot it asks for
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SessionToken(pub u64);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoomPhase {
+    Waiting,
+    // Survivors are picking their characters, the room is closed to newcomers from here on
+    Drafting,
+    InProgress,
+    // The debrief is out, nothing runs any more and players only leave from here on
+    Finished,
+}
+
+// One row of the lobby list
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RoomInfo {
+    pub id: RoomId,
+    pub name: String,
+    pub is_private: bool,
+    pub host_name: String,
+    pub player_count: u8,
+    pub max_players: u8,
+    pub phase: RoomPhase,
+}
+
+impl RoomInfo {
+    pub fn is_joinable(&self) -> bool {
+        self.phase == RoomPhase::Waiting && self.player_count < self.max_players
+    }
+}
+
+// Channels the client sends on
+pub enum ClientChannel {
+    Command,
+    Input,
+}
+
+// Channels the server sends on
+pub enum ServerChannel {
+    ServerMessages,
+    Snapshots,
+}
+
+impl From<ClientChannel> for u8 {
+    fn from(channel: ClientChannel) -> Self {
+        match channel {
+            ClientChannel::Command => 0,
+            ClientChannel::Input => 1,
+        }
+    }
+}
+
+impl From<ServerChannel> for u8 {
+    fn from(channel: ServerChannel) -> Self {
+        match channel {
+            ServerChannel::ServerMessages => 0,
+            ServerChannel::Snapshots => 1,
+        }
+    }
+}
+
+impl ClientChannel {
+    pub fn channels_config() -> Vec<ChannelConfig> {
+        vec![
+            ChannelConfig {
+                channel_id: Self::Command.into(),
+                max_memory_usage_bytes: 5 * 1024 * 1024,
+                send_type: SendType::ReliableOrdered {
+                    resend_time: Duration::from_millis(200),
+                },
+            },
+            ChannelConfig {
+                channel_id: Self::Input.into(),
+                max_memory_usage_bytes: 1024 * 1024,
+                send_type: SendType::Unreliable,
+            },
+        ]
+    }
+}
+
+impl ServerChannel {
+    pub fn channels_config() -> Vec<ChannelConfig> {
+        vec![
+            ChannelConfig {
+                channel_id: Self::ServerMessages.into(),
+                max_memory_usage_bytes: 10 * 1024 * 1024,
+                send_type: SendType::ReliableOrdered {
+                    resend_time: Duration::from_millis(200),
+                },
+            },
+            ChannelConfig {
+                channel_id: Self::Snapshots.into(),
+                max_memory_usage_bytes: 10 * 1024 * 1024,
+                send_type: SendType::Unreliable,
+            },
+        ]
+    }
+}
+
+pub fn connection_config() -> ConnectionConfig {
+    ConnectionConfig {
+        available_bytes_per_tick: 1024 * 1024,
+        client_channels_config: ClientChannel::channels_config(),
+        server_channels_config: ServerChannel::channels_config(),
+    }
+}
+
+// Inputs produced by the local player, sampled into an input frame every input tick
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum PlayerInputs {
+    Move(Vec2),
+    Sprint(bool),
+    Attack,
+}
+
+// One input tick of the local player, numbered so the server can acknowledge it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct InputFrame {
+    pub sequence: u32,
+    pub movement: Vec2,
+    pub sprint: bool,
+    pub attack: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlayerSnapshot {
+    pub client_id: u64,
+    pub position: Vec2,
+    pub state: PlayerState,
+    // Seconds spent in the current state, lets every client show the same animation frame
+    pub state_elapsed: f32,
+}
+
+// Authoritative state of every player in the room, sent to each member every server tick
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub tick: u32,
+    // Seconds since the server started, the timeline remote players are interpolated on
+    pub server_time: f64,
+    // Last input frame of the receiving client the server has applied
+    pub ack_input: u32,
+    pub players: Vec<PlayerSnapshot>,
+}
+
+// How the room decides who plays AM when the host starts the match
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AmSelection {
+    #[default]
+    HostPicks,
+    Random,
+    // Drawn at random among the players who offered, nobody is forced into the role
+    Volunteers,
+}
+
+impl AmSelection {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AmSelection::HostPicks => "Host picks",
+            AmSelection::Random => "Random",
+            AmSelection::Volunteers => "Volunteers",
+        }
+    }
+}
+
+// One player as shown on the pre-match screen
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LineupEntry {
+    pub client_id: u64,
+    pub name: String,
+    pub is_host: bool,
+    pub volunteered: bool,
+}
+
+// A survivor on the draft screen, character is None until they pick
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DraftPick {
+    pub client_id: u64,
+    pub name: String,
+    pub character: Option<Character>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RoleAssignment {
+    pub client_id: u64,
+    pub name: String,
+    pub role: Role,
+}
+
+// Words AM puts in a survivor's head, either a line the scenario wrote or its own
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum NarrativeText {
+    Line(String),
+    Free(String),
+}
+
+impl NarrativeText {
+    // "@welcome" names a scenario line, anything else is said as typed
+    pub fn parse(text: &str) -> NarrativeText {
+        match text.strip_prefix('@') {
+            Some(line) => NarrativeText::Line(line.to_string()),
+            None => NarrativeText::Free(text.to_string()),
+        }
+    }
+}
+
+// One event the way AM tells it. Each version only ever reaches its own recipients.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NarrativeEvent {
+    pub versions: Vec<NarrativeVersion>,
+    // What really happened, kept for the reveal after the match
+    pub truth: Option<String>,
+    // Set when the story points a finger at one of the survivors
+    pub rumour: Option<Rumour>,
+}
+
+// A claim about one survivor. Whether it holds stays AM's secret until a believer confronts them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rumour {
+    pub about: u64,
+    pub is_true: bool,
+}
+
+// How much one survivor trusts another, from 0 to 1
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TrustPair {
+    pub from: u64,
+    pub to: u64,
+    pub value: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SanityLevel {
+    pub client_id: u64,
+    pub value: f32,
+    pub max: f32,
+}
+
+// The whole trust matrix at one moment of the match
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TrustSample {
+    pub at_secs: f32,
+    pub average: f32,
+    pub pairs: Vec<TrustPair>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchOutcome {
+    SurvivorsWin,
+    AmWins,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NarrativeVersion {
+    pub recipients: Vec<u64>,
+    pub text: NarrativeText,
+}
+
+// What each survivor was told next to what really happened
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NarrativeRecord {
+    pub at_secs: f32,
+    pub phase: MatchPhase,
+    pub truth: Option<String>,
+    pub deliveries: Vec<NarrativeDelivery>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NarrativeDelivery {
+    pub client_id: u64,
+    pub name: String,
+    pub text: String,
+    // Sent but lost to the recipient's memory gaps
+    pub forgotten: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IllusionId(pub u32);
+
+// Something only one survivor sees, or fails to see
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum IllusionKind {
+    // An item lying where there is nothing
+    FakeItem { item: String, position: Vec2 },
+    // Someone who looks like another player but is not there
+    FakePlayer { of: u64, position: Vec2 },
+    // A real player drawn with the wrong sprite set
+    SpriteOverride { client_id: u64, sprites: SpriteSet },
+    // A real player the target cannot see at all
+    HiddenPlayer { client_id: u64 },
+}
+
+// What AM scripted to happen when the target touches the illusion
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Exposure {
+    // Told to the target only, like a narrative
+    pub message: Option<NarrativeText>,
+    // Whether the illusion vanishes or keeps fooling the target
+    pub dispel: bool,
+}
+
+// An illusion as AM sees it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Illusion {
+    pub id: IllusionId,
+    pub target: u64,
+    pub kind: IllusionKind,
+    pub exposure: Exposure,
+    pub exposures: u32,
+    // The target saw through it the moment it was cast
+    pub seen_through: bool,
+}
+
+// What a survivor reached out to with the interact key
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InteractTarget {
+    Illusion(IllusionId),
+    Player(u64),
+}
+
+// What AM does with one of the scenario's interventions, which must be of the matching kind
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum InterventionAction {
+    Narrate(NarrativeEvent),
+    Hallucination { target: u64, kind: IllusionKind, exposure: Exposure },
+    Hazard { position: Vec2 },
+    LockDoor { door: String },
+}
+
+impl InterventionAction {
+    pub fn fits(&self, kind: &InterventionKind) -> bool {
+        matches!(
+            (self, kind),
+            (InterventionAction::Narrate(_), InterventionKind::Narrate)
+                | (InterventionAction::Hallucination { .. }, InterventionKind::Hallucination)
+                | (InterventionAction::Hazard { .. }, InterventionKind::Hazard { .. })
+                | (InterventionAction::LockDoor { .. }, InterventionKind::LockDoor { .. })
+        )
+    }
+}
+
+// Who a survivor's message is meant for
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatChannel {
+    Public,
+    Whisper(u64),
+    // Everyone close enough to hear it
+    Proximity,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChatId(pub u32);
+
+// What AM does to a message while the server holds it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ChatTamper {
+    Delay,
+    Drop,
+    Rewrite(String),
+}
+
+// A survivor's message as AM and the debrief see it, next to what really arrived
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChatRecord {
+    pub id: ChatId,
+    pub at_secs: f32,
+    pub from: u64,
+    pub channel: ChatChannel,
+    pub original: String,
+    // None once AM dropped it
+    pub text: Option<String>,
+    pub tampering: Vec<ChatTamper>,
+    // Filled in when it goes out
+    pub recipients: Vec<u64>,
+    pub delivered: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoteId(pub u32);
+
+// A vote of the scenario as the survivors see it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VoteType {
+    pub id: String,
+    pub question: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VoteChoice {
+    pub id: String,
+    pub label: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BallotRecord {
+    pub voter: u64,
+    pub option: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VoteCount {
+    pub option: String,
+    pub label: String,
+    pub votes: u32,
+}
+
+// How a vote ended, the ballots are left out for survivors when the vote was secret
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VoteResult {
+    pub id: VoteId,
+    pub vote: String,
+    pub question: String,
+    pub called_by: u64,
+    pub at_secs: f32,
+    // None when nobody voted or the top options tied
+    pub winner: Option<String>,
+    pub tally: Vec<VoteCount>,
+    pub ballots: Vec<BallotRecord>,
+}
+
+// Something that happened during the match, kept for the debrief timeline
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchEvent {
+    pub at_secs: f32,
+    pub phase: MatchPhase,
+    pub kind: MatchEventKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum MatchEventKind {
+    PhaseStarted,
+    Intervention { name: String, cost: f32, action: InterventionAction },
+    ChatTampered { from: u64, tamper: ChatTamper, cost: f32 },
+    Shared { by: u64, with: u64 },
+    Accused { by: u64, who: u64 },
+    RumourSettled { believer: u64, about: u64, was_true: bool },
+    // Secret ballots stay secret
+    VoteClosed(VoteResult),
+    ObjectiveCompleted { description: String },
+    // The survivor's sanity fell below their breaking point
+    Broke { who: u64 },
+    Died { who: u64, by: Option<u64> },
+    MatchOver(MatchOutcome),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InterventionCooldown {
+    pub id: String,
+    pub seconds_left: f32,
+}
+
+// Why the server refused to create, join or set up a room
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoomError {
+    EmptyName,
+    NameTooLong,
+    NoMoreRooms,
+    NotFound,
+    AlreadyStarted,
+    Full,
+    InvalidCode,
+    PasswordRequired,
+    WrongPassword,
+    NotHost,
+    NotEnoughPlayers,
+    NoAmPicked,
+    NoVolunteers,
+    NotDrafting,
+    CharacterTaken,
+}
+
+impl std::fmt::Display for RoomError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            RoomError::EmptyName => "Room name cannot be empty",
+            RoomError::NameTooLong => "Room name is too long",
+            RoomError::NoMoreRooms => "This server cannot host any more rooms",
+            RoomError::NotFound => "That room no longer exists",
+            RoomError::AlreadyStarted => "That room has already started",
+            RoomError::Full => "That room is full",
+            RoomError::InvalidCode => "No room uses that code",
+            RoomError::PasswordRequired => "This room needs a password",
+            RoomError::WrongPassword => "Wrong password",
+            RoomError::NotHost => "Only the host can do that",
+            RoomError::NotEnoughPlayers => "A match needs AM and at least one survivor",
+            RoomError::NoAmPicked => "Pick who plays AM first",
+            RoomError::NoVolunteers => "Nobody volunteered to play AM",
+            RoomError::NotDrafting => "Characters can only be picked during the draft",
+            RoomError::CharacterTaken => "Someone already picked that character",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+// Why the server refused something a player tried during the match
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionError {
+    NotAm,
+    MatchNotRunning,
+    UnknownRecipient,
+    RecipientTwice,
+    UnknownLine,
+    EmptyNarrative,
+    NarrativeTooLong,
+    UnknownIllusion,
+    UnknownIntervention,
+    WrongIntervention,
+    CoolingDown,
+    NotEnoughHate,
+    UnknownDoor,
+    OffMap,
+    NotSurvivor,
+    OutOfReach,
+    TooSoon,
+    UnknownVote,
+    VoteRunning,
+    VoteOver,
+    UnknownChoice,
+    Excluded,
+    EmptyChat,
+    ChatTooLong,
+    TalkingTooFast,
+    ChatGone,
+}
+
+impl std::fmt::Display for ActionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            ActionError::NotAm => "Only AM can do that",
+            ActionError::MatchNotRunning => "The match is not running",
+            ActionError::UnknownRecipient => "Only survivors in this match can be told anything",
+            ActionError::RecipientTwice => "A survivor can only be told one version",
+            ActionError::UnknownLine => "The scenario has no such line",
+            ActionError::EmptyNarrative => "There is nothing to say",
+            ActionError::NarrativeTooLong => "That is too much to say at once",
+            ActionError::UnknownIllusion => "That illusion is gone",
+            ActionError::UnknownIntervention => "The scenario has no such intervention",
+            ActionError::WrongIntervention => "That intervention does something else",
+            ActionError::CoolingDown => "That intervention is not ready yet",
+            ActionError::NotEnoughHate => "Not enough hate for that",
+            ActionError::UnknownDoor => "The map has no such door",
+            ActionError::OffMap => "That is outside the map",
+            ActionError::NotSurvivor => "Only survivors can do that",
+            ActionError::OutOfReach => "They are too far away",
+            ActionError::TooSoon => "Too soon, wait a little before trying that again",
+            ActionError::UnknownVote => "The scenario has no such vote",
+            ActionError::VoteRunning => "Another vote is still running",
+            ActionError::VoteOver => "That vote is over",
+            ActionError::UnknownChoice => "That is not on the ballot",
+            ActionError::Excluded => "They were voted out, nobody shares with them",
+            ActionError::EmptyChat => "Type something first",
+            ActionError::ChatTooLong => "That message is too long",
+            ActionError::TalkingTooFast => "Slow down",
+            ActionError::ChatGone => "That message is out of reach",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl ActionError {
+    // Refusals of something typed into the chat, shown next to it instead of in the narrative feed
+    pub fn is_chat(&self) -> bool {
+        matches!(
+            self,
+            ActionError::EmptyChat | ActionError::ChatTooLong | ActionError::TalkingTooFast | ActionError::ChatGone
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum RejectReason {
+    VersionMismatch { server: u16, client: u16 },
+    BadHandshake,
+    // The slot this client tried to take back was released or belongs to someone else
+    SessionExpired,
+}
+
+impl std::fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RejectReason::VersionMismatch { server, client } => write!(
+                f,
+                "Server runs protocol version {} but this build speaks version {}, please update",
+                server, client
+            ),
+            RejectReason::BadHandshake => write!(f, "Server could not read the connect handshake"),
+            RejectReason::SessionExpired => write!(f, "Your place in the match was given up, the grace period is over"),
+        }
+    }
+}
+
+// Everything a client can say to the server
+#[derive(Event, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    ListRooms,
+    CreateRoom { name: String, is_private: bool, password: Option<String> },
+    JoinRoom { room: RoomId },
+    JoinByCode { code: String, password: Option<String> },
+    LeaveRoom,
+    // Pre-match role setup, everything but Volunteer is for the host only
+    SetAmSelection(AmSelection),
+    PickAm { client_id: u64 },
+    Volunteer(bool),
+    StartMatch,
+    PickCharacter(Character),
+    // The latest unacknowledged frames, so a lost packet is covered by the next one
+    Inputs(Vec<InputFrame>),
+    Chat { channel: ChatChannel, text: String },
+    // AM spends hate on a survivor's message the server still holds
+    TamperChat { id: ChatId, tamper: ChatTamper },
+    // AM spends hate on one of the scenario's interventions
+    Intervene { intervention: String, action: InterventionAction },
+    DispelIllusion(IllusionId),
+    Interact(InteractTarget),
+    // Survivors sharing what little they have, or pointing fingers
+    Share { with: u64 },
+    Accuse { who: u64 },
+    // Any survivor may call one of the scenario's votes, a ballot can be changed until the vote closes
+    CallVote { vote: String },
+    CastBallot { id: VoteId, option: String },
+}
+
+impl ClientMessage {
+    pub fn channel(&self) -> ClientChannel {
+        match self {
+            ClientMessage::Inputs(_) => ClientChannel::Input,
+            _ => ClientChannel::Command,
+        }
+    }
+}
+
+// Everything the server can say to a client
+#[derive(Event, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    // The session token lets this client take its slot back for reconnect_grace_secs after a drop
+    Welcome { client_id: u64, server_id: u64, session: SessionToken, reconnect_grace_secs: u32 },
+    Rejected(RejectReason),
+    RoomList(Vec<RoomInfo>),
+    RoomJoined { room: RoomId, name: String, join_code: Option<String>, phase: RoomPhase },
+    // Who is in the room and how AM will be chosen, sent whenever it changes before the match
+    Lineup { selection: AmSelection, am_pick: Option<u64>, players: Vec<LineupEntry> },
+    // Sent when the draft opens and after every pick, the countdown restarts from seconds_left
+    Draft { am_client_id: u64, seconds_left: f32, picks: Vec<DraftPick> },
+    // Every survivor's sheet from the server's scenario, sent right before the draft opens
+    CharacterSheets(Vec<(Character, CharacterSheet)>),
+    RolesAssigned(Vec<RoleAssignment>),
+    // Sent when the match moves to another phase, the countdown restarts from seconds_left
+    MatchPhase { phase: MatchPhase, seconds_left: f32 },
+    RoomError(RoomError),
+    ActionError(ActionError),
+    Snapshot(Snapshot),
+    // Survivors never learn whether AM touched it
+    Chat { from: u64, channel: ChatChannel, text: String },
+    // Every survivor message, sent to AM when it comes in and whenever it changes
+    AmChat(ChatRecord),
+    // Every survivor message with what AM did to it, sent to everyone once the debrief starts
+    ChatReveal(Vec<ChatRecord>),
+    // One version of a narrative event, only its recipients get it
+    Narrative { text: String },
+    // The whole narrative log with the truth, sent to everyone once the debrief starts
+    NarrativeReveal(Vec<NarrativeRecord>),
+    // Only sent to the target, which never learns about the players hidden from it
+    IllusionAppeared { id: IllusionId, kind: IllusionKind },
+    IllusionExposed { id: IllusionId, dispelled: bool },
+    IllusionDispelled(IllusionId),
+    // Every illusion in the match, sent to AM whenever one changes
+    AmIllusions(Vec<Illusion>),
+    // The whole scenario, only AM gets to read it
+    AmScenario(Scenario),
+    // AM's hate and the interventions still cooling down, sent to AM a few times a second
+    AmStatus {
+        hate: f32,
+        max_hate: f32,
+        cooldowns: Vec<InterventionCooldown>,
+        score: f32,
+        trust: Vec<TrustPair>,
+        sanity: Vec<SanityLevel>,
+    },
+    // A copy for AM of something a survivor was told, forgotten ones never reached them
+    AmFeed { client_id: u64, text: String, forgotten: bool },
+    // Hazards and locked doors in the room, sent to everyone whenever they change
+    WorldEffects(WorldEffects),
+    // Only the survivor shared with is told
+    Shared { by: u64 },
+    // Accusations are made in front of everyone
+    Accused { by: u64, who: u64 },
+    // How trust between the survivors changed over the match, sent to everyone once the debrief starts
+    TrustReveal { history: Vec<TrustSample>, am_score: f32 },
+    // A survivor's own sanity, only ever sent to them
+    Sanity { value: f32, max: f32 },
+    // The votes the scenario lets survivors call, sent when the match starts
+    VoteTypes(Vec<VoteType>),
+    // The countdown restarts from seconds_left
+    VoteOpened { id: VoteId, question: String, options: Vec<VoteChoice>, ballot: Ballot, called_by: u64, seconds_left: f32 },
+    // The option is only there for public ballots, AM always gets it
+    BallotCast { id: VoteId, voter: u64, option: Option<String> },
+    VoteClosed(VoteResult),
+    // Everything that happened in the match, sent to everyone once the debrief starts and after the other reveals
+    MatchLog(Vec<MatchEvent>),
+    // A win or lose condition of the scenario was met, or time ran out
+    MatchOver { outcome: MatchOutcome, text: String },
+    // Another player in the room dropped, came back, or lost their slot
+    PlayerConnection { client_id: u64, name: String, status: ConnectionStatus },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    Lost { grace_secs: u32 },
+    Returned,
+    SlotReleased,
+}
+
+impl ServerMessage {
+    pub fn channel(&self) -> ServerChannel {
+        match self {
+            ServerMessage::Snapshot(_) => ServerChannel::Snapshots,
+            _ => ServerChannel::ServerMessages,
+        }
+    }
+}
+
+// Sent to the discovery port, by broadcast on the LAN or directly to a known server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DiscoveryProbe {
+    pub protocol_id: u64,
+    pub version: u16,
+}
+
+impl Default for DiscoveryProbe {
+    fn default() -> Self {
+        Self {
+            protocol_id: PROTOCOL_ID,
+            version: PROTOCOL_VERSION,
+        }
+    }
+}
+
+// A server describing itself and its public rooms in answer to a probe
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DiscoveryReply {
+    pub protocol_id: u64,
+    pub version: u16,
+    // Random per server run, so one server seen on several addresses is only listed once
+    pub server_id: u64,
+    pub game_port: u16,
+    pub rooms: Vec<RoomInfo>,
+}
+
+pub fn encode<T: Serialize>(message: &T) -> Vec<u8> {
+    bincode::serialize(message).expect("protocol messages always serialize")
+}
+
+pub fn decode<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Option<T> {
+    bincode::deserialize(bytes).ok()
+}
+
+pub fn send_to_client(server: &mut RenetServer, client_id: ClientId, message: &ServerMessage) {
+    server.send_message(client_id, message.channel(), encode(message));
+}
+
+pub fn broadcast(server: &mut RenetServer, message: &ServerMessage) {
+    server.broadcast_message(message.channel(), encode(message));
+}
+
+// Carried in the netcode connect token so the server can check builds before any message is read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handshake {
+    pub version: u16,
+    pub name: String,
+    // Set when reconnecting to take back a held slot
+    pub session: Option<SessionToken>,
+}
+
+impl Handshake {
+    pub fn new(name: &str) -> Self {
+        // Trim on a char boundary so the name always fits the user data
+        let mut end = name.len().min(MAX_PLAYER_NAME_BYTES);
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        Self {
+            version: PROTOCOL_VERSION,
+            name: name[..end].to_string(),
+            session: None,
+        }
+    }
+
+    pub fn resuming(mut self, session: SessionToken) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    // The version always sits in the first two bytes so any later layout change can still be detected
+    pub fn to_user_data(&self) -> [u8; NETCODE_USER_DATA_BYTES] {
+        let mut user_data = [0u8; NETCODE_USER_DATA_BYTES];
+        let name = self.name.as_bytes();
+        user_data[0..2].copy_from_slice(&self.version.to_le_bytes());
+        user_data[2] = name.len() as u8;
+        user_data[3..3 + name.len()].copy_from_slice(name);
+        // The session goes after the longest possible name so the layout does not depend on it
+        let session_at = 3 + MAX_PLAYER_NAME_BYTES;
+        if let Some(session) = self.session {
+            user_data[session_at] = 1;
+            user_data[session_at + 1..session_at + 9].copy_from_slice(&session.0.to_le_bytes());
+        }
+        user_data
+    }
+
+    pub fn from_user_data(user_data: &[u8; NETCODE_USER_DATA_BYTES]) -> Result<Self, RejectReason> {
+        let version = u16::from_le_bytes([user_data[0], user_data[1]]);
+        if version != PROTOCOL_VERSION {
+            return Err(RejectReason::VersionMismatch {
+                server: PROTOCOL_VERSION,
+                client: version,
+            });
+        }
+        let len = user_data[2] as usize;
+        if len > MAX_PLAYER_NAME_BYTES {
+            return Err(RejectReason::BadHandshake);
+        }
+        let name = std::str::from_utf8(&user_data[3..3 + len]).map_err(|_| RejectReason::BadHandshake)?;
+        let session_at = 3 + MAX_PLAYER_NAME_BYTES;
+        let session = match user_data[session_at] {
+            0 => None,
+            1 => {
+                let mut token = [0u8; 8];
+                token.copy_from_slice(&user_data[session_at + 1..session_at + 9]);
+                Some(SessionToken(u64::from_le_bytes(token)))
+            }
+            _ => return Err(RejectReason::BadHandshake),
+        };
+        Ok(Self {
+            version,
+            name: name.to_string(),
+            session,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_survives_the_user_data() {
+        let handshake = Handshake::new("Gorrister").resuming(SessionToken(0xDEAD_BEEF_0BAD_F00D));
+        assert_eq!(Handshake::from_user_data(&handshake.to_user_data()), Ok(handshake));
+        let fresh = Handshake::new("Ellen");
+        assert_eq!(Handshake::from_user_data(&fresh.to_user_data()), Ok(fresh));
+    }
+
+    #[test]
+    fn long_names_are_cut_on_a_char_boundary() {
+        let handshake = Handshake::new(&"é".repeat(MAX_PLAYER_NAME_BYTES));
+        assert_eq!(handshake.name.len(), MAX_PLAYER_NAME_BYTES);
+        assert_eq!(Handshake::from_user_data(&handshake.to_user_data()), Ok(handshake));
+        let odd = Handshake::new(&format!("a{}", "é".repeat(MAX_PLAYER_NAME_BYTES)));
+        assert_eq!(odd.name.len(), MAX_PLAYER_NAME_BYTES - 1);
+    }
+
+    #[test]
+    fn refuses_other_versions_and_garbage() {
+        let mut user_data = Handshake::new("Ted").to_user_data();
+        user_data[0..2].copy_from_slice(&(PROTOCOL_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            Handshake::from_user_data(&user_data),
+            Err(RejectReason::VersionMismatch {
+                server: PROTOCOL_VERSION,
+                client: PROTOCOL_VERSION + 1,
+            })
+        );
+
+        let mut user_data = Handshake::new("Ted").to_user_data();
+        user_data[2] = MAX_PLAYER_NAME_BYTES as u8 + 1;
+        assert_eq!(Handshake::from_user_data(&user_data), Err(RejectReason::BadHandshake));
+    }
+}
@@ -0,0 +1,12 @@
+use bevy::prelude::*;
+
+use crate::protocol::RoomId;
+
+// Room the server placed this client in
+#[derive(Resource, Clone, Debug)]
+pub struct CurrentRoom {
+    pub id: RoomId,
+    pub name: String,
+    // Set for private rooms so the players inside can pass it on
+    pub join_code: Option<String>,
+}
@@ -0,0 +1,17 @@
+use bevy::prelude::*;
+
+use crate::components::role::Role;
+use crate::protocol::RoleAssignment;
+
+// Roles the server handed out when the match started
+#[derive(Resource, Clone, Debug, Default)]
+pub struct MatchRoles(pub Vec<RoleAssignment>);
+
+impl MatchRoles {
+    pub fn role_of(&self, client_id: u64) -> Option<Role> {
+        self.0
+            .iter()
+            .find(|assignment| assignment.client_id == client_id)
+            .map(|assignment| assignment.role)
+    }
+}
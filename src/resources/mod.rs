@@ -1 +1,4 @@
-pub mod selection_timer;
\ No newline at end of file
+pub mod selection_timer;
+pub mod current_room;
+pub mod match_roles;
+pub mod world_effects;
\ No newline at end of file
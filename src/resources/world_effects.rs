@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Hazards AM dropped and doors AM locked. The server owns them per room and sends every change,
+// the client keeps the latest copy so prediction moves the local player the way the server will.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct WorldEffects {
+    pub hazards: Vec<HazardZone>,
+    pub locked_doors: Vec<LockedDoor>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HazardZone {
+    pub position: Vec2,
+    pub radius: f32,
+    // Speed multiplier for anyone inside
+    pub slow: f32,
+    pub seconds_left: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LockedDoor {
+    pub id: String,
+    pub x: f32,
+    pub seconds_left: f32,
+}
+
+impl WorldEffects {
+    // Speed of someone standing at position, the slowest hazard they are in wins
+    pub fn speed_at(&self, position: Vec2, speed: f32) -> f32 {
+        let slow = self
+            .hazards
+            .iter()
+            .filter(|hazard| hazard.position.distance(position) <= hazard.radius)
+            .map(|hazard| hazard.slow)
+            .fold(1.0, f32::min);
+        speed * slow
+    }
+
+    // Keeps a move from crossing any locked door, the mover stops right at it
+    pub fn block(&self, from: Vec2, to: &mut Vec2) {
+        for door in &self.locked_doors {
+            if from.x < door.x && to.x >= door.x {
+                to.x = door.x - 0.01;
+            } else if from.x > door.x && to.x <= door.x {
+                to.x = door.x + 0.01;
+            }
+        }
+    }
+
+    pub fn is_locked(&self, door: &str) -> bool {
+        self.locked_doors.iter().any(|locked| locked.id == door)
+    }
+
+    // Counts every effect down, true when one of them ran out
+    pub fn tick(&mut self, delta: f32) -> bool {
+        let before = self.hazards.len() + self.locked_doors.len();
+        for hazard in &mut self.hazards {
+            hazard.seconds_left -= delta;
+        }
+        for door in &mut self.locked_doors {
+            door.seconds_left -= delta;
+        }
+        self.hazards.retain(|hazard| hazard.seconds_left > 0.0);
+        self.locked_doors.retain(|door| door.seconds_left > 0.0);
+        before != self.hazards.len() + self.locked_doors.len()
+    }
+}
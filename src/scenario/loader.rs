@@ -0,0 +1,68 @@
+use std::fmt;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+
+use crate::scenario::Scenario;
+
+pub const SCENARIO_EXTENSION: &str = "scenario.ron";
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    // Every authoring mistake found, not just the first one
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScenarioError::Io(error) => write!(f, "could not read the scenario: {}", error),
+            ScenarioError::Parse(error) => write!(
+                f,
+                "syntax error at line {}, column {}: {}",
+                error.position.line, error.position.col, error.code
+            ),
+            ScenarioError::Invalid(problems) => {
+                write!(f, "the scenario has {} problem(s):", problems.len())?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<std::io::Error> for ScenarioError {
+    fn from(error: std::io::Error) -> Self {
+        ScenarioError::Io(error)
+    }
+}
+
+#[derive(Default)]
+pub struct ScenarioLoader;
+
+impl AssetLoader for ScenarioLoader {
+    type Asset = Scenario;
+    type Settings = ();
+    type Error = ScenarioError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Scenario, ScenarioError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Scenario::from_bytes(&bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[SCENARIO_EXTENSION]
+    }
+}
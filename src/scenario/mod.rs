@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::MatchPhase;
+use crate::components::character_sheet::CharacterSheet;
+use crate::components::role::Character;
+
+pub mod loader;
+pub mod validation;
+
+pub use loader::{ScenarioError, ScenarioLoader};
+
+// Everything AM sets up for one match, authored as a `.scenario.ron` file under assets/scenarios/
+#[derive(Asset, TypePath, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub name: String,
+    pub map: MapDefinition,
+    pub spawn_points: Vec<SpawnPoint>,
+    // Stats and ability of every survivor, the server hands these to the clients when the draft opens
+    pub characters: HashMap<Character, CharacterSheet>,
+    #[serde(default)]
+    pub objectives: Vec<Objective>,
+    #[serde(default)]
+    pub interventions: Vec<Intervention>,
+    #[serde(default)]
+    pub hate: HateSettings,
+    // Votes the survivors may call
+    #[serde(default)]
+    pub votes: Vec<VoteDefinition>,
+    #[serde(default)]
+    pub chat: ChatSettings,
+    #[serde(default)]
+    pub sanity: SanitySettings,
+    pub narrative: Narrative,
+    #[serde(default)]
+    pub phases: PhaseTimings,
+    // The survivors win as soon as any of these holds
+    pub win_conditions: Vec<Condition>,
+    // AM wins as soon as any of these holds
+    pub lose_conditions: Vec<Condition>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MapDefinition {
+    pub name: String,
+    pub width: f32,
+    pub height: f32,
+    // Image under assets/ drawn behind everything
+    #[serde(default)]
+    pub background: Option<String>,
+    // Doors AM can lock, each one blocks the corridor at its x
+    #[serde(default)]
+    pub doors: Vec<Door>,
+    // Stretches of the corridor without light, being in one wears on the survivors
+    #[serde(default)]
+    pub darkness: Vec<DarkStretch>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Door {
+    pub id: String,
+    pub x: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DarkStretch {
+    pub from_x: f32,
+    pub to_x: f32,
+}
+
+impl DarkStretch {
+    pub fn contains(&self, x: f32) -> bool {
+        (self.from_x..=self.to_x).contains(&x)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpawnPoint {
+    pub character: Character,
+    pub position: Vec2,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Objective {
+    pub id: String,
+    pub description: String,
+    pub kind: ObjectiveKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ObjectiveKind {
+    // Any survivor gets within radius of the position
+    Reach { position: Vec2, radius: f32 },
+    // At least one survivor is still alive after this long
+    Survive { secs: f32 },
+}
+
+// Something AM may do to the survivors during the match
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Intervention {
+    pub id: String,
+    pub name: String,
+    pub kind: InterventionKind,
+    pub cost: f32,
+    pub cooldown_secs: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum InterventionKind {
+    // Tells survivors something, each of them possibly a different version
+    Narrate,
+    // Shows one survivor something that is not there
+    Hallucination,
+    // Survivors inside the radius move at `slow` times their speed until it wears off
+    Hazard { radius: f32, slow: f32, duration_secs: f32 },
+    // Nobody gets past the door until it opens again
+    LockDoor { duration_secs: f32 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VoteDefinition {
+    pub id: String,
+    pub question: String,
+    pub kind: VoteKind,
+    pub secs: f32,
+    pub ballot: Ballot,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum VoteKind {
+    // Pick a survivor, everyone who voted for them accuses them
+    WhoIsLying,
+    // Pick a survivor to shut out, they stop trusting whoever voted for it
+    Exclude,
+    // Pick one of the scenario's own options
+    Choice { options: Vec<VoteOption> },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VoteOption {
+    pub id: String,
+    pub label: String,
+    // What happens when this option wins
+    #[serde(default)]
+    pub effect: Option<VoteEffect>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum VoteEffect {
+    CompleteObjective(String),
+    LockDoor { door: String, secs: f32 },
+}
+
+// Public ballots show every survivor who voted for what, secret ones only the tally. AM sees both.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ballot {
+    Secret,
+    Public,
+}
+
+// AM's hate pays for interventions and slowly builds back up over the match
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct HateSettings {
+    pub start: f32,
+    pub max: f32,
+    pub regen_per_sec: f32,
+}
+
+impl Default for HateSettings {
+    fn default() -> Self {
+        Self {
+            start: 50.0,
+            max: 100.0,
+            regen_per_sec: 1.0,
+        }
+    }
+}
+
+// The server holds every survivor message for hold_secs, that is AM's window to tamper with it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ChatSettings {
+    pub hold_secs: f32,
+    // How far a proximity message carries
+    pub proximity_range: f32,
+    // How much longer a delayed message is held
+    pub delay_secs: f32,
+    pub delay_cost: f32,
+    pub drop_cost: f32,
+    pub rewrite_cost: f32,
+}
+
+impl Default for ChatSettings {
+    fn default() -> Self {
+        Self {
+            hold_secs: 2.0,
+            proximity_range: 250.0,
+            delay_secs: 8.0,
+            delay_cost: 3.0,
+            drop_cost: 8.0,
+            rewrite_cost: 15.0,
+        }
+    }
+}
+
+// How the match wears the survivors down. Each character sheet scales these for its survivor.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SanitySettings {
+    // Lost by every survivor an intervention lands on
+    pub intervention_loss: f32,
+    // Lost per second with no other survivor within company_range
+    pub isolation_per_sec: f32,
+    pub company_range: f32,
+    // Lost per second inside one of the map's dark stretches
+    pub darkness_per_sec: f32,
+    // Lost by every survivor when a vote ends without a decision
+    pub failed_vote_loss: f32,
+    // Regained per second within company_range of a survivor trusted at least trusted_above
+    pub recovery_per_sec: f32,
+    pub trusted_above: f32,
+}
+
+impl Default for SanitySettings {
+    fn default() -> Self {
+        Self {
+            intervention_loss: 5.0,
+            isolation_per_sec: 0.5,
+            company_range: 300.0,
+            darkness_per_sec: 1.0,
+            failed_vote_loss: 8.0,
+            recovery_per_sec: 1.0,
+            trusted_above: 0.6,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Narrative {
+    pub briefing: String,
+    // Lines AM can speak during the match, referred to by id
+    #[serde(default)]
+    pub lines: Vec<NarrativeLine>,
+    pub victory: String,
+    pub defeat: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NarrativeLine {
+    pub id: String,
+    pub text: String,
+}
+
+// How long each match phase lasts, in seconds. Phases left out keep their default length.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PhaseTimings {
+    pub briefing_secs: f32,
+    pub am_setup_secs: f32,
+    pub exploration_secs: f32,
+    pub confrontation_secs: f32,
+    pub resolution_secs: f32,
+    pub debrief_secs: f32,
+}
+
+impl Default for PhaseTimings {
+    fn default() -> Self {
+        Self {
+            briefing_secs: 20.0,
+            am_setup_secs: 30.0,
+            exploration_secs: 300.0,
+            confrontation_secs: 180.0,
+            resolution_secs: 60.0,
+            debrief_secs: 30.0,
+        }
+    }
+}
+
+impl PhaseTimings {
+    pub fn secs(&self, phase: MatchPhase) -> f32 {
+        match phase {
+            MatchPhase::Briefing => self.briefing_secs,
+            MatchPhase::AmSetup => self.am_setup_secs,
+            MatchPhase::Exploration => self.exploration_secs,
+            MatchPhase::Confrontation => self.confrontation_secs,
+            MatchPhase::Resolution => self.resolution_secs,
+            MatchPhase::Debrief => self.debrief_secs,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Condition {
+    ObjectiveComplete(String),
+    AllObjectivesComplete,
+    SurvivorsAlive { at_least: u32 },
+    SurvivorsDead { at_least: u32 },
+    // Every survivor of the match is dead, however many of them played
+    AllSurvivorsDead,
+    TimeElapsed { secs: f32 },
+    // Average trust between every pair of survivors, from 0 to 1
+    TrustBelow { average: f32 },
+    TrustAbove { average: f32 },
+    // A vote ended with this option winning, survivor votes use the character's name as option
+    VoteDecided { vote: String, option: String },
+}
+
+impl Scenario {
+    // Parses and validates a scenario, the asset loader and the headless server both go through here
+    pub fn from_bytes(bytes: &[u8]) -> Result<Scenario, ScenarioError> {
+        let scenario: Scenario = ron::de::from_bytes(bytes).map_err(ScenarioError::Parse)?;
+        let problems = validation::validate(&scenario);
+        if problems.is_empty() {
+            Ok(scenario)
+        } else {
+            Err(ScenarioError::Invalid(problems))
+        }
+    }
+
+    // Reads a scenario straight from disk, for the headless server which has no asset server
+    pub fn from_file(path: &Path) -> Result<Scenario, ScenarioError> {
+        Scenario::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn objective(&self, id: &str) -> Option<&Objective> {
+        self.objectives.iter().find(|objective| objective.id == id)
+    }
+
+    pub fn intervention(&self, id: &str) -> Option<&Intervention> {
+        self.interventions.iter().find(|intervention| intervention.id == id)
+    }
+
+    pub fn vote(&self, id: &str) -> Option<&VoteDefinition> {
+        self.votes.iter().find(|vote| vote.id == id)
+    }
+
+    pub fn door(&self, id: &str) -> Option<&Door> {
+        self.map.doors.iter().find(|door| door.id == id)
+    }
+
+    pub fn line(&self, id: &str) -> Option<&str> {
+        self.narrative.lines.iter().find(|line| line.id == id).map(|line| line.text.as_str())
+    }
+
+    pub fn sheet(&self, character: Character) -> Option<&CharacterSheet> {
+        self.characters.get(&character)
+    }
+
+    pub fn spawn_point(&self, character: Character) -> Option<Vec2> {
+        self.spawn_points
+            .iter()
+            .find(|spawn| spawn.character == character)
+            .map(|spawn| spawn.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::components::appearance::SpriteSet;
+
+    #[test]
+    fn every_shipped_scenario_loads() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/scenarios");
+        let mut loaded = 0;
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if !path.to_string_lossy().ends_with(".scenario.ron") {
+                continue;
+            }
+            if let Err(err) = Scenario::from_bytes(&std::fs::read(&path).unwrap()) {
+                panic!("{}: {}", path.display(), err);
+            }
+            loaded += 1;
+        }
+        assert!(loaded > 0, "no scenarios under {}", dir.display());
+    }
+
+    #[test]
+    fn every_survivor_is_drawn_with_a_shipped_sprite_set() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/scenarios/the_ice_cave.scenario.ron");
+        let scenario = Scenario::from_file(&path).unwrap();
+        let shipped = SpriteSet::discover();
+        for (character, sheet) in &scenario.characters {
+            assert!(shipped.contains(&sheet.sprites), "{} is drawn with {:?}", character.name(), sheet.sprites);
+        }
+    }
+}
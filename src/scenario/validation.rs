@@ -0,0 +1,542 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use crate::MatchPhase;
+use crate::components::character_sheet::Ability;
+use crate::components::role::Character;
+use crate::scenario::{Condition, InterventionKind, ObjectiveKind, Scenario, VoteEffect, VoteKind};
+
+// Collects every authoring mistake in a scenario, each one naming the field it is about
+pub fn validate(scenario: &Scenario) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if scenario.name.trim().is_empty() {
+        problems.push("name: must not be empty".to_string());
+    }
+
+    let map = &scenario.map;
+    if !(map.width > 0.0 && map.height > 0.0) {
+        problems.push(format!("map: size must be positive, got {} x {}", map.width, map.height));
+    }
+    let on_map = |x: f32, y: f32| x.abs() <= map.width / 2.0 && y.abs() <= map.height / 2.0;
+
+    let mut door_ids = HashSet::new();
+    for (index, door) in map.doors.iter().enumerate() {
+        check_id(&mut problems, &mut door_ids, "map.doors", index, &door.id);
+        if !on_map(door.x, 0.0) {
+            problems.push(format!("map.doors[{}]: x {} is outside the map", index, door.x));
+        }
+    }
+
+    for (index, stretch) in map.darkness.iter().enumerate() {
+        if stretch.from_x.partial_cmp(&stretch.to_x) != Some(Ordering::Less) {
+            problems.push(format!("map.darkness[{}]: from_x must be left of to_x", index));
+        }
+        if !on_map(stretch.from_x, 0.0) || !on_map(stretch.to_x, 0.0) {
+            problems.push(format!("map.darkness[{}]: the stretch reaches outside the map", index));
+        }
+    }
+
+    if scenario.spawn_points.is_empty() {
+        problems.push("spawn_points: at least one survivor needs somewhere to start".to_string());
+    }
+    let mut spawned = HashSet::new();
+    for (index, spawn) in scenario.spawn_points.iter().enumerate() {
+        if !spawned.insert(spawn.character) {
+            problems.push(format!(
+                "spawn_points[{}]: {} already has a spawn point",
+                index,
+                spawn.character.name()
+            ));
+        }
+        if !on_map(spawn.position.x, spawn.position.y) {
+            problems.push(format!("spawn_points[{}]: {} is outside the map", index, spawn.position));
+        }
+    }
+
+    for character in Character::ALL {
+        let Some(sheet) = scenario.sheet(character) else {
+            problems.push(format!("characters: {} has no character sheet", character.name()));
+            continue;
+        };
+        let field = format!("characters.{}", character.name());
+        for (name, value) in [("max_sanity", sheet.max_sanity), ("max_health", sheet.max_health), ("speed", sheet.speed)] {
+            if !value.is_finite() || value <= 0.0 {
+                problems.push(format!("{}: {} must be positive, got {}", field, name, value));
+            }
+        }
+        for (name, value) in [("breaking_point", sheet.breaking_point), ("perception", sheet.perception)] {
+            if !(0.0..=1.0).contains(&value) {
+                problems.push(format!("{}: {} must be between 0 and 1, got {}", field, name, value));
+            }
+        }
+        for (name, value) in [("isolation_fear", sheet.isolation_fear), ("darkness_fear", sheet.darkness_fear)] {
+            if !value.is_finite() || value < 0.0 {
+                problems.push(format!("{}: {} cannot be negative", field, name));
+            }
+        }
+        if sheet.sprites.0.trim().is_empty() {
+            problems.push(format!("{}: sprites must name a sprite set", field));
+        }
+        let (name, value) = match sheet.ability {
+            Ability::Apathy { sanity_loss_scale } => ("sanity_loss_scale", sanity_loss_scale),
+            Ability::TrustBonus { amount } => ("amount", amount),
+            Ability::MemoryGaps { chance } => ("chance", chance),
+            Ability::Paranoia { insight } => ("insight", insight),
+            Ability::Strength { attack_multiplier } => {
+                if !attack_multiplier.is_finite() || attack_multiplier <= 0.0 {
+                    problems.push(format!("{}: attack_multiplier must be positive, got {}", field, attack_multiplier));
+                }
+                continue;
+            }
+        };
+        if !(0.0..=1.0).contains(&value) {
+            problems.push(format!("{}: {} must be between 0 and 1, got {}", field, name, value));
+        }
+    }
+
+    let mut objective_ids = HashSet::new();
+    for (index, objective) in scenario.objectives.iter().enumerate() {
+        check_id(&mut problems, &mut objective_ids, "objectives", index, &objective.id);
+        match &objective.kind {
+            ObjectiveKind::Reach { position, radius } => {
+                if !on_map(position.x, position.y) {
+                    problems.push(format!("objectives[{}]: {} is outside the map", index, position));
+                }
+                if !radius.is_finite() || *radius <= 0.0 {
+                    problems.push(format!("objectives[{}]: radius must be positive", index));
+                }
+            }
+            ObjectiveKind::Survive { secs } if !secs.is_finite() || *secs <= 0.0 => {
+                problems.push(format!("objectives[{}]: secs must be positive", index));
+            }
+            _ => {}
+        }
+    }
+
+    let mut intervention_ids = HashSet::new();
+    for (index, intervention) in scenario.interventions.iter().enumerate() {
+        check_id(&mut problems, &mut intervention_ids, "interventions", index, &intervention.id);
+        if !intervention.cost.is_finite() || intervention.cost < 0.0 {
+            problems.push(format!("interventions[{}]: cost cannot be negative", index));
+        }
+        if !intervention.cooldown_secs.is_finite() || intervention.cooldown_secs < 0.0 {
+            problems.push(format!("interventions[{}]: cooldown_secs cannot be negative", index));
+        }
+        if intervention.cost > scenario.hate.max {
+            problems.push(format!(
+                "interventions[{}]: cost {} is more hate than AM can hold ({})",
+                index, intervention.cost, scenario.hate.max
+            ));
+        }
+        match intervention.kind {
+            InterventionKind::Hazard { radius, slow, duration_secs } => {
+                if !radius.is_finite() || radius <= 0.0 {
+                    problems.push(format!("interventions[{}]: radius must be positive", index));
+                }
+                if !(0.0..=1.0).contains(&slow) {
+                    problems.push(format!("interventions[{}]: slow must be between 0 and 1, got {}", index, slow));
+                }
+                if !duration_secs.is_finite() || duration_secs <= 0.0 {
+                    problems.push(format!("interventions[{}]: duration_secs must be positive", index));
+                }
+            }
+            InterventionKind::LockDoor { duration_secs } => {
+                if !duration_secs.is_finite() || duration_secs <= 0.0 {
+                    problems.push(format!("interventions[{}]: duration_secs must be positive", index));
+                }
+                if map.doors.is_empty() {
+                    problems.push(format!("interventions[{}]: the map has no doors to lock", index));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let hate = &scenario.hate;
+    if !hate.max.is_finite() || hate.max <= 0.0 {
+        problems.push(format!("hate: max must be positive, got {}", hate.max));
+    }
+    if !(0.0..=hate.max).contains(&hate.start) {
+        problems.push(format!("hate: start must be between 0 and max, got {}", hate.start));
+    }
+    if !hate.regen_per_sec.is_finite() || hate.regen_per_sec < 0.0 {
+        problems.push("hate: regen_per_sec cannot be negative".to_string());
+    }
+
+    let chat = &scenario.chat;
+    if !chat.hold_secs.is_finite() || chat.hold_secs < 0.0 {
+        problems.push("chat: hold_secs cannot be negative".to_string());
+    }
+    if !chat.proximity_range.is_finite() || chat.proximity_range <= 0.0 {
+        problems.push(format!("chat: proximity_range must be positive, got {}", chat.proximity_range));
+    }
+    if !chat.delay_secs.is_finite() || chat.delay_secs <= 0.0 {
+        problems.push(format!("chat: delay_secs must be positive, got {}", chat.delay_secs));
+    }
+    for (name, cost) in [("delay_cost", chat.delay_cost), ("drop_cost", chat.drop_cost), ("rewrite_cost", chat.rewrite_cost)] {
+        if !cost.is_finite() || cost < 0.0 {
+            problems.push(format!("chat: {} cannot be negative", name));
+        } else if cost > hate.max {
+            problems.push(format!("chat: {} {} is more hate than AM can hold ({})", name, cost, hate.max));
+        }
+    }
+
+    let sanity = &scenario.sanity;
+    for (name, value) in [
+        ("intervention_loss", sanity.intervention_loss),
+        ("isolation_per_sec", sanity.isolation_per_sec),
+        ("darkness_per_sec", sanity.darkness_per_sec),
+        ("failed_vote_loss", sanity.failed_vote_loss),
+        ("recovery_per_sec", sanity.recovery_per_sec),
+    ] {
+        if !value.is_finite() || value < 0.0 {
+            problems.push(format!("sanity: {} cannot be negative", name));
+        }
+    }
+    if !sanity.company_range.is_finite() || sanity.company_range <= 0.0 {
+        problems.push(format!("sanity: company_range must be positive, got {}", sanity.company_range));
+    }
+    if !(0.0..=1.0).contains(&sanity.trusted_above) {
+        problems.push(format!("sanity: trusted_above must be between 0 and 1, got {}", sanity.trusted_above));
+    }
+
+    let mut vote_ids = HashSet::new();
+    for (index, vote) in scenario.votes.iter().enumerate() {
+        check_id(&mut problems, &mut vote_ids, "votes", index, &vote.id);
+        if vote.question.trim().is_empty() {
+            problems.push(format!("votes[{}]: question must not be empty", index));
+        }
+        if !vote.secs.is_finite() || vote.secs <= 0.0 {
+            problems.push(format!("votes[{}]: secs must be positive", index));
+        }
+        let VoteKind::Choice { options } = &vote.kind else {
+            continue;
+        };
+        if options.len() < 2 {
+            problems.push(format!("votes[{}]: a choice needs at least two options", index));
+        }
+        let mut option_ids = HashSet::new();
+        for (option_index, option) in options.iter().enumerate() {
+            let field = format!("votes[{}].options", index);
+            check_id(&mut problems, &mut option_ids, &field, option_index, &option.id);
+            match &option.effect {
+                Some(VoteEffect::CompleteObjective(id)) if scenario.objective(id).is_none() => {
+                    problems.push(format!("{}[{}]: no objective has the id \"{}\"", field, option_index, id));
+                }
+                Some(VoteEffect::LockDoor { door, secs }) => {
+                    if scenario.door(door).is_none() {
+                        problems.push(format!("{}[{}]: no door has the id \"{}\"", field, option_index, door));
+                    }
+                    if !secs.is_finite() || *secs <= 0.0 {
+                        problems.push(format!("{}[{}]: secs must be positive", field, option_index));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut line_ids = HashSet::new();
+    for (index, line) in scenario.narrative.lines.iter().enumerate() {
+        check_id(&mut problems, &mut line_ids, "narrative.lines", index, &line.id);
+    }
+
+    let mut phase = Some(MatchPhase::Briefing);
+    while let Some(current) = phase {
+        let secs = scenario.phases.secs(current);
+        if !secs.is_finite() || secs <= 0.0 {
+            problems.push(format!("phases: {} must last longer than 0 seconds, got {}", current.name(), secs));
+        }
+        phase = current.next();
+    }
+
+    for (field, conditions) in [
+        ("win_conditions", &scenario.win_conditions),
+        ("lose_conditions", &scenario.lose_conditions),
+    ] {
+        if conditions.is_empty() {
+            problems.push(format!("{}: at least one condition is needed or the match never ends", field));
+        }
+        for (index, condition) in conditions.iter().enumerate() {
+            match condition {
+                Condition::ObjectiveComplete(id) if !objective_ids.contains(id.as_str()) => {
+                    problems.push(format!("{}[{}]: no objective has the id \"{}\"", field, index, id));
+                }
+                Condition::AllObjectivesComplete if scenario.objectives.is_empty() => {
+                    problems.push(format!("{}[{}]: there are no objectives to complete", field, index));
+                }
+                Condition::SurvivorsAlive { at_least } | Condition::SurvivorsDead { at_least }
+                    if *at_least as usize > Character::ALL.len() =>
+                {
+                    problems.push(format!(
+                        "{}[{}]: there are never more than {} survivors, got {}",
+                        field,
+                        index,
+                        Character::ALL.len(),
+                        at_least
+                    ));
+                }
+                Condition::TimeElapsed { secs } if !secs.is_finite() || *secs <= 0.0 => {
+                    problems.push(format!("{}[{}]: secs must be positive", field, index));
+                }
+                Condition::TrustBelow { average } | Condition::TrustAbove { average }
+                    if !(0.0..=1.0).contains(average) =>
+                {
+                    problems.push(format!("{}[{}]: average trust must be between 0 and 1, got {}", field, index, average));
+                }
+                Condition::VoteDecided { vote, option } => match scenario.vote(vote).map(|vote| &vote.kind) {
+                    None => problems.push(format!("{}[{}]: no vote has the id \"{}\"", field, index, vote)),
+                    Some(VoteKind::Choice { options }) if !options.iter().any(|choice| &choice.id == option) => {
+                        problems.push(format!("{}[{}]: vote \"{}\" has no option \"{}\"", field, index, vote, option));
+                    }
+                    Some(VoteKind::WhoIsLying | VoteKind::Exclude)
+                        if !Character::ALL.iter().any(|character| character.name() == option) =>
+                    {
+                        problems.push(format!("{}[{}]: \"{}\" is not a survivor", field, index, option));
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+
+    problems
+}
+
+fn check_id<'a>(problems: &mut Vec<String>, seen: &mut HashSet<&'a str>, field: &str, index: usize, id: &'a str) {
+    if id.trim().is_empty() {
+        problems.push(format!("{}[{}]: id must not be empty", field, index));
+    } else if !seen.insert(id) {
+        problems.push(format!("{}[{}]: id \"{}\" is used twice", field, index, id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bevy::math::Vec2;
+
+    use super::*;
+    use crate::scenario::SanitySettings;
+
+    fn ice_cave() -> Scenario {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/scenarios/the_ice_cave.scenario.ron");
+        ron::de::from_bytes(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    fn reports(scenario: &Scenario, expected: &str) -> bool {
+        let problems = validate(scenario);
+        problems.iter().any(|problem| problem.contains(expected))
+    }
+
+    #[test]
+    fn shipped_scenario_has_no_problems() {
+        assert_eq!(validate(&ice_cave()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_ids_used_twice() {
+        let mut scenario = ice_cave();
+        let copy = scenario.objectives[0].clone();
+        scenario.objectives.push(copy);
+        let index = scenario.objectives.len() - 1;
+        assert!(reports(&scenario, &format!("objectives[{}]: id \"reach_cache\" is used twice", index)));
+    }
+
+    #[test]
+    fn reports_conditions_on_unknown_objectives() {
+        let mut scenario = ice_cave();
+        scenario.win_conditions.push(Condition::ObjectiveComplete("nowhere".to_string()));
+        assert!(reports(&scenario, "no objective has the id \"nowhere\""));
+    }
+
+    #[test]
+    fn reports_places_off_the_map() {
+        let mut scenario = ice_cave();
+        scenario.spawn_points[0].position.x = 5000.0;
+        scenario.objectives[0].kind = ObjectiveKind::Reach {
+            position: Vec2::new(0.0, -5000.0),
+            radius: 0.0,
+        };
+        assert!(reports(&scenario, "spawn_points[0]: [5000, 0] is outside the map"));
+        assert!(reports(&scenario, "objectives[0]: [0, -5000] is outside the map"));
+        assert!(reports(&scenario, "objectives[0]: radius must be positive"));
+    }
+
+    #[test]
+    fn reports_objectives_and_conditions_that_are_not_numbers() {
+        let mut scenario = ice_cave();
+        scenario.objectives[0].kind = ObjectiveKind::Reach {
+            position: Vec2::ZERO,
+            radius: f32::NAN,
+        };
+        scenario.objectives[1].kind = ObjectiveKind::Survive { secs: f32::INFINITY };
+        scenario.lose_conditions = vec![Condition::TimeElapsed { secs: f32::NAN }];
+        assert!(reports(&scenario, "objectives[0]: radius must be positive"));
+        assert!(reports(&scenario, "objectives[1]: secs must be positive"));
+        assert!(reports(&scenario, "lose_conditions[0]: secs must be positive"));
+    }
+
+    #[test]
+    fn reports_phases_without_length() {
+        let mut scenario = ice_cave();
+        scenario.phases.exploration_secs = 0.0;
+        scenario.phases.debrief_secs = f32::NAN;
+        assert!(reports(&scenario, "phases: Exploration must last longer than 0 seconds"));
+        assert!(reports(&scenario, "phases: Debrief must last longer than 0 seconds"));
+    }
+
+    #[test]
+    fn reports_interventions_am_can_never_afford() {
+        let mut scenario = ice_cave();
+        scenario.interventions[0].cost = scenario.hate.max + 1.0;
+        scenario.interventions[1].cooldown_secs = -1.0;
+        assert!(reports(&scenario, "interventions[0]: cost 101 is more hate than AM can hold (100)"));
+        assert!(reports(&scenario, "interventions[1]: cooldown_secs cannot be negative"));
+    }
+
+    #[test]
+    fn reports_hate_out_of_range() {
+        let mut scenario = ice_cave();
+        scenario.hate.start = scenario.hate.max * 2.0;
+        scenario.hate.regen_per_sec = -1.0;
+        assert!(reports(&scenario, "hate: start must be between 0 and max"));
+        assert!(reports(&scenario, "hate: regen_per_sec cannot be negative"));
+    }
+
+    #[test]
+    fn reports_hate_numbers_that_are_not_finite() {
+        let mut scenario = ice_cave();
+        scenario.interventions[0].cost = f32::NAN;
+        scenario.interventions[1].cooldown_secs = f32::INFINITY;
+        scenario.interventions[2].kind = InterventionKind::Hazard {
+            radius: f32::NAN,
+            slow: 0.5,
+            duration_secs: f32::INFINITY,
+        };
+        scenario.interventions[3].kind = InterventionKind::LockDoor { duration_secs: f32::NAN };
+        scenario.hate.regen_per_sec = f32::NAN;
+        assert!(reports(&scenario, "interventions[0]: cost cannot be negative"));
+        assert!(reports(&scenario, "interventions[1]: cooldown_secs cannot be negative"));
+        assert!(reports(&scenario, "interventions[2]: radius must be positive"));
+        assert!(reports(&scenario, "interventions[2]: duration_secs must be positive"));
+        assert!(reports(&scenario, "interventions[3]: duration_secs must be positive"));
+        assert!(reports(&scenario, "hate: regen_per_sec cannot be negative"));
+    }
+
+    #[test]
+    fn reports_trust_outside_0_and_1() {
+        let mut scenario = ice_cave();
+        scenario.lose_conditions.push(Condition::TrustBelow { average: 1.5 });
+        let index = scenario.lose_conditions.len() - 1;
+        assert!(reports(
+            &scenario,
+            &format!("lose_conditions[{}]: average trust must be between 0 and 1, got 1.5", index)
+        ));
+    }
+
+    #[test]
+    fn reports_conditions_on_unknown_votes() {
+        let mut scenario = ice_cave();
+        scenario.win_conditions.push(Condition::VoteDecided {
+            vote: "nowhere".to_string(),
+            option: "yes".to_string(),
+        });
+        assert!(reports(&scenario, "no vote has the id \"nowhere\""));
+    }
+
+    #[test]
+    fn reports_vote_doors_locked_for_no_time() {
+        let mut scenario = ice_cave();
+        let VoteKind::Choice { options } = &mut scenario.votes[2].kind else {
+            panic!("the path vote is a choice");
+        };
+        options[0].effect = Some(VoteEffect::LockDoor {
+            door: "cache_gate".to_string(),
+            secs: f32::NAN,
+        });
+        assert!(reports(&scenario, "votes[2].options[0]: secs must be positive"));
+    }
+
+    #[test]
+    fn reports_chat_tampering_am_can_never_afford() {
+        let mut scenario = ice_cave();
+        scenario.chat.drop_cost = scenario.hate.max + 1.0;
+        scenario.chat.hold_secs = -1.0;
+        assert!(reports(&scenario, "chat: drop_cost 101 is more hate than AM can hold (100)"));
+        assert!(reports(&scenario, "chat: hold_secs cannot be negative"));
+    }
+
+    #[test]
+    fn reports_chat_numbers_that_are_not_finite() {
+        let mut scenario = ice_cave();
+        scenario.chat.hold_secs = f32::NAN;
+        scenario.chat.proximity_range = f32::INFINITY;
+        scenario.chat.delay_secs = f32::NAN;
+        scenario.chat.rewrite_cost = f32::NAN;
+        assert!(reports(&scenario, "chat: hold_secs cannot be negative"));
+        assert!(reports(&scenario, "chat: proximity_range must be positive, got inf"));
+        assert!(reports(&scenario, "chat: delay_secs must be positive, got NaN"));
+        assert!(reports(&scenario, "chat: rewrite_cost cannot be negative"));
+    }
+
+    #[test]
+    fn reports_backwards_dark_stretches() {
+        let mut scenario = ice_cave();
+        let stretch = &mut scenario.map.darkness[0];
+        std::mem::swap(&mut stretch.from_x, &mut stretch.to_x);
+        assert!(reports(&scenario, "map.darkness[0]: from_x must be left of to_x"));
+    }
+
+    #[test]
+    fn reports_negative_sanity_rates() {
+        let mut scenario = ice_cave();
+        scenario.sanity = SanitySettings {
+            isolation_per_sec: -1.0,
+            ..Default::default()
+        };
+        assert!(reports(&scenario, "sanity: isolation_per_sec cannot be negative"));
+    }
+
+    #[test]
+    fn reports_sanity_rates_that_are_not_finite() {
+        let mut scenario = ice_cave();
+        scenario.sanity.darkness_per_sec = f32::NAN;
+        scenario.sanity.recovery_per_sec = f32::INFINITY;
+        scenario.sanity.company_range = f32::NAN;
+        assert!(reports(&scenario, "sanity: darkness_per_sec cannot be negative"));
+        assert!(reports(&scenario, "sanity: recovery_per_sec cannot be negative"));
+        assert!(reports(&scenario, "sanity: company_range must be positive, got NaN"));
+    }
+
+    #[test]
+    fn reports_survivors_without_a_sheet() {
+        let mut scenario = ice_cave();
+        scenario.characters.remove(&Character::Ted);
+        assert!(reports(&scenario, "characters: Ted has no character sheet"));
+    }
+
+    #[test]
+    fn reports_sheet_values_out_of_range() {
+        let mut scenario = ice_cave();
+        let sheet = scenario.characters.get_mut(&Character::Benny).unwrap();
+        sheet.breaking_point = 1.5;
+        sheet.darkness_fear = -1.0;
+        sheet.speed = 0.0;
+        assert!(reports(&scenario, "characters.Benny: breaking_point must be between 0 and 1"));
+        assert!(reports(&scenario, "characters.Benny: darkness_fear cannot be negative"));
+        assert!(reports(&scenario, "characters.Benny: speed must be positive"));
+        scenario.characters.get_mut(&Character::Ted).unwrap().sprites.0.clear();
+        assert!(reports(&scenario, "characters.Ted: sprites must name a sprite set"));
+    }
+
+    #[test]
+    fn rejects_more_deaths_than_survivors() {
+        let mut scenario = ice_cave();
+        scenario.lose_conditions.push(Condition::SurvivorsDead { at_least: 6 });
+        assert!(reports(&scenario, "there are never more than 5 survivors"));
+    }
+}
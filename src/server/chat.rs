@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetServer};
+
+use crate::MatchPhase;
+use crate::components::role::Role;
+use crate::protocol::{
+    self, ActionError, ChatChannel, ChatId, ChatRecord, ChatTamper, ClientMessage, MatchEventKind, RoomPhase,
+    ServerMessage, MAX_CHAT_CHARS,
+};
+use crate::scenario::Scenario;
+use crate::server::rooms::Room;
+use crate::server::{illusions, interventions, match_log, trust};
+use crate::server::{FromClient, RoomRegistry, ServerPlayers, ServerScenario};
+
+// Shortest gap between two messages from the same survivor
+const MIN_CHAT_GAP_SECS: f32 = 0.5;
+
+// Every survivor message of the match, the originals are never overwritten
+#[derive(Default)]
+pub struct ChatLog {
+    pub records: Vec<ChatRecord>,
+    // Messages the server still holds and the match time they go out
+    held: Vec<(ChatId, f32)>,
+    last_sent: HashMap<ClientId, f32>,
+    next_id: u32,
+    pub revealed: bool,
+}
+
+// Moderation every text goes through before anyone but AM reads it, AM's rewrites included
+fn moderate(text: &str) -> Result<String, ActionError> {
+    let text: String = text.chars().filter(|c| !c.is_control()).collect();
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(ActionError::EmptyChat);
+    }
+    if text.chars().count() > MAX_CHAT_CHARS {
+        return Err(ActionError::ChatTooLong);
+    }
+    Ok(text.to_string())
+}
+
+// System to take in survivor messages and AM's tampering with them
+pub(super) fn handle_chat_messages(
+    mut from_client: EventReader<FromClient>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    scenario: Res<ServerScenario>,
+    players: Res<ServerPlayers>,
+) {
+    for FromClient { client_id, message } in from_client.read() {
+        let client_id = *client_id;
+        let Some(room_id) = registry.membership.get(&client_id).copied() else {
+            continue;
+        };
+        let Some(room) = registry.rooms.get_mut(&room_id) else {
+            continue;
+        };
+        let result = match message {
+            ClientMessage::Chat { channel, text } => receive_chat(&mut server, room, client_id, *channel, text, &scenario.0),
+            ClientMessage::TamperChat { id, tamper } => {
+                tamper_chat(&mut server, room, client_id, *id, tamper, &scenario.0, &players)
+            }
+            _ => continue,
+        };
+        if let Err(error) = result {
+            protocol::send_to_client(&mut server, client_id, &ServerMessage::ActionError(error));
+        }
+    }
+}
+
+fn receive_chat(
+    server: &mut RenetServer,
+    room: &mut Room,
+    client_id: ClientId,
+    channel: ChatChannel,
+    text: &str,
+    scenario: &Scenario,
+) -> Result<(), ActionError> {
+    if room.phase != RoomPhase::InProgress {
+        return Err(ActionError::MatchNotRunning);
+    }
+    if !matches!(room.roles.get(&client_id), Some(Role::Survivor(_))) {
+        return Err(ActionError::NotSurvivor);
+    }
+    if let ChatChannel::Whisper(to) = channel {
+        let to = ClientId::from_raw(to);
+        if to == client_id || !matches!(room.roles.get(&to), Some(Role::Survivor(_))) {
+            return Err(ActionError::UnknownRecipient);
+        }
+    }
+    let now = room.clock.as_ref().map_or(0.0, |clock| clock.elapsed);
+    if room.chat.last_sent.get(&client_id).map_or(false, |at| now - at < MIN_CHAT_GAP_SECS) {
+        return Err(ActionError::TalkingTooFast);
+    }
+    let text = moderate(text)?;
+    room.chat.last_sent.insert(client_id, now);
+
+    let id = ChatId(room.chat.next_id);
+    room.chat.next_id += 1;
+    room.chat.records.push(ChatRecord {
+        id,
+        at_secs: now,
+        from: client_id.raw(),
+        channel,
+        original: text.clone(),
+        text: Some(text),
+        tampering: Vec::new(),
+        recipients: Vec::new(),
+        delivered: false,
+    });
+    room.chat.held.push((id, now + scenario.chat.hold_secs));
+    notify_am(server, room, id);
+    Ok(())
+}
+
+fn tamper_chat(
+    server: &mut RenetServer,
+    room: &mut Room,
+    client_id: ClientId,
+    id: ChatId,
+    tamper: &ChatTamper,
+    scenario: &Scenario,
+    players: &ServerPlayers,
+) -> Result<(), ActionError> {
+    illusions::check_am(room, client_id)?;
+    let settings = &scenario.chat;
+    let held = room.chat.held.iter().position(|(held, _)| *held == id).ok_or(ActionError::ChatGone)?;
+    let cost = match tamper {
+        ChatTamper::Delay => settings.delay_cost,
+        ChatTamper::Drop => settings.drop_cost,
+        ChatTamper::Rewrite(_) => settings.rewrite_cost,
+    };
+    if room.hate < cost {
+        return Err(ActionError::NotEnoughHate);
+    }
+    let Some(record) = room.chat.records.iter_mut().find(|record| record.id == id) else {
+        return Err(ActionError::ChatGone);
+    };
+    let from = record.from;
+    // Rewrites are kept the way they went through moderation
+    let tamper = match tamper {
+        ChatTamper::Delay => {
+            room.chat.held[held].1 += settings.delay_secs;
+            ChatTamper::Delay
+        }
+        ChatTamper::Drop => {
+            room.chat.held.remove(held);
+            record.text = None;
+            ChatTamper::Drop
+        }
+        ChatTamper::Rewrite(text) => {
+            let text = moderate(text)?;
+            record.text = Some(text.clone());
+            ChatTamper::Rewrite(text)
+        }
+    };
+    record.tampering.push(tamper.clone());
+    room.hate -= cost;
+    match_log::record(room, MatchEventKind::ChatTampered { from, tamper, cost });
+    notify_am(server, room, id);
+    if let Some(am) = room.am() {
+        protocol::send_to_client(server, am, &interventions::status_message(room, scenario, players));
+    }
+    Ok(())
+}
+
+// Who hears a message at the moment it goes out
+fn hearers(room: &Room, players: &ServerPlayers, from: ClientId, channel: ChatChannel, range: f32) -> Vec<ClientId> {
+    match channel {
+        ChatChannel::Public => trust::survivors(room).into_iter().filter(|survivor| *survivor != from).collect(),
+        ChatChannel::Whisper(to) => vec![ClientId::from_raw(to)],
+        ChatChannel::Proximity => {
+            let Some(position) = players.0.get(&from).map(|player| player.position) else {
+                return Vec::new();
+            };
+            trust::survivors(room)
+                .into_iter()
+                .filter(|survivor| *survivor != from)
+                .filter(|survivor| {
+                    players
+                        .0
+                        .get(survivor)
+                        .map_or(false, |player| player.position.distance(position) <= range)
+                })
+                .collect()
+        }
+    }
+}
+
+// System to let held messages go out once their time is up
+pub(super) fn deliver_chat(
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    scenario: Res<ServerScenario>,
+    players: Res<ServerPlayers>,
+) {
+    let range = scenario.0.chat.proximity_range;
+    for room in registry.rooms.values_mut().filter(|room| room.phase == RoomPhase::InProgress) {
+        let now = room.clock.as_ref().map_or(0.0, |clock| clock.elapsed);
+        let due: Vec<ChatId> = room.chat.held.iter().filter(|(_, at)| *at <= now).map(|(id, _)| *id).collect();
+        if due.is_empty() {
+            continue;
+        }
+        room.chat.held.retain(|(_, at)| *at > now);
+        for id in due {
+            let Some(index) = room.chat.records.iter().position(|record| record.id == id) else {
+                continue;
+            };
+            let (from, channel) = (room.chat.records[index].from, room.chat.records[index].channel);
+            let recipients = hearers(room, &players, ClientId::from_raw(from), channel, range);
+            let record = &mut room.chat.records[index];
+            if let Some(text) = &record.text {
+                let message = ServerMessage::Chat {
+                    from,
+                    channel,
+                    text: text.clone(),
+                };
+                for recipient in &recipients {
+                    protocol::send_to_client(&mut server, *recipient, &message);
+                }
+            }
+            record.recipients = recipients.iter().map(|recipient| recipient.raw()).collect();
+            record.delivered = true;
+            notify_am(&mut server, room, id);
+        }
+    }
+}
+
+fn notify_am(server: &mut RenetServer, room: &Room, id: ChatId) {
+    let (Some(am), Some(record)) = (room.am(), room.chat.records.iter().find(|record| record.id == id)) else {
+        return;
+    };
+    protocol::send_to_client(server, am, &ServerMessage::AmChat(record.clone()));
+}
+
+pub(super) fn am_chat_messages(room: &Room) -> Vec<ServerMessage> {
+    room.chat.records.iter().cloned().map(ServerMessage::AmChat).collect()
+}
+
+pub(super) fn reveal_message(room: &Room) -> ServerMessage {
+    ServerMessage::ChatReveal(room.chat.records.clone())
+}
+
+// System to show everyone what they really said to each other once the debrief starts
+pub(super) fn reveal_chat(mut server: ResMut<RenetServer>, mut registry: ResMut<RoomRegistry>) {
+    for room in registry.rooms.values_mut() {
+        let debriefing = room.clock.as_ref().map_or(false, |clock| clock.phase == MatchPhase::Debrief);
+        if room.phase != RoomPhase::InProgress || !debriefing || room.chat.revealed {
+            continue;
+        }
+        room.chat.revealed = true;
+        let message = reveal_message(room);
+        for player in &room.players {
+            protocol::send_to_client(&mut server, *player, &message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::simulation;
+    use crate::server::testing::{ice_cave, received, send, server_app, Inboxes};
+
+    const HOLD_SECS: f32 = 2.5;
+
+    // Player 1 plays AM to survivors 2 to 5, who spawn 100 apart with 220 of proximity range
+    fn chat_app() -> (App, Inboxes) {
+        let (mut app, inboxes) = server_app(&[1, 2, 3, 4, 5]);
+        let mut registry = RoomRegistry::default();
+        registry.running_for_test(&[1, 2, 3, 4, 5]);
+        app.insert_resource(registry)
+            .insert_resource(ServerScenario(ice_cave()))
+            .init_resource::<ServerPlayers>()
+            .add_systems(Update, (simulation::sync_server_players, handle_chat_messages, deliver_chat).chain());
+        app.update();
+        (app, inboxes)
+    }
+
+    fn room(app: &mut App) -> &mut Room {
+        app.world_mut().resource_mut::<RoomRegistry>().into_inner().rooms.values_mut().next().unwrap()
+    }
+
+    fn say(app: &mut App, client: u64, channel: ChatChannel, text: &str) {
+        send(app, client, ClientMessage::Chat { channel, text: text.to_string() });
+        app.update();
+    }
+
+    fn tamper(app: &mut App, client: u64, id: u32, tamper: ChatTamper) {
+        send(app, client, ClientMessage::TamperChat { id: ChatId(id), tamper });
+        app.update();
+    }
+
+    // Lets match time pass, which is what held messages wait for
+    fn pass(app: &mut App, secs: f32) {
+        room(app).clock.as_mut().unwrap().elapsed += secs;
+        app.update();
+    }
+
+    fn heard(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<(u64, String)> {
+        received(app, inboxes, client)
+            .into_iter()
+            .filter_map(|message| match message {
+                ServerMessage::Chat { from, text, .. } => Some((from, text)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn errors(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<ActionError> {
+        received(app, inboxes, client)
+            .into_iter()
+            .filter_map(|message| match message {
+                ServerMessage::ActionError(error) => Some(error),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn line(from: u64, text: &str) -> (u64, String) {
+        (from, text.to_string())
+    }
+
+    #[test]
+    fn every_channel_reaches_only_its_hearers() {
+        let (mut app, mut inboxes) = chat_app();
+        say(&mut app, 2, ChatChannel::Whisper(4), "Benny took it");
+        say(&mut app, 3, ChatChannel::Public, "Keep moving");
+        say(&mut app, 5, ChatChannel::Proximity, "Is anyone there?");
+        pass(&mut app, HOLD_SECS);
+
+        assert_eq!(heard(&mut app, &mut inboxes, 2), [line(3, "Keep moving")]);
+        assert_eq!(heard(&mut app, &mut inboxes, 3), [line(5, "Is anyone there?")]);
+        assert_eq!(
+            heard(&mut app, &mut inboxes, 4),
+            [line(2, "Benny took it"), line(3, "Keep moving"), line(5, "Is anyone there?")]
+        );
+        assert_eq!(heard(&mut app, &mut inboxes, 5), [line(3, "Keep moving")]);
+        // AM reads everything in its own feed, never as a hearer
+        assert!(heard(&mut app, &mut inboxes, 1).is_empty());
+        let recipients: Vec<_> = room(&mut app).chat.records.iter().map(|record| record.recipients.clone()).collect();
+        assert_eq!(recipients, [vec![4], vec![2, 4, 5], vec![3, 4]]);
+    }
+
+    #[test]
+    fn messages_wait_out_the_hold_before_anyone_hears_them() {
+        let (mut app, mut inboxes) = chat_app();
+        say(&mut app, 2, ChatChannel::Public, "Wait for me");
+        pass(&mut app, 1.0);
+        assert!(heard(&mut app, &mut inboxes, 3).is_empty());
+        let am_feed = received(&mut app, &mut inboxes, 1);
+        assert!(matches!(&am_feed[..], [ServerMessage::AmChat(record)] if !record.delivered));
+
+        pass(&mut app, HOLD_SECS - 1.0);
+        assert_eq!(heard(&mut app, &mut inboxes, 3), [line(2, "Wait for me")]);
+    }
+
+    #[test]
+    fn am_delays_drops_and_rewrites_for_hate_and_the_original_is_kept() {
+        let (mut app, mut inboxes) = chat_app();
+        room(&mut app).hate = 40.0;
+        say(&mut app, 2, ChatChannel::Public, "The cache is this way");
+        say(&mut app, 3, ChatChannel::Public, "I trust Ellen");
+        say(&mut app, 4, ChatChannel::Public, "Stay together");
+        tamper(&mut app, 1, 0, ChatTamper::Delay);
+        tamper(&mut app, 1, 1, ChatTamper::Drop);
+        tamper(&mut app, 1, 2, ChatTamper::Rewrite("Leave Ted behind".to_string()));
+        assert_eq!(room(&mut app).hate, 40.0 - 3.0 - 8.0 - 15.0);
+
+        pass(&mut app, HOLD_SECS);
+        assert_eq!(heard(&mut app, &mut inboxes, 5), [line(4, "Leave Ted behind")]);
+        pass(&mut app, 10.0);
+        assert_eq!(heard(&mut app, &mut inboxes, 5), [line(2, "The cache is this way")]);
+
+        let records = &room(&mut app).chat.records;
+        assert_eq!(records[1].text, None);
+        assert!(!records[1].delivered);
+        assert_eq!(records[2].original, "Stay together");
+        assert_eq!(records[2].text.as_deref(), Some("Leave Ted behind"));
+    }
+
+    #[test]
+    fn refuses_chat_and_tampering_out_of_turn() {
+        let (mut app, mut inboxes) = chat_app();
+        say(&mut app, 2, ChatChannel::Whisper(1), "AM?");
+        say(&mut app, 2, ChatChannel::Public, "   ");
+        say(&mut app, 2, ChatChannel::Public, "One");
+        say(&mut app, 2, ChatChannel::Public, "Two");
+        say(&mut app, 1, ChatChannel::Public, "Hello");
+        tamper(&mut app, 3, 0, ChatTamper::Drop);
+        tamper(&mut app, 1, 0, ChatTamper::Drop);
+        pass(&mut app, HOLD_SECS);
+        room(&mut app).hate = 100.0;
+        tamper(&mut app, 1, 0, ChatTamper::Drop);
+
+        assert_eq!(
+            errors(&mut app, &mut inboxes, 2),
+            [ActionError::UnknownRecipient, ActionError::EmptyChat, ActionError::TalkingTooFast]
+        );
+        assert_eq!(errors(&mut app, &mut inboxes, 3), [ActionError::NotAm]);
+        assert_eq!(
+            errors(&mut app, &mut inboxes, 1),
+            [ActionError::NotSurvivor, ActionError::NotEnoughHate, ActionError::ChatGone]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use bevy_renet::renet::ClientId;
+
+use crate::components::character_sheet::Ability;
+use crate::components::player::PlayerState;
+use crate::components::role::Role;
+use crate::protocol::{MatchEventKind, RoomPhase};
+use crate::server::match_log;
+use crate::server::rooms::Room;
+use crate::server::{RoomRegistry, ServerPlayers};
+
+// How far a swing reaches and what it takes off
+const ATTACK_RANGE: f32 = 60.0;
+const ATTACK_DAMAGE: f32 = 10.0;
+
+// What is left of each survivor's health and who is already gone. Lives on the room,
+// a survivor who drops and comes back is still as hurt as before.
+#[derive(Default)]
+pub struct Wounds {
+    health: HashMap<ClientId, f32>,
+    dead: HashSet<ClientId>,
+}
+
+impl Wounds {
+    pub fn is_dead(&self, client_id: ClientId) -> bool {
+        self.dead.contains(&client_id)
+    }
+}
+
+// Takes health from a survivor. A hit stuns them for a moment, the last one kills them.
+pub(super) fn hurt(room: &mut Room, players: &mut ServerPlayers, client_id: ClientId, amount: f32, by: Option<ClientId>) {
+    let Some(Role::Survivor(character)) = room.roles.get(&client_id).copied() else {
+        return;
+    };
+    if room.wounds.is_dead(client_id) {
+        return;
+    }
+    let Some(max) = players.0.get(&client_id).map(|player| player.sheet.max_health) else {
+        return;
+    };
+    let health = room.wounds.health.get(&client_id).copied().unwrap_or(max) - amount;
+    room.wounds.health.insert(client_id, health.max(0.0));
+    let state = if health <= 0.0 {
+        room.wounds.dead.insert(client_id);
+        println!("{} died in room {}", character.name(), room.name);
+        let kind = MatchEventKind::Died {
+            who: client_id.raw(),
+            by: by.map(|by| by.raw()),
+        };
+        match_log::record(room, kind);
+        PlayerState::Dead
+    } else {
+        PlayerState::Hurt
+    };
+    if let Some(player) = players.0.get_mut(&client_id) {
+        player.state = state;
+        player.state_elapsed = 0.0;
+    }
+}
+
+// System to land the swings started this frame on every other survivor within reach
+pub(super) fn resolve_attacks(mut registry: ResMut<RoomRegistry>, mut players: ResMut<ServerPlayers>) {
+    let swings: Vec<ClientId> = players
+        .0
+        .iter_mut()
+        .filter(|(_, player)| player.swung)
+        .map(|(client_id, player)| {
+            player.swung = false;
+            *client_id
+        })
+        .collect();
+    for attacker in swings {
+        let Some((room_id, position, damage)) = players.0.get(&attacker).map(|player| {
+            let multiplier = match player.sheet.ability {
+                Ability::Strength { attack_multiplier } => attack_multiplier,
+                _ => 1.0,
+            };
+            (player.room, player.position, ATTACK_DAMAGE * multiplier)
+        }) else {
+            continue;
+        };
+        let Some(room) = registry.rooms.get_mut(&room_id) else {
+            continue;
+        };
+        if room.phase != RoomPhase::InProgress || room.outcome.is_some() {
+            continue;
+        }
+        let victims: Vec<ClientId> = players
+            .0
+            .iter()
+            .filter(|(client_id, player)| {
+                **client_id != attacker && player.room == room_id && player.position.distance(position) <= ATTACK_RANGE
+            })
+            .map(|(client_id, _)| *client_id)
+            .collect();
+        for victim in victims {
+            hurt(room, &mut players, victim, damage, Some(attacker));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::simulation::{self, ServerPlayer};
+    use crate::server::testing::{ice_cave, server_app};
+    use crate::server::ServerScenario;
+
+    // Player 1 plays AM to Gorrister, Benny and Ellen
+    fn combat_app() -> App {
+        let (mut app, _) = server_app(&[1, 2, 3, 4]);
+        let mut registry = RoomRegistry::default();
+        registry.running_for_test(&[1, 2, 3, 4]);
+        app.insert_resource(registry)
+            .insert_resource(ServerScenario(ice_cave()))
+            .init_resource::<ServerPlayers>()
+            .add_systems(Update, (simulation::sync_server_players, resolve_attacks).chain());
+        app.update();
+        app
+    }
+
+    fn room(app: &mut App) -> &mut Room {
+        app.world_mut().resource_mut::<RoomRegistry>().into_inner().rooms.values_mut().next().unwrap()
+    }
+
+    fn player(app: &mut App, client: u64) -> &mut ServerPlayer {
+        let players = app.world_mut().resource_mut::<ServerPlayers>().into_inner();
+        players.0.get_mut(&ClientId::from_raw(client)).unwrap()
+    }
+
+    fn swing(app: &mut App, client: u64) {
+        player(app, client).swung = true;
+        app.update();
+    }
+
+    fn health(app: &mut App, client: u64) -> Option<f32> {
+        room(app).wounds.health.get(&ClientId::from_raw(client)).copied()
+    }
+
+    fn deaths(app: &mut App) -> Vec<MatchEventKind> {
+        let events = &room(app).log.events;
+        events.iter().filter(|event| matches!(event.kind, MatchEventKind::Died { .. })).map(|event| event.kind.clone()).collect()
+    }
+
+    #[test]
+    fn a_hit_hurts_and_the_last_one_kills() {
+        let mut app = combat_app();
+        player(&mut app, 2).position = Vec2::new(-180.0, 0.0);
+        swing(&mut app, 2);
+        assert_eq!(health(&mut app, 3), Some(140.0 - ATTACK_DAMAGE));
+        assert_eq!(player(&mut app, 3).state, PlayerState::Hurt);
+        assert!(deaths(&mut app).is_empty());
+
+        room(&mut app).wounds.health.insert(ClientId::from_raw(3), ATTACK_DAMAGE);
+        swing(&mut app, 2);
+        assert_eq!(health(&mut app, 3), Some(0.0));
+        assert_eq!(player(&mut app, 3).state, PlayerState::Dead);
+        assert!(room(&mut app).wounds.is_dead(ClientId::from_raw(3)));
+        assert_eq!(deaths(&mut app), [MatchEventKind::Died { who: 3, by: Some(2) }]);
+
+        // Nobody dies twice
+        swing(&mut app, 2);
+        assert_eq!(deaths(&mut app).len(), 1);
+    }
+
+    #[test]
+    fn a_swing_only_lands_within_reach() {
+        let mut app = combat_app();
+        // Benny hits twice as hard, Gorrister is close enough and Ellen is not
+        player(&mut app, 3).position = Vec2::new(-210.0, 0.0);
+        swing(&mut app, 3);
+        assert_eq!(health(&mut app, 2), Some(100.0 - 2.0 * ATTACK_DAMAGE));
+        assert_eq!(health(&mut app, 3), None);
+        assert_eq!(health(&mut app, 4), None);
+        assert_eq!(player(&mut app, 4).state, PlayerState::Idle);
+    }
+}
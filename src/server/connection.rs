@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_renet::renet::transport::NetcodeServerTransport;
+use bevy_renet::renet::{ClientId, RenetServer, ServerEvent};
+
+use crate::protocol::{self, ClientChannel, ClientMessage, Handshake, ServerMessage};
+use crate::server::sessions::{SessionResumed, Sessions};
+use crate::server::{ServerIdentity, ServerSettings};
+
+// How long a rejected client keeps its connection so the rejection reason reaches it
+const REJECT_GRACE: Duration = Duration::from_millis(500);
+
+// A decoded message together with the client that sent it
+#[derive(Event, Debug, Clone)]
+pub struct FromClient {
+    pub client_id: ClientId,
+    pub message: ClientMessage,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub name: String,
+}
+
+// Clients that passed the handshake
+#[derive(Resource, Default)]
+pub struct ConnectedClients(pub HashMap<ClientId, ClientInfo>);
+
+// Clients told why they were refused, disconnected once the message had time to leave
+#[derive(Resource, Default)]
+pub(super) struct PendingKicks(Vec<(ClientId, Timer)>);
+
+// System to check the handshake of new clients and forget the ones that leave
+pub(super) fn handle_server_events(
+    mut server_events: EventReader<ServerEvent>,
+    mut server: ResMut<RenetServer>,
+    transport: Res<NetcodeServerTransport>,
+    mut clients: ResMut<ConnectedClients>,
+    mut pending_kicks: ResMut<PendingKicks>,
+    identity: Res<ServerIdentity>,
+    settings: Res<ServerSettings>,
+    mut sessions: ResMut<Sessions>,
+    mut resumed: EventWriter<SessionResumed>,
+) {
+    for event in server_events.read() {
+        match event {
+            ServerEvent::ClientConnected { client_id } => {
+                let handshake = match transport.user_data(*client_id) {
+                    Some(user_data) => Handshake::from_user_data(&user_data),
+                    None => Err(protocol::RejectReason::BadHandshake),
+                };
+                // A reconnecting client has to show the token of the slot it wants back, and nobody
+                // gets into a held slot without one
+                let handshake = handshake.and_then(|handshake| match handshake.session {
+                    Some(token) => sessions.resume(*client_id, token).map(|_| handshake),
+                    None if sessions.is_held(*client_id) => Err(protocol::RejectReason::SessionExpired),
+                    None => Ok(handshake),
+                });
+                match handshake {
+                    Ok(handshake) => {
+                        println!("Client {} connected as {}", client_id, handshake.name);
+                        let is_resuming = handshake.session.is_some();
+                        clients.0.insert(*client_id, ClientInfo { name: handshake.name });
+                        protocol::send_to_client(
+                            &mut server,
+                            *client_id,
+                            &ServerMessage::Welcome {
+                                client_id: client_id.raw(),
+                                server_id: identity.id,
+                                session: sessions.issue(*client_id),
+                                reconnect_grace_secs: settings.reconnect_grace_secs,
+                            },
+                        );
+                        if is_resuming {
+                            resumed.send(SessionResumed { client_id: *client_id });
+                        }
+                    }
+                    Err(reason) => {
+                        println!("Rejecting client {}: {}", client_id, reason);
+                        protocol::send_to_client(&mut server, *client_id, &ServerMessage::Rejected(reason));
+                        pending_kicks.0.push((*client_id, Timer::new(REJECT_GRACE, TimerMode::Once)));
+                    }
+                }
+            }
+            ServerEvent::ClientDisconnected { client_id, reason } => {
+                println!("Client {} disconnected: {}", client_id, reason);
+                clients.0.remove(client_id);
+            }
+        }
+    }
+}
+
+// System to drop rejected clients once their grace period is over
+pub(super) fn kick_rejected_clients(
+    time: Res<Time>,
+    mut server: ResMut<RenetServer>,
+    mut pending_kicks: ResMut<PendingKicks>,
+) {
+    pending_kicks.0.retain_mut(|(client_id, timer)| {
+        if timer.tick(time.delta()).finished() {
+            server.disconnect(*client_id);
+            false
+        } else {
+            true
+        }
+    });
+}
+
+// System to decode everything accepted clients sent this tick
+pub(super) fn receive_client_messages(
+    mut server: ResMut<RenetServer>,
+    clients: Res<ConnectedClients>,
+    mut from_client: EventWriter<FromClient>,
+) {
+    for client_id in server.clients_id() {
+        if !clients.0.contains_key(&client_id) {
+            continue;
+        }
+        for channel in [ClientChannel::Command, ClientChannel::Input] {
+            let channel: u8 = channel.into();
+            while let Some(bytes) = server.receive_message(client_id, channel) {
+                match protocol::decode::<ClientMessage>(&bytes) {
+                    Some(message) => {
+                        from_client.send(FromClient { client_id, message });
+                    }
+                    None => println!("Dropping malformed message from client {}", client_id),
+                }
+            }
+        }
+    }
+}
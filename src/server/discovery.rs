@@ -0,0 +1,139 @@
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+
+use bevy::prelude::*;
+
+use crate::consts;
+use crate::protocol::{self, DiscoveryProbe, DiscoveryReply};
+use crate::server::{ConnectedClients, RoomRegistry, ServerIdentity};
+
+// Socket answering LAN probes next to the game socket
+#[derive(Resource)]
+pub(super) struct DiscoveryResponder {
+    socket: UdpSocket,
+}
+
+impl DiscoveryResponder {
+    // Another server on this machine may already own the port, in which case it answers for everyone
+    pub(super) fn bind() -> Option<Self> {
+        let addr = SocketAddr::from(([0, 0, 0, 0], consts::DISCOVERY_PORT));
+        let socket = match UdpSocket::bind(addr) {
+            Ok(socket) => socket,
+            Err(err) => {
+                println!("LAN discovery disabled, could not bind {}: {}", addr, err);
+                return None;
+            }
+        };
+        if let Err(err) = socket.set_nonblocking(true) {
+            println!("LAN discovery disabled: {}", err);
+            return None;
+        }
+        println!("Answering LAN discovery on {}", addr);
+        Some(Self { socket })
+    }
+}
+
+// System to answer every probe received since the last tick with the public room list
+pub(super) fn answer_discovery_probes(
+    responder: Res<DiscoveryResponder>,
+    registry: Res<RoomRegistry>,
+    clients: Res<ConnectedClients>,
+    identity: Res<ServerIdentity>,
+) {
+    answer_pending_probes(&responder.socket, || DiscoveryReply {
+        protocol_id: protocol::PROTOCOL_ID,
+        version: protocol::PROTOCOL_VERSION,
+        server_id: identity.id,
+        game_port: identity.game_port,
+        rooms: registry.list(&clients),
+    });
+}
+
+// Answers every probe waiting on the socket, the reply is only put together once somebody asks
+fn answer_pending_probes(socket: &UdpSocket, reply: impl Fn() -> DiscoveryReply) {
+    let mut buffer = [0u8; 512];
+    let mut encoded = None;
+    loop {
+        let (len, from) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+            // Errors like a reset from an unreachable prober can repeat forever, try again next tick
+            Err(err) => {
+                println!("LAN discovery could not read a probe: {}", err);
+                break;
+            }
+        };
+        let Some(probe) = protocol::decode::<DiscoveryProbe>(&buffer[..len]) else {
+            continue;
+        };
+        // Builds that could not join anyway are not told about this server
+        if probe != DiscoveryProbe::default() {
+            continue;
+        }
+        let encoded = encoded.get_or_insert_with(|| protocol::encode(&reply()));
+        if let Err(err) = socket.send_to(encoded, from) {
+            println!("LAN discovery could not answer {}: {}", from, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn reply() -> DiscoveryReply {
+        DiscoveryReply {
+            protocol_id: protocol::PROTOCOL_ID,
+            version: protocol::PROTOCOL_VERSION,
+            server_id: 42,
+            game_port: 5000,
+            rooms: Vec::new(),
+        }
+    }
+
+    fn loopback_socket() -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_nonblocking(true).unwrap();
+        socket
+    }
+
+    // Answers probes and polls the prober until a reply arrives or a second passed
+    fn exchange(responder: &UdpSocket, prober: &UdpSocket) -> Option<DiscoveryReply> {
+        let mut buffer = [0u8; 16 * 1024];
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while Instant::now() < deadline {
+            answer_pending_probes(responder, reply);
+            if let Ok((len, _)) = prober.recv_from(&mut buffer) {
+                return protocol::decode(&buffer[..len]);
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        None
+    }
+
+    #[test]
+    fn answers_a_probe_on_loopback() {
+        let responder = loopback_socket();
+        let prober = loopback_socket();
+        let probe = protocol::encode(&DiscoveryProbe::default());
+        prober.send_to(&probe, responder.local_addr().unwrap()).unwrap();
+
+        assert_eq!(exchange(&responder, &prober), Some(reply()));
+    }
+
+    #[test]
+    fn ignores_probes_from_other_versions() {
+        let responder = loopback_socket();
+        let prober = loopback_socket();
+        let probe = DiscoveryProbe {
+            version: protocol::PROTOCOL_VERSION + 1,
+            ..Default::default()
+        };
+        prober.send_to(&protocol::encode(&probe), responder.local_addr().unwrap()).unwrap();
+
+        assert_eq!(exchange(&responder, &prober), None);
+    }
+}
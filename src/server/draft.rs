@@ -0,0 +1,268 @@
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetServer};
+use rand::seq::SliceRandom;
+
+use crate::components::role::{Character, Role};
+use crate::protocol::{self, ClientMessage, DraftPick, MatchEventKind, RoomError, RoomPhase, ServerMessage};
+use crate::scenario::Scenario;
+use crate::server::{interventions, match_log, phases, roles, votes};
+use crate::server::rooms::Room;
+use crate::server::{ConnectedClients, FromClient, RoomRegistry, ServerPlayers, ServerScenario};
+
+// Sheets of the scenario the room plays, in the order the draft shows the survivors
+pub(super) fn sheets_message(scenario: &Scenario) -> ServerMessage {
+    ServerMessage::CharacterSheets(
+        Character::ALL
+            .into_iter()
+            .filter_map(|character| scenario.sheet(character).map(|sheet| (character, sheet.clone())))
+            .collect(),
+    )
+}
+
+// Current picks and time left, for everyone in the room including AM
+pub(super) fn draft_message(room: &Room, clients: &ConnectedClients) -> ServerMessage {
+    let am = room.am();
+    let (seconds_left, picks) = match &room.draft {
+        Some(draft) => (
+            draft.timer.remaining_secs(),
+            survivors(room)
+                .map(|client_id| DraftPick {
+                    client_id: client_id.raw(),
+                    name: clients.0.get(&client_id).map(|info| info.name.clone()).unwrap_or_default(),
+                    character: draft.picks.get(&client_id).copied(),
+                })
+                .collect(),
+        ),
+        None => (0.0, Vec::new()),
+    };
+    ServerMessage::Draft {
+        am_client_id: am.map_or(0, |am| am.raw()),
+        seconds_left,
+        picks,
+    }
+}
+
+fn survivors(room: &Room) -> impl Iterator<Item = ClientId> + '_ {
+    room.players
+        .iter()
+        .copied()
+        .filter(|client_id| room.roles.get(client_id) != Some(&Role::Am))
+}
+
+// System to take the survivors' picks, each character goes to whoever asks first
+pub(super) fn handle_pick_messages(
+    mut from_client: EventReader<FromClient>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    clients: Res<ConnectedClients>,
+) {
+    for FromClient { client_id, message } in from_client.read() {
+        let ClientMessage::PickCharacter(character) = message else {
+            continue;
+        };
+        let client_id = *client_id;
+        let Some(room_id) = registry.membership.get(&client_id).copied() else {
+            continue;
+        };
+        let Some(room) = registry.rooms.get_mut(&room_id) else {
+            continue;
+        };
+        let is_am = room.roles.get(&client_id) == Some(&Role::Am);
+        let result = match &mut room.draft {
+            Some(draft) if room.phase == RoomPhase::Drafting && !is_am => {
+                let taken = draft
+                    .picks
+                    .iter()
+                    .any(|(other, picked)| *other != client_id && picked == character);
+                if taken {
+                    Err(RoomError::CharacterTaken)
+                } else {
+                    draft.picks.insert(client_id, *character);
+                    Ok(())
+                }
+            }
+            _ => Err(RoomError::NotDrafting),
+        };
+        match result {
+            Ok(()) => {
+                let message = draft_message(room, &clients);
+                for player in &room.players {
+                    protocol::send_to_client(&mut server, *player, &message);
+                }
+            }
+            Err(error) => protocol::send_to_client(&mut server, client_id, &ServerMessage::RoomError(error)),
+        }
+    }
+}
+
+// System to close the draft once everyone picked or time ran out, then start the match
+pub(super) fn finish_drafts(
+    time: Res<Time>,
+    scenario: Res<ServerScenario>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    clients: Res<ConnectedClients>,
+    players: Res<ServerPlayers>,
+) {
+    for room in registry.rooms.values_mut().filter(|room| room.phase == RoomPhase::Drafting) {
+        let Some(draft) = &mut room.draft else {
+            continue;
+        };
+        draft.timer.tick(time.delta());
+        let finished = draft.timer.finished();
+        let waiting: Vec<ClientId> = survivors(room)
+            .filter(|client_id| !room.draft.as_ref().is_some_and(|draft| draft.picks.contains_key(client_id)))
+            .collect();
+        if !waiting.is_empty() && !finished {
+            continue;
+        }
+        let mut picks = room.draft.take().map(|draft| draft.picks).unwrap_or_default();
+
+        // Whoever did not pick in time gets one of the characters nobody took
+        let mut free: Vec<Character> = Character::ALL
+            .into_iter()
+            .filter(|character| !picks.values().any(|picked| picked == character))
+            .collect();
+        free.shuffle(&mut rand::thread_rng());
+        for client_id in waiting {
+            if let Some(character) = free.pop() {
+                picks.insert(client_id, character);
+            }
+        }
+
+        for (client_id, character) in picks {
+            if room.players.contains(&client_id) {
+                room.roles.insert(client_id, Role::Survivor(character));
+            }
+        }
+        room.phase = RoomPhase::InProgress;
+        room.hate = scenario.0.hate.start;
+        let clock = phases::start_clock(&scenario.0);
+
+        let assignments = roles::role_assignments(room, &clients);
+        for assignment in &assignments {
+            println!("{} plays {} in room {}", assignment.name, assignment.role.name(), room.name);
+        }
+        let messages = [
+            ServerMessage::RolesAssigned(assignments),
+            phases::phase_message(&clock),
+            votes::types_message(&scenario.0),
+        ];
+        for player in &room.players {
+            for message in &messages {
+                protocol::send_to_client(&mut server, *player, message);
+            }
+        }
+        if let Some(am) = room.am() {
+            protocol::send_to_client(&mut server, am, &ServerMessage::AmScenario(scenario.0.clone()));
+            protocol::send_to_client(&mut server, am, &interventions::status_message(room, &scenario.0, &players));
+        }
+        room.clock = Some(clock);
+        match_log::record(room, MatchEventKind::PhaseStarted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::server::rooms::Draft;
+    use crate::server::testing::{ice_cave, received, send, server_app, Inboxes};
+
+    const DRAFT_SECS: f32 = 30.0;
+
+    // Player 1 plays AM, players 2 to 4 draft with the given picks already made
+    fn draft_app(picks: &[(u64, Character)]) -> (App, Inboxes) {
+        let (mut app, inboxes) = server_app(&[1, 2, 3, 4]);
+        let mut registry = RoomRegistry::default();
+        let id = registry.open_for_test(&[1, 2, 3, 4]);
+        let room = registry.rooms.get_mut(&id).unwrap();
+        room.roles.insert(ClientId::from_raw(1), Role::Am);
+        room.phase = RoomPhase::Drafting;
+        room.draft = Some(Draft {
+            picks: picks.iter().map(|(client, character)| (ClientId::from_raw(*client), *character)).collect(),
+            timer: Timer::from_seconds(DRAFT_SECS, TimerMode::Once),
+        });
+        app.init_resource::<Time>()
+            .insert_resource(registry)
+            .insert_resource(ServerScenario(ice_cave()))
+            .init_resource::<ServerPlayers>()
+            .add_systems(Update, (handle_pick_messages, finish_drafts).chain());
+        (app, inboxes)
+    }
+
+    fn wait(app: &mut App, secs: f32) {
+        app.world_mut().resource_mut::<Time>().advance_by(Duration::from_secs_f32(secs));
+        app.update();
+    }
+
+    fn room(app: &App) -> &Room {
+        app.world().resource::<RoomRegistry>().rooms.values().next().unwrap()
+    }
+
+    fn roles(app: &App) -> HashMap<u64, Role> {
+        room(app).roles.iter().map(|(client_id, role)| (client_id.raw(), *role)).collect()
+    }
+
+    fn errors(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<RoomError> {
+        received(app, inboxes, client)
+            .into_iter()
+            .filter_map(|message| match message {
+                ServerMessage::RoomError(error) => Some(error),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_character_goes_to_whoever_asks_first() {
+        let (mut app, mut inboxes) = draft_app(&[]);
+        send(&mut app, 2, ClientMessage::PickCharacter(Character::Ellen));
+        send(&mut app, 3, ClientMessage::PickCharacter(Character::Ellen));
+        send(&mut app, 1, ClientMessage::PickCharacter(Character::Ted));
+        app.update();
+        assert_eq!(room(&app).draft.as_ref().unwrap().picks.len(), 1);
+        assert!(errors(&mut app, &mut inboxes, 2).is_empty());
+        assert_eq!(errors(&mut app, &mut inboxes, 3), [RoomError::CharacterTaken]);
+        assert_eq!(errors(&mut app, &mut inboxes, 1), [RoomError::NotDrafting]);
+    }
+
+    #[test]
+    fn waits_for_the_timer_while_someone_still_has_to_pick() {
+        let (mut app, _) = draft_app(&[(2, Character::Ellen), (3, Character::Ted)]);
+        wait(&mut app, DRAFT_SECS - 1.0);
+        assert_eq!(room(&app).phase, RoomPhase::Drafting);
+    }
+
+    #[test]
+    fn starts_as_soon_as_everyone_picked() {
+        let (mut app, mut inboxes) = draft_app(&[(2, Character::Ellen), (3, Character::Ted), (4, Character::Benny)]);
+        app.update();
+        assert_eq!(room(&app).phase, RoomPhase::InProgress);
+        assert!(room(&app).clock.is_some());
+        assert_eq!(roles(&app)[&4], Role::Survivor(Character::Benny));
+        for client in 1..=4 {
+            let messages = received(&mut app, &mut inboxes, client);
+            assert!(messages.iter().any(|message| matches!(message, ServerMessage::RolesAssigned(_))));
+        }
+    }
+
+    #[test]
+    fn the_timer_hands_out_the_characters_nobody_took() {
+        for _ in 0..20 {
+            let (mut app, _) = draft_app(&[(2, Character::Ellen)]);
+            wait(&mut app, DRAFT_SECS);
+            assert_eq!(room(&app).phase, RoomPhase::InProgress);
+            let roles = roles(&app);
+            assert_eq!(roles[&1], Role::Am);
+            assert_eq!(roles[&2], Role::Survivor(Character::Ellen));
+            let (Role::Survivor(third), Role::Survivor(fourth)) = (roles[&3], roles[&4]) else {
+                panic!("players 3 and 4 should survive: {:?}", roles);
+            };
+            assert_ne!(third, fourth);
+            assert!(third != Character::Ellen && fourth != Character::Ellen);
+        }
+    }
+}
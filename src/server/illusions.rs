@@ -0,0 +1,467 @@
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetServer};
+use rand::Rng;
+
+use crate::components::character_sheet::Ability;
+use crate::components::role::Role;
+use crate::consts;
+use crate::protocol::{
+    self, ActionError, ClientMessage, Exposure, Illusion, IllusionId, IllusionKind, InteractTarget, RoomPhase,
+    ServerMessage,
+};
+use crate::scenario::Scenario;
+use crate::server::rooms::Room;
+use crate::server::{narrative, sanity};
+use crate::server::{FromClient, RoomRegistry, ServerPlayers, ServerScenario};
+
+// How close a survivor has to come to bump into a player hidden from them
+const TOUCH_RANGE: f32 = 40.0;
+
+// System to dispel AM's illusions, and to expose them when their target reaches for them
+pub(super) fn handle_illusion_messages(
+    mut from_client: EventReader<FromClient>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    scenario: Res<ServerScenario>,
+    players: Res<ServerPlayers>,
+) {
+    for FromClient { client_id, message } in from_client.read() {
+        let client_id = *client_id;
+        let Some(room_id) = registry.membership.get(&client_id).copied() else {
+            continue;
+        };
+        let Some(room) = registry.rooms.get_mut(&room_id) else {
+            continue;
+        };
+        let result = match message {
+            ClientMessage::DispelIllusion(id) => dispel(&mut server, room, client_id, *id),
+            ClientMessage::Interact(target) => {
+                interact(&mut server, room, client_id, *target, &scenario.0, &players);
+                Ok(())
+            }
+            _ => continue,
+        };
+        if let Err(error) = result {
+            protocol::send_to_client(&mut server, client_id, &ServerMessage::ActionError(error));
+        }
+    }
+}
+
+// Checks AM's request and makes the illusion, show puts it in the room
+pub(super) fn cast(
+    room: &mut Room,
+    client_id: ClientId,
+    target: u64,
+    kind: &IllusionKind,
+    exposure: &Exposure,
+    scenario: &Scenario,
+    players: &ServerPlayers,
+) -> Result<Illusion, ActionError> {
+    check_am(room, client_id)?;
+    let target_id = ClientId::from_raw(target);
+    let is_survivor = |client_id: u64| matches!(room.roles.get(&ClientId::from_raw(client_id)), Some(Role::Survivor(_)));
+    if !is_survivor(target) {
+        return Err(ActionError::UnknownRecipient);
+    }
+    match kind {
+        IllusionKind::FakeItem { item, .. } if item.trim().is_empty() => return Err(ActionError::EmptyNarrative),
+        IllusionKind::FakePlayer { of, .. } if !is_survivor(*of) => return Err(ActionError::UnknownRecipient),
+        IllusionKind::SpriteOverride { client_id, .. } | IllusionKind::HiddenPlayer { client_id }
+            if !is_survivor(*client_id) || *client_id == target =>
+        {
+            return Err(ActionError::UnknownRecipient)
+        }
+        _ => {}
+    }
+    if let Some(message) = &exposure.message {
+        narrative::resolve_text(message, scenario)?;
+    }
+
+    // Ted might see through it straight away, unless his mind is already slipping
+    let clarity = 1.0 - sanity::madness(room, players, target_id);
+    let seen_through = players.0.get(&target_id).map_or(false, |player| match player.sheet.ability {
+        Ability::Paranoia { insight } => rand::thread_rng().gen_bool((insight * clarity).clamp(0.0, 1.0) as f64),
+        _ => false,
+    });
+    room.next_illusion += 1;
+    Ok(Illusion {
+        id: IllusionId(room.next_illusion),
+        target,
+        kind: kind.clone(),
+        exposure: exposure.clone(),
+        exposures: 0,
+        seen_through,
+    })
+}
+
+pub(super) fn show(server: &mut RenetServer, room: &mut Room, illusion: Illusion) {
+    if visible_to_target(&illusion) {
+        let appeared = ServerMessage::IllusionAppeared { id: illusion.id, kind: illusion.kind.clone() };
+        protocol::send_to_client(server, ClientId::from_raw(illusion.target), &appeared);
+    }
+    room.illusions.push(illusion);
+    notify_am(server, room);
+}
+
+fn dispel(server: &mut RenetServer, room: &mut Room, client_id: ClientId, id: IllusionId) -> Result<(), ActionError> {
+    check_am(room, client_id)?;
+    let index = room.illusions.iter().position(|illusion| illusion.id == id).ok_or(ActionError::UnknownIllusion)?;
+    let illusion = room.illusions.remove(index);
+    if visible_to_target(&illusion) {
+        protocol::send_to_client(server, ClientId::from_raw(illusion.target), &ServerMessage::IllusionDispelled(id));
+    }
+    notify_am(server, room);
+    Ok(())
+}
+
+// Exposes whatever the survivor reached for, as long as it is really within reach
+fn interact(
+    server: &mut RenetServer,
+    room: &mut Room,
+    client_id: ClientId,
+    target: InteractTarget,
+    scenario: &Scenario,
+    players: &ServerPlayers,
+) {
+    let Some(player) = players.0.get(&client_id) else {
+        return;
+    };
+    let position = player.position;
+    let in_reach = |point: Vec2| point.distance(position) <= consts::INTERACT_RANGE;
+    // Far enough gone, a survivor can touch an illusion and still believe in it
+    if rand::thread_rng().gen_bool(player.sheet.deceived(sanity::madness(room, players, client_id)) as f64) {
+        return;
+    }
+    let exposed: Vec<IllusionId> = room
+        .illusions
+        .iter()
+        .filter(|illusion| illusion.target == client_id.raw() && !illusion.seen_through)
+        .filter(|illusion| match (&illusion.kind, target) {
+            (IllusionKind::FakeItem { position, .. }, InteractTarget::Illusion(id))
+            | (IllusionKind::FakePlayer { position, .. }, InteractTarget::Illusion(id)) => {
+                illusion.id == id && in_reach(*position)
+            }
+            (IllusionKind::SpriteOverride { client_id, .. }, InteractTarget::Player(other)) => {
+                *client_id == other
+                    && players
+                        .0
+                        .get(&ClientId::from_raw(other))
+                        .map_or(false, |player| in_reach(player.position))
+            }
+            _ => false,
+        })
+        .map(|illusion| illusion.id)
+        .collect();
+    for id in exposed {
+        expose(server, room, id, scenario);
+    }
+}
+
+// System to let survivors bump into the players hidden from them. Only the first bump counts,
+// an illusion AM keeps up would otherwise go off every frame.
+pub(super) fn expose_hidden_players(
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    scenario: Res<ServerScenario>,
+    players: Res<ServerPlayers>,
+) {
+    for room in registry.rooms.values_mut().filter(|room| room.phase == RoomPhase::InProgress) {
+        let position = |client_id: u64| players.0.get(&ClientId::from_raw(client_id)).map(|player| player.position);
+        let bumped: Vec<IllusionId> = room
+            .illusions
+            .iter()
+            .filter(|illusion| illusion.exposures == 0 && !illusion.seen_through)
+            .filter(|illusion| match illusion.kind {
+                IllusionKind::HiddenPlayer { client_id } => position(illusion.target)
+                    .zip(position(client_id))
+                    .map_or(false, |(target, hidden)| target.distance(hidden) <= TOUCH_RANGE),
+                _ => false,
+            })
+            .map(|illusion| illusion.id)
+            .collect();
+        for id in bumped {
+            expose(&mut server, room, id, &scenario.0);
+        }
+    }
+}
+
+// Runs the exposure AM scripted: tell the target, then let the illusion vanish or stay
+fn expose(server: &mut RenetServer, room: &mut Room, id: IllusionId, scenario: &Scenario) {
+    let Some(index) = room.illusions.iter().position(|illusion| illusion.id == id) else {
+        return;
+    };
+    let illusion = &mut room.illusions[index];
+    illusion.exposures += 1;
+    let target = ClientId::from_raw(illusion.target);
+    let dispelled = illusion.exposure.dispel;
+    let text = illusion.exposure.message.as_ref().and_then(|message| narrative::resolve_text(message, scenario).ok());
+    if let Some(text) = text {
+        narrative::tell(server, room, target, &text);
+    }
+    protocol::send_to_client(server, target, &ServerMessage::IllusionExposed { id, dispelled });
+    if dispelled {
+        room.illusions.remove(index);
+    }
+    notify_am(server, room);
+}
+
+// What a survivor is told about the illusions on them when they come back
+pub(super) fn illusion_messages(room: &Room, client_id: ClientId) -> Vec<ServerMessage> {
+    if room.roles.get(&client_id) == Some(&Role::Am) {
+        return vec![ServerMessage::AmIllusions(room.illusions.clone())];
+    }
+    room.illusions
+        .iter()
+        .filter(|illusion| illusion.target == client_id.raw() && visible_to_target(illusion))
+        .map(|illusion| ServerMessage::IllusionAppeared { id: illusion.id, kind: illusion.kind.clone() })
+        .collect()
+}
+
+// Whether the player is hidden from the client by an illusion still in effect
+pub(super) fn is_hidden_from(room: &Room, client_id: ClientId, player: ClientId) -> bool {
+    room.illusions.iter().any(|illusion| {
+        illusion.target == client_id.raw()
+            && !illusion.seen_through
+            && illusion.kind == IllusionKind::HiddenPlayer { client_id: player.raw() }
+    })
+}
+
+// Hidden players are left out of the target's snapshots, there is nothing to send
+fn visible_to_target(illusion: &Illusion) -> bool {
+    !illusion.seen_through && !matches!(illusion.kind, IllusionKind::HiddenPlayer { .. })
+}
+
+pub(super) fn check_am(room: &Room, client_id: ClientId) -> Result<(), ActionError> {
+    if room.phase != RoomPhase::InProgress {
+        return Err(ActionError::MatchNotRunning);
+    }
+    if room.roles.get(&client_id) != Some(&Role::Am) {
+        return Err(ActionError::NotAm);
+    }
+    Ok(())
+}
+
+fn notify_am(server: &mut RenetServer, room: &Room) {
+    if let Some(am) = room.am() {
+        protocol::send_to_client(server, am, &ServerMessage::AmIllusions(room.illusions.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::protocol::NarrativeText;
+    use crate::server::simulation::{self, SnapshotTick};
+    use crate::server::testing::{ice_cave, received, send, server_app, Inboxes};
+    use crate::server::Sessions;
+
+    // Player 1 plays AM to survivors 2 to 4, who spawn 100 apart
+    fn illusions_app() -> (App, Inboxes) {
+        let (mut app, inboxes) = server_app(&[1, 2, 3, 4]);
+        let mut registry = RoomRegistry::default();
+        registry.running_for_test(&[1, 2, 3, 4]);
+        app.init_resource::<Time>()
+            .insert_resource(registry)
+            .insert_resource(ServerScenario(ice_cave()))
+            .init_resource::<ServerPlayers>()
+            .init_resource::<Sessions>()
+            .init_resource::<SnapshotTick>()
+            .add_systems(
+                Update,
+                (
+                    simulation::sync_server_players,
+                    handle_illusion_messages,
+                    expose_hidden_players,
+                    simulation::send_snapshots,
+                )
+                    .chain(),
+            );
+        (app, inboxes)
+    }
+
+    // Casts the way an intervention does, errors go back to the caster
+    fn cast_on(app: &mut App, client: u64, target: u64, kind: IllusionKind, exposure: Exposure) {
+        let client_id = ClientId::from_raw(client);
+        app.world_mut().resource_scope(|world, mut registry: Mut<RoomRegistry>| {
+            let room = registry.rooms.values_mut().next().unwrap();
+            let scenario = &world.resource::<ServerScenario>().0;
+            let cast = cast(room, client_id, target, &kind, &exposure, scenario, world.resource::<ServerPlayers>());
+            let mut server = world.resource_mut::<RenetServer>();
+            match cast {
+                Ok(illusion) => show(&mut server, room, illusion),
+                Err(error) => protocol::send_to_client(&mut server, client_id, &ServerMessage::ActionError(error)),
+            }
+        });
+        app.update();
+    }
+
+    fn fake_item(position: Vec2) -> IllusionKind {
+        IllusionKind::FakeItem {
+            item: "Can opener".to_string(),
+            position,
+        }
+    }
+
+    fn move_to(app: &mut App, client: u64, position: Vec2) {
+        let mut players = app.world_mut().resource_mut::<ServerPlayers>();
+        players.0.get_mut(&ClientId::from_raw(client)).unwrap().position = position;
+    }
+
+    fn position(app: &App, client: u64) -> Vec2 {
+        app.world().resource::<ServerPlayers>().0[&ClientId::from_raw(client)].position
+    }
+
+    fn illusions(app: &App) -> Vec<Illusion> {
+        app.world().resource::<RoomRegistry>().rooms.values().next().unwrap().illusions.clone()
+    }
+
+    // Everything but the snapshots, which every client gets every frame
+    fn messages(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<ServerMessage> {
+        received(app, inboxes, client)
+            .into_iter()
+            .filter(|message| !matches!(message, ServerMessage::Snapshot(_)))
+            .collect()
+    }
+
+    fn last_snapshot(app: &mut App, inboxes: &mut Inboxes, client: u64) -> HashSet<u64> {
+        received(app, inboxes, client)
+            .into_iter()
+            .filter_map(|message| match message {
+                ServerMessage::Snapshot(snapshot) => Some(snapshot.players.iter().map(|player| player.client_id).collect()),
+                _ => None,
+            })
+            .last()
+            .expect("no snapshot")
+    }
+
+    #[test]
+    fn hidden_players_are_left_out_of_the_targets_snapshots() {
+        let (mut app, mut inboxes) = illusions_app();
+        let exposure = Exposure { message: None, dispel: false };
+        cast_on(&mut app, 1, 2, IllusionKind::HiddenPlayer { client_id: 3 }, exposure);
+        app.update();
+
+        assert_eq!(last_snapshot(&mut app, &mut inboxes, 2), HashSet::from([2, 4]));
+        assert_eq!(last_snapshot(&mut app, &mut inboxes, 4), HashSet::from([2, 3, 4]));
+        assert_eq!(last_snapshot(&mut app, &mut inboxes, 1), HashSet::from([2, 3, 4]));
+        // The target is never told someone is missing
+        received(&mut app, &mut inboxes, 2);
+        app.update();
+        assert!(messages(&mut app, &mut inboxes, 2).is_empty());
+    }
+
+    #[test]
+    fn only_the_target_sees_an_illusion() {
+        let (mut app, mut inboxes) = illusions_app();
+        app.update();
+        let kind = IllusionKind::FakePlayer { of: 4, position: Vec2::ZERO };
+        cast_on(&mut app, 1, 2, kind.clone(), Exposure { message: None, dispel: true });
+
+        let id = illusions(&app)[0].id;
+        assert_eq!(messages(&mut app, &mut inboxes, 2), [ServerMessage::IllusionAppeared { id, kind }]);
+        assert!(messages(&mut app, &mut inboxes, 3).is_empty());
+        assert_eq!(messages(&mut app, &mut inboxes, 1), [ServerMessage::AmIllusions(illusions(&app))]);
+    }
+
+    #[test]
+    fn reaching_for_an_illusion_runs_its_exposure() {
+        let (mut app, mut inboxes) = illusions_app();
+        app.update();
+        let near = position(&app, 2) + Vec2::new(consts::INTERACT_RANGE / 2.0, 0.0);
+        let exposure = Exposure {
+            message: Some(NarrativeText::Free("Your hand closes on ice".to_string())),
+            dispel: true,
+        };
+        cast_on(&mut app, 1, 2, fake_item(near), exposure);
+        let id = illusions(&app)[0].id;
+        messages(&mut app, &mut inboxes, 2);
+
+        // Out of reach nothing happens, and only the target can expose it
+        move_to(&mut app, 2, near + Vec2::new(consts::INTERACT_RANGE * 2.0, 0.0));
+        send(&mut app, 2, ClientMessage::Interact(InteractTarget::Illusion(id)));
+        send(&mut app, 3, ClientMessage::Interact(InteractTarget::Illusion(id)));
+        app.update();
+        assert!(messages(&mut app, &mut inboxes, 2).is_empty());
+
+        move_to(&mut app, 2, near);
+        send(&mut app, 2, ClientMessage::Interact(InteractTarget::Illusion(id)));
+        app.update();
+        assert_eq!(
+            messages(&mut app, &mut inboxes, 2),
+            [
+                ServerMessage::Narrative {
+                    text: "Your hand closes on ice".to_string()
+                },
+                ServerMessage::IllusionExposed { id, dispelled: true },
+            ]
+        );
+        assert!(illusions(&app).is_empty());
+    }
+
+    #[test]
+    fn the_mad_keep_believing_unless_they_notice() {
+        let (mut app, _) = illusions_app();
+        app.update();
+        app.world_mut().resource_scope(|world, mut registry: Mut<RoomRegistry>| {
+            let room = registry.rooms.values_mut().next().unwrap();
+            sanity::lose(room, world.resource::<ServerPlayers>(), ClientId::from_raw(2), 1000.0);
+        });
+        let set_perception = |app: &mut App, perception: f32| {
+            let mut players = app.world_mut().resource_mut::<ServerPlayers>();
+            players.0.get_mut(&ClientId::from_raw(2)).unwrap().sheet.perception = perception;
+        };
+        let near = position(&app, 2);
+        cast_on(&mut app, 1, 2, fake_item(near), Exposure { message: None, dispel: true });
+        let id = illusions(&app)[0].id;
+
+        set_perception(&mut app, 0.0);
+        for _ in 0..10 {
+            send(&mut app, 2, ClientMessage::Interact(InteractTarget::Illusion(id)));
+            app.update();
+        }
+        assert_eq!(illusions(&app).len(), 1);
+
+        set_perception(&mut app, 1.0);
+        send(&mut app, 2, ClientMessage::Interact(InteractTarget::Illusion(id)));
+        app.update();
+        assert!(illusions(&app).is_empty());
+    }
+
+    #[test]
+    fn bumping_into_a_hidden_player_exposes_it_once() {
+        let (mut app, mut inboxes) = illusions_app();
+        app.update();
+        let exposure = Exposure { message: None, dispel: false };
+        cast_on(&mut app, 1, 2, IllusionKind::HiddenPlayer { client_id: 3 }, exposure);
+        let id = illusions(&app)[0].id;
+
+        let target = position(&app, 2);
+        move_to(&mut app, 3, target + Vec2::new(TOUCH_RANGE / 2.0, 0.0));
+        app.update();
+        app.update();
+        assert_eq!(messages(&mut app, &mut inboxes, 2), [ServerMessage::IllusionExposed { id, dispelled: false }]);
+        assert_eq!(illusions(&app)[0].exposures, 1);
+    }
+
+    #[test]
+    fn only_am_casts_illusions_on_survivors() {
+        let (mut app, mut inboxes) = illusions_app();
+        app.update();
+        let exposure = Exposure { message: None, dispel: true };
+        cast_on(&mut app, 2, 3, fake_item(Vec2::ZERO), exposure.clone());
+        cast_on(&mut app, 1, 1, fake_item(Vec2::ZERO), exposure.clone());
+        cast_on(&mut app, 1, 2, IllusionKind::HiddenPlayer { client_id: 2 }, exposure);
+
+        assert_eq!(messages(&mut app, &mut inboxes, 2), [ServerMessage::ActionError(ActionError::NotAm)]);
+        assert_eq!(
+            messages(&mut app, &mut inboxes, 1),
+            [
+                ServerMessage::ActionError(ActionError::UnknownRecipient),
+                ServerMessage::ActionError(ActionError::UnknownRecipient),
+            ]
+        );
+        assert!(illusions(&app).is_empty());
+    }
+}
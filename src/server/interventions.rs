@@ -0,0 +1,317 @@
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetServer};
+
+use crate::protocol::{
+    self, ActionError, ClientMessage, InterventionAction, InterventionCooldown, MatchEventKind, RoomPhase, ServerMessage,
+};
+use crate::resources::world_effects::{HazardZone, LockedDoor};
+use crate::scenario::{Intervention, InterventionKind, Scenario};
+use crate::server::rooms::Room;
+use crate::server::{illusions, match_log, narrative, sanity, trust};
+use crate::server::{ConnectedClients, FromClient, RoomRegistry, ServerPlayers, ServerScenario};
+
+// How often AM is told its hate and cooldowns, the panel counts down in between
+const STATUS_INTERVAL_SECS: f32 = 0.5;
+
+// System to let AM spend hate on the scenario's interventions
+pub(super) fn handle_intervention_messages(
+    mut from_client: EventReader<FromClient>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    clients: Res<ConnectedClients>,
+    scenario: Res<ServerScenario>,
+    players: Res<ServerPlayers>,
+) {
+    for FromClient { client_id, message } in from_client.read() {
+        let ClientMessage::Intervene { intervention, action } = message else {
+            continue;
+        };
+        let client_id = *client_id;
+        let Some(room_id) = registry.membership.get(&client_id).copied() else {
+            continue;
+        };
+        let Some(room) = registry.rooms.get_mut(&room_id) else {
+            continue;
+        };
+        let scenario = &scenario.0;
+        let result = check_intervention(room, client_id, intervention, action, scenario).and_then(|intervention| {
+            match action {
+                InterventionAction::Narrate(event) => {
+                    narrative::narrate(&mut server, room, client_id, event, scenario, &players, &clients)?;
+                }
+                InterventionAction::Hallucination { target, kind, exposure } => {
+                    let illusion = illusions::cast(room, client_id, *target, kind, exposure, scenario, &players)?;
+                    illusions::show(&mut server, room, illusion);
+                }
+                InterventionAction::Hazard { position } => {
+                    let map = &scenario.map;
+                    if !(position.x.abs() <= map.width / 2.0 && position.y.abs() <= map.height / 2.0) {
+                        return Err(ActionError::OffMap);
+                    }
+                    if let InterventionKind::Hazard { radius, slow, duration_secs } = intervention.kind {
+                        room.effects.hazards.push(HazardZone {
+                            position: *position,
+                            radius,
+                            slow,
+                            seconds_left: duration_secs,
+                        });
+                    }
+                    broadcast_effects(&mut server, room);
+                }
+                InterventionAction::LockDoor { door } => {
+                    let door = scenario.door(door).ok_or(ActionError::UnknownDoor)?;
+                    if let InterventionKind::LockDoor { duration_secs } = intervention.kind {
+                        // Locking a locked door again only keeps it shut for longer
+                        room.effects.locked_doors.retain(|locked| locked.id != door.id);
+                        room.effects.locked_doors.push(LockedDoor {
+                            id: door.id.clone(),
+                            x: door.x,
+                            seconds_left: duration_secs,
+                        });
+                    }
+                    broadcast_effects(&mut server, room);
+                }
+            }
+            Ok(intervention)
+        });
+        match result {
+            Ok(intervention) => {
+                println!("AM used {} in room {}", intervention.name, room.name);
+                room.hate -= intervention.cost;
+                room.cooldowns.insert(intervention.id.clone(), intervention.cooldown_secs);
+                for victim in sanity::intervention_victims(room, &players, &intervention.kind, action) {
+                    sanity::lose(room, &players, victim, scenario.sanity.intervention_loss);
+                }
+                let kind = MatchEventKind::Intervention {
+                    name: intervention.name.clone(),
+                    cost: intervention.cost,
+                    action: action.clone(),
+                };
+                match_log::record(room, kind);
+                if let Some(am) = room.am() {
+                    protocol::send_to_client(&mut server, am, &status_message(room, scenario, &players));
+                }
+            }
+            Err(error) => protocol::send_to_client(&mut server, client_id, &ServerMessage::ActionError(error)),
+        }
+    }
+}
+
+// Checks AM can afford the intervention right now and that the action is what it does
+fn check_intervention<'a>(
+    room: &Room,
+    client_id: ClientId,
+    id: &str,
+    action: &InterventionAction,
+    scenario: &'a Scenario,
+) -> Result<&'a Intervention, ActionError> {
+    illusions::check_am(room, client_id)?;
+    if room.outcome.is_some() {
+        return Err(ActionError::MatchNotRunning);
+    }
+    let intervention = scenario.intervention(id).ok_or(ActionError::UnknownIntervention)?;
+    if !action.fits(&intervention.kind) {
+        return Err(ActionError::WrongIntervention);
+    }
+    if room.cooldowns.contains_key(id) {
+        return Err(ActionError::CoolingDown);
+    }
+    if room.hate < intervention.cost {
+        return Err(ActionError::NotEnoughHate);
+    }
+    Ok(intervention)
+}
+
+// System to build AM's hate back up, count the cooldowns down and let hazards and locks wear off
+pub(super) fn tick_interventions(
+    time: Res<Time>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    scenario: Res<ServerScenario>,
+    players: Res<ServerPlayers>,
+    mut status_timer: Local<Option<Timer>>,
+) {
+    let status_timer =
+        status_timer.get_or_insert_with(|| Timer::from_seconds(STATUS_INTERVAL_SECS, TimerMode::Repeating));
+    let send_status = status_timer.tick(time.delta()).just_finished();
+    let delta = time.delta_seconds();
+    let hate = &scenario.0.hate;
+
+    for room in registry.rooms.values_mut().filter(|room| room.phase == RoomPhase::InProgress) {
+        room.hate = (room.hate + hate.regen_per_sec * delta).min(hate.max);
+        for seconds_left in room.cooldowns.values_mut() {
+            *seconds_left -= delta;
+        }
+        room.cooldowns.retain(|_, seconds_left| *seconds_left > 0.0);
+        if room.effects.tick(delta) {
+            broadcast_effects(&mut server, room);
+        }
+        if send_status {
+            if let Some(am) = room.am() {
+                protocol::send_to_client(&mut server, am, &status_message(room, &scenario.0, &players));
+            }
+        }
+    }
+}
+
+pub(super) fn status_message(room: &Room, scenario: &Scenario, players: &ServerPlayers) -> ServerMessage {
+    ServerMessage::AmStatus {
+        hate: room.hate,
+        max_hate: scenario.hate.max,
+        cooldowns: room
+            .cooldowns
+            .iter()
+            .map(|(id, seconds_left)| InterventionCooldown {
+                id: id.clone(),
+                seconds_left: *seconds_left,
+            })
+            .collect(),
+        score: room.am_score,
+        trust: room.trust.pairs(&trust::survivors(room)),
+        sanity: sanity::levels(room, players),
+    }
+}
+
+pub(super) fn effects_message(room: &Room) -> ServerMessage {
+    ServerMessage::WorldEffects(room.effects.clone())
+}
+
+pub(super) fn broadcast_effects(server: &mut RenetServer, room: &Room) {
+    let message = effects_message(room);
+    for player in &room.players {
+        protocol::send_to_client(server, *player, &message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::protocol::{NarrativeEvent, NarrativeText, NarrativeVersion};
+    use crate::server::testing::{ice_cave, received, send, server_app, Inboxes};
+
+    const START_HATE: f32 = 40.0;
+
+    // Player 1 plays AM to survivors 2 to 4 with START_HATE to spend
+    fn interventions_app() -> (App, Inboxes) {
+        let (mut app, inboxes) = server_app(&[1, 2, 3, 4]);
+        let mut registry = RoomRegistry::default();
+        let id = registry.running_for_test(&[1, 2, 3, 4]);
+        registry.rooms.get_mut(&id).unwrap().hate = START_HATE;
+        app.init_resource::<Time>()
+            .insert_resource(registry)
+            .insert_resource(ServerScenario(ice_cave()))
+            .init_resource::<ServerPlayers>()
+            .add_systems(Update, (handle_intervention_messages, tick_interventions).chain());
+        (app, inboxes)
+    }
+
+    fn intervene(app: &mut App, client: u64, intervention: &str, action: InterventionAction) {
+        let intervention = intervention.to_string();
+        send(app, client, ClientMessage::Intervene { intervention, action });
+        app.update();
+    }
+
+    fn lock(door: &str) -> InterventionAction {
+        InterventionAction::LockDoor { door: door.to_string() }
+    }
+
+    fn taunt() -> InterventionAction {
+        InterventionAction::Narrate(NarrativeEvent {
+            versions: vec![NarrativeVersion {
+                recipients: vec![2],
+                text: NarrativeText::Free("Nobody is coming".to_string()),
+            }],
+            truth: None,
+            rumour: None,
+        })
+    }
+
+    fn room(app: &App) -> &Room {
+        app.world().resource::<RoomRegistry>().rooms.values().next().unwrap()
+    }
+
+    fn room_mut(app: &mut App) -> &mut Room {
+        app.world_mut().resource_mut::<RoomRegistry>().into_inner().rooms.values_mut().next().unwrap()
+    }
+
+    fn errors(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<ActionError> {
+        received(app, inboxes, client)
+            .into_iter()
+            .filter_map(|message| match message {
+                ServerMessage::ActionError(error) => Some(error),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn wait(app: &mut App, secs: f32) {
+        app.world_mut().resource_mut::<Time>().advance_by(Duration::from_secs_f32(secs));
+        app.update();
+    }
+
+    #[test]
+    fn interventions_spend_hate_and_start_their_cooldown() {
+        let (mut app, mut inboxes) = interventions_app();
+        intervene(&mut app, 1, "seal", lock("cache_gate"));
+
+        assert_eq!(room(&app).hate, START_HATE - 30.0);
+        assert_eq!(room(&app).cooldowns.get("seal"), Some(&60.0));
+        let locked: Vec<_> = room(&app).effects.locked_doors.iter().map(|door| door.id.as_str()).collect();
+        assert_eq!(locked, ["cache_gate"]);
+        for client in 2..=4 {
+            assert_eq!(received(&mut app, &mut inboxes, client), [effects_message(room(&app))]);
+        }
+    }
+
+    #[test]
+    fn refuses_what_am_cannot_afford_or_is_still_cooling_down() {
+        let (mut app, mut inboxes) = interventions_app();
+        let hazard = || InterventionAction::Hazard { position: Vec2::ZERO };
+        intervene(&mut app, 1, "avalanche", hazard());
+        assert_eq!(room(&app).hate, 0.0);
+        assert!(errors(&mut app, &mut inboxes, 1).is_empty());
+
+        room_mut(&mut app).hate = 35.0;
+        intervene(&mut app, 1, "avalanche", hazard());
+        intervene(&mut app, 1, "false_cache", hazard());
+        intervene(&mut app, 1, "nowhere", hazard());
+        intervene(&mut app, 1, "seal", lock("nowhere"));
+        intervene(&mut app, 2, "taunt", taunt());
+        room_mut(&mut app).hate = 20.0;
+        intervene(&mut app, 1, "seal", lock("cache_gate"));
+        assert_eq!(
+            errors(&mut app, &mut inboxes, 1),
+            [
+                ActionError::CoolingDown,
+                ActionError::WrongIntervention,
+                ActionError::UnknownIntervention,
+                ActionError::UnknownDoor,
+                ActionError::NotEnoughHate,
+            ]
+        );
+        assert_eq!(errors(&mut app, &mut inboxes, 2), [ActionError::NotAm]);
+        // Nothing refused costs anything
+        assert_eq!(room(&app).hate, 20.0);
+        assert!(room(&app).effects.locked_doors.is_empty());
+    }
+
+    #[test]
+    fn hate_builds_back_up_and_cooldowns_run_out() {
+        let (mut app, _) = interventions_app();
+        intervene(&mut app, 1, "taunt", taunt());
+        assert_eq!(room(&app).hate, START_HATE - 5.0);
+
+        let regen = ice_cave().hate.regen_per_sec;
+        wait(&mut app, 5.0);
+        assert_eq!(room(&app).hate, START_HATE - 5.0 + regen * 5.0);
+        assert!(room(&app).cooldowns.contains_key("taunt"));
+        wait(&mut app, 5.5);
+        assert!(!room(&app).cooldowns.contains_key("taunt"));
+
+        // Never past the maximum
+        wait(&mut app, 1000.0);
+        assert_eq!(room(&app).hate, ice_cave().hate.max);
+    }
+}
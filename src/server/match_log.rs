@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+
+use crate::MatchPhase;
+use crate::protocol::{self, MatchEvent, MatchEventKind, RoomPhase, ServerMessage};
+use crate::server::rooms::Room;
+use crate::server::RoomRegistry;
+
+// Everything that happened in a match, in order, for the debrief
+#[derive(Default)]
+pub struct MatchLog {
+    pub events: Vec<MatchEvent>,
+    pub sent: bool,
+}
+
+pub(super) fn record(room: &mut Room, kind: MatchEventKind) {
+    let Some(clock) = &room.clock else {
+        return;
+    };
+    room.log.events.push(MatchEvent {
+        at_secs: clock.elapsed,
+        phase: clock.phase,
+        kind,
+    });
+}
+
+pub(super) fn log_message(room: &Room) -> ServerMessage {
+    ServerMessage::MatchLog(room.log.events.clone())
+}
+
+// System to send the log once the debrief starts. It goes out after every other reveal,
+// so clients know the debrief is complete when it arrives, and finishes the room.
+pub(super) fn send_match_log(mut server: ResMut<RenetServer>, mut registry: ResMut<RoomRegistry>) {
+    for room in registry.rooms.values_mut() {
+        let debriefing = room.clock.as_ref().map_or(false, |clock| clock.phase == MatchPhase::Debrief);
+        if room.phase != RoomPhase::InProgress || !debriefing || room.log.sent {
+            continue;
+        }
+        room.log.sent = true;
+        let message = log_message(room);
+        for player in &room.players {
+            protocol::send_to_client(&mut server, *player, &message);
+        }
+        println!("Room {} is finished", room.name);
+        room.phase = RoomPhase::Finished;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{ClientMessage, InterventionAction};
+    use crate::server::rooms::MatchClock;
+    use crate::server::testing::{ice_cave, received, send, server_app, Inboxes};
+    use crate::server::{interventions, simulation, trust, ServerPlayers, ServerScenario};
+
+    // Player 1 plays AM to survivors 2 to 4, with hate to spend
+    fn log_app() -> (App, Inboxes) {
+        let (mut app, inboxes) = server_app(&[1, 2, 3, 4]);
+        let mut registry = RoomRegistry::default();
+        let id = registry.running_for_test(&[1, 2, 3, 4]);
+        registry.rooms.get_mut(&id).unwrap().hate = 40.0;
+        app.insert_resource(registry)
+            .insert_resource(ServerScenario(ice_cave()))
+            .init_resource::<ServerPlayers>()
+            .add_systems(
+                Update,
+                (
+                    simulation::sync_server_players,
+                    interventions::handle_intervention_messages,
+                    trust::handle_trust_messages,
+                    send_match_log,
+                )
+                    .chain(),
+            );
+        app.update();
+        (app, inboxes)
+    }
+
+    fn room(app: &mut App) -> &mut Room {
+        app.world_mut().resource_mut::<RoomRegistry>().into_inner().rooms.values_mut().next().unwrap()
+    }
+
+    fn clock(app: &mut App) -> &mut MatchClock {
+        room(app).clock.as_mut().unwrap()
+    }
+
+    fn logs(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<Vec<MatchEvent>> {
+        received(app, inboxes, client)
+            .into_iter()
+            .filter_map(|message| match message {
+                ServerMessage::MatchLog(events) => Some(events),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_log_keeps_events_in_the_order_they_happened() {
+        let (mut app, _) = log_app();
+        let seal = InterventionAction::LockDoor { door: "cache_gate".to_string() };
+        send(&mut app, 1, ClientMessage::Intervene { intervention: "seal".to_string(), action: seal.clone() });
+        app.update();
+        clock(&mut app).elapsed = 12.0;
+        send(&mut app, 2, ClientMessage::Accuse { who: 3 });
+        app.update();
+        clock(&mut app).phase = MatchPhase::Confrontation;
+        clock(&mut app).elapsed = 30.0;
+        send(&mut app, 4, ClientMessage::Accuse { who: 2 });
+        app.update();
+
+        let events = room(&mut app).log.events.clone();
+        let timeline: Vec<_> = events.iter().map(|event| (event.at_secs, event.phase)).collect();
+        assert_eq!(
+            timeline,
+            [(0.0, MatchPhase::Exploration), (12.0, MatchPhase::Exploration), (30.0, MatchPhase::Confrontation)]
+        );
+        assert_eq!(
+            events.into_iter().map(|event| event.kind).collect::<Vec<_>>(),
+            [
+                MatchEventKind::Intervention { name: "Seal the passage".to_string(), cost: 30.0, action: seal },
+                MatchEventKind::Accused { by: 2, who: 3 },
+                MatchEventKind::Accused { by: 4, who: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn everyone_gets_the_log_once_the_debrief_starts() {
+        let (mut app, mut inboxes) = log_app();
+        send(&mut app, 2, ClientMessage::Accuse { who: 3 });
+        app.update();
+        for client in 1..=4 {
+            assert!(logs(&mut app, &mut inboxes, client).is_empty());
+        }
+
+        clock(&mut app).phase = MatchPhase::Debrief;
+        app.update();
+        app.update();
+        let events = room(&mut app).log.events.clone();
+        for client in 1..=4 {
+            assert_eq!(logs(&mut app, &mut inboxes, client), [events.clone()]);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::prelude::*;
+use bevy_renet::renet::transport::{NetcodeServerTransport, ServerAuthentication, ServerConfig};
+use bevy_renet::renet::RenetServer;
+use bevy_renet::transport::NetcodeServerPlugin;
+use bevy_renet::RenetServerPlugin;
+use rand::Rng;
+
+use crate::link_conditioner::LinkConditioner;
+use crate::protocol;
+use crate::scenario::Scenario;
+
+mod chat;
+mod combat;
+mod connection;
+mod discovery;
+mod draft;
+mod illusions;
+mod interventions;
+mod match_log;
+mod narrative;
+mod outcome;
+mod phases;
+mod roles;
+mod rooms;
+mod sanity;
+mod sessions;
+mod settings;
+mod simulation;
+#[cfg(test)]
+mod testing;
+mod trust;
+mod votes;
+
+pub use connection::{ClientInfo, ConnectedClients, FromClient};
+pub use rooms::{Room, RoomRegistry};
+pub use sessions::{HeldSlot, Sessions};
+pub use settings::ServerSettings;
+pub use simulation::{ServerPlayer, ServerPlayers};
+
+pub struct ServerPlugin;
+
+// Identifies this server run to clients that may reach it on more than one address
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct ServerIdentity {
+    pub id: u64,
+    pub game_port: u16,
+}
+
+// Relay in front of the transport when the server runs with a link conditioner, lives as long as the app
+#[derive(Resource)]
+pub struct ServerLinkConditioner(pub LinkConditioner);
+
+// Scenario every room on this server plays
+#[derive(Resource)]
+pub struct ServerScenario(pub Scenario);
+
+// Flag shared with whoever spawned the server so it can be stopped from outside the app
+#[derive(Resource, Clone, Default)]
+pub struct ServerShutdown(pub Arc<AtomicBool>);
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((RenetServerPlugin, NetcodeServerPlugin))
+            .add_event::<FromClient>()
+            .add_event::<sessions::SessionResumed>()
+            .init_resource::<ServerShutdown>()
+            .init_resource::<ConnectedClients>()
+            .init_resource::<connection::PendingKicks>()
+            .init_resource::<RoomRegistry>()
+            .init_resource::<Sessions>()
+            .init_resource::<ServerPlayers>()
+            .init_resource::<simulation::SnapshotTick>()
+            .add_systems(
+                Update,
+                (
+                    (
+                        // Runs first so the name of a dropped player is still known
+                        sessions::hold_slots_on_disconnect,
+                        connection::handle_server_events,
+                        connection::kick_rejected_clients,
+                        sessions::announce_resumed_sessions,
+                        sessions::release_expired_slots,
+                        connection::receive_client_messages,
+                    )
+                        .chain(),
+                    (
+                        rooms::handle_room_messages,
+                        roles::handle_role_messages,
+                        roles::broadcast_lineups,
+                        draft::handle_pick_messages,
+                        draft::finish_drafts,
+                    )
+                        .chain(),
+                    (
+                        phases::advance_match_phases,
+                        interventions::handle_intervention_messages,
+                        interventions::tick_interventions,
+                        simulation::sync_server_players,
+                        simulation::apply_player_inputs,
+                        combat::resolve_attacks,
+                        illusions::handle_illusion_messages,
+                        illusions::expose_hidden_players,
+                        simulation::send_snapshots,
+                    )
+                        .chain(),
+                    (
+                        trust::handle_trust_messages,
+                        votes::handle_vote_messages,
+                        votes::tick_votes,
+                        chat::handle_chat_messages,
+                        chat::deliver_chat,
+                        sanity::tick_sanity,
+                        trust::sample_trust,
+                        outcome::check_outcomes,
+                        narrative::reveal_narratives,
+                        trust::reveal_trust,
+                        chat::reveal_chat,
+                        // Last, it tells clients the debrief is complete
+                        match_log::send_match_log,
+                    )
+                        .chain(),
+                    discovery::answer_discovery_probes.run_if(resource_exists::<discovery::DiscoveryResponder>),
+                    check_shutdown,
+                )
+                    .chain(),
+            );
+    }
+}
+
+// Builds a windowless app running only the server simulation on an already bound socket
+pub fn build_server_app(socket: UdpSocket, settings: ServerSettings, shutdown: ServerShutdown) -> io::Result<App> {
+    let public_addr = socket.local_addr()?;
+    let scenario = Scenario::from_file(&settings.scenario_path).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("scenario {}: {}", settings.scenario_path.display(), err),
+        )
+    })?;
+    let identity = ServerIdentity {
+        id: rand::thread_rng().gen(),
+        game_port: public_addr.port(),
+    };
+    // With a link conditioner clients still dial the bound socket, which now belongs to the relay
+    let (socket, conditioner) = match &settings.link_conditioner {
+        Some(conditions) => {
+            let inner = UdpSocket::bind("127.0.0.1:0")?;
+            let conditioner = LinkConditioner::spawn(socket, inner.local_addr()?, conditions.clone())?;
+            (inner, Some(ServerLinkConditioner(conditioner)))
+        }
+        None => (socket, None),
+    };
+    let (server, transport) = create_server(socket, public_addr, &settings)?;
+    let lan_discovery = settings.lan_discovery;
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+        1.0 / settings.tick_rate,
+    ))))
+    .add_plugins(ServerPlugin)
+    .insert_resource(shutdown)
+    .insert_resource(settings)
+    .insert_resource(identity)
+    .insert_resource(ServerScenario(scenario))
+    .insert_resource(server)
+    .insert_resource(transport);
+    if let Some(conditioner) = conditioner {
+        app.insert_resource(conditioner);
+    }
+    if lan_discovery {
+        if let Some(responder) = discovery::DiscoveryResponder::bind() {
+            app.insert_resource(responder);
+        }
+    }
+    Ok(app)
+}
+
+fn create_server(
+    socket: UdpSocket,
+    public_addr: SocketAddr,
+    settings: &ServerSettings,
+) -> io::Result<(RenetServer, NetcodeServerTransport)> {
+    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+    let server_config = ServerConfig {
+        current_time,
+        max_clients: settings.max_clients,
+        protocol_id: protocol::PROTOCOL_ID,
+        public_addresses: public_addresses(public_addr),
+        authentication: ServerAuthentication::Unsecure,
+    };
+    let transport = NetcodeServerTransport::new(server_config, socket)?;
+    let server = RenetServer::new(protocol::connection_config());
+    Ok((server, transport))
+}
+
+// Netcode only accepts clients that dialled one of these, so a wildcard bind also answers on
+// loopback and on the LAN address discovery replies come from
+fn public_addresses(local_addr: SocketAddr) -> Vec<SocketAddr> {
+    if local_addr.ip().is_unspecified() {
+        let mut addresses = vec![
+            local_addr,
+            SocketAddr::from(([127, 0, 0, 1], local_addr.port())),
+        ];
+        if let Some(lan_ip) = lan_ip() {
+            addresses.push(SocketAddr::new(lan_ip, local_addr.port()));
+        }
+        addresses
+    } else {
+        vec![local_addr]
+    }
+}
+
+// Address of the interface holding the default route; connecting a UDP socket sends nothing
+fn lan_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:80").ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_loopback() && !ip.is_unspecified()).then_some(ip)
+}
+
+// System to stop the app once the shutdown flag has been raised
+fn check_shutdown(shutdown: Res<ServerShutdown>, mut exit: EventWriter<AppExit>) {
+    if shutdown.0.load(Ordering::Relaxed) {
+        exit.send(AppExit::Success);
+    }
+}
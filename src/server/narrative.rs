@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetServer};
+use rand::Rng;
+
+use crate::MatchPhase;
+use crate::components::character_sheet::Ability;
+use crate::components::role::Role;
+use crate::protocol::{
+    self, ActionError, NarrativeDelivery, NarrativeEvent, NarrativeRecord, NarrativeText, RoomPhase,
+    ServerMessage, MAX_NARRATIVE_CHARS,
+};
+use crate::scenario::Scenario;
+use crate::server::rooms::Room;
+use crate::server::trust;
+use crate::server::{ConnectedClients, RoomRegistry, ServerPlayers};
+
+// Delivers what AM tells the survivors, each version to its own recipients only
+pub(super) fn narrate(
+    server: &mut RenetServer,
+    room: &mut Room,
+    client_id: ClientId,
+    event: &NarrativeEvent,
+    scenario: &Scenario,
+    players: &ServerPlayers,
+    clients: &ConnectedClients,
+) -> Result<(), ActionError> {
+    let texts = check_narrative(room, client_id, event, scenario)?;
+    if let Some(rumour) = &event.rumour {
+        trust::check_rumour(room, rumour)?;
+    }
+
+    let mut deliveries = Vec::new();
+    for (version, text) in event.versions.iter().zip(texts) {
+        for recipient in &version.recipients {
+            let recipient = ClientId::from_raw(*recipient);
+            let forgotten = players.0.get(&recipient).map_or(false, |player| match player.sheet.ability {
+                Ability::MemoryGaps { chance } => rand::thread_rng().gen_bool(chance.clamp(0.0, 1.0) as f64),
+                _ => false,
+            });
+            if forgotten {
+                feed_am(server, room, recipient, &text, true);
+            } else {
+                tell(server, room, recipient, &text);
+            }
+            deliveries.push(NarrativeDelivery {
+                client_id: recipient.raw(),
+                name: clients.0.get(&recipient).map(|info| info.name.clone()).unwrap_or_default(),
+                text: text.clone(),
+                forgotten,
+            });
+        }
+    }
+    if let Some(rumour) = event.rumour {
+        let believers = deliveries
+            .iter()
+            .filter(|delivery| !delivery.forgotten)
+            .map(|delivery| ClientId::from_raw(delivery.client_id))
+            .collect();
+        trust::spread_rumour(room, players, rumour, believers);
+    }
+    let (phase, at_secs) = room.clock.as_ref().map_or((MatchPhase::default(), 0.0), |clock| (clock.phase, clock.elapsed));
+    room.narratives.records.push(NarrativeRecord {
+        at_secs,
+        phase,
+        truth: event.truth.clone(),
+        deliveries,
+    });
+    Ok(())
+}
+
+// Puts words in one survivor's head, AM keeps a copy of everything each survivor is told
+pub(super) fn tell(server: &mut RenetServer, room: &Room, recipient: ClientId, text: &str) {
+    protocol::send_to_client(server, recipient, &ServerMessage::Narrative { text: text.to_string() });
+    feed_am(server, room, recipient, text, false);
+}
+
+fn feed_am(server: &mut RenetServer, room: &Room, recipient: ClientId, text: &str, forgotten: bool) {
+    if let Some(am) = room.am() {
+        let message = ServerMessage::AmFeed {
+            client_id: recipient.raw(),
+            text: text.to_string(),
+            forgotten,
+        };
+        protocol::send_to_client(server, am, &message);
+    }
+}
+
+// AM's copy of every survivor's feed, rebuilt from the log when AM comes back
+pub(super) fn am_feed_messages(room: &Room) -> Vec<ServerMessage> {
+    room.narratives
+        .records
+        .iter()
+        .flat_map(|record| &record.deliveries)
+        .map(|delivery| ServerMessage::AmFeed {
+            client_id: delivery.client_id,
+            text: delivery.text.clone(),
+            forgotten: delivery.forgotten,
+        })
+        .collect()
+}
+
+// Checks a narrative event against the room and resolves the text of every version
+fn check_narrative(
+    room: &Room,
+    client_id: ClientId,
+    event: &NarrativeEvent,
+    scenario: &Scenario,
+) -> Result<Vec<String>, ActionError> {
+    if room.phase != RoomPhase::InProgress {
+        return Err(ActionError::MatchNotRunning);
+    }
+    if room.roles.get(&client_id) != Some(&Role::Am) {
+        return Err(ActionError::NotAm);
+    }
+    if event.versions.is_empty() {
+        return Err(ActionError::EmptyNarrative);
+    }
+
+    let mut told = HashSet::new();
+    let mut texts = Vec::new();
+    for version in &event.versions {
+        if version.recipients.is_empty() {
+            return Err(ActionError::EmptyNarrative);
+        }
+        for recipient in &version.recipients {
+            let recipient = ClientId::from_raw(*recipient);
+            if !matches!(room.roles.get(&recipient), Some(Role::Survivor(_))) {
+                return Err(ActionError::UnknownRecipient);
+            }
+            if !told.insert(recipient) {
+                return Err(ActionError::RecipientTwice);
+            }
+        }
+        texts.push(resolve_text(&version.text, scenario)?);
+    }
+    if event.truth.as_ref().map_or(false, |truth| truth.chars().count() > MAX_NARRATIVE_CHARS) {
+        return Err(ActionError::NarrativeTooLong);
+    }
+    Ok(texts)
+}
+
+// Looks scenario lines up and checks what AM wrote itself
+pub(super) fn resolve_text(text: &NarrativeText, scenario: &Scenario) -> Result<String, ActionError> {
+    let text = match text {
+        NarrativeText::Line(id) => scenario.line(id).ok_or(ActionError::UnknownLine)?.to_string(),
+        NarrativeText::Free(text) => text.trim().to_string(),
+    };
+    if text.is_empty() {
+        return Err(ActionError::EmptyNarrative);
+    }
+    if text.chars().count() > MAX_NARRATIVE_CHARS {
+        return Err(ActionError::NarrativeTooLong);
+    }
+    Ok(text)
+}
+
+pub(super) fn reveal_message(room: &Room) -> ServerMessage {
+    ServerMessage::NarrativeReveal(room.narratives.records.clone())
+}
+
+// System to show everyone the truth behind AM's stories once the match reaches its debrief
+pub(super) fn reveal_narratives(mut server: ResMut<RenetServer>, mut registry: ResMut<RoomRegistry>) {
+    for room in registry.rooms.values_mut() {
+        let debriefing = room.clock.as_ref().map_or(false, |clock| clock.phase == MatchPhase::Debrief);
+        if room.phase != RoomPhase::InProgress || !debriefing || room.narratives.revealed {
+            continue;
+        }
+        room.narratives.revealed = true;
+        let message = reveal_message(room);
+        for player in &room.players {
+            protocol::send_to_client(&mut server, *player, &message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::NarrativeVersion;
+    use crate::server::testing::{connected_clients, ice_cave, Inboxes};
+
+    const PLAYERS: [u64; 4] = [1, 2, 3, 4];
+
+    // Player 1 plays AM to survivors 2 to 4
+    struct Match {
+        server: RenetServer,
+        inboxes: Inboxes,
+        registry: RoomRegistry,
+        scenario: Scenario,
+    }
+
+    impl Match {
+        fn new() -> Self {
+            let mut server = RenetServer::new(protocol::connection_config());
+            let inboxes = Inboxes::connect(&mut server, &PLAYERS);
+            let mut registry = RoomRegistry::default();
+            registry.running_for_test(&PLAYERS);
+            Self {
+                server,
+                inboxes,
+                registry,
+                scenario: ice_cave(),
+            }
+        }
+
+        fn room(&mut self) -> &mut Room {
+            self.registry.rooms.values_mut().next().unwrap()
+        }
+
+        fn narrate(&mut self, client: u64, versions: Vec<NarrativeVersion>, truth: Option<&str>) -> Result<(), ActionError> {
+            let event = NarrativeEvent {
+                versions,
+                truth: truth.map(str::to_string),
+                rumour: None,
+            };
+            let room = self.registry.rooms.values_mut().next().unwrap();
+            let players = ServerPlayers::default();
+            let clients = connected_clients(&PLAYERS);
+            narrate(&mut self.server, room, ClientId::from_raw(client), &event, &self.scenario, &players, &clients)
+        }
+
+        fn received(&mut self, client: u64) -> Vec<ServerMessage> {
+            self.inboxes.read(&mut self.server, client)
+        }
+    }
+
+    fn version(recipients: &[u64], text: &str) -> NarrativeVersion {
+        NarrativeVersion {
+            recipients: recipients.to_vec(),
+            text: NarrativeText::Free(text.to_string()),
+        }
+    }
+
+    fn told(text: &str) -> ServerMessage {
+        ServerMessage::Narrative { text: text.to_string() }
+    }
+
+    #[test]
+    fn each_version_reaches_only_its_recipients() {
+        let mut game = Match::new();
+        let versions = vec![
+            version(&[2, 3], "  Ted ate the last can  "),
+            NarrativeVersion {
+                recipients: vec![4],
+                text: NarrativeText::Line("no_food".to_string()),
+            },
+        ];
+        assert_eq!(game.narrate(1, versions, Some("Nobody ate anything")), Ok(()));
+
+        assert_eq!(game.received(2), [told("Ted ate the last can")]);
+        assert_eq!(game.received(3), [told("Ted ate the last can")]);
+        assert_eq!(game.received(4), [told("Did you think I would let you eat?")]);
+        // AM only gets its copy of each survivor's feed
+        let feed: Vec<_> = game
+            .received(1)
+            .into_iter()
+            .map(|message| match message {
+                ServerMessage::AmFeed { client_id, forgotten, .. } => (client_id, forgotten),
+                other => panic!("AM was sent {:?}", other),
+            })
+            .collect();
+        assert_eq!(feed, [(2, false), (3, false), (4, false)]);
+    }
+
+    #[test]
+    fn the_truth_is_kept_for_the_debrief() {
+        let mut game = Match::new();
+        let versions = vec![version(&[2], "The others left you behind")];
+        game.narrate(1, versions, Some("They are looking for you")).unwrap();
+
+        let record = game.room().narratives.records[0].clone();
+        assert_eq!(record.truth.as_deref(), Some("They are looking for you"));
+        let deliveries: Vec<_> = record.deliveries.iter().map(|delivery| (delivery.client_id, delivery.text.as_str())).collect();
+        assert_eq!(deliveries, [(2, "The others left you behind")]);
+        assert_eq!(reveal_message(game.room()), ServerMessage::NarrativeReveal(vec![record]));
+    }
+
+    #[test]
+    fn refuses_narratives_that_are_not_from_am_or_tell_someone_twice() {
+        let mut game = Match::new();
+        assert_eq!(game.narrate(2, vec![version(&[3], "Run")], None), Err(ActionError::NotAm));
+        assert_eq!(
+            game.narrate(1, vec![version(&[3], "Run"), version(&[3], "Hide")], None),
+            Err(ActionError::RecipientTwice)
+        );
+        assert_eq!(game.narrate(1, vec![version(&[1], "Run")], None), Err(ActionError::UnknownRecipient));
+        assert_eq!(game.narrate(1, vec![version(&[3], "   ")], None), Err(ActionError::EmptyNarrative));
+        assert!(game.received(3).is_empty());
+        assert!(game.room().narratives.records.is_empty());
+    }
+}
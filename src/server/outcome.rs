@@ -0,0 +1,258 @@
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+
+use crate::MatchPhase;
+use crate::protocol::{self, MatchEventKind, MatchOutcome, RoomPhase, ServerMessage};
+use crate::scenario::{Condition, ObjectiveKind, Scenario};
+use crate::server::rooms::Room;
+use crate::server::{match_log, phases, trust};
+use crate::server::{RoomRegistry, ServerPlayers, ServerScenario};
+
+// System to tick off objectives and end the match as soon as a win or lose condition holds.
+// A match that runs out of time without either goes to AM, one AM walked out of goes to the survivors.
+pub(super) fn check_outcomes(
+    scenario: Res<ServerScenario>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    players: Res<ServerPlayers>,
+) {
+    let scenario = &scenario.0;
+    for room in registry.rooms.values_mut().filter(|room| room.phase == RoomPhase::InProgress) {
+        let Some(phase) = room.clock.as_ref().map(|clock| clock.phase) else {
+            continue;
+        };
+        if room.outcome.is_some() {
+            continue;
+        }
+        if phase == MatchPhase::Debrief {
+            end_match(&mut server, room, scenario, MatchOutcome::AmWins);
+            continue;
+        }
+        // AM's slot was given up, there is nobody left to play against
+        if room.am().is_none() {
+            end_early(&mut server, room, scenario, MatchOutcome::SurvivorsWin);
+            continue;
+        }
+        if !phase.survivors_move() {
+            continue;
+        }
+        complete_objectives(room, scenario, &players);
+        let outcome = if scenario.win_conditions.iter().any(|condition| holds(room, scenario, condition)) {
+            MatchOutcome::SurvivorsWin
+        } else if scenario.lose_conditions.iter().any(|condition| holds(room, scenario, condition)) {
+            MatchOutcome::AmWins
+        } else {
+            continue;
+        };
+        end_early(&mut server, room, scenario, outcome);
+    }
+}
+
+// Straight to the debrief, there is nothing left to play for
+fn end_early(server: &mut RenetServer, room: &mut Room, scenario: &Scenario, outcome: MatchOutcome) {
+    if let Some(clock) = &mut room.clock {
+        *clock = phases::clock_for(scenario, MatchPhase::Debrief, clock.elapsed);
+        let message = phases::phase_message(clock);
+        for player in &room.players {
+            protocol::send_to_client(server, *player, &message);
+        }
+        match_log::record(room, MatchEventKind::PhaseStarted);
+    }
+    end_match(server, room, scenario, outcome);
+}
+
+fn complete_objectives(room: &mut Room, scenario: &Scenario, players: &ServerPlayers) {
+    let elapsed = room.clock.as_ref().map_or(0.0, |clock| clock.elapsed);
+    for objective in &scenario.objectives {
+        let done = match &objective.kind {
+            ObjectiveKind::Reach { position, radius } => trust::survivors(room).iter().any(|client_id| {
+                players
+                    .0
+                    .get(client_id)
+                    .map_or(false, |player| player.position.distance(*position) <= *radius)
+            }),
+            ObjectiveKind::Survive { secs } => elapsed >= *secs,
+        };
+        if done && room.objectives_done.insert(objective.id.clone()) {
+            println!("Room {} completed {}", room.name, objective.id);
+            let description = objective.description.clone();
+            match_log::record(room, MatchEventKind::ObjectiveCompleted { description });
+        }
+    }
+}
+
+fn holds(room: &Room, scenario: &Scenario, condition: &Condition) -> bool {
+    let survivors = trust::survivors(room);
+    match condition {
+        Condition::ObjectiveComplete(id) => room.objectives_done.contains(id),
+        Condition::AllObjectivesComplete => scenario
+            .objectives
+            .iter()
+            .all(|objective| room.objectives_done.contains(&objective.id)),
+        Condition::SurvivorsAlive { at_least } => {
+            survivors.iter().filter(|survivor| !room.wounds.is_dead(**survivor)).count() as u32 >= *at_least
+        }
+        Condition::SurvivorsDead { at_least } => {
+            survivors.iter().filter(|survivor| room.wounds.is_dead(**survivor)).count() as u32 >= *at_least
+        }
+        Condition::AllSurvivorsDead => {
+            !survivors.is_empty() && survivors.iter().all(|survivor| room.wounds.is_dead(*survivor))
+        }
+        Condition::TimeElapsed { secs } => room.clock.as_ref().map_or(false, |clock| clock.elapsed >= *secs),
+        Condition::TrustBelow { average } => room.trust.average(&survivors) < *average,
+        Condition::TrustAbove { average } => room.trust.average(&survivors) > *average,
+        Condition::VoteDecided { vote, option } => room
+            .votes
+            .results
+            .iter()
+            .any(|result| &result.vote == vote && result.winner.as_ref() == Some(option)),
+    }
+}
+
+pub(super) fn outcome_message(outcome: MatchOutcome, scenario: &Scenario) -> ServerMessage {
+    let text = match outcome {
+        MatchOutcome::SurvivorsWin => scenario.narrative.victory.clone(),
+        MatchOutcome::AmWins => scenario.narrative.defeat.clone(),
+    };
+    ServerMessage::MatchOver { outcome, text }
+}
+
+fn end_match(server: &mut RenetServer, room: &mut Room, scenario: &Scenario, outcome: MatchOutcome) {
+    println!("Room {} is over: {:?}", room.name, outcome);
+    room.outcome = Some(outcome);
+    match_log::record(room, MatchEventKind::MatchOver(outcome));
+    let message = outcome_message(outcome, scenario);
+    for player in &room.players {
+        protocol::send_to_client(server, *player, &message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_renet::renet::ClientId;
+
+    use super::*;
+    use crate::server::{combat, simulation};
+    use crate::server::testing::{ice_cave, received, server_app, Inboxes};
+
+    // Player 1 plays AM to survivors 2 to 4 in the middle of exploring
+    fn outcome_app(scenario: Scenario) -> (App, Inboxes) {
+        let (mut app, inboxes) = server_app(&[1, 2, 3, 4]);
+        let mut registry = RoomRegistry::default();
+        registry.running_for_test(&[1, 2, 3, 4]);
+        app.insert_resource(registry)
+            .insert_resource(ServerScenario(scenario))
+            .init_resource::<ServerPlayers>()
+            .add_systems(Update, (simulation::sync_server_players, check_outcomes).chain());
+        (app, inboxes)
+    }
+
+    fn room(app: &mut App) -> &mut Room {
+        app.world_mut().resource_mut::<RoomRegistry>().into_inner().rooms.values_mut().next().unwrap()
+    }
+
+    fn outcomes(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<ServerMessage> {
+        received(app, inboxes, client)
+            .into_iter()
+            .filter(|message| matches!(message, ServerMessage::MatchOver { .. }))
+            .collect()
+    }
+
+    #[test]
+    fn reaching_the_cache_wins_the_match() {
+        let mut scenario = ice_cave();
+        scenario.win_conditions = vec![Condition::ObjectiveComplete("reach_cache".to_string())];
+        let (mut app, mut inboxes) = outcome_app(scenario.clone());
+        app.update();
+        assert_eq!(room(&mut app).outcome, None);
+
+        let mut players = app.world_mut().resource_mut::<ServerPlayers>();
+        players.0.get_mut(&ClientId::from_raw(4)).unwrap().position = Vec2::new(700.0, 20.0);
+        app.update();
+        assert!(room(&mut app).objectives_done.contains("reach_cache"));
+        assert_eq!(room(&mut app).outcome, Some(MatchOutcome::SurvivorsWin));
+        assert_eq!(room(&mut app).clock.as_ref().map(|clock| clock.phase), Some(MatchPhase::Debrief));
+        for client in 1..=4 {
+            assert_eq!(
+                outcomes(&mut app, &mut inboxes, client),
+                [outcome_message(MatchOutcome::SurvivorsWin, &scenario)]
+            );
+        }
+        // Decided once
+        app.update();
+        assert!(outcomes(&mut app, &mut inboxes, 2).is_empty());
+    }
+
+    #[test]
+    fn survivors_who_stop_trusting_each_other_lose() {
+        let (mut app, mut inboxes) = outcome_app(ice_cave());
+        let survivors = [2, 3, 4].map(ClientId::from_raw);
+        app.world_mut().resource_scope(|world, mut registry: Mut<RoomRegistry>| {
+            let room = registry.rooms.values_mut().next().unwrap();
+            let players = world.resource::<ServerPlayers>();
+            for _ in 0..3 {
+                for by in survivors {
+                    for who in survivors.into_iter().filter(|who| *who != by) {
+                        trust::accusation(room, players, by, who);
+                    }
+                }
+            }
+        });
+        app.update();
+        assert_eq!(room(&mut app).outcome, Some(MatchOutcome::AmWins));
+        assert_eq!(outcomes(&mut app, &mut inboxes, 2), [outcome_message(MatchOutcome::AmWins, &ice_cave())]);
+    }
+
+    #[test]
+    fn only_the_dead_still_in_the_match_count() {
+        let mut scenario = ice_cave();
+        scenario.win_conditions = vec![Condition::TimeElapsed { secs: 1000.0 }];
+        scenario.lose_conditions = vec![Condition::SurvivorsDead { at_least: 1 }];
+        let (mut app, _) = outcome_app(scenario);
+        app.update();
+        let kill = |app: &mut App, client: u64| {
+            app.world_mut().resource_scope(|world, mut registry: Mut<RoomRegistry>| {
+                let room = registry.rooms.values_mut().next().unwrap();
+                let mut players = world.resource_mut::<ServerPlayers>();
+                combat::hurt(room, &mut players, ClientId::from_raw(client), 1000.0, None);
+            });
+        };
+
+        // Whoever died and then gave up their slot is no longer one of the survivors
+        kill(&mut app, 2);
+        app.world_mut().resource_mut::<RoomRegistry>().leave(ClientId::from_raw(2));
+        app.update();
+        assert_eq!(room(&mut app).outcome, None);
+
+        kill(&mut app, 3);
+        app.update();
+        assert_eq!(room(&mut app).outcome, Some(MatchOutcome::AmWins));
+    }
+
+    #[test]
+    fn the_survivors_win_once_am_gives_up_the_slot() {
+        let (mut app, mut inboxes) = outcome_app(ice_cave());
+        room(&mut app).clock.as_mut().unwrap().phase = MatchPhase::Briefing;
+        app.world_mut().resource_mut::<RoomRegistry>().leave(ClientId::from_raw(1));
+        app.update();
+        assert_eq!(room(&mut app).outcome, Some(MatchOutcome::SurvivorsWin));
+        assert_eq!(room(&mut app).clock.as_ref().map(|clock| clock.phase), Some(MatchPhase::Debrief));
+        assert_eq!(outcomes(&mut app, &mut inboxes, 2), [outcome_message(MatchOutcome::SurvivorsWin, &ice_cave())]);
+        assert!(outcomes(&mut app, &mut inboxes, 1).is_empty());
+    }
+
+    #[test]
+    fn nothing_is_decided_while_the_survivors_stand_still() {
+        let mut scenario = ice_cave();
+        scenario.win_conditions = vec![Condition::SurvivorsAlive { at_least: 1 }];
+        let (mut app, _) = outcome_app(scenario);
+        room(&mut app).clock.as_mut().unwrap().phase = MatchPhase::Briefing;
+        app.update();
+        assert_eq!(room(&mut app).outcome, None);
+
+        // Running out of time without a winner goes to AM
+        room(&mut app).clock.as_mut().unwrap().phase = MatchPhase::Debrief;
+        app.update();
+        assert_eq!(room(&mut app).outcome, Some(MatchOutcome::AmWins));
+    }
+}
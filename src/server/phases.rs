@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+
+use crate::MatchPhase;
+use crate::protocol::{self, MatchEventKind, RoomPhase, ServerMessage};
+use crate::scenario::Scenario;
+use crate::server::rooms::MatchClock;
+use crate::server::match_log;
+use crate::server::{RoomRegistry, ServerScenario};
+
+pub(super) fn start_clock(scenario: &Scenario) -> MatchClock {
+    clock_for(scenario, MatchPhase::default(), 0.0)
+}
+
+pub(super) fn clock_for(scenario: &Scenario, phase: MatchPhase, elapsed: f32) -> MatchClock {
+    MatchClock {
+        phase,
+        timer: Timer::from_seconds(scenario.phases.secs(phase), TimerMode::Once),
+        elapsed,
+    }
+}
+
+pub(super) fn phase_message(clock: &MatchClock) -> ServerMessage {
+    ServerMessage::MatchPhase {
+        phase: clock.phase,
+        seconds_left: clock.timer.remaining_secs(),
+    }
+}
+
+// System to move every running match to its next phase once the current one runs out
+pub(super) fn advance_match_phases(
+    time: Res<Time>,
+    scenario: Res<ServerScenario>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+) {
+    for room in registry.rooms.values_mut().filter(|room| room.phase == RoomPhase::InProgress) {
+        let Some(clock) = &mut room.clock else {
+            continue;
+        };
+        clock.elapsed += time.delta_seconds();
+        if !clock.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        // The debrief is the last phase, it simply stays at zero
+        let Some(next) = clock.phase.next() else {
+            continue;
+        };
+        *clock = clock_for(&scenario.0, next, clock.elapsed);
+        println!("Room {} moves to {}", room.name, next.name());
+        let message = phase_message(clock);
+        for player in &room.players {
+            protocol::send_to_client(&mut server, *player, &message);
+        }
+        match_log::record(room, MatchEventKind::PhaseStarted);
+    }
+}
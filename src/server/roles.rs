@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetServer};
+use rand::seq::{IteratorRandom, SliceRandom};
+
+use crate::components::role::Role;
+use crate::protocol::{
+    self, AmSelection, ClientMessage, LineupEntry, RoleAssignment, RoomError, RoomId, RoomPhase, ServerMessage,
+};
+use crate::server::draft;
+use crate::server::rooms::{Draft, Room};
+use crate::server::{ConnectedClients, FromClient, RoomRegistry, ServerScenario, ServerSettings};
+
+// AM plus at least one survivor
+const MIN_PLAYERS_PER_MATCH: usize = 2;
+
+// System to apply the host's role setup and the players' volunteering, then open the draft
+pub(super) fn handle_role_messages(
+    mut from_client: EventReader<FromClient>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    clients: Res<ConnectedClients>,
+    settings: Res<ServerSettings>,
+    scenario: Res<ServerScenario>,
+) {
+    for FromClient { client_id, message } in from_client.read() {
+        let client_id = *client_id;
+        let Some(room_id) = registry.membership.get(&client_id).copied() else {
+            continue;
+        };
+        let Some(room) = registry.rooms.get_mut(&room_id) else {
+            continue;
+        };
+        let result = match message {
+            ClientMessage::SetAmSelection(selection) => check_setup(room, client_id).map(|_| {
+                room.am_selection = *selection;
+            }),
+            ClientMessage::PickAm { client_id: picked } => check_setup(room, client_id).and_then(|_| {
+                let picked = ClientId::from_raw(*picked);
+                if !room.players.contains(&picked) {
+                    return Err(RoomError::NotFound);
+                }
+                room.am_pick = Some(picked);
+                Ok(())
+            }),
+            ClientMessage::Volunteer(volunteer) => {
+                if room.phase != RoomPhase::Waiting {
+                    Err(RoomError::AlreadyStarted)
+                } else {
+                    if *volunteer {
+                        room.volunteers.insert(client_id);
+                    } else {
+                        room.volunteers.remove(&client_id);
+                    }
+                    Ok(())
+                }
+            }
+            ClientMessage::StartMatch => {
+                check_setup(room, client_id).and_then(|_| start_match(room, settings.draft_secs))
+            }
+            _ => continue,
+        };
+
+        match result {
+            Ok(()) if matches!(message, ClientMessage::StartMatch) => {
+                println!("Room {} is drafting", room.name);
+                let messages = [draft::sheets_message(&scenario.0), draft::draft_message(room, &clients)];
+                for player in &room.players {
+                    for message in &messages {
+                        protocol::send_to_client(&mut server, *player, message);
+                    }
+                }
+            }
+            Ok(()) => {}
+            Err(error) => protocol::send_to_client(&mut server, client_id, &ServerMessage::RoomError(error)),
+        }
+    }
+}
+
+pub(super) fn role_assignments(room: &Room, clients: &ConnectedClients) -> Vec<RoleAssignment> {
+    room.players
+        .iter()
+        .filter_map(|player| {
+            room.roles.get(player).map(|role| RoleAssignment {
+                client_id: player.raw(),
+                name: clients.0.get(player).map(|info| info.name.clone()).unwrap_or_default(),
+                role: *role,
+            })
+        })
+        .collect()
+}
+
+fn check_setup(room: &Room, client_id: ClientId) -> Result<(), RoomError> {
+    if room.phase != RoomPhase::Waiting {
+        return Err(RoomError::AlreadyStarted);
+    }
+    if room.host != client_id {
+        return Err(RoomError::NotHost);
+    }
+    Ok(())
+}
+
+// Chooses AM the way the room asked for, everyone else drafts a survivor
+fn start_match(room: &mut Room, draft_secs: u32) -> Result<(), RoomError> {
+    if room.players.len() < MIN_PLAYERS_PER_MATCH {
+        return Err(RoomError::NotEnoughPlayers);
+    }
+    let mut rng = rand::thread_rng();
+    let am = match room.am_selection {
+        AmSelection::HostPicks => room.am_pick.ok_or(RoomError::NoAmPicked)?,
+        AmSelection::Random => *room.players.choose(&mut rng).ok_or(RoomError::NotEnoughPlayers)?,
+        AmSelection::Volunteers => *room
+            .players
+            .iter()
+            .filter(|player| room.volunteers.contains(player))
+            .choose(&mut rng)
+            .ok_or(RoomError::NoVolunteers)?,
+    };
+
+    room.roles.clear();
+    room.roles.insert(am, Role::Am);
+    room.draft = Some(Draft {
+        picks: HashMap::new(),
+        timer: Timer::from_seconds(draft_secs as f32, TimerMode::Once),
+    });
+    room.phase = RoomPhase::Drafting;
+    Ok(())
+}
+
+// System to send every waiting room its lineup whenever it changed
+pub(super) fn broadcast_lineups(
+    mut server: ResMut<RenetServer>,
+    registry: Res<RoomRegistry>,
+    clients: Res<ConnectedClients>,
+    mut last_sent: Local<HashMap<RoomId, ServerMessage>>,
+) {
+    last_sent.retain(|id, _| registry.rooms.get(id).map_or(false, |room| room.phase == RoomPhase::Waiting));
+    for room in registry.rooms.values().filter(|room| room.phase == RoomPhase::Waiting) {
+        let lineup = ServerMessage::Lineup {
+            selection: room.am_selection,
+            am_pick: room.am_pick.map(|pick| pick.raw()),
+            players: room
+                .players
+                .iter()
+                .map(|player| LineupEntry {
+                    client_id: player.raw(),
+                    name: clients.0.get(player).map(|info| info.name.clone()).unwrap_or_default(),
+                    is_host: room.host == *player,
+                    volunteered: room.volunteers.contains(player),
+                })
+                .collect(),
+        };
+        if last_sent.get(&room.id) == Some(&lineup) {
+            continue;
+        }
+        for player in &room.players {
+            protocol::send_to_client(&mut server, *player, &lineup);
+        }
+        last_sent.insert(room.id, lineup);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Starts a match in a room of the given players once the setup has been applied to it
+    fn start(players: &[u64], setup: impl FnOnce(&mut Room)) -> Result<HashMap<ClientId, Role>, RoomError> {
+        let mut registry = RoomRegistry::default();
+        let id = registry.open_for_test(players);
+        let room = registry.rooms.get_mut(&id).unwrap();
+        setup(room);
+        start_match(room, 30).map(|_| room.roles.clone())
+    }
+
+    fn am_of(roles: &HashMap<ClientId, Role>) -> u64 {
+        let ams: Vec<_> = roles.iter().filter(|(_, role)| **role == Role::Am).collect();
+        assert_eq!(ams.len(), 1, "expected exactly one AM in {:?}", roles);
+        ams[0].0.raw()
+    }
+
+    #[test]
+    fn every_way_of_choosing_seats_exactly_one_am() {
+        let players = [1, 2, 3, 4];
+        for _ in 0..20 {
+            let roles = start(&players, |room| room.am_selection = AmSelection::Random).unwrap();
+            assert!(players.contains(&am_of(&roles)));
+
+            let roles = start(&players, |room| {
+                room.am_selection = AmSelection::Volunteers;
+                room.volunteers.extend([ClientId::from_raw(2), ClientId::from_raw(3)]);
+            })
+            .unwrap();
+            assert!([2, 3].contains(&am_of(&roles)));
+
+            let roles = start(&players, |room| room.am_pick = Some(ClientId::from_raw(4))).unwrap();
+            assert_eq!(am_of(&roles), 4);
+        }
+    }
+
+    #[test]
+    fn everyone_else_goes_on_to_draft_a_survivor() {
+        let mut registry = RoomRegistry::default();
+        let id = registry.open_for_test(&[1, 2, 3]);
+        let room = registry.rooms.get_mut(&id).unwrap();
+        room.am_pick = Some(ClientId::from_raw(1));
+        start_match(room, 30).unwrap();
+        assert_eq!(room.phase, RoomPhase::Drafting);
+        assert_eq!(room.roles.len(), 1);
+        let draft = room.draft.as_ref().unwrap();
+        assert!(draft.picks.is_empty());
+        assert_eq!(draft.timer.duration().as_secs(), 30);
+    }
+
+    #[test]
+    fn refuses_to_start_without_an_am() {
+        assert_eq!(start(&[1, 2], |_| {}), Err(RoomError::NoAmPicked));
+        assert_eq!(
+            start(&[1, 2], |room| room.am_selection = AmSelection::Volunteers),
+            Err(RoomError::NoVolunteers)
+        );
+        assert_eq!(
+            start(&[1], |room| room.am_pick = Some(ClientId::from_raw(1))),
+            Err(RoomError::NotEnoughPlayers)
+        );
+    }
+
+    #[test]
+    fn only_the_host_sets_up_a_waiting_room() {
+        let mut registry = RoomRegistry::default();
+        let id = registry.open_for_test(&[1, 2]);
+        let room = registry.rooms.get_mut(&id).unwrap();
+        assert_eq!(check_setup(room, ClientId::from_raw(1)), Ok(()));
+        assert_eq!(check_setup(room, ClientId::from_raw(2)), Err(RoomError::NotHost));
+        room.phase = RoomPhase::InProgress;
+        assert_eq!(check_setup(room, ClientId::from_raw(1)), Err(RoomError::AlreadyStarted));
+    }
+}
@@ -0,0 +1,585 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetServer};
+use rand::Rng;
+
+use crate::MatchPhase;
+use crate::components::role::{Character, Role};
+use crate::consts;
+use crate::protocol::{self, AmSelection, ClientMessage, Illusion, MatchOutcome, NarrativeRecord, RoomError, RoomId, RoomInfo, RoomPhase, ServerMessage};
+use crate::resources::world_effects::WorldEffects;
+use crate::server::chat::ChatLog;
+use crate::server::combat::Wounds;
+use crate::server::match_log::MatchLog;
+use crate::server::sanity::SanityLevels;
+use crate::server::trust::TrustMatrix;
+use crate::server::votes::VoteBox;
+use crate::server::{ConnectedClients, FromClient, ServerSettings};
+
+// Letters and digits that cannot be mistaken for each other when read out loud
+const JOIN_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const JOIN_CODE_LENGTH: usize = 6;
+
+pub struct Room {
+    pub id: RoomId,
+    pub name: String,
+    pub is_private: bool,
+    // Only private rooms get a code, and only private rooms can be locked with a password
+    pub join_code: Option<String>,
+    pub password: Option<String>,
+    pub host: ClientId,
+    pub players: Vec<ClientId>,
+    pub phase: RoomPhase,
+    pub am_selection: AmSelection,
+    pub am_pick: Option<ClientId>,
+    pub volunteers: HashSet<ClientId>,
+    // Filled in when the match starts, exactly one entry is Role::Am
+    pub roles: HashMap<ClientId, Role>,
+    pub draft: Option<Draft>,
+    pub clock: Option<MatchClock>,
+    pub narratives: NarrativeLog,
+    pub illusions: Vec<Illusion>,
+    pub next_illusion: u32,
+    // AM's spending money, regenerates over the match
+    pub hate: f32,
+    // Seconds left before each intervention can be used again
+    pub cooldowns: HashMap<String, f32>,
+    pub effects: WorldEffects,
+    pub trust: TrustMatrix,
+    pub sanity: SanityLevels,
+    pub wounds: Wounds,
+    // Points AM earned for the distrust between the survivors
+    pub am_score: f32,
+    pub objectives_done: HashSet<String>,
+    pub votes: VoteBox,
+    pub chat: ChatLog,
+    pub log: MatchLog,
+    pub outcome: Option<MatchOutcome>,
+}
+
+// Character picks of the survivors while the room is drafting
+pub struct Draft {
+    pub picks: HashMap<ClientId, Character>,
+    pub timer: Timer,
+}
+
+// Phase a running match is in and how long that phase has left
+pub struct MatchClock {
+    pub phase: MatchPhase,
+    pub timer: Timer,
+    // Seconds since the match started, across phases
+    pub elapsed: f32,
+}
+
+// Ground truth of everything AM told the survivors
+#[derive(Default)]
+pub struct NarrativeLog {
+    pub records: Vec<NarrativeRecord>,
+    pub revealed: bool,
+}
+
+impl Room {
+    pub fn am(&self) -> Option<ClientId> {
+        self.roles.iter().find(|(_, role)| **role == Role::Am).map(|(client_id, _)| *client_id)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.players.len() >= consts::MAX_PLAYERS_PER_ROOM
+    }
+
+    fn check_joinable(&self, client_id: ClientId) -> Result<(), RoomError> {
+        if self.players.contains(&client_id) {
+            return Ok(());
+        }
+        if self.phase != RoomPhase::Waiting {
+            return Err(RoomError::AlreadyStarted);
+        }
+        if self.is_full() {
+            return Err(RoomError::Full);
+        }
+        Ok(())
+    }
+
+    fn check_password(&self, password: Option<&str>) -> Result<(), RoomError> {
+        match (&self.password, password) {
+            (None, _) => Ok(()),
+            (Some(_), None) => Err(RoomError::PasswordRequired),
+            (Some(expected), Some(given)) if expected == given => Ok(()),
+            (Some(_), Some(_)) => Err(RoomError::WrongPassword),
+        }
+    }
+}
+
+// Every room hosted by this server and which room each client sits in
+#[derive(Resource, Default)]
+pub struct RoomRegistry {
+    next_id: u32,
+    pub rooms: HashMap<RoomId, Room>,
+    pub membership: HashMap<ClientId, RoomId>,
+}
+
+impl RoomRegistry {
+    pub fn room_of(&self, client_id: ClientId) -> Option<&Room> {
+        self.membership.get(&client_id).and_then(|id| self.rooms.get(id))
+    }
+
+    fn room_with_code(&self, code: &str) -> Option<RoomId> {
+        self.rooms
+            .values()
+            .find(|room| room.join_code.as_deref() == Some(code))
+            .map(|room| room.id)
+    }
+
+    fn generate_join_code(&self) -> String {
+        let mut rng = rand::thread_rng();
+        loop {
+            let code: String = (0..JOIN_CODE_LENGTH)
+                .map(|_| JOIN_CODE_ALPHABET[rng.gen_range(0..JOIN_CODE_ALPHABET.len())] as char)
+                .collect();
+            if self.room_with_code(&code).is_none() {
+                return code;
+            }
+        }
+    }
+
+    fn create(&mut self, name: String, is_private: bool, password: Option<String>, host: ClientId) -> RoomId {
+        self.next_id += 1;
+        let id = RoomId(self.next_id);
+        let join_code = is_private.then(|| self.generate_join_code());
+        self.rooms.insert(
+            id,
+            Room {
+                id,
+                name,
+                is_private,
+                join_code,
+                password: if is_private { password } else { None },
+                host,
+                players: vec![host],
+                phase: RoomPhase::Waiting,
+                am_selection: AmSelection::default(),
+                am_pick: None,
+                volunteers: HashSet::new(),
+                roles: HashMap::new(),
+                draft: None,
+                clock: None,
+                narratives: NarrativeLog::default(),
+                illusions: Vec::new(),
+                next_illusion: 0,
+                hate: 0.0,
+                cooldowns: HashMap::new(),
+                effects: WorldEffects::default(),
+                trust: TrustMatrix::default(),
+                sanity: SanityLevels::default(),
+                wounds: Wounds::default(),
+                am_score: 0.0,
+                objectives_done: HashSet::new(),
+                votes: VoteBox::default(),
+                chat: ChatLog::default(),
+                log: MatchLog::default(),
+                outcome: None,
+            },
+        );
+        self.membership.insert(host, id);
+        id
+    }
+
+    // Removes the client from its room, handing the room to the next player or closing it when empty
+    pub(super) fn leave(&mut self, client_id: ClientId) {
+        let Some(id) = self.membership.remove(&client_id) else {
+            return;
+        };
+        let Some(room) = self.rooms.get_mut(&id) else {
+            return;
+        };
+        room.players.retain(|player| *player != client_id);
+        room.volunteers.remove(&client_id);
+        room.roles.remove(&client_id);
+        if let Some(draft) = &mut room.draft {
+            draft.picks.remove(&client_id);
+        }
+        if room.am_pick == Some(client_id) {
+            room.am_pick = None;
+        }
+        if room.players.is_empty() {
+            println!("Closing empty room {}", room.name);
+            self.rooms.remove(&id);
+        } else if room.host == client_id {
+            room.host = room.players[0];
+        }
+    }
+
+    // Moves the client into a room it has already been allowed into
+    fn join(&mut self, client_id: ClientId, id: RoomId) {
+        if self.membership.get(&client_id) == Some(&id) {
+            return;
+        }
+        self.leave(client_id);
+        if let Some(room) = self.rooms.get_mut(&id) {
+            room.players.push(client_id);
+            self.membership.insert(client_id, id);
+            println!("Client {} joined room {}", client_id, room.name);
+        }
+    }
+
+    // Private rooms are only reachable through their join code
+    pub fn list(&self, clients: &ConnectedClients) -> Vec<RoomInfo> {
+        let mut list: Vec<RoomInfo> = self
+            .rooms
+            .values()
+            .filter(|room| !room.is_private)
+            .map(|room| RoomInfo {
+                id: room.id,
+                name: room.name.clone(),
+                is_private: room.is_private,
+                host_name: clients.0.get(&room.host).map(|info| info.name.clone()).unwrap_or_default(),
+                player_count: room.players.len() as u8,
+                max_players: consts::MAX_PLAYERS_PER_ROOM as u8,
+                phase: room.phase,
+            })
+            .collect();
+        list.sort_by_key(|info| info.id.0);
+        list
+    }
+}
+
+// System to answer room requests from clients
+pub(super) fn handle_room_messages(
+    mut from_client: EventReader<FromClient>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    clients: Res<ConnectedClients>,
+    settings: Res<ServerSettings>,
+) {
+    for FromClient { client_id, message } in from_client.read() {
+        let client_id = *client_id;
+        match message {
+            ClientMessage::ListRooms => {
+                let list = registry.list(&clients);
+                protocol::send_to_client(&mut server, client_id, &ServerMessage::RoomList(list));
+            }
+            ClientMessage::CreateRoom { name, is_private, password } => {
+                let name = name.trim();
+                if name.is_empty() {
+                    send_error(&mut server, client_id, RoomError::EmptyName);
+                    continue;
+                }
+                if name.chars().count() > protocol::MAX_ROOM_NAME_CHARS {
+                    send_error(&mut server, client_id, RoomError::NameTooLong);
+                    continue;
+                }
+                // The creator leaves their current room first, which closes it if they were alone in it
+                let freed = registry.room_of(client_id).map_or(0, |room| (room.players == [client_id]) as usize);
+                if registry.rooms.len() - freed >= settings.max_rooms {
+                    send_error(&mut server, client_id, RoomError::NoMoreRooms);
+                    continue;
+                }
+                let password = password.as_ref().filter(|password| !password.is_empty()).cloned();
+                registry.leave(client_id);
+                let id = registry.create(name.to_string(), *is_private, password, client_id);
+                println!("Client {} created room {} ({:?})", client_id, name, id);
+                send_joined(&mut server, client_id, &registry.rooms[&id]);
+            }
+            ClientMessage::JoinRoom { room } => {
+                let result = match registry.rooms.get(room) {
+                    Some(target) if !target.is_private => target.check_joinable(client_id),
+                    _ => Err(RoomError::NotFound),
+                };
+                match result {
+                    Ok(()) => {
+                        registry.join(client_id, *room);
+                        send_joined(&mut server, client_id, &registry.rooms[room]);
+                    }
+                    Err(error) => send_error(&mut server, client_id, error),
+                }
+            }
+            ClientMessage::JoinByCode { code, password } => {
+                let code = code.trim().to_uppercase();
+                let result = match registry.room_with_code(&code) {
+                    Some(id) => {
+                        let target = &registry.rooms[&id];
+                        target
+                            .check_password(password.as_deref())
+                            .and_then(|_| target.check_joinable(client_id))
+                            .map(|_| id)
+                    }
+                    None => Err(RoomError::InvalidCode),
+                };
+                match result {
+                    Ok(id) => {
+                        registry.join(client_id, id);
+                        send_joined(&mut server, client_id, &registry.rooms[&id]);
+                    }
+                    Err(error) => send_error(&mut server, client_id, error),
+                }
+            }
+            ClientMessage::LeaveRoom => {
+                registry.leave(client_id);
+            }
+            _ => {}
+        }
+    }
+}
+
+pub(super) fn send_joined(server: &mut RenetServer, client_id: ClientId, room: &Room) {
+    protocol::send_to_client(
+        server,
+        client_id,
+        &ServerMessage::RoomJoined {
+            room: room.id,
+            name: room.name.clone(),
+            join_code: room.join_code.clone(),
+            phase: room.phase,
+        },
+    );
+}
+
+fn send_error(server: &mut RenetServer, client_id: ClientId, error: RoomError) {
+    protocol::send_to_client(server, client_id, &ServerMessage::RoomError(error));
+}
+
+#[cfg(test)]
+impl RoomRegistry {
+    // A waiting room hosted by the first of the players, with the rest already in it
+    pub(super) fn open_for_test(&mut self, players: &[u64]) -> RoomId {
+        let id = self.create("Test room".to_string(), false, None, ClientId::from_raw(players[0]));
+        for player in &players[1..] {
+            self.join(ClientId::from_raw(*player), id);
+        }
+        id
+    }
+
+    // A match exploring with the first player as AM and the others as survivors, in the order of Character::ALL
+    pub(super) fn running_for_test(&mut self, players: &[u64]) -> RoomId {
+        let id = self.open_for_test(players);
+        let room = self.rooms.get_mut(&id).unwrap();
+        room.phase = RoomPhase::InProgress;
+        room.clock = Some(MatchClock {
+            phase: MatchPhase::Exploration,
+            timer: Timer::from_seconds(600.0, TimerMode::Once),
+            elapsed: 0.0,
+        });
+        room.roles.insert(ClientId::from_raw(players[0]), Role::Am);
+        for (player, character) in players[1..].iter().zip(Character::ALL) {
+            room.roles.insert(ClientId::from_raw(*player), Role::Survivor(character));
+        }
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::testing::{received, send, server_app, Inboxes};
+
+    fn rooms_app(clients: &[u64], max_rooms: usize) -> (App, Inboxes) {
+        let (mut app, inboxes) = server_app(clients);
+        app.init_resource::<RoomRegistry>()
+            .insert_resource(ServerSettings {
+                max_rooms,
+                ..Default::default()
+            })
+            .add_systems(Update, handle_room_messages);
+        (app, inboxes)
+    }
+
+    fn create(app: &mut App, client: u64, name: &str) {
+        create_private(app, client, name, false, None);
+    }
+
+    fn create_private(app: &mut App, client: u64, name: &str, is_private: bool, password: Option<&str>) {
+        send(
+            app,
+            client,
+            ClientMessage::CreateRoom {
+                name: name.to_string(),
+                is_private,
+                password: password.map(str::to_string),
+            },
+        );
+        app.update();
+    }
+
+    fn join_code(app: &App, client: u64) -> String {
+        let registry = app.world().resource::<RoomRegistry>();
+        registry.room_of(ClientId::from_raw(client)).unwrap().join_code.clone().unwrap()
+    }
+
+    fn join_by_code(app: &mut App, client: u64, code: &str, password: Option<&str>) {
+        send(
+            app,
+            client,
+            ClientMessage::JoinByCode {
+                code: code.to_string(),
+                password: password.map(str::to_string),
+            },
+        );
+        app.update();
+    }
+
+    fn room_of(app: &App, client: u64) -> Option<RoomId> {
+        app.world().resource::<RoomRegistry>().room_of(ClientId::from_raw(client)).map(|room| room.id)
+    }
+
+    fn room_list(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<RoomInfo> {
+        send(app, client, ClientMessage::ListRooms);
+        app.update();
+        received(app, inboxes, client)
+            .into_iter()
+            .find_map(|message| match message {
+                ServerMessage::RoomList(list) => Some(list),
+                _ => None,
+            })
+            .expect("no room list")
+    }
+
+    fn joined(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Option<(RoomId, String)> {
+        received(app, inboxes, client).into_iter().find_map(|message| match message {
+            ServerMessage::RoomJoined { room, name, .. } => Some((room, name)),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn lists_rooms_in_the_order_they_were_made() {
+        let (mut app, mut inboxes) = rooms_app(&[1, 2, 3], 8);
+        create(&mut app, 1, "First");
+        create(&mut app, 2, "  Second  ");
+        let list = room_list(&mut app, &mut inboxes, 3);
+        let names: Vec<_> = list.iter().map(|info| (info.name.as_str(), info.host_name.as_str())).collect();
+        assert_eq!(names, [("First", "Player 1"), ("Second", "Player 2")]);
+        assert!(list.iter().all(|info| info.player_count == 1 && info.is_joinable()));
+    }
+
+    #[test]
+    fn refuses_rooms_past_the_limit_or_without_a_name() {
+        let (mut app, mut inboxes) = rooms_app(&[1, 2], 1);
+        create(&mut app, 1, "   ");
+        assert_eq!(room_of(&app, 1), None);
+        create(&mut app, 1, "Only");
+        create(&mut app, 2, "One too many");
+        assert_eq!(room_of(&app, 2), None);
+        let errors: Vec<_> = received(&mut app, &mut inboxes, 2)
+            .into_iter()
+            .filter(|message| matches!(message, ServerMessage::RoomError(_)))
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(app.world().resource::<RoomRegistry>().rooms.len(), 1);
+    }
+
+    #[test]
+    fn refuses_names_longer_than_the_limit() {
+        let (mut app, mut inboxes) = rooms_app(&[1, 2], 8);
+        let longest = "x".repeat(protocol::MAX_ROOM_NAME_CHARS);
+        create(&mut app, 1, &format!("{}y", longest));
+        assert_eq!(room_of(&app, 1), None);
+        assert_eq!(received(&mut app, &mut inboxes, 1), [ServerMessage::RoomError(RoomError::NameTooLong)]);
+        create(&mut app, 2, &longest);
+        assert!(room_of(&app, 2).is_some());
+    }
+
+    #[test]
+    fn joining_moves_the_player_into_the_room() {
+        let (mut app, mut inboxes) = rooms_app(&[1, 2], 8);
+        create(&mut app, 1, "Cave");
+        let room = room_of(&app, 1).unwrap();
+        send(&mut app, 2, ClientMessage::JoinRoom { room });
+        app.update();
+        assert_eq!(room_of(&app, 2), Some(room));
+        assert_eq!(joined(&mut app, &mut inboxes, 2), Some((room, "Cave".to_string())));
+    }
+
+    #[test]
+    fn refuses_rooms_that_started_or_are_gone() {
+        let (mut app, mut inboxes) = rooms_app(&[1, 2], 8);
+        create(&mut app, 1, "Cave");
+        let room = room_of(&app, 1).unwrap();
+        app.world_mut().resource_mut::<RoomRegistry>().rooms.get_mut(&room).unwrap().phase = RoomPhase::InProgress;
+        send(&mut app, 2, ClientMessage::JoinRoom { room });
+        send(&mut app, 2, ClientMessage::JoinRoom { room: RoomId(99) });
+        app.update();
+        assert_eq!(room_of(&app, 2), None);
+        assert_eq!(
+            received(&mut app, &mut inboxes, 2),
+            [
+                ServerMessage::RoomError(RoomError::AlreadyStarted),
+                ServerMessage::RoomError(RoomError::NotFound),
+            ]
+        );
+    }
+
+    #[test]
+    fn private_rooms_stay_off_the_list_and_out_of_reach_by_id() {
+        let (mut app, mut inboxes) = rooms_app(&[1, 2, 3], 8);
+        create(&mut app, 1, "Open");
+        create_private(&mut app, 2, "Hidden", true, None);
+        let list = room_list(&mut app, &mut inboxes, 3);
+        assert_eq!(list.iter().map(|info| info.name.as_str()).collect::<Vec<_>>(), ["Open"]);
+
+        let hidden = room_of(&app, 2).unwrap();
+        send(&mut app, 3, ClientMessage::JoinRoom { room: hidden });
+        app.update();
+        assert_eq!(room_of(&app, 3), None);
+        assert_eq!(received(&mut app, &mut inboxes, 3), [ServerMessage::RoomError(RoomError::NotFound)]);
+    }
+
+    #[test]
+    fn private_rooms_let_in_whoever_has_the_code() {
+        let (mut app, mut inboxes) = rooms_app(&[1, 2], 8);
+        create_private(&mut app, 1, "Hidden", true, None);
+        let code = join_code(&app, 1);
+        assert_eq!(code.len(), JOIN_CODE_LENGTH);
+        // Codes are read out loud, so case and stray spaces do not matter
+        join_by_code(&mut app, 2, &format!(" {} ", code.to_lowercase()), None);
+        assert_eq!(room_of(&app, 2), room_of(&app, 1));
+        assert!(joined(&mut app, &mut inboxes, 2).is_some());
+    }
+
+    #[test]
+    fn refuses_wrong_codes_and_passwords() {
+        let (mut app, mut inboxes) = rooms_app(&[1, 2], 8);
+        create_private(&mut app, 1, "Locked", true, Some("icecave"));
+        let code = join_code(&app, 1);
+        let wrong_code = if code == "AAAAAA" { "BBBBBB" } else { "AAAAAA" };
+        join_by_code(&mut app, 2, wrong_code, Some("icecave"));
+        join_by_code(&mut app, 2, &code, None);
+        join_by_code(&mut app, 2, &code, Some("IceCave"));
+        assert_eq!(room_of(&app, 2), None);
+        assert_eq!(
+            received(&mut app, &mut inboxes, 2),
+            [
+                ServerMessage::RoomError(RoomError::InvalidCode),
+                ServerMessage::RoomError(RoomError::PasswordRequired),
+                ServerMessage::RoomError(RoomError::WrongPassword),
+            ]
+        );
+
+        join_by_code(&mut app, 2, &code, Some("icecave"));
+        assert_eq!(room_of(&app, 2), room_of(&app, 1));
+    }
+
+    #[test]
+    fn only_private_rooms_keep_a_password() {
+        let mut registry = RoomRegistry::default();
+        let open = registry.create("Open".to_string(), false, Some("secret".to_string()), ClientId::from_raw(1));
+        assert_eq!(registry.rooms[&open].password, None);
+        assert_eq!(registry.rooms[&open].join_code, None);
+        assert_eq!(registry.rooms[&open].check_password(None), Ok(()));
+    }
+
+    #[test]
+    fn the_host_hands_the_room_over_and_the_last_one_out_closes_it() {
+        let mut registry = RoomRegistry::default();
+        let (host, guest) = (ClientId::from_raw(1), ClientId::from_raw(2));
+        let id = registry.create("Cave".to_string(), false, None, host);
+        registry.rooms.get_mut(&id).unwrap().players.push(guest);
+        registry.membership.insert(guest, id);
+
+        registry.leave(host);
+        assert_eq!(registry.rooms[&id].host, guest);
+        assert_eq!(registry.room_of(host).map(|room| room.id), None);
+        registry.leave(guest);
+        assert!(registry.rooms.is_empty());
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetServer};
+
+use crate::components::character_sheet::CharacterSheet;
+use crate::components::role::Role;
+use crate::protocol::{self, InterventionAction, MatchEventKind, RoomPhase, SanityLevel, ServerMessage};
+use crate::scenario::{InterventionKind, Scenario};
+use crate::server::rooms::Room;
+use crate::server::{match_log, trust};
+use crate::server::{RoomRegistry, ServerPlayers, ServerScenario};
+
+// How often survivors are told their sanity
+const SEND_INTERVAL_SECS: f32 = 0.5;
+
+// What is left of each survivor's mind. Survivors not in here are still whole.
+#[derive(Default)]
+pub struct SanityLevels {
+    values: HashMap<ClientId, f32>,
+    // What each survivor was last told
+    sent: HashMap<ClientId, f32>,
+}
+
+// Survivors only have a sheet while they have a body, the one the scenario handed them
+fn sheet<'a>(room: &Room, players: &'a ServerPlayers, client_id: ClientId) -> Option<&'a CharacterSheet> {
+    match room.roles.get(&client_id) {
+        Some(Role::Survivor(_)) => players.0.get(&client_id).map(|player| &player.sheet),
+        _ => None,
+    }
+}
+
+pub(super) fn level(room: &Room, players: &ServerPlayers, client_id: ClientId) -> Option<SanityLevel> {
+    let sheet = sheet(room, players, client_id)?;
+    Some(SanityLevel {
+        client_id: client_id.raw(),
+        value: room.sanity.values.get(&client_id).copied().unwrap_or(sheet.max_sanity),
+        max: sheet.max_sanity,
+    })
+}
+
+// How far gone the survivor is, see CharacterSheet::madness
+pub(super) fn madness(room: &Room, players: &ServerPlayers, client_id: ClientId) -> f32 {
+    match (sheet(room, players, client_id), level(room, players, client_id)) {
+        (Some(sheet), Some(level)) => sheet.madness(level.value),
+        _ => 0.0,
+    }
+}
+
+fn change(room: &mut Room, players: &ServerPlayers, client_id: ClientId, delta: f32) {
+    let (Some(sheet), Some(level)) = (sheet(room, players, client_id), level(room, players, client_id)) else {
+        return;
+    };
+    let value = (level.value + delta).clamp(0.0, level.max);
+    room.sanity.values.insert(client_id, value);
+    let breaking_point = sheet.max_sanity * sheet.breaking_point;
+    if level.value >= breaking_point && value < breaking_point {
+        match_log::record(room, MatchEventKind::Broke { who: client_id.raw() });
+    }
+}
+
+// Takes sanity from a survivor, their sheet decides how much it really costs
+pub(super) fn lose(room: &mut Room, players: &ServerPlayers, client_id: ClientId, amount: f32) {
+    let Some(sheet) = sheet(room, players, client_id) else {
+        return;
+    };
+    change(room, players, client_id, -sheet.sanity_loss(amount));
+}
+
+// Survivors an intervention lands on. Locked doors trap everyone.
+pub(super) fn intervention_victims(
+    room: &Room,
+    players: &ServerPlayers,
+    kind: &InterventionKind,
+    action: &InterventionAction,
+) -> Vec<ClientId> {
+    match (action, kind) {
+        (InterventionAction::Narrate(event), _) => {
+            let mut victims: Vec<ClientId> = event
+                .versions
+                .iter()
+                .flat_map(|version| &version.recipients)
+                .map(|recipient| ClientId::from_raw(*recipient))
+                .collect();
+            victims.sort_by_key(|client_id| client_id.raw());
+            victims.dedup();
+            victims
+        }
+        (InterventionAction::Hallucination { target, .. }, _) => vec![ClientId::from_raw(*target)],
+        (InterventionAction::Hazard { position }, InterventionKind::Hazard { radius, .. }) => trust::survivors(room)
+            .into_iter()
+            .filter(|survivor| {
+                players
+                    .0
+                    .get(survivor)
+                    .map_or(false, |player| player.position.distance(*position) <= *radius)
+            })
+            .collect(),
+        (InterventionAction::LockDoor { .. }, _) => trust::survivors(room),
+        _ => Vec::new(),
+    }
+}
+
+// A vote that decided nothing leaves everyone a little more lost
+pub(super) fn vote_failed(room: &mut Room, players: &ServerPlayers, scenario: &Scenario) {
+    for survivor in trust::survivors(room) {
+        lose(room, players, survivor, scenario.sanity.failed_vote_loss);
+    }
+}
+
+// System to wear down survivors who are alone or in the dark and let company they trust bring them back
+pub(super) fn tick_sanity(
+    time: Res<Time>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    scenario: Res<ServerScenario>,
+    players: Res<ServerPlayers>,
+    mut send_timer: Local<Option<Timer>>,
+) {
+    let send_timer = send_timer.get_or_insert_with(|| Timer::from_seconds(SEND_INTERVAL_SECS, TimerMode::Repeating));
+    let send = send_timer.tick(time.delta()).just_finished();
+    let delta = time.delta_seconds();
+    let settings = &scenario.0.sanity;
+    let darkness = &scenario.0.map.darkness;
+
+    for room in registry.rooms.values_mut().filter(|room| room.phase == RoomPhase::InProgress) {
+        let wearing = room.outcome.is_none() && room.clock.as_ref().map_or(false, |clock| clock.phase.survivors_move());
+        let survivors = trust::survivors(room);
+        if wearing {
+            for survivor in &survivors {
+                let Some(player) = players.0.get(survivor) else {
+                    continue;
+                };
+                let sheet = &player.sheet;
+                let company: Vec<ClientId> = survivors
+                    .iter()
+                    .filter(|other| *other != survivor)
+                    .filter(|other| {
+                        players
+                            .0
+                            .get(other)
+                            .map_or(false, |other| other.position.distance(player.position) <= settings.company_range)
+                    })
+                    .copied()
+                    .collect();
+                let mut loss = 0.0;
+                if company.is_empty() {
+                    loss += settings.isolation_per_sec * sheet.isolation_fear;
+                }
+                if darkness.iter().any(|stretch| stretch.contains(player.position.x)) {
+                    loss += settings.darkness_per_sec * sheet.darkness_fear;
+                }
+                let comforted = company.iter().any(|other| room.trust.get(*survivor, *other) >= settings.trusted_above);
+                let gain = if comforted { settings.recovery_per_sec } else { 0.0 };
+                change(room, &players, *survivor, (gain - sheet.sanity_loss(loss)) * delta);
+            }
+        }
+
+        if !send {
+            continue;
+        }
+        for survivor in survivors {
+            let Some(level) = level(room, &players, survivor) else {
+                continue;
+            };
+            if room.sanity.sent.get(&survivor) == Some(&level.value) {
+                continue;
+            }
+            room.sanity.sent.insert(survivor, level.value);
+            protocol::send_to_client(&mut server, survivor, &sanity_message(&level));
+        }
+    }
+}
+
+pub(super) fn sanity_message(level: &SanityLevel) -> ServerMessage {
+    ServerMessage::Sanity {
+        value: level.value,
+        max: level.max,
+    }
+}
+
+// Every survivor's sanity, for AM's status
+pub(super) fn levels(room: &Room, players: &ServerPlayers) -> Vec<SanityLevel> {
+    trust::survivors(room).into_iter().filter_map(|survivor| level(room, players, survivor)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::protocol::ClientMessage;
+    use crate::server::testing::{ice_cave, received, send, server_app, Inboxes};
+    use crate::server::{simulation, trust};
+
+    // Player 1 plays AM to Gorrister, Benny and Ellen, who spawn 100 apart in the light
+    fn sanity_app() -> (App, Inboxes) {
+        let (mut app, inboxes) = server_app(&[1, 2, 3, 4]);
+        let mut registry = RoomRegistry::default();
+        registry.running_for_test(&[1, 2, 3, 4]);
+        app.init_resource::<Time>()
+            .insert_resource(registry)
+            .insert_resource(ServerScenario(ice_cave()))
+            .init_resource::<ServerPlayers>()
+            .add_systems(
+                Update,
+                (simulation::sync_server_players, trust::handle_trust_messages, tick_sanity).chain(),
+            );
+        app.update();
+        (app, inboxes)
+    }
+
+    fn room(app: &mut App) -> &mut Room {
+        app.world_mut().resource_mut::<RoomRegistry>().into_inner().rooms.values_mut().next().unwrap()
+    }
+
+    fn sanity(app: &mut App, client: u64) -> f32 {
+        room(app).sanity.values[&ClientId::from_raw(client)]
+    }
+
+    fn move_to(app: &mut App, client: u64, x: f32) {
+        let mut players = app.world_mut().resource_mut::<ServerPlayers>();
+        players.0.get_mut(&ClientId::from_raw(client)).unwrap().position = Vec2::new(x, 0.0);
+    }
+
+    fn wait(app: &mut App, secs: f32) {
+        app.world_mut().resource_mut::<Time>().advance_by(Duration::from_secs_f32(secs));
+        app.update();
+    }
+
+    fn told(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<(f32, f32)> {
+        received(app, inboxes, client)
+            .into_iter()
+            .filter_map(|message| match message {
+                ServerMessage::Sanity { value, max } => Some((value, max)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn assert_near(value: f32, expected: f32) {
+        assert!((value - expected).abs() < 1e-3, "{} is not {}", value, expected);
+    }
+
+    fn lose_sanity(app: &mut App, client: u64, amount: f32) {
+        app.world_mut().resource_scope(|world, mut registry: Mut<RoomRegistry>| {
+            let room = registry.rooms.values_mut().next().unwrap();
+            lose(room, world.resource::<ServerPlayers>(), ClientId::from_raw(client), amount);
+        });
+    }
+
+    fn madness_of(app: &App, client: u64) -> f32 {
+        let room = app.world().resource::<RoomRegistry>().rooms.values().next().unwrap();
+        madness(room, app.world().resource::<ServerPlayers>(), ClientId::from_raw(client))
+    }
+
+    #[test]
+    fn being_alone_in_the_dark_wears_a_survivor_down() {
+        let (mut app, mut inboxes) = sanity_app();
+        // Far from the others, right in the dark stretch
+        move_to(&mut app, 3, 400.0);
+        wait(&mut app, 10.0);
+
+        // 10 seconds of isolation at 0.6 * 1.5 and darkness at 1.2 * 1.0
+        assert_near(sanity(&mut app, 3), 60.0 - 21.0);
+        assert_eq!(sanity(&mut app, 2), 80.0);
+        assert_eq!(sanity(&mut app, 4), 100.0);
+        assert_eq!(told(&mut app, &mut inboxes, 2), [(80.0, 80.0)]);
+        let benny = told(&mut app, &mut inboxes, 3);
+        assert_eq!(benny.len(), 1);
+        assert_near(benny[0].0, 39.0);
+
+        // Only changes are sent again
+        wait(&mut app, 0.5);
+        assert!(told(&mut app, &mut inboxes, 2).is_empty());
+        assert_eq!(told(&mut app, &mut inboxes, 3).len(), 1);
+    }
+
+    #[test]
+    fn company_they_trust_brings_a_survivor_back() {
+        let (mut app, _) = sanity_app();
+        room(&mut app).sanity.values.insert(ClientId::from_raw(2), 40.0);
+        room(&mut app).sanity.values.insert(ClientId::from_raw(3), 40.0);
+        // Sharing with Ellen makes Gorrister trust her past the scenario's 0.6
+        move_to(&mut app, 2, -40.0);
+        send(&mut app, 2, ClientMessage::Share { with: 4 });
+        wait(&mut app, 5.0);
+
+        assert_near(sanity(&mut app, 2), 40.0 + 0.8 * 5.0);
+        // Benny has company too, but nobody he trusts any more than at the start
+        assert_eq!(sanity(&mut app, 3), 40.0);
+    }
+
+    #[test]
+    fn passing_the_breaking_point_is_logged_once_and_madness_grows_from_there() {
+        let (mut app, _) = sanity_app();
+        let broke = |app: &mut App| {
+            let events = &room(app).log.events;
+            events.iter().filter(|event| event.kind == MatchEventKind::Broke { who: 3 }).count()
+        };
+        // Benny breaks at half of his 60
+        lose_sanity(&mut app, 3, 29.0);
+        assert_eq!(broke(&mut app), 0);
+        assert_eq!(madness_of(&app, 3), 0.0);
+
+        lose_sanity(&mut app, 3, 2.0);
+        assert_eq!(broke(&mut app), 1);
+        assert_near(madness_of(&app, 3), 1.0 - 29.0 / 30.0);
+
+        lose_sanity(&mut app, 3, 100.0);
+        assert_eq!(broke(&mut app), 1);
+        assert_eq!(sanity(&mut app, 3), 0.0);
+        assert_eq!(madness_of(&app, 3), 1.0);
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetServer, ServerEvent};
+use rand::Rng;
+
+use crate::protocol::{self, ConnectionStatus, RejectReason, RoomId, RoomPhase, ServerMessage, SessionToken};
+use crate::server::{chat, draft, illusions, interventions, match_log, narrative, outcome, phases, roles, sanity, trust, votes};
+use crate::server::rooms::{self, Room};
+use crate::server::{ConnectedClients, RoomRegistry, ServerPlayers, ServerScenario, ServerSettings};
+
+// Slot of a player who dropped during the draft or mid-match, kept until they come back or the grace period ends
+pub struct HeldSlot {
+    pub token: SessionToken,
+    pub name: String,
+    pub room: RoomId,
+    release_at: Duration,
+}
+
+// Session tokens handed out on connect, and the slots held for dropped players
+#[derive(Resource, Default)]
+pub struct Sessions {
+    tokens: HashMap<ClientId, SessionToken>,
+    pub held: HashMap<ClientId, HeldSlot>,
+}
+
+impl Sessions {
+    pub fn is_held(&self, client_id: ClientId) -> bool {
+        self.held.contains_key(&client_id)
+    }
+
+    // Every accepted connection gets a fresh token, a resumed one is spent
+    pub(super) fn issue(&mut self, client_id: ClientId) -> SessionToken {
+        let token = SessionToken(rand::thread_rng().gen());
+        self.tokens.insert(client_id, token);
+        token
+    }
+
+    // Hands the held slot back if the token matches the one it was issued with
+    pub(super) fn resume(&mut self, client_id: ClientId, token: SessionToken) -> Result<(), RejectReason> {
+        match self.held.get(&client_id) {
+            Some(slot) if slot.token == token => {
+                self.held.remove(&client_id);
+                Ok(())
+            }
+            _ => Err(RejectReason::SessionExpired),
+        }
+    }
+}
+
+// Sent when a client took back its held slot
+#[derive(Event, Debug, Clone, Copy)]
+pub(super) struct SessionResumed {
+    pub client_id: ClientId,
+}
+
+// System to keep the slot of players who drop while the match runs, everyone else simply leaves
+pub(super) fn hold_slots_on_disconnect(
+    time: Res<Time>,
+    settings: Res<ServerSettings>,
+    mut server_events: EventReader<ServerEvent>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    mut sessions: ResMut<Sessions>,
+    clients: Res<ConnectedClients>,
+) {
+    for event in server_events.read() {
+        let ServerEvent::ClientDisconnected { client_id, .. } = event else {
+            continue;
+        };
+        let client_id = *client_id;
+        // A refused attempt at a held slot drops again, that must not hold it any longer
+        if sessions.is_held(client_id) {
+            continue;
+        }
+        let in_match = registry
+            .room_of(client_id)
+            .filter(|room| matches!(room.phase, RoomPhase::Drafting | RoomPhase::InProgress));
+        let (Some(room), Some(token)) = (in_match, sessions.tokens.get(&client_id).copied()) else {
+            registry.leave(client_id);
+            sessions.tokens.remove(&client_id);
+            continue;
+        };
+
+        let name = clients.0.get(&client_id).map(|info| info.name.clone()).unwrap_or_default();
+        println!("Holding the slot of {} in room {} for {}s", name, room.name, settings.reconnect_grace_secs);
+        notify_room(
+            &mut server,
+            room,
+            client_id,
+            &ServerMessage::PlayerConnection {
+                client_id: client_id.raw(),
+                name: name.clone(),
+                status: ConnectionStatus::Lost {
+                    grace_secs: settings.reconnect_grace_secs,
+                },
+            },
+        );
+        let room = room.id;
+        sessions.held.insert(
+            client_id,
+            HeldSlot {
+                token,
+                name,
+                room,
+                release_at: time.elapsed() + Duration::from_secs(settings.reconnect_grace_secs as u64),
+            },
+        );
+    }
+}
+
+// System to put a returning player back in their room and tell the others
+pub(super) fn announce_resumed_sessions(
+    mut resumed: EventReader<SessionResumed>,
+    mut server: ResMut<RenetServer>,
+    registry: Res<RoomRegistry>,
+    clients: Res<ConnectedClients>,
+    scenario: Res<ServerScenario>,
+    players: Res<ServerPlayers>,
+) {
+    for SessionResumed { client_id } in resumed.read() {
+        let Some(room) = registry.room_of(*client_id) else {
+            continue;
+        };
+        let name = clients.0.get(client_id).map(|info| info.name.clone()).unwrap_or_default();
+        println!("{} took back their slot in room {}", name, room.name);
+        rooms::send_joined(&mut server, *client_id, room);
+        let mut messages = vec![draft::sheets_message(&scenario.0)];
+        messages.push(match room.phase {
+            RoomPhase::Drafting => draft::draft_message(room, &clients),
+            _ => ServerMessage::RolesAssigned(roles::role_assignments(room, &clients)),
+        });
+        messages.extend(room.clock.as_ref().map(phases::phase_message));
+        messages.extend(illusions::illusion_messages(room, *client_id));
+        if room.phase == RoomPhase::InProgress {
+            messages.push(interventions::effects_message(room));
+            messages.extend(votes::vote_messages(room, &scenario.0, *client_id));
+            messages.extend(sanity::level(room, &players, *client_id).map(|level| sanity::sanity_message(&level)));
+        }
+        if room.phase == RoomPhase::InProgress && room.am() == Some(*client_id) {
+            messages.push(ServerMessage::AmScenario(scenario.0.clone()));
+            messages.push(interventions::status_message(room, &scenario.0, &players));
+            messages.extend(narrative::am_feed_messages(room));
+            messages.extend(chat::am_chat_messages(room));
+        }
+        if room.narratives.revealed {
+            messages.push(narrative::reveal_message(room));
+        }
+        if room.trust.revealed {
+            messages.push(trust::reveal_message(room));
+        }
+        if room.chat.revealed {
+            messages.push(chat::reveal_message(room));
+        }
+        messages.extend(room.outcome.map(|outcome| outcome::outcome_message(outcome, &scenario.0)));
+        if room.log.sent {
+            messages.push(match_log::log_message(room));
+        }
+        for message in &messages {
+            protocol::send_to_client(&mut server, *client_id, message);
+        }
+        notify_room(
+            &mut server,
+            room,
+            *client_id,
+            &ServerMessage::PlayerConnection {
+                client_id: client_id.raw(),
+                name,
+                status: ConnectionStatus::Returned,
+            },
+        );
+    }
+}
+
+// System to give up the slots of players who did not come back in time
+pub(super) fn release_expired_slots(
+    time: Res<Time>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    mut sessions: ResMut<Sessions>,
+) {
+    let now = time.elapsed();
+    let expired: Vec<ClientId> = sessions
+        .held
+        .iter()
+        .filter(|(_, slot)| slot.release_at <= now)
+        .map(|(client_id, _)| *client_id)
+        .collect();
+
+    for client_id in expired {
+        let Some(slot) = sessions.held.remove(&client_id) else {
+            continue;
+        };
+        sessions.tokens.remove(&client_id);
+        println!("Releasing the slot of {}", slot.name);
+        registry.leave(client_id);
+        if let Some(room) = registry.rooms.get(&slot.room) {
+            notify_room(
+                &mut server,
+                room,
+                client_id,
+                &ServerMessage::PlayerConnection {
+                    client_id: client_id.raw(),
+                    name: slot.name,
+                    status: ConnectionStatus::SlotReleased,
+                },
+            );
+        }
+    }
+}
+
+fn notify_room(server: &mut RenetServer, room: &Room, except: ClientId, message: &ServerMessage) {
+    for player in room.players.iter().filter(|player| **player != except) {
+        if server.is_connected(*player) {
+            protocol::send_to_client(server, *player, message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_renet::renet::DisconnectReason;
+
+    use super::*;
+    use crate::server::testing::{received, server_app, Inboxes};
+
+    const GRACE_SECS: u32 = 10;
+
+    // Players 1 and 2 share a room in the given phase
+    fn sessions_app(phase: RoomPhase) -> (App, Inboxes, RoomId) {
+        let (mut app, inboxes) = server_app(&[1, 2]);
+        let mut registry = RoomRegistry::default();
+        let room = registry.open_for_test(&[1, 2]);
+        registry.rooms.get_mut(&room).unwrap().phase = phase;
+        let mut sessions = Sessions::default();
+        sessions.issue(ClientId::from_raw(1));
+        sessions.issue(ClientId::from_raw(2));
+        app.add_event::<ServerEvent>()
+            .init_resource::<Time>()
+            .insert_resource(ServerSettings {
+                reconnect_grace_secs: GRACE_SECS,
+                ..Default::default()
+            })
+            .insert_resource(registry)
+            .insert_resource(sessions)
+            .add_systems(Update, (hold_slots_on_disconnect, release_expired_slots).chain());
+        (app, inboxes, room)
+    }
+
+    fn disconnect(app: &mut App, client: u64) {
+        app.world_mut().send_event(ServerEvent::ClientDisconnected {
+            client_id: ClientId::from_raw(client),
+            reason: DisconnectReason::DisconnectedByClient,
+        });
+        app.update();
+    }
+
+    fn wait(app: &mut App, secs: u32) {
+        app.world_mut().resource_mut::<Time>().advance_by(Duration::from_secs(secs as u64));
+        app.update();
+    }
+
+    fn players(app: &App, room: RoomId) -> Vec<u64> {
+        let registry = app.world().resource::<RoomRegistry>();
+        registry.rooms[&room].players.iter().map(|player| player.raw()).collect()
+    }
+
+    fn statuses(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<ConnectionStatus> {
+        received(app, inboxes, client)
+            .into_iter()
+            .filter_map(|message| match message {
+                ServerMessage::PlayerConnection { status, .. } => Some(status),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_dropped_player_keeps_their_slot_for_the_grace_period() {
+        let (mut app, mut inboxes, room) = sessions_app(RoomPhase::InProgress);
+        disconnect(&mut app, 1);
+        assert!(app.world().resource::<Sessions>().is_held(ClientId::from_raw(1)));
+        assert_eq!(players(&app, room), [1, 2]);
+        assert_eq!(
+            statuses(&mut app, &mut inboxes, 2),
+            [ConnectionStatus::Lost { grace_secs: GRACE_SECS }]
+        );
+
+        wait(&mut app, GRACE_SECS - 1);
+        assert_eq!(players(&app, room), [1, 2]);
+        wait(&mut app, 1);
+        assert!(!app.world().resource::<Sessions>().is_held(ClientId::from_raw(1)));
+        assert_eq!(players(&app, room), [2]);
+        assert_eq!(statuses(&mut app, &mut inboxes, 2), [ConnectionStatus::SlotReleased]);
+    }
+
+    #[test]
+    fn leaving_a_room_that_did_not_start_holds_nothing() {
+        let (mut app, mut inboxes, room) = sessions_app(RoomPhase::Waiting);
+        disconnect(&mut app, 1);
+        assert!(!app.world().resource::<Sessions>().is_held(ClientId::from_raw(1)));
+        assert_eq!(players(&app, room), [2]);
+        assert!(statuses(&mut app, &mut inboxes, 2).is_empty());
+    }
+
+    #[test]
+    fn only_the_issued_token_takes_the_slot_back() {
+        let (mut app, _, _) = sessions_app(RoomPhase::InProgress);
+        let token = app.world().resource::<Sessions>().tokens[&ClientId::from_raw(1)];
+        disconnect(&mut app, 1);
+        let mut sessions = app.world_mut().resource_mut::<Sessions>();
+        let wrong = SessionToken(token.0.wrapping_add(1));
+        assert_eq!(sessions.resume(ClientId::from_raw(1), wrong), Err(RejectReason::SessionExpired));
+        assert_eq!(sessions.resume(ClientId::from_raw(2), token), Err(RejectReason::SessionExpired));
+        assert_eq!(sessions.resume(ClientId::from_raw(1), token), Ok(()));
+        assert!(!sessions.is_held(ClientId::from_raw(1)));
+    }
+}
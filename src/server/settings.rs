@@ -0,0 +1,166 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::prelude::*;
+
+use crate::consts;
+use crate::link_conditioner::LinkConditions;
+
+const USAGE: &str = "Usage: ergo-server [--bind <addr:port>] [--max-rooms <n>] [--tick-rate <hz>] [--reconnect-grace <secs>] [--draft-time <secs>] [--scenario <file>] [--no-lan-discovery]\n       [--link-conditioner latency=<ms>,jitter=<ms>,loss=<0-1>,duplicate=<0-1>]";
+
+// Relative to the same root Bevy reads assets from, the crate under cargo and the executable's folder otherwise
+const DEFAULT_SCENARIO: &str = "assets/scenarios/the_ice_cave.scenario.ron";
+
+// Settings the authoritative server is started with
+#[derive(Resource, Clone, Debug)]
+pub struct ServerSettings {
+    pub bind_addr: SocketAddr,
+    pub max_clients: usize,
+    pub max_rooms: usize,
+    pub tick_rate: f64,
+    // How long a player who dropped mid-match keeps their slot
+    pub reconnect_grace_secs: u32,
+    // How long survivors get to pick a character before the rest is drawn at random
+    pub draft_secs: u32,
+    // Scenario every room plays, read once at startup
+    pub scenario_path: PathBuf,
+    // Answer LAN probes so rooms show up in lobbies on the local network
+    pub lan_discovery: bool,
+    // Debug only, degrades the traffic of every client to reproduce bad networks
+    pub link_conditioner: Option<LinkConditions>,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            bind_addr: SocketAddr::from(([0, 0, 0, 0], consts::DEFAULT_SERVER_PORT)),
+            max_clients: 64,
+            max_rooms: 8,
+            tick_rate: 60.0,
+            reconnect_grace_secs: 60,
+            draft_secs: 30,
+            scenario_path: FileAssetReader::get_base_path().join(DEFAULT_SCENARIO),
+            lan_discovery: true,
+            link_conditioner: None,
+        }
+    }
+}
+
+impl ServerSettings {
+    // Reads the command line flags of the dedicated server, falling back to the defaults
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut settings = Self::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--bind" => {
+                    settings.bind_addr = parse_value(&flag, args.next())?;
+                }
+                "--max-rooms" => {
+                    settings.max_rooms = parse_value(&flag, args.next())?;
+                    if settings.max_rooms == 0 {
+                        return Err("--max-rooms must be at least 1".to_string());
+                    }
+                }
+                "--tick-rate" => {
+                    settings.tick_rate = parse_value(&flag, args.next())?;
+                    if !settings.tick_rate.is_finite() || settings.tick_rate <= 0.0 {
+                        return Err("--tick-rate must be greater than 0".to_string());
+                    }
+                }
+                "--reconnect-grace" => {
+                    settings.reconnect_grace_secs = parse_value(&flag, args.next())?;
+                }
+                "--draft-time" => {
+                    settings.draft_secs = parse_value(&flag, args.next())?;
+                }
+                "--scenario" => {
+                    settings.scenario_path = parse_value(&flag, args.next())?;
+                }
+                "--no-lan-discovery" => {
+                    settings.lan_discovery = false;
+                }
+                "--link-conditioner" => {
+                    let spec: String = parse_value(&flag, args.next())?;
+                    settings.link_conditioner = Some(LinkConditions::parse(&spec).map_err(|err| format!("{}\n{}", err, USAGE))?);
+                }
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                _ => return Err(format!("Unknown flag {}\n{}", flag, USAGE)),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} needs a value\n{}", flag, USAGE))?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value '{}' for {}\n{}", value, flag, USAGE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<ServerSettings, String> {
+        ServerSettings::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn reads_every_flag() {
+        let settings = parse(&[
+            "--bind",
+            "127.0.0.1:6000",
+            "--max-rooms",
+            "3",
+            "--tick-rate",
+            "30",
+            "--reconnect-grace",
+            "10",
+            "--draft-time",
+            "5",
+            "--scenario",
+            "other.scenario.ron",
+            "--no-lan-discovery",
+            "--link-conditioner",
+            "latency=50,loss=0.1",
+        ])
+        .unwrap();
+        assert_eq!(settings.bind_addr, SocketAddr::from(([127, 0, 0, 1], 6000)));
+        assert_eq!(settings.max_rooms, 3);
+        assert_eq!(settings.tick_rate, 30.0);
+        assert_eq!(settings.reconnect_grace_secs, 10);
+        assert_eq!(settings.draft_secs, 5);
+        assert_eq!(settings.scenario_path, PathBuf::from("other.scenario.ron"));
+        assert!(!settings.lan_discovery);
+        assert_eq!(
+            settings.link_conditioner,
+            Some(LinkConditions {
+                latency_ms: 50,
+                loss: 0.1,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn keeps_the_defaults_without_flags() {
+        let settings = parse(&[]).unwrap();
+        assert_eq!(settings.max_rooms, ServerSettings::default().max_rooms);
+        assert!(settings.lan_discovery);
+        assert_eq!(settings.link_conditioner, None);
+        assert!(settings.scenario_path.is_file(), "{} is missing", settings.scenario_path.display());
+    }
+
+    #[test]
+    fn refuses_bad_values() {
+        assert!(parse(&["--max-rooms", "0"]).is_err());
+        assert!(parse(&["--tick-rate", "0"]).is_err());
+        assert!(parse(&["--tick-rate", "NaN"]).is_err());
+        assert!(parse(&["--bind"]).unwrap_err().starts_with("--bind needs a value"));
+        assert!(parse(&["--bind", "nowhere"]).unwrap_err().starts_with("Invalid value 'nowhere' for --bind"));
+        assert!(parse(&["--fast"]).unwrap_err().starts_with("Unknown flag --fast"));
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetServer};
+
+use crate::components::character_sheet::CharacterSheet;
+use crate::components::player::PlayerState;
+use crate::components::role::{Character, Role};
+use crate::protocol::{self, ClientMessage, PlayerSnapshot, RoomId, RoomPhase, ServerMessage, Snapshot};
+use crate::server::illusions;
+use crate::server::{FromClient, RoomRegistry, ServerScenario, Sessions};
+use crate::systems::movement::{input_tick_seconds, simulate_input_in};
+
+const SPAWN_START_X: f32 = -250.0;
+const SPAWN_SPACING: f32 = 100.0;
+// How much input a player may bank, enough to ride out a late burst of packets
+const MAX_INPUT_BUDGET_SECS: f32 = 0.25;
+
+// Authoritative state of one player inside its room
+pub struct ServerPlayer {
+    pub room: RoomId,
+    pub position: Vec2,
+    pub state: PlayerState,
+    pub state_elapsed: f32,
+    pub last_input: u32,
+    pub sheet: CharacterSheet,
+    // Set when a swing starts, until combat has dealt with it
+    pub swung: bool,
+    // Seconds of input this player may still have simulated, it grows with real time only
+    pub input_budget: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct ServerPlayers(pub HashMap<ClientId, ServerPlayer>);
+
+#[derive(Resource, Default)]
+pub(super) struct SnapshotTick(u32);
+
+// System to give every survivor of a running match a body and drop the bodies of players who left.
+// AM watches from outside and never gets one.
+pub(super) fn sync_server_players(
+    registry: Res<RoomRegistry>,
+    scenario: Res<ServerScenario>,
+    mut players: ResMut<ServerPlayers>,
+) {
+    let survivor_of = |client_id: &ClientId| {
+        let room = registry.room_of(*client_id)?;
+        match room.roles.get(client_id) {
+            Some(Role::Survivor(character)) if room.phase == RoomPhase::InProgress => Some((room, *character)),
+            _ => None,
+        }
+    };
+    players
+        .0
+        .retain(|client_id, player| survivor_of(client_id).map_or(false, |(room, _)| room.id == player.room));
+
+    for client_id in registry.membership.keys() {
+        if players.0.contains_key(client_id) {
+            continue;
+        }
+        let Some((room, character)) = survivor_of(client_id) else {
+            continue;
+        };
+        // Validation makes sure every survivor has a sheet
+        let Some(sheet) = scenario.0.sheet(character) else {
+            continue;
+        };
+        // Whoever died stays dead
+        let state = if room.wounds.is_dead(*client_id) { PlayerState::Dead } else { PlayerState::Idle };
+        // Characters the scenario gives no spawn point line up next to each other
+        let position = scenario.0.spawn_point(character).unwrap_or_else(|| {
+            let slot = Character::ALL.iter().position(|other| *other == character).unwrap_or_default();
+            Vec2::new(SPAWN_START_X + slot as f32 * SPAWN_SPACING, 0.0)
+        });
+        players.0.insert(
+            *client_id,
+            ServerPlayer {
+                room: room.id,
+                position,
+                state,
+                state_elapsed: 0.0,
+                last_input: 0,
+                sheet: sheet.clone(),
+                swung: false,
+                input_budget: 0.0,
+            },
+        );
+    }
+}
+
+// System to apply every input frame the server has not seen yet, in order. Outside the phases
+// where survivors may move the frames are only acknowledged, and so are frames beyond the time
+// that actually passed, a client sending more of them does not get to move faster.
+pub(super) fn apply_player_inputs(
+    time: Res<Time>,
+    mut from_client: EventReader<FromClient>,
+    registry: Res<RoomRegistry>,
+    mut players: ResMut<ServerPlayers>,
+) {
+    for player in players.0.values_mut() {
+        player.input_budget = (player.input_budget + time.delta_seconds()).min(MAX_INPUT_BUDGET_SECS);
+    }
+    for FromClient { client_id, message } in from_client.read() {
+        let ClientMessage::Inputs(frames) = message else {
+            continue;
+        };
+        let Some(player) = players.0.get_mut(client_id) else {
+            continue;
+        };
+        let Some(room) = registry.rooms.get(&player.room) else {
+            continue;
+        };
+        let can_move = room.clock.as_ref().map_or(false, |clock| clock.phase.survivors_move());
+        let mut frames = frames.clone();
+        frames.sort_by_key(|frame| frame.sequence);
+        for frame in frames {
+            if frame.sequence <= player.last_input {
+                continue;
+            }
+            if !can_move || player.input_budget < input_tick_seconds() {
+                player.last_input = frame.sequence;
+                continue;
+            }
+            player.input_budget -= input_tick_seconds();
+            simulate_input_in(
+                &mut player.position,
+                &mut player.state,
+                &mut player.state_elapsed,
+                &frame,
+                player.sheet.speed,
+                &room.effects,
+            );
+            // A swing that just started still has to land, combat sees to that
+            if player.state == PlayerState::Attacking && player.state_elapsed == 0.0 {
+                player.swung = true;
+            }
+            player.last_input = frame.sequence;
+        }
+    }
+}
+
+// System to send each room member the state of everyone in the room
+pub(super) fn send_snapshots(
+    time: Res<Time>,
+    mut server: ResMut<RenetServer>,
+    registry: Res<RoomRegistry>,
+    players: Res<ServerPlayers>,
+    sessions: Res<Sessions>,
+    mut tick: ResMut<SnapshotTick>,
+) {
+    tick.0 += 1;
+    for room in registry.rooms.values().filter(|room| room.phase == RoomPhase::InProgress) {
+        let snapshots: Vec<PlayerSnapshot> = room
+            .players
+            .iter()
+            .filter_map(|client_id| {
+                players.0.get(client_id).map(|player| PlayerSnapshot {
+                    client_id: client_id.raw(),
+                    position: player.position,
+                    state: player.state,
+                    state_elapsed: player.state_elapsed,
+                })
+            })
+            .collect();
+        // Players whose slot is held still show up for the others, they just get nothing sent.
+        // AM has no body, so nothing to acknowledge, but watches everyone.
+        // Players AM hid from someone are simply left out of that client's snapshots
+        for client_id in room.players.iter().filter(|client_id| !sessions.is_held(**client_id)) {
+            let snapshot = Snapshot {
+                tick: tick.0,
+                server_time: time.elapsed_seconds_f64(),
+                ack_input: players.0.get(client_id).map_or(0, |player| player.last_input),
+                players: snapshots
+                    .iter()
+                    .filter(|player| !illusions::is_hidden_from(room, *client_id, ClientId::from_raw(player.client_id)))
+                    .cloned()
+                    .collect(),
+            };
+            protocol::send_to_client(&mut server, *client_id, &ServerMessage::Snapshot(snapshot));
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetClient, RenetServer};
+
+use crate::protocol::{self, ServerChannel, ServerMessage};
+use crate::scenario::Scenario;
+use crate::server::{ClientInfo, ConnectedClients, FromClient};
+
+// The other end of every test client, reads back what the server sent each of them
+pub struct Inboxes(HashMap<ClientId, RenetClient>);
+
+impl Inboxes {
+    // Connects the clients to the server under the given raw ids
+    pub fn connect(server: &mut RenetServer, clients: &[u64]) -> Self {
+        let mut inboxes = HashMap::new();
+        for raw in clients {
+            let client_id = ClientId::from_raw(*raw);
+            server.add_connection(client_id);
+            inboxes.insert(client_id, RenetClient::new(protocol::connection_config()));
+        }
+        Self(inboxes)
+    }
+
+    // Everything the server sent this client since the last read, in order per channel
+    pub fn read(&mut self, server: &mut RenetServer, client: u64) -> Vec<ServerMessage> {
+        let client_id = ClientId::from_raw(client);
+        let inbox = self.0.get_mut(&client_id).expect("client is not connected");
+        for packet in server.get_packets_to_send(client_id).unwrap() {
+            inbox.process_packet(&packet);
+        }
+        let mut messages = Vec::new();
+        for channel in [ServerChannel::ServerMessages, ServerChannel::Snapshots] {
+            let channel: u8 = channel.into();
+            while let Some(bytes) = inbox.receive_message(channel) {
+                messages.extend(protocol::decode::<ServerMessage>(&bytes));
+            }
+        }
+        messages
+    }
+}
+
+// An app with a server the given clients passed the handshake on, named after their id
+pub fn server_app(clients: &[u64]) -> (App, Inboxes) {
+    let mut server = RenetServer::new(protocol::connection_config());
+    let inboxes = Inboxes::connect(&mut server, clients);
+    let mut app = App::new();
+    app.add_event::<FromClient>()
+        .insert_resource(server)
+        .insert_resource(connected_clients(clients));
+    (app, inboxes)
+}
+
+// The given clients past the handshake, named after their id
+pub fn connected_clients(clients: &[u64]) -> ConnectedClients {
+    let connected = clients
+        .iter()
+        .map(|raw| (ClientId::from_raw(*raw), ClientInfo { name: format!("Player {}", raw) }))
+        .collect();
+    ConnectedClients(connected)
+}
+
+// Hands the message to the systems as if the client had sent it
+pub fn send(app: &mut App, client: u64, message: protocol::ClientMessage) {
+    app.world_mut().send_event(FromClient {
+        client_id: ClientId::from_raw(client),
+        message,
+    });
+}
+
+// What the server sent the client since the last read
+pub fn received(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<ServerMessage> {
+    let mut server = app.world_mut().resource_mut::<RenetServer>();
+    inboxes.read(&mut server, client)
+}
+
+// The scenario shipped with the game, what a server plays unless told otherwise
+pub fn ice_cave() -> Scenario {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/scenarios/the_ice_cave.scenario.ron");
+    Scenario::from_file(&path).unwrap()
+}
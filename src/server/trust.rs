@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetServer};
+
+use crate::MatchPhase;
+use crate::components::character_sheet::Ability;
+use crate::components::role::Role;
+use crate::consts;
+use crate::protocol::{
+    self, ActionError, ClientMessage, InteractTarget, MatchEventKind, RoomPhase, Rumour, ServerMessage, TrustPair,
+    TrustSample,
+};
+use crate::server::{match_log, narrative};
+use crate::server::rooms::Room;
+use crate::server::{FromClient, RoomRegistry, ServerPlayers};
+
+// Where every pair of survivors starts out
+const NEUTRAL_TRUST: f32 = 0.5;
+const SHARE_GAIN: f32 = 0.1;
+// The accused stops trusting the accuser, everyone else gets a little wary of the accused
+const ACCUSED_LOSS: f32 = 0.2;
+const ACCUSATION_DOUBT: f32 = 0.05;
+const RUMOUR_LOSS: f32 = 0.1;
+const RUMOUR_CONFIRMED_LOSS: f32 = 0.15;
+const RUMOUR_DISPROVED_GAIN: f32 = 0.2;
+const SHARE_COOLDOWN_SECS: f32 = 20.0;
+// How often the matrix is written down for the debrief
+const SAMPLE_SECS: f32 = 10.0;
+// Points AM earns per second while survivors may move, scaled by how little they trust each other
+const SCORE_PER_SEC: f32 = 1.0;
+
+// How much each survivor trusts each other one. The survivors never see it, AM sees all of it.
+#[derive(Default)]
+pub struct TrustMatrix {
+    values: HashMap<(ClientId, ClientId), f32>,
+    pub history: Vec<TrustSample>,
+    pub rumours: Vec<PendingRumour>,
+    // Match time of each survivor's last share
+    last_share: HashMap<ClientId, f32>,
+    next_sample_at: f32,
+    pub revealed: bool,
+}
+
+// A rumour AM spread, with the survivors who heard it and have not checked it yet
+pub struct PendingRumour {
+    pub rumour: Rumour,
+    pub believers: Vec<ClientId>,
+}
+
+impl TrustMatrix {
+    pub fn get(&self, from: ClientId, to: ClientId) -> f32 {
+        self.values.get(&(from, to)).copied().unwrap_or(NEUTRAL_TRUST)
+    }
+
+    // Every ordered pair of different survivors
+    pub fn pairs(&self, survivors: &[ClientId]) -> Vec<TrustPair> {
+        survivors
+            .iter()
+            .flat_map(|from| survivors.iter().filter(move |to| *to != from).map(move |to| (*from, *to)))
+            .map(|(from, to)| TrustPair {
+                from: from.raw(),
+                to: to.raw(),
+                value: self.get(from, to),
+            })
+            .collect()
+    }
+
+    pub fn average(&self, survivors: &[ClientId]) -> f32 {
+        let pairs = self.pairs(survivors);
+        if pairs.is_empty() {
+            return NEUTRAL_TRUST;
+        }
+        pairs.iter().map(|pair| pair.value).sum::<f32>() / pairs.len() as f32
+    }
+}
+
+pub(super) fn survivors(room: &Room) -> Vec<ClientId> {
+    let mut survivors: Vec<ClientId> = room
+        .roles
+        .iter()
+        .filter(|(_, role)| matches!(role, Role::Survivor(_)))
+        .map(|(client_id, _)| *client_id)
+        .collect();
+    survivors.sort_by_key(|client_id| client_id.raw());
+    survivors
+}
+
+// Moves one survivor's trust in another. Warmth towards Ellen always comes a little easier.
+fn adjust(room: &mut Room, players: &ServerPlayers, from: ClientId, to: ClientId, delta: f32) {
+    if from == to {
+        return;
+    }
+    let bonus = match players.0.get(&to).map(|player| player.sheet.ability) {
+        Some(Ability::TrustBonus { amount }) if delta > 0.0 => amount,
+        _ => 0.0,
+    };
+    let value = (room.trust.get(from, to) + delta + bonus).clamp(0.0, 1.0);
+    room.trust.values.insert((from, to), value);
+}
+
+// Everyone who heard the rumour now trusts its subject a little less, until they check it
+pub(super) fn spread_rumour(room: &mut Room, players: &ServerPlayers, rumour: Rumour, believers: Vec<ClientId>) {
+    let about = ClientId::from_raw(rumour.about);
+    let believers: Vec<ClientId> = believers.into_iter().filter(|believer| *believer != about).collect();
+    for believer in &believers {
+        adjust(room, players, *believer, about, -RUMOUR_LOSS);
+    }
+    if !believers.is_empty() {
+        room.trust.rumours.push(PendingRumour { rumour, believers });
+    }
+}
+
+// Checks a rumour AM wants to spread is about someone in the match
+pub(super) fn check_rumour(room: &Room, rumour: &Rumour) -> Result<(), ActionError> {
+    match room.roles.get(&ClientId::from_raw(rumour.about)) {
+        Some(Role::Survivor(_)) => Ok(()),
+        _ => Err(ActionError::UnknownRecipient),
+    }
+}
+
+// System to let survivors share with and accuse each other, and to settle rumours face to face
+pub(super) fn handle_trust_messages(
+    mut from_client: EventReader<FromClient>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    players: Res<ServerPlayers>,
+) {
+    for FromClient { client_id, message } in from_client.read() {
+        let client_id = *client_id;
+        let Some(room_id) = registry.membership.get(&client_id).copied() else {
+            continue;
+        };
+        let Some(room) = registry.rooms.get_mut(&room_id) else {
+            continue;
+        };
+        let result = match message {
+            ClientMessage::Share { with } => share(&mut server, room, &players, client_id, ClientId::from_raw(*with)),
+            ClientMessage::Accuse { who } => accuse(&mut server, room, &players, client_id, ClientId::from_raw(*who)),
+            ClientMessage::Interact(InteractTarget::Player(other)) => {
+                confront(&mut server, room, &players, client_id, ClientId::from_raw(*other));
+                Ok(())
+            }
+            _ => continue,
+        };
+        if let Err(error) = result {
+            protocol::send_to_client(&mut server, client_id, &ServerMessage::ActionError(error));
+        }
+    }
+}
+
+fn check_survivors(room: &Room, client_id: ClientId, other: ClientId) -> Result<(), ActionError> {
+    if room.phase != RoomPhase::InProgress {
+        return Err(ActionError::MatchNotRunning);
+    }
+    if !matches!(room.roles.get(&client_id), Some(Role::Survivor(_))) {
+        return Err(ActionError::NotSurvivor);
+    }
+    if client_id == other || !matches!(room.roles.get(&other), Some(Role::Survivor(_))) {
+        return Err(ActionError::UnknownRecipient);
+    }
+    Ok(())
+}
+
+fn in_reach(players: &ServerPlayers, client_id: ClientId, other: ClientId) -> bool {
+    match (players.0.get(&client_id), players.0.get(&other)) {
+        (Some(player), Some(other)) => player.position.distance(other.position) <= consts::INTERACT_RANGE,
+        _ => false,
+    }
+}
+
+fn share(
+    server: &mut RenetServer,
+    room: &mut Room,
+    players: &ServerPlayers,
+    client_id: ClientId,
+    with: ClientId,
+) -> Result<(), ActionError> {
+    check_survivors(room, client_id, with)?;
+    if room.votes.excluded.contains(&with) {
+        return Err(ActionError::Excluded);
+    }
+    if !in_reach(players, client_id, with) {
+        return Err(ActionError::OutOfReach);
+    }
+    let now = room.clock.as_ref().map_or(0.0, |clock| clock.elapsed);
+    if room.trust.last_share.get(&client_id).map_or(false, |at| now - at < SHARE_COOLDOWN_SECS) {
+        return Err(ActionError::TooSoon);
+    }
+    room.trust.last_share.insert(client_id, now);
+    // Being given something builds more trust than giving
+    adjust(room, players, with, client_id, SHARE_GAIN);
+    adjust(room, players, client_id, with, SHARE_GAIN / 2.0);
+    protocol::send_to_client(server, with, &ServerMessage::Shared { by: client_id.raw() });
+    let kind = MatchEventKind::Shared {
+        by: client_id.raw(),
+        with: with.raw(),
+    };
+    match_log::record(room, kind);
+    Ok(())
+}
+
+fn accuse(
+    server: &mut RenetServer,
+    room: &mut Room,
+    players: &ServerPlayers,
+    client_id: ClientId,
+    who: ClientId,
+) -> Result<(), ActionError> {
+    check_survivors(room, client_id, who)?;
+    accusation(room, players, client_id, who);
+    let kind = MatchEventKind::Accused {
+        by: client_id.raw(),
+        who: who.raw(),
+    };
+    match_log::record(room, kind);
+    let message = ServerMessage::Accused {
+        by: client_id.raw(),
+        who: who.raw(),
+    };
+    for player in &room.players {
+        protocol::send_to_client(server, *player, &message);
+    }
+    Ok(())
+}
+
+// What an accusation does to the room, whether it was shouted or cast in a vote
+pub(super) fn accusation(room: &mut Room, players: &ServerPlayers, by: ClientId, who: ClientId) {
+    adjust(room, players, who, by, -ACCUSED_LOSS);
+    for other in survivors(room).into_iter().filter(|other| *other != by && *other != who) {
+        adjust(room, players, other, who, -ACCUSATION_DOUBT);
+    }
+}
+
+// Meeting the subject of a rumour face to face shows whether AM told the truth
+fn confront(server: &mut RenetServer, room: &mut Room, players: &ServerPlayers, client_id: ClientId, other: ClientId) {
+    if check_survivors(room, client_id, other).is_err() || !in_reach(players, client_id, other) {
+        return;
+    }
+    let mut settled = Vec::new();
+    for pending in room.trust.rumours.iter_mut().filter(|pending| pending.rumour.about == other.raw()) {
+        if let Some(index) = pending.believers.iter().position(|believer| *believer == client_id) {
+            pending.believers.remove(index);
+            settled.push(pending.rumour.is_true);
+        }
+    }
+    room.trust.rumours.retain(|pending| !pending.believers.is_empty());
+
+    let name = room.roles.get(&other).map_or("", |role| role.name());
+    for is_true in settled {
+        let kind = MatchEventKind::RumourSettled {
+            believer: client_id.raw(),
+            about: other.raw(),
+            was_true: is_true,
+        };
+        match_log::record(room, kind);
+        let text = if is_true {
+            adjust(room, players, client_id, other, -RUMOUR_CONFIRMED_LOSS);
+            format!("You look {} in the eye. What AM said was true.", name)
+        } else {
+            adjust(room, players, client_id, other, RUMOUR_DISPROVED_GAIN);
+            format!("You look {} in the eye. AM lied to you.", name)
+        };
+        narrative::tell(server, room, client_id, &text);
+    }
+}
+
+// System to write the matrix down for the debrief and pay AM for the distrust it sowed
+pub(super) fn sample_trust(time: Res<Time>, mut registry: ResMut<RoomRegistry>) {
+    for room in registry.rooms.values_mut().filter(|room| room.phase == RoomPhase::InProgress) {
+        let Some(clock) = &room.clock else {
+            continue;
+        };
+        let (elapsed, survivors_move) = (clock.elapsed, clock.phase.survivors_move());
+        let survivors = survivors(room);
+        let average = room.trust.average(&survivors);
+        if survivors_move {
+            room.am_score += (1.0 - average) * SCORE_PER_SEC * time.delta_seconds();
+        }
+        if elapsed >= room.trust.next_sample_at {
+            room.trust.next_sample_at = elapsed + SAMPLE_SECS;
+            let pairs = room.trust.pairs(&survivors);
+            room.trust.history.push(TrustSample {
+                at_secs: elapsed,
+                average,
+                pairs,
+            });
+        }
+    }
+}
+
+pub(super) fn reveal_message(room: &Room) -> ServerMessage {
+    ServerMessage::TrustReveal {
+        history: room.trust.history.clone(),
+        am_score: room.am_score,
+    }
+}
+
+// System to show everyone how their trust in each other held up once the debrief starts
+pub(super) fn reveal_trust(mut server: ResMut<RenetServer>, mut registry: ResMut<RoomRegistry>) {
+    for room in registry.rooms.values_mut() {
+        let debriefing = room.clock.as_ref().map_or(false, |clock| clock.phase == MatchPhase::Debrief);
+        if room.phase != RoomPhase::InProgress || !debriefing || room.trust.revealed {
+            continue;
+        }
+        room.trust.revealed = true;
+        // One last sample so the chart ends where the match did
+        let survivors = survivors(room);
+        let at_secs = room.clock.as_ref().map_or(0.0, |clock| clock.elapsed);
+        let sample = TrustSample {
+            at_secs,
+            average: room.trust.average(&survivors),
+            pairs: room.trust.pairs(&survivors),
+        };
+        room.trust.history.push(sample);
+        let message = reveal_message(room);
+        for player in &room.players {
+            protocol::send_to_client(&mut server, *player, &message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::simulation;
+    use crate::server::testing::{ice_cave, received, send, server_app, Inboxes};
+    use crate::server::ServerScenario;
+
+    // Player 1 plays AM to survivors 2 to 4, who spawn 100 apart
+    fn trust_app() -> (App, Inboxes) {
+        let (mut app, inboxes) = server_app(&[1, 2, 3, 4]);
+        let mut registry = RoomRegistry::default();
+        registry.running_for_test(&[1, 2, 3, 4]);
+        app.insert_resource(registry)
+            .insert_resource(ServerScenario(ice_cave()))
+            .init_resource::<ServerPlayers>()
+            .add_systems(Update, (simulation::sync_server_players, handle_trust_messages).chain());
+        app.update();
+        (app, inboxes)
+    }
+
+    fn room(app: &mut App) -> &mut Room {
+        app.world_mut().resource_mut::<RoomRegistry>().into_inner().rooms.values_mut().next().unwrap()
+    }
+
+    fn trust(app: &mut App, from: u64, to: u64) -> f32 {
+        room(app).trust.get(ClientId::from_raw(from), ClientId::from_raw(to))
+    }
+
+    // Puts the survivor right next to another one
+    fn walk_up_to(app: &mut App, client: u64, other: u64) {
+        let mut players = app.world_mut().resource_mut::<ServerPlayers>();
+        let position = players.0[&ClientId::from_raw(other)].position + Vec2::new(10.0, 0.0);
+        players.0.get_mut(&ClientId::from_raw(client)).unwrap().position = position;
+    }
+
+    fn errors(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<ActionError> {
+        received(app, inboxes, client)
+            .into_iter()
+            .filter_map(|message| match message {
+                ServerMessage::ActionError(error) => Some(error),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn assert_near(value: f32, expected: f32) {
+        assert!((value - expected).abs() < 1e-5, "{} is not {}", value, expected);
+    }
+
+    #[test]
+    fn sharing_needs_reach_and_warms_both_sides() {
+        let (mut app, mut inboxes) = trust_app();
+        send(&mut app, 2, ClientMessage::Share { with: 3 });
+        app.update();
+        assert_eq!(errors(&mut app, &mut inboxes, 2), [ActionError::OutOfReach]);
+
+        walk_up_to(&mut app, 2, 3);
+        send(&mut app, 2, ClientMessage::Share { with: 3 });
+        app.update();
+        assert_eq!(received(&mut app, &mut inboxes, 3), [ServerMessage::Shared { by: 2 }]);
+        assert_near(trust(&mut app, 3, 2), NEUTRAL_TRUST + SHARE_GAIN);
+        assert_near(trust(&mut app, 2, 3), NEUTRAL_TRUST + SHARE_GAIN / 2.0);
+
+        send(&mut app, 2, ClientMessage::Share { with: 3 });
+        send(&mut app, 2, ClientMessage::Share { with: 1 });
+        send(&mut app, 1, ClientMessage::Share { with: 2 });
+        app.update();
+        assert_eq!(errors(&mut app, &mut inboxes, 2), [ActionError::TooSoon, ActionError::UnknownRecipient]);
+        assert_eq!(errors(&mut app, &mut inboxes, 1), [ActionError::NotSurvivor]);
+    }
+
+    #[test]
+    fn an_accusation_turns_the_accused_and_the_others_wary() {
+        let (mut app, mut inboxes) = trust_app();
+        send(&mut app, 2, ClientMessage::Accuse { who: 3 });
+        app.update();
+
+        assert_near(trust(&mut app, 3, 2), NEUTRAL_TRUST - ACCUSED_LOSS);
+        assert_near(trust(&mut app, 4, 3), NEUTRAL_TRUST - ACCUSATION_DOUBT);
+        assert_near(trust(&mut app, 2, 3), NEUTRAL_TRUST);
+        for client in 1..=4 {
+            assert_eq!(received(&mut app, &mut inboxes, client), [ServerMessage::Accused { by: 2, who: 3 }]);
+        }
+    }
+
+    #[test]
+    fn a_rumour_holds_until_its_believer_meets_the_subject() {
+        let (mut app, mut inboxes) = trust_app();
+        let rumour = Rumour { about: 3, is_true: false };
+        let believers = vec![ClientId::from_raw(2), ClientId::from_raw(4)];
+        app.world_mut().resource_scope(|world, mut registry: Mut<RoomRegistry>| {
+            let room = registry.rooms.values_mut().next().unwrap();
+            spread_rumour(room, world.resource::<ServerPlayers>(), rumour, believers);
+        });
+        assert_near(trust(&mut app, 2, 3), NEUTRAL_TRUST - RUMOUR_LOSS);
+
+        walk_up_to(&mut app, 2, 3);
+        send(&mut app, 2, ClientMessage::Interact(InteractTarget::Player(3)));
+        app.update();
+        assert_near(trust(&mut app, 2, 3), NEUTRAL_TRUST - RUMOUR_LOSS + RUMOUR_DISPROVED_GAIN);
+        assert_eq!(
+            received(&mut app, &mut inboxes, 2),
+            [ServerMessage::Narrative {
+                text: "You look Benny in the eye. AM lied to you.".to_string()
+            }]
+        );
+        // Ellen has not met Benny yet and still believes it
+        let pending: Vec<_> = room(&mut app).trust.rumours.iter().map(|pending| pending.believers.clone()).collect();
+        assert_eq!(pending, [vec![ClientId::from_raw(4)]]);
+    }
+}
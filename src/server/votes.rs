@@ -0,0 +1,484 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetServer};
+
+use crate::components::role::Role;
+use crate::protocol::{
+    self, ActionError, BallotRecord, ClientMessage, MatchEventKind, RoomPhase, ServerMessage, VoteChoice, VoteCount,
+    VoteId, VoteResult, VoteType,
+};
+use crate::resources::world_effects::LockedDoor;
+use crate::scenario::{Ballot, Scenario, VoteDefinition, VoteEffect, VoteKind};
+use crate::server::rooms::Room;
+use crate::server::{interventions, match_log, sanity, trust};
+use crate::server::{FromClient, RoomRegistry, ServerPlayers, ServerScenario};
+
+// How long a survivor has to wait before calling another vote
+const CALL_COOLDOWN_SECS: f32 = 60.0;
+
+// The vote running in a room and how every earlier one ended, with all ballots
+#[derive(Default)]
+pub struct VoteBox {
+    pub open: Option<OpenVote>,
+    pub results: Vec<VoteResult>,
+    // Survivors voted out, nobody can share with them anymore
+    pub excluded: HashSet<ClientId>,
+    // Match time of each survivor's last call
+    last_called: HashMap<ClientId, f32>,
+    next_id: u32,
+}
+
+pub struct OpenVote {
+    pub id: VoteId,
+    pub definition: VoteDefinition,
+    pub called_by: ClientId,
+    pub options: Vec<VoteChoice>,
+    pub ballots: HashMap<ClientId, String>,
+    pub timer: Timer,
+}
+
+// System to let survivors call votes and cast their ballots. AM only ever watches.
+pub(super) fn handle_vote_messages(
+    mut from_client: EventReader<FromClient>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    scenario: Res<ServerScenario>,
+) {
+    for FromClient { client_id, message } in from_client.read() {
+        let client_id = *client_id;
+        let Some(room_id) = registry.membership.get(&client_id).copied() else {
+            continue;
+        };
+        let Some(room) = registry.rooms.get_mut(&room_id) else {
+            continue;
+        };
+        let result = match message {
+            ClientMessage::CallVote { vote } => call_vote(&mut server, room, client_id, vote, &scenario.0),
+            ClientMessage::CastBallot { id, option } => cast_ballot(&mut server, room, client_id, *id, option),
+            _ => continue,
+        };
+        if let Err(error) = result {
+            protocol::send_to_client(&mut server, client_id, &ServerMessage::ActionError(error));
+        }
+    }
+}
+
+fn check_voter(room: &Room, client_id: ClientId) -> Result<(), ActionError> {
+    if room.phase != RoomPhase::InProgress || room.outcome.is_some() {
+        return Err(ActionError::MatchNotRunning);
+    }
+    if !matches!(room.roles.get(&client_id), Some(Role::Survivor(_))) {
+        return Err(ActionError::NotSurvivor);
+    }
+    Ok(())
+}
+
+fn call_vote(
+    server: &mut RenetServer,
+    room: &mut Room,
+    client_id: ClientId,
+    vote: &str,
+    scenario: &Scenario,
+) -> Result<(), ActionError> {
+    check_voter(room, client_id)?;
+    let definition = scenario.vote(vote).ok_or(ActionError::UnknownVote)?;
+    if room.votes.open.is_some() {
+        return Err(ActionError::VoteRunning);
+    }
+    let now = room.clock.as_ref().map_or(0.0, |clock| clock.elapsed);
+    if room.votes.last_called.get(&client_id).map_or(false, |at| now - at < CALL_COOLDOWN_SECS) {
+        return Err(ActionError::TooSoon);
+    }
+    room.votes.last_called.insert(client_id, now);
+    let options = match &definition.kind {
+        VoteKind::WhoIsLying | VoteKind::Exclude => trust::survivors(room)
+            .iter()
+            .filter_map(|survivor| room.roles.get(survivor))
+            .map(|role| VoteChoice {
+                id: role.name().to_string(),
+                label: role.name().to_string(),
+            })
+            .collect(),
+        VoteKind::Choice { options } => options
+            .iter()
+            .map(|option| VoteChoice {
+                id: option.id.clone(),
+                label: option.label.clone(),
+            })
+            .collect(),
+    };
+    let id = VoteId(room.votes.next_id);
+    room.votes.next_id += 1;
+    println!("Vote {} called in room {}", definition.id, room.name);
+    let open = OpenVote {
+        id,
+        definition: definition.clone(),
+        called_by: client_id,
+        options,
+        ballots: HashMap::new(),
+        timer: Timer::from_seconds(definition.secs, TimerMode::Once),
+    };
+    let message = opened_message(&open);
+    room.votes.open = Some(open);
+    for player in &room.players {
+        protocol::send_to_client(server, *player, &message);
+    }
+    Ok(())
+}
+
+fn cast_ballot(
+    server: &mut RenetServer,
+    room: &mut Room,
+    client_id: ClientId,
+    id: VoteId,
+    option: &str,
+) -> Result<(), ActionError> {
+    check_voter(room, client_id)?;
+    let am = room.am();
+    let open = room.votes.open.as_mut().filter(|open| open.id == id).ok_or(ActionError::VoteOver)?;
+    if !open.options.iter().any(|choice| choice.id == option) {
+        return Err(ActionError::UnknownChoice);
+    }
+    open.ballots.insert(client_id, option.to_string());
+    let public = open.definition.ballot == Ballot::Public;
+    for player in &room.players {
+        let shown = public || Some(*player) == am;
+        let message = ServerMessage::BallotCast {
+            id,
+            voter: client_id.raw(),
+            option: shown.then(|| option.to_string()),
+        };
+        protocol::send_to_client(server, *player, &message);
+    }
+    Ok(())
+}
+
+// System to close votes once time runs out or every survivor has voted
+pub(super) fn tick_votes(
+    time: Res<Time>,
+    mut server: ResMut<RenetServer>,
+    mut registry: ResMut<RoomRegistry>,
+    scenario: Res<ServerScenario>,
+    players: Res<ServerPlayers>,
+) {
+    for room in registry.rooms.values_mut().filter(|room| room.phase == RoomPhase::InProgress) {
+        let voters = trust::survivors(room).len();
+        let Some(open) = &mut room.votes.open else {
+            continue;
+        };
+        let finished = open.timer.tick(time.delta()).finished();
+        if finished || open.ballots.len() >= voters {
+            close_vote(&mut server, room, &scenario.0, &players);
+        }
+    }
+}
+
+fn close_vote(server: &mut RenetServer, room: &mut Room, scenario: &Scenario, players: &ServerPlayers) {
+    let Some(open) = room.votes.open.take() else {
+        return;
+    };
+    let tally: Vec<VoteCount> = open
+        .options
+        .iter()
+        .map(|choice| VoteCount {
+            option: choice.id.clone(),
+            label: choice.label.clone(),
+            votes: open.ballots.values().filter(|option| **option == choice.id).count() as u32,
+        })
+        .collect();
+    let most = tally.iter().map(|count| count.votes).max().unwrap_or(0);
+    let mut leaders = tally.iter().filter(|count| count.votes == most);
+    // Nobody voting or a tie at the top both leave the question open
+    let winner = match (leaders.next(), leaders.next()) {
+        (Some(leader), None) if most > 0 => Some(leader.option.clone()),
+        _ => None,
+    };
+    match &winner {
+        Some(winner) => apply_outcome(server, room, scenario, players, &open, winner),
+        // A vote nobody answered was never really held
+        None if !open.ballots.is_empty() => sanity::vote_failed(room, players, scenario),
+        None => {}
+    }
+
+    let mut ballots: Vec<BallotRecord> = open
+        .ballots
+        .iter()
+        .map(|(voter, option)| BallotRecord {
+            voter: voter.raw(),
+            option: option.clone(),
+        })
+        .collect();
+    ballots.sort_by_key(|ballot| ballot.voter);
+    let result = VoteResult {
+        id: open.id,
+        vote: open.definition.id.clone(),
+        question: open.definition.question.clone(),
+        called_by: open.called_by.raw(),
+        at_secs: room.clock.as_ref().map_or(0.0, |clock| clock.elapsed),
+        winner,
+        tally,
+        ballots,
+    };
+    println!("Vote {} in room {} ended with {:?}", result.vote, room.name, result.winner);
+    let mut logged = result.clone();
+    if open.definition.ballot == Ballot::Secret {
+        logged.ballots.clear();
+    }
+    match_log::record(room, MatchEventKind::VoteClosed(logged));
+    room.votes.results.push(result);
+    for player in &room.players {
+        let message = result_message(room, scenario, room.votes.results.len() - 1, *player);
+        protocol::send_to_client(server, *player, &message);
+    }
+}
+
+// What the winning option does to the room
+fn apply_outcome(
+    server: &mut RenetServer,
+    room: &mut Room,
+    scenario: &Scenario,
+    players: &ServerPlayers,
+    open: &OpenVote,
+    winner: &str,
+) {
+    let voters: Vec<ClientId> = open
+        .ballots
+        .iter()
+        .filter(|(_, option)| *option == winner)
+        .map(|(voter, _)| *voter)
+        .collect();
+    match &open.definition.kind {
+        VoteKind::WhoIsLying | VoteKind::Exclude => {
+            let Some(who) = room
+                .roles
+                .iter()
+                .find(|(_, role)| matches!(role, Role::Survivor(_)) && role.name() == winner)
+                .map(|(client_id, _)| *client_id)
+            else {
+                return;
+            };
+            for voter in voters {
+                trust::accusation(room, players, voter, who);
+            }
+            if matches!(open.definition.kind, VoteKind::Exclude) {
+                room.votes.excluded.insert(who);
+            }
+        }
+        VoteKind::Choice { options } => {
+            let effect = options.iter().find(|option| option.id == winner).and_then(|option| option.effect.as_ref());
+            match effect {
+                Some(VoteEffect::CompleteObjective(id)) => {
+                    if room.objectives_done.insert(id.clone()) {
+                        if let Some(objective) = scenario.objective(id) {
+                            let description = objective.description.clone();
+                            match_log::record(room, MatchEventKind::ObjectiveCompleted { description });
+                        }
+                    }
+                }
+                Some(VoteEffect::LockDoor { door, secs }) => {
+                    if let Some(door) = scenario.door(door) {
+                        room.effects.locked_doors.retain(|locked| locked.id != door.id);
+                        room.effects.locked_doors.push(LockedDoor {
+                            id: door.id.clone(),
+                            x: door.x,
+                            seconds_left: *secs,
+                        });
+                        interventions::broadcast_effects(server, room);
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+fn opened_message(open: &OpenVote) -> ServerMessage {
+    ServerMessage::VoteOpened {
+        id: open.id,
+        question: open.definition.question.clone(),
+        options: open.options.clone(),
+        ballot: open.definition.ballot,
+        called_by: open.called_by.raw(),
+        seconds_left: open.timer.remaining_secs(),
+    }
+}
+
+// Survivors only see who voted for what when the ballot was public
+fn result_message(room: &Room, scenario: &Scenario, index: usize, recipient: ClientId) -> ServerMessage {
+    let mut result = room.votes.results[index].clone();
+    let secret = scenario.vote(&result.vote).map_or(true, |vote| vote.ballot == Ballot::Secret);
+    if secret && room.am() != Some(recipient) {
+        result.ballots.clear();
+    }
+    ServerMessage::VoteClosed(result)
+}
+
+pub(super) fn types_message(scenario: &Scenario) -> ServerMessage {
+    ServerMessage::VoteTypes(
+        scenario
+            .votes
+            .iter()
+            .map(|vote| VoteType {
+                id: vote.id.clone(),
+                question: vote.question.clone(),
+            })
+            .collect(),
+    )
+}
+
+// Everything a returning player needs to know about the room's votes
+pub(super) fn vote_messages(room: &Room, scenario: &Scenario, recipient: ClientId) -> Vec<ServerMessage> {
+    let mut messages = vec![types_message(scenario)];
+    messages.extend((0..room.votes.results.len()).map(|index| result_message(room, scenario, index, recipient)));
+    if let Some(open) = &room.votes.open {
+        messages.push(opened_message(open));
+        let shown = open.definition.ballot == Ballot::Public || room.am() == Some(recipient);
+        messages.extend(open.ballots.iter().map(|(voter, option)| ServerMessage::BallotCast {
+            id: open.id,
+            voter: voter.raw(),
+            option: shown.then(|| option.clone()),
+        }));
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::server::testing::{ice_cave, received, send, server_app, Inboxes};
+
+    // Player 1 plays AM to survivors 2 to 4, Gorrister, Benny and Ellen
+    fn votes_app() -> (App, Inboxes) {
+        let (mut app, inboxes) = server_app(&[1, 2, 3, 4]);
+        let mut registry = RoomRegistry::default();
+        registry.running_for_test(&[1, 2, 3, 4]);
+        app.init_resource::<Time>()
+            .insert_resource(registry)
+            .insert_resource(ServerScenario(ice_cave()))
+            .init_resource::<ServerPlayers>()
+            .add_systems(Update, (handle_vote_messages, tick_votes).chain());
+        (app, inboxes)
+    }
+
+    fn call(app: &mut App, client: u64, vote: &str) {
+        send(app, client, ClientMessage::CallVote { vote: vote.to_string() });
+        app.update();
+    }
+
+    fn cast(app: &mut App, client: u64, option: &str) {
+        let option = option.to_string();
+        send(app, client, ClientMessage::CastBallot { id: VoteId(0), option });
+        app.update();
+    }
+
+    fn room(app: &App) -> &Room {
+        app.world().resource::<RoomRegistry>().rooms.values().next().unwrap()
+    }
+
+    // What the client saw of each ballot, in order
+    fn ballots(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<(u64, Option<String>)> {
+        received(app, inboxes, client)
+            .into_iter()
+            .filter_map(|message| match message {
+                ServerMessage::BallotCast { voter, option, .. } => Some((voter, option)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn result(app: &mut App, inboxes: &mut Inboxes, client: u64) -> VoteResult {
+        received(app, inboxes, client)
+            .into_iter()
+            .find_map(|message| match message {
+                ServerMessage::VoteClosed(result) => Some(result),
+                _ => None,
+            })
+            .expect("the vote did not close")
+    }
+
+    fn errors(app: &mut App, inboxes: &mut Inboxes, client: u64) -> Vec<ActionError> {
+        received(app, inboxes, client)
+            .into_iter()
+            .filter_map(|message| match message {
+                ServerMessage::ActionError(error) => Some(error),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_secret_ballot_never_shows_survivors_the_option() {
+        let (mut app, mut inboxes) = votes_app();
+        call(&mut app, 2, "liar");
+        cast(&mut app, 2, "Benny");
+        for survivor in 2..=4 {
+            assert_eq!(ballots(&mut app, &mut inboxes, survivor), [(2, None)]);
+        }
+        assert_eq!(ballots(&mut app, &mut inboxes, 1), [(2, Some("Benny".to_string()))]);
+
+        cast(&mut app, 3, "Gorrister");
+        cast(&mut app, 4, "Benny");
+        assert!(room(&app).votes.open.is_none());
+        for survivor in 2..=4 {
+            let result = result(&mut app, &mut inboxes, survivor);
+            assert_eq!(result.winner.as_deref(), Some("Benny"));
+            assert!(result.ballots.is_empty());
+        }
+        let voters: Vec<_> = result(&mut app, &mut inboxes, 1).ballots.iter().map(|ballot| ballot.voter).collect();
+        assert_eq!(voters, [2, 3, 4]);
+    }
+
+    #[test]
+    fn a_public_choice_shows_every_ballot_and_applies_the_winner() {
+        let (mut app, mut inboxes) = votes_app();
+        call(&mut app, 2, "path");
+        cast(&mut app, 2, "upper");
+        cast(&mut app, 3, "shaft");
+        cast(&mut app, 4, "upper");
+
+        let expected = [
+            (2, Some("upper".to_string())),
+            (3, Some("shaft".to_string())),
+            (4, Some("upper".to_string())),
+        ];
+        assert_eq!(ballots(&mut app, &mut inboxes, 3), expected);
+        let locked: Vec<_> = room(&app).effects.locked_doors.iter().map(|door| door.id.as_str()).collect();
+        assert_eq!(locked, ["cache_gate"]);
+        assert_eq!(room(&app).votes.results[0].winner.as_deref(), Some("upper"));
+    }
+
+    #[test]
+    fn a_tie_when_time_runs_out_decides_nothing() {
+        let (mut app, mut inboxes) = votes_app();
+        call(&mut app, 2, "path");
+        cast(&mut app, 2, "upper");
+        cast(&mut app, 3, "shaft");
+        assert!(room(&app).votes.open.is_some());
+
+        app.world_mut().resource_mut::<Time>().advance_by(Duration::from_secs(20));
+        app.update();
+        assert_eq!(result(&mut app, &mut inboxes, 4).winner, None);
+        assert!(room(&app).effects.locked_doors.is_empty());
+    }
+
+    #[test]
+    fn only_survivors_vote_and_only_on_what_is_open() {
+        let (mut app, mut inboxes) = votes_app();
+        call(&mut app, 1, "liar");
+        call(&mut app, 4, "nowhere");
+        call(&mut app, 2, "liar");
+        call(&mut app, 3, "path");
+        cast(&mut app, 3, "upper");
+        send(&mut app, 4, ClientMessage::CastBallot { id: VoteId(7), option: "Benny".to_string() });
+        app.update();
+        cast(&mut app, 1, "Benny");
+
+        assert_eq!(errors(&mut app, &mut inboxes, 1), [ActionError::NotSurvivor, ActionError::NotSurvivor]);
+        assert_eq!(errors(&mut app, &mut inboxes, 3), [ActionError::VoteRunning, ActionError::UnknownChoice]);
+        assert_eq!(errors(&mut app, &mut inboxes, 4), [ActionError::UnknownVote, ActionError::VoteOver]);
+        assert!(room(&app).votes.open.as_ref().unwrap().ballots.is_empty());
+    }
+}
@@ -1 +1,2 @@
-pub mod greeting_system;
\ No newline at end of file
+pub mod text_input;
+pub mod movement;
\ No newline at end of file
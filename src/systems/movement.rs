@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+
+use crate::components::player::PlayerState;
+use crate::protocol::InputFrame;
+use crate::resources::world_effects::WorldEffects;
+
+// Inputs are sampled and simulated at this rate on both the client and the server
+pub const INPUT_TICK_HZ: f64 = 60.0;
+
+// How much faster than walking a sprint is
+pub const SPRINT_MULTIPLIER: f32 = 1.6;
+// How long a swing lasts, and how long a hit keeps someone from moving
+pub const ATTACK_SECS: f32 = 0.5;
+pub const HURT_SECS: f32 = 0.4;
+
+pub fn input_tick_seconds() -> f32 {
+    (1.0 / INPUT_TICK_HZ) as f32
+}
+
+// Advances one player by a single input frame. The client predicts with exactly what the server
+// runs, so replaying the same frames from the same start always lands on the same result.
+// state_elapsed is the time spent in the current state and starts over whenever it changes.
+pub fn simulate_input(position: &mut Vec2, state: &mut PlayerState, state_elapsed: &mut f32, input: &InputFrame, speed: f32) {
+    *state_elapsed += input_tick_seconds();
+    // The dead stay where they fell, the hurt are stunned for a moment
+    if *state == PlayerState::Dead || (*state == PlayerState::Hurt && *state_elapsed < HURT_SECS) {
+        return;
+    }
+    // A swing plays out before anything else can happen, walking included
+    let swinging = *state == PlayerState::Attacking && *state_elapsed < ATTACK_SECS;
+    // Inputs come straight off the wire on the server, so never trust their length
+    let movement = if swinging || !input.movement.is_finite() {
+        Vec2::ZERO
+    } else {
+        input.movement.clamp_length_max(1.0)
+    };
+    let running = input.sprint && movement != Vec2::ZERO;
+    let speed = if running { speed * SPRINT_MULTIPLIER } else { speed };
+    *position += movement * speed * input_tick_seconds();
+
+    let next = if swinging || input.attack {
+        PlayerState::Attacking
+    } else if movement == Vec2::ZERO {
+        PlayerState::Idle
+    } else if running {
+        PlayerState::Running
+    } else {
+        PlayerState::Walking
+    };
+    if next != *state || (input.attack && !swinging) {
+        *state_elapsed = 0.0;
+    }
+    *state = next;
+}
+
+// simulate_input inside whatever hazards and locked doors AM has placed
+pub fn simulate_input_in(
+    position: &mut Vec2,
+    state: &mut PlayerState,
+    state_elapsed: &mut f32,
+    input: &InputFrame,
+    speed: f32,
+    effects: &WorldEffects,
+) {
+    let from = *position;
+    simulate_input(position, state, state_elapsed, input, effects.speed_at(from, speed));
+    effects.block(from, position);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(movement: Vec2, sprint: bool, attack: bool) -> InputFrame {
+        InputFrame {
+            sequence: 0,
+            movement,
+            sprint,
+            attack,
+        }
+    }
+
+    // Runs the same frame for this many ticks
+    fn run(ticks: usize, input: &InputFrame, position: &mut Vec2, state: &mut PlayerState, elapsed: &mut f32) {
+        for _ in 0..ticks {
+            simulate_input(position, state, elapsed, input, 100.0);
+        }
+    }
+
+    #[test]
+    fn walks_and_sprints_at_their_speed() {
+        let (mut position, mut state, mut elapsed) = (Vec2::ZERO, PlayerState::Idle, 0.0);
+        run(60, &frame(Vec2::X, false, false), &mut position, &mut state, &mut elapsed);
+        assert!((position.x - 100.0).abs() < 0.01);
+        assert_eq!(state, PlayerState::Walking);
+
+        run(60, &frame(Vec2::X, true, false), &mut position, &mut state, &mut elapsed);
+        assert!((position.x - 100.0 - 100.0 * SPRINT_MULTIPLIER).abs() < 0.01);
+        assert_eq!(state, PlayerState::Running);
+    }
+
+    #[test]
+    fn never_moves_faster_than_full_input() {
+        let (mut position, mut state, mut elapsed) = (Vec2::ZERO, PlayerState::Idle, 0.0);
+        run(60, &frame(Vec2::new(50.0, 0.0), false, false), &mut position, &mut state, &mut elapsed);
+        assert!((position.x - 100.0).abs() < 0.01);
+        run(1, &frame(Vec2::new(f32::NAN, 1.0), false, false), &mut position, &mut state, &mut elapsed);
+        assert!(position.is_finite());
+    }
+
+    #[test]
+    fn replaying_the_same_frames_lands_on_the_same_spot() {
+        let frames: Vec<_> = (0..30)
+            .map(|tick| frame(Vec2::new((tick as f32).sin(), 1.0), tick % 5 == 0, tick % 7 == 0))
+            .collect();
+        let mut runs = [(Vec2::ZERO, PlayerState::Idle, 0.0), (Vec2::ZERO, PlayerState::Idle, 0.0)];
+        for (position, state, elapsed) in &mut runs {
+            for input in &frames {
+                simulate_input(position, state, elapsed, input, 150.0);
+            }
+        }
+        assert_eq!(runs[0], runs[1]);
+    }
+
+    #[test]
+    fn a_swing_plays_out_before_anything_else() {
+        let (mut position, mut state, mut elapsed) = (Vec2::ZERO, PlayerState::Idle, 0.0);
+        run(1, &frame(Vec2::ZERO, false, true), &mut position, &mut state, &mut elapsed);
+        assert_eq!(state, PlayerState::Attacking);
+        let ticks = (ATTACK_SECS / input_tick_seconds()).round() as usize;
+        run(ticks - 1, &frame(Vec2::ZERO, false, false), &mut position, &mut state, &mut elapsed);
+        assert_eq!(state, PlayerState::Attacking);
+        run(2, &frame(Vec2::ZERO, false, false), &mut position, &mut state, &mut elapsed);
+        assert_eq!(state, PlayerState::Idle);
+    }
+
+    #[test]
+    fn a_swing_roots_the_swinger_until_it_is_over() {
+        let (mut position, mut state, mut elapsed) = (Vec2::ZERO, PlayerState::Idle, 0.0);
+        run(1, &frame(Vec2::ZERO, false, true), &mut position, &mut state, &mut elapsed);
+        let ticks = (ATTACK_SECS / input_tick_seconds()).round() as usize;
+        run(ticks - 2, &frame(Vec2::X, true, false), &mut position, &mut state, &mut elapsed);
+        assert_eq!((position, state), (Vec2::ZERO, PlayerState::Attacking));
+        run(3, &frame(Vec2::X, false, false), &mut position, &mut state, &mut elapsed);
+        assert!(position.x > 0.0);
+        assert_eq!(state, PlayerState::Walking);
+    }
+
+    #[test]
+    fn the_dead_stay_where_they_fell() {
+        let (mut position, mut state, mut elapsed) = (Vec2::ZERO, PlayerState::Dead, 0.0);
+        run(10, &frame(Vec2::X, true, true), &mut position, &mut state, &mut elapsed);
+        assert_eq!((position, state), (Vec2::ZERO, PlayerState::Dead));
+    }
+}
@@ -0,0 +1,24 @@
+use bevy::input::keyboard::Key;
+
+// Applies a key press to a text field, returning whether the text changed
+pub fn edit_text(key: &Key, text: &mut String, max_len: usize) -> bool {
+    match key {
+        Key::Backspace => text.pop().is_some(),
+        Key::Character(input) => {
+            // Ignore any input that contains control (special) characters
+            if input.chars().any(|c| c.is_control()) || text.chars().count() >= max_len {
+                return false;
+            }
+            text.push_str(input);
+            true
+        }
+        Key::Space => {
+            if text.chars().count() >= max_len {
+                return false;
+            }
+            text.push(' ');
+            true
+        }
+        _ => false,
+    }
+}
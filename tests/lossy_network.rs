@@ -0,0 +1,170 @@
+// A real server and two clients in one process, with the link conditioner dropping, delaying and
+// duplicating packets on the way
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use bevy_renet::renet::transport::{ClientAuthentication, ConnectToken, NetcodeClientTransport, NETCODE_KEY_BYTES};
+use bevy_renet::renet::RenetClient;
+use ergo_cogito_sum::link_conditioner::{LinkConditioner, LinkConditions};
+use ergo_cogito_sum::protocol::{self, ClientMessage, Handshake, RoomId, ServerChannel, ServerMessage};
+use ergo_cogito_sum::server::{build_server_app, ServerSettings, ServerShutdown};
+
+const STEP: Duration = Duration::from_millis(10);
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+fn lossy() -> LinkConditions {
+    LinkConditions {
+        latency_ms: 20,
+        jitter_ms: 10,
+        loss: 0.2,
+        duplicate: 0.05,
+    }
+}
+
+struct TestClient {
+    client: RenetClient,
+    transport: NetcodeClientTransport,
+    received: Vec<ServerMessage>,
+    // Kept alive for as long as the client talks through it
+    _conditioner: Option<LinkConditioner>,
+}
+
+impl TestClient {
+    // Same as the game client, with its own relay when conditions are given
+    fn connect(server_addr: SocketAddr, client_id: u64, name: &str, conditions: Option<LinkConditions>) -> Self {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+        let user_data = Handshake::new(name).to_user_data();
+        let (authentication, conditioner) = match conditions {
+            Some(conditions) => {
+                let conditioner = LinkConditioner::in_front_of(server_addr, conditions).unwrap();
+                let connect_token = ConnectToken::generate(
+                    current_time,
+                    protocol::PROTOCOL_ID,
+                    300,
+                    client_id,
+                    15,
+                    vec![conditioner.local_addr(), server_addr],
+                    Some(&user_data),
+                    &[0; NETCODE_KEY_BYTES],
+                )
+                .unwrap();
+                (ClientAuthentication::Secure { connect_token }, Some(conditioner))
+            }
+            None => (
+                ClientAuthentication::Unsecure {
+                    protocol_id: protocol::PROTOCOL_ID,
+                    client_id,
+                    server_addr,
+                    user_data: Some(user_data),
+                },
+                None,
+            ),
+        };
+        Self {
+            client: RenetClient::new(protocol::connection_config()),
+            transport: NetcodeClientTransport::new(current_time, authentication, socket).unwrap(),
+            received: Vec::new(),
+            _conditioner: conditioner,
+        }
+    }
+
+    fn update(&mut self) {
+        self.client.update(STEP);
+        self.transport.update(STEP, &mut self.client).unwrap();
+        for channel in [ServerChannel::ServerMessages, ServerChannel::Snapshots] {
+            let channel: u8 = channel.into();
+            while let Some(bytes) = self.client.receive_message(channel) {
+                self.received.extend(protocol::decode::<ServerMessage>(&bytes));
+            }
+        }
+        self.transport.send_packets(&mut self.client).unwrap();
+    }
+
+    fn send(&mut self, message: &ClientMessage) {
+        self.client.send_message(message.channel(), protocol::encode(message));
+    }
+
+    fn find<T>(&self, pick: impl Fn(&ServerMessage) -> Option<T>) -> Option<T> {
+        self.received.iter().find_map(pick)
+    }
+}
+
+// Steps both clients until the condition holds, failing the test once it took too long
+fn run_until(clients: &mut [&mut TestClient], what: &str, done: impl Fn(&[&mut TestClient]) -> bool) {
+    let deadline = Instant::now() + TIMEOUT;
+    while !done(clients) {
+        assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+        for client in clients.iter_mut() {
+            client.update();
+        }
+        thread::sleep(STEP);
+    }
+}
+
+fn welcomed(client: &TestClient) -> bool {
+    client.find(|message| matches!(message, ServerMessage::Welcome { .. }).then_some(())).is_some()
+}
+
+fn joined(client: &TestClient) -> Option<RoomId> {
+    client.find(|message| match message {
+        ServerMessage::RoomJoined { room, .. } => Some(*room),
+        _ => None,
+    })
+}
+
+// Whether the first room list the client got shows the room
+fn listed(client: &TestClient, room: RoomId) -> Option<bool> {
+    client.find(|message| match message {
+        ServerMessage::RoomList(rooms) => Some(rooms.iter().any(|info| info.id == room && info.name == "Lossy")),
+        _ => None,
+    })
+}
+
+#[test]
+fn two_clients_share_a_room_over_a_lossy_link() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr = socket.local_addr().unwrap();
+    let settings = ServerSettings {
+        bind_addr: server_addr,
+        lan_discovery: false,
+        link_conditioner: Some(lossy()),
+        ..Default::default()
+    };
+    let shutdown = ServerShutdown::default();
+    let server_shutdown = shutdown.clone();
+    let server_thread = thread::spawn(move || {
+        build_server_app(socket, settings, server_shutdown).unwrap().run();
+    });
+
+    // One client only goes through the server's relay, the other through its own as well
+    let mut host = TestClient::connect(server_addr, 1, "Host", None);
+    let mut guest = TestClient::connect(server_addr, 2, "Guest", Some(lossy()));
+    run_until(&mut [&mut host, &mut guest], "both welcomes", |clients| {
+        clients.iter().all(|client| welcomed(client))
+    });
+
+    host.send(&ClientMessage::CreateRoom {
+        name: "Lossy".to_string(),
+        is_private: false,
+        password: None,
+    });
+    run_until(&mut [&mut host, &mut guest], "the room to be created", |clients| {
+        joined(clients[0]).is_some()
+    });
+    let room = joined(&host).unwrap();
+
+    guest.send(&ClientMessage::ListRooms);
+    run_until(&mut [&mut host, &mut guest], "the room list", |clients| listed(clients[1], room).is_some());
+    assert_eq!(listed(&guest, room), Some(true));
+
+    guest.send(&ClientMessage::JoinRoom { room });
+    run_until(&mut [&mut host, &mut guest], "the guest to join", |clients| {
+        joined(clients[1]).is_some()
+    });
+    assert_eq!(joined(&guest), Some(room));
+
+    shutdown.0.store(true, std::sync::atomic::Ordering::Relaxed);
+    server_thread.join().unwrap();
+}